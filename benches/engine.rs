@@ -0,0 +1,126 @@
+//! Benchmarks of the engine's throughput across different workloads, and of its peak memory use.
+//! Run with `cargo bench`; the peak memory use is printed before the throughput benchmarks start.
+//! Run again with `--features fixed-point` to compare the throughput of keeping balances in fixed-point.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering}
+};
+
+use bigdecimal::{BigDecimal, Zero};
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group};
+use transaction_system::{Engine, Fixed, Generator, Transaction, TransactionType};
+
+/// The number of clients every workload is spread across.
+const CLIENTS: u16 = 1000;
+
+/// The number of transactions in each throughput benchmark.
+const TRANSACTIONS: usize = 100_000;
+
+/// The backend balances are kept in by this build, which every throughput benchmark is named after.
+#[cfg(feature = "fixed-point")]
+const BACKEND: &str = "fixed";
+
+/// The backend balances are kept in by this build, which every throughput benchmark is named after.
+#[cfg(not(feature = "fixed-point"))]
+const BACKEND: &str = "bigdecimal";
+
+/// An allocator that keeps track of the most memory that was allocated at once.
+struct PeakAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+/// A workload of deposits only, spread evenly across the clients.
+fn deposits(count: usize) -> Vec<Transaction> {
+    (1..=count as u32)
+        .map(|id| Transaction::new(TransactionType::Deposit, (id % u32::from(CLIENTS)) as u16 + 1, id, Some(BigDecimal::new(id.into(), 4))))
+        .collect()
+}
+
+/// Every workload that is benchmarked, by name.
+fn workloads(count: usize) -> Vec<(&'static str, Vec<Transaction>)> {
+    vec![
+        ("deposit_heavy", deposits(count)),
+        ("mixed", Generator::new(CLIENTS, 0.01, 42).take(count).collect()),
+        ("dispute_heavy", Generator::new(CLIENTS, 0.25, 42).take(count).collect()),
+    ]
+}
+
+/// Process every transaction of a workload with a new engine.
+fn process(transactions: &[Transaction]) -> Engine {
+    let mut engine = Engine::new();
+    for transaction in transactions {
+        let _ = black_box(engine.process(transaction));
+    }
+    engine
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("throughput");
+    group.throughput(Throughput::Elements(TRANSACTIONS as u64));
+    group.sample_size(10);
+
+    for (name, transactions) in workloads(TRANSACTIONS) {
+        group.bench_with_input(BenchmarkId::new(name, BACKEND), &transactions, |b, transactions| b.iter(|| process(transactions)));
+    }
+    group.finish();
+}
+
+/// Add up the amounts of a workload as each backend keeps a balance, converting every amount as the engine does.
+fn arithmetic(c: &mut Criterion) {
+    let amounts = deposits(TRANSACTIONS).iter().filter_map(|transaction| transaction.amount().cloned()).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("arithmetic");
+    group.throughput(Throughput::Elements(amounts.len() as u64));
+
+    group.bench_with_input("fixed", &amounts, |b, amounts| b.iter(|| {
+        amounts.iter().try_fold(Fixed::ZERO, |total, amount| Fixed::try_from(amount).ok()?.checked_add(total))
+    }));
+    group.bench_with_input("bigdecimal", &amounts, |b, amounts| b.iter(|| {
+        amounts.iter().fold(BigDecimal::zero(), |total, amount| total + amount)
+    }));
+    group.finish();
+}
+
+/// Print the most memory used at once while processing each workload, on top of the memory holding the workload itself.
+fn peak_memory() {
+    for count in [10_000, 100_000, 1_000_000] {
+        for (name, transactions) in workloads(count) {
+            let baseline = CURRENT.load(Ordering::Relaxed);
+            PEAK.store(baseline, Ordering::Relaxed);
+
+            drop(black_box(process(&transactions)));
+
+            let peak = PEAK.load(Ordering::Relaxed) - baseline;
+            println!("peak_memory/{}/{}: {} bytes ({} bytes per transaction)", name, count, peak, peak / count);
+        }
+    }
+}
+
+criterion_group!(benches, throughput, arithmetic);
+
+fn main() {
+    peak_memory();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,27 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    grpc();
+}
+
+/// Generate the gRPC service, from messages that are defined by hand in `src/grpc.rs`.
+// NOTE: The service is described here rather than in a .proto file, so building does not need protoc installed.
+#[cfg(feature = "grpc")]
+fn grpc() {
+    use tonic_build::manual::{Builder, Method, Service};
+
+    let method = |name: &str, route: &str, input: &str, output: &str| Method::builder()
+        .name(name)
+        .route_name(route)
+        .input_type(format!("crate::grpc::{}", input))
+        .output_type(format!("crate::grpc::{}", output))
+        .codec_path("tonic::codec::ProstCodec");
+
+    let service = Service::builder()
+        .name("Ledger")
+        .package("transactions")
+        .method(method("submit_transactions", "SubmitTransactions", "TransactionMessage", "SubmitSummary").client_streaming().build())
+        .method(method("get_account", "GetAccount", "AccountRequest", "AccountReply").build())
+        .build();
+
+    Builder::new().compile(&[service]);
+}
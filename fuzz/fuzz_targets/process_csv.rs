@@ -0,0 +1,27 @@
+//! Feed arbitrary bytes through the CSV reader and the engine, checking for panics and broken invariants.
+//! Run with `cargo +nightly fuzz run process_csv`.
+#![no_main]
+
+use bigdecimal::{BigDecimal, Zero};
+use libfuzzer_sys::fuzz_target;
+use transaction_system::{Engine, records_from_reader};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(records) = records_from_reader(data) else {
+        return;
+    };
+
+    let mut engine = Engine::new().with_check_invariants(true);
+    for record in records.into_iter().flatten() {
+        // NOTE: A broken invariant rejects the transaction rather than panicking, so it is only found by looking for it.
+        let result = engine.process(&record.transaction);
+        assert!(!matches!(&result, Err(e) if e.is_violation()), "{:?}", result);
+    }
+
+    for client in engine.clients().unwrap() {
+        for balance in client.balances() {
+            assert_eq!(balance.total(), balance.available() + balance.held());
+            assert!(balance.held() >= BigDecimal::zero());
+        }
+    }
+});
@@ -0,0 +1,55 @@
+/*
+ * The C API of the transaction engine, built as a shared library with the `ffi` feature.
+ *
+ * Every function is safe to call with null, and an engine is not thread safe, so it must only be used by one thread
+ * at a time.
+ */
+
+#ifndef TX_ENGINE_H
+#define TX_ENGINE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An argument was a null pointer. */
+#define TX_ERROR_NULL (-1)
+
+/* The CSV could not be read at all, such as when its header is malformed. */
+#define TX_ERROR_INPUT (-2)
+
+/* The engine panicked, and can no longer be used. */
+#define TX_ERROR_PANIC (-3)
+
+/* An engine, as an opaque handle. */
+typedef struct TxEngine TxEngine;
+
+/* Create a new engine with the default configuration, to be freed with tx_engine_free. */
+TxEngine *tx_engine_new(void);
+
+/*
+ * Process every transaction of a CSV document of `len` bytes, with a header row, in order.
+ * Returns the number of rows that were malformed or rejected, or a negative TX_ERROR_* code.
+ */
+int32_t tx_engine_process_csv(TxEngine *engine, const uint8_t *csv, size_t len);
+
+/*
+ * Write every client account as a JSON array, as a NUL-terminated string to be freed with tx_engine_string_free.
+ * Returns null if the accounts can not be written.
+ */
+char *tx_engine_accounts_json(const TxEngine *engine);
+
+/* Free a string returned by tx_engine_accounts_json. */
+void tx_engine_string_free(char *string);
+
+/* Free an engine. */
+void tx_engine_free(TxEngine *engine);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
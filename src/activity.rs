@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Applied, TransactionError};
+
+/// The activity of a client's account, counted as each of its transactions is processed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Activity {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The number of transactions of the client that were accepted.
+    pub processed: u64,
+
+    /// The number of transactions of the client that were rejected.
+    pub rejected: u64,
+
+    /// The number of the client's transactions that are disputed, and neither resolved nor charged back yet.
+    pub open_disputes: u32,
+
+    /// The latest timestamp of any transaction of the client, accepted or rejected, or `None` if none had a timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_activity: Option<DateTime<Utc>>
+}
+
+impl Activity {
+    /// Create the activity of a client with no transactions yet.
+    pub fn new(client: u16) -> Self {
+        Self { client, ..Self::default() }
+    }
+
+    /// Count a transaction of the client, with the outcome of processing it.
+    pub(crate) fn count(&mut self, timestamp: Option<DateTime<Utc>>, result: &Result<Applied, TransactionError>) {
+        match result {
+            Ok(applied) => {
+                self.processed += 1;
+                match applied {
+                    Applied::Disputed { .. } => self.open_disputes += 1,
+                    Applied::Resolved { .. } | Applied::ChargedBack { .. } => self.open_disputes = self.open_disputes.saturating_sub(1),
+                    _ => ()
+                }
+            },
+            Err(_) => self.rejected += 1
+        }
+        self.last_activity = self.last_activity.max(timestamp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, OutputFormat, Precision, Transaction, TransactionType};
+
+    #[test]
+    fn count_the_activity_of_each_account() {
+        let at = DateTime::from_timestamp(3_600, 0).unwrap();
+
+        let mut engine = Engine::new().with_activity(true);
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())).with_timestamp(at)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(5.into()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)).unwrap();
+        assert!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, Some(50.into()))).is_err());
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 4, Some(1.into()))).unwrap();
+
+        assert_eq!(engine.activity(1), Some(&Activity { client: 1, processed: 5, rejected: 1, open_disputes: 1, last_activity: Some(at) }));
+        assert_eq!(engine.activity(3), None);
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_activity(&mut output, &[(None, engine.accounts_with_activity().unwrap())], Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+id,available,held,total,locked,processed,rejected,open_disputes,last_activity
+1,5.0000,10.0000,15.0000,false,5,1,1,1970-01-01T01:00:00+00:00
+2,1.0000,0.0000,1.0000,false,1,0,0,
+");
+
+        // NOTE: The counters are kept by a snapshot, so they carry on after a restore.
+        let mut restored = Engine::new().with_activity(true);
+        restored.restore(engine.snapshot().unwrap()).unwrap();
+        assert_eq!(restored.activity(1), engine.activity(1));
+        assert!(Engine::new().snapshot().unwrap().activity.is_empty());
+    }
+}
@@ -0,0 +1,418 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll}
+};
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{Applied, Client, Engine, Ownership, Precision, Snapshot, StorageError, Transaction, TransactionError, TransactionType, shard_for};
+
+/// The number of messages that can be queued for each shard before submitting waits for it to catch up.
+const SHARD_CAPACITY: usize = 1024;
+
+/// A function called with the engine of every shard a transaction changed, right after the transaction is applied.
+/// A transfer between clients of different shards calls it once with each of the two engines.
+pub type Observer = Arc<dyn Fn(&Engine, &Transaction, &Applied) + Send + Sync>;
+
+/// A message sent to the task that owns a shard.
+enum Message {
+    /// Process a transaction, replying with whether it was accepted.
+    Process(Box<Transaction>, oneshot::Sender<Result<Applied, TransactionError>>),
+
+    /// Run a function against the engine.
+    Run(Box<dyn FnOnce(&mut Engine) + Send>),
+
+    /// Hand the engine over to apply a transfer between shards, and wait until it is handed back.
+    Lend(oneshot::Sender<Engine>, oneshot::Receiver<Engine>),
+
+    /// Stop the task, handing the engine over for good.
+    Stop(oneshot::Sender<Engine>),
+}
+
+/// An engine split into shards by client id, with each shard owned by its own async task.
+///
+/// Transactions are sent to the task owning their client over a bounded channel, so each client's transactions are
+/// still processed in the order they were submitted, while a shard that is slow to reach its storage does not hold up
+/// the others. The handle can be cloned to submit from many tasks at once.
+///
+/// As with [`process_parallel`](crate::process_parallel), duplicate transaction ids are detected before routing, so an id
+/// is claimed by the first transaction that uses it, even if that transaction is later rejected. A transfer between clients
+/// of different shards waits for both shards to reach it, and is then applied across them.
+#[derive(Clone)]
+pub struct ShardedEngine {
+    /// The channel to the task owning each shard.
+    senders: Vec<mpsc::Sender<Message>>,
+
+    /// The ids claimed by every transaction submitted so far.
+    ids: Arc<Mutex<HashSet<u32>>>,
+
+    /// Held while asking more than one shard for its engine, so every shard is asked in the same order.
+    ordering: Arc<tokio::sync::Mutex<()>>,
+
+    /// The function called with every transaction that is applied.
+    observer: Option<Observer>,
+
+    /// The number of decimal places every amount is rounded to.
+    precision: Precision,
+
+    /// The owners of every joint account, if any are shared.
+    ownership: Option<Ownership>
+}
+
+impl ShardedEngine {
+    /// Spawn a task for each of a number of shards, with every shard's engine created by the given function.
+    /// Must be called from within a tokio runtime.
+    pub fn spawn<F: Fn() -> Engine>(shards: usize, engine: F) -> Self {
+        Self::spawn_with(shards, engine, None)
+    }
+
+    /// Spawn a task for each shard, as in [`ShardedEngine::spawn`], calling the observer with every transaction that is applied.
+    pub fn spawn_with<F: Fn() -> Engine>(shards: usize, engine: F, observer: Option<Observer>) -> Self {
+        let engines = (0..shards.max(1)).map(|_| engine()).collect::<Vec<_>>();
+        let precision = engines[0].precision();
+        let ownership = engines[0].ownership().cloned();
+
+        let senders = engines.into_iter()
+            .map(|engine| {
+                let (sender, receiver) = mpsc::channel(SHARD_CAPACITY);
+                tokio::spawn(run_shard(engine, receiver, observer.clone()));
+                sender
+            })
+            .collect();
+
+        Self {
+            senders,
+            ids: Arc::new(Mutex::new(HashSet::new())),
+            ordering: Arc::new(tokio::sync::Mutex::new(())),
+            observer,
+            precision,
+            ownership
+        }
+    }
+
+    /// The number of decimal places every amount is rounded to.
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Queue a transaction to be processed, waiting only if its shard has fallen too far behind.
+    /// The returned future resolves to whether the transaction was accepted, once its shard has processed it.
+    pub async fn submit(&self, transaction: Transaction) -> Pending {
+        let (reply, pending) = oneshot::channel();
+
+        // NOTE: Every owner of a joint account is routed to the shard of the account, so they share its balance.
+        let transaction = match self.ownership.as_ref().map(|ownership| ownership.resolve(&transaction)) {
+            Some(Cow::Owned(resolved)) => resolved,
+            _ => transaction
+        };
+
+        if !transaction.type_.references_existing() && !self.ids.lock().unwrap().insert(transaction.id) {
+            let _ = reply.send(Err(TransactionError::DuplicateTransaction(transaction.id)));
+            return Pending(pending);
+        }
+
+        let shards = self.senders.len();
+        let source = shard_for(transaction.client_id, shards);
+        let destination = transaction.destination
+            .filter(|_| transaction.type_ == TransactionType::Transfer)
+            .map(|destination| shard_for(destination, shards))
+            .filter(|&destination| destination != source);
+
+        let Some(destination) = destination else {
+            self.send(source, Message::Process(Box::new(transaction), reply)).await;
+            return Pending(pending);
+        };
+
+        // NOTE: While the lock is held no other transfer can ask for an engine, so any two transfers reach every shard
+        // they share in the same order, and neither can be left waiting on an engine the other is holding.
+        let ((source, source_back), (destination, destination_back)) = {
+            let _ordering = self.ordering.lock().await;
+            (self.lend(source).await, self.lend(destination).await)
+        };
+
+        let observer = self.observer.clone();
+        tokio::spawn(async move {
+            let mut source = source.await.expect("shard task stopped");
+            let mut destination = destination.await.expect("shard task stopped");
+
+            let result = source.transfer_to(&mut destination, &transaction);
+            if let (Ok(applied), Some(observer)) = (&result, &observer) {
+                observer(&source, &transaction, applied);
+                observer(&destination, &transaction, applied);
+            }
+
+            let _ = source_back.send(source);
+            let _ = destination_back.send(destination);
+            let _ = reply.send(result);
+        });
+
+        Pending(pending)
+    }
+
+    /// Process a transaction, waiting until its shard has applied or rejected it.
+    pub async fn process(&self, transaction: Transaction) -> Result<Applied, TransactionError> {
+        self.submit(transaction).await.await
+    }
+
+    /// Get a client by id, once every transaction already submitted for it has been processed.
+    pub async fn client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+        self.run(shard_for(id, self.senders.len()), move |engine| engine.client(id)).await
+            .await
+            .expect("shard task stopped")
+    }
+
+    /// Capture the complete state of every shard, once every transaction already submitted has been processed.
+    pub async fn snapshot(&self) -> Result<Snapshot, StorageError> {
+        // NOTE: Every shard is asked while holding the lock, so a transfer between shards is captured in both or neither.
+        let mut parts = Vec::with_capacity(self.senders.len());
+        {
+            let _ordering = self.ordering.lock().await;
+            for shard in 0..self.senders.len() {
+                parts.push(self.run(shard, |engine| engine.snapshot()).await);
+            }
+        }
+
+        let mut snapshot = Snapshot::default();
+        for part in parts {
+            let part = part.await.expect("shard task stopped")?;
+            snapshot.clients.extend(part.clients);
+            snapshot.transactions.extend(part.transactions);
+            snapshot.retired.extend(part.retired);
+            snapshot.latest = snapshot.latest.max(part.latest);
+            snapshot.accrual_day = snapshot.accrual_day.max(part.accrual_day);
+            snapshot.accrued.extend(part.accrued);
+            snapshot.outflows.extend(part.outflows);
+            snapshot.scheduled.extend(part.scheduled);
+            snapshot.activity.extend(part.activity);
+        }
+
+        snapshot.clients.sort_by_key(Client::id);
+        snapshot.transactions.sort_by_key(|entry| entry.transaction.id);
+        snapshot.retired.sort_unstable();
+        snapshot.outflows.sort_by_key(|outflow| outflow.client);
+        snapshot.scheduled.sort_by_key(|scheduled| scheduled.transaction.id);
+        snapshot.activity.sort_by_key(|activity| activity.client);
+        Ok(snapshot)
+    }
+
+    /// Restore the state captured by a snapshot, handing each client and its transactions to the shard that owns it.
+    pub async fn restore(&self, snapshot: Snapshot) -> Result<(), StorageError> {
+        let shards = self.senders.len();
+        let mut parts = vec![Snapshot { latest: snapshot.latest, accrual_day: snapshot.accrual_day, ..Snapshot::default() }; shards];
+
+        for client in snapshot.clients {
+            parts[shard_for(client.id(), shards)].clients.push(client);
+        }
+        for accrued in snapshot.accrued {
+            parts[shard_for(accrued.client, shards)].accrued.push(accrued);
+        }
+        for outflow in snapshot.outflows {
+            parts[shard_for(outflow.client, shards)].outflows.push(outflow);
+        }
+        for activity in snapshot.activity {
+            parts[shard_for(activity.client, shards)].activity.push(activity);
+        }
+
+        {
+            let mut ids = self.ids.lock().unwrap();
+            for entry in snapshot.transactions {
+                ids.insert(entry.transaction.id);
+                parts[shard_for(entry.transaction.client_id, shards)].transactions.push(entry);
+            }
+            for scheduled in snapshot.scheduled {
+                ids.insert(scheduled.transaction.id);
+                parts[shard_for(scheduled.transaction.client_id, shards)].scheduled.push(scheduled);
+            }
+
+            // NOTE: A retired id is only kept so it is still seen as a duplicate, which is checked before routing,
+            // so it does not matter which shard keeps it.
+            for id in snapshot.retired {
+                ids.insert(id);
+                parts[id as usize % shards].retired.push(id);
+            }
+        }
+
+        for (shard, part) in parts.into_iter().enumerate() {
+            self.run(shard, move |engine| engine.restore(part)).await
+                .await
+                .expect("shard task stopped")?;
+        }
+        Ok(())
+    }
+
+    /// Stop every shard once it has processed everything submitted to it, and merge them back into a single engine.
+    /// Any other handle to the same shards must not be used afterwards.
+    pub async fn into_engine(self) -> Engine {
+        let mut stopped = Vec::with_capacity(self.senders.len());
+        for shard in 0..self.senders.len() {
+            let (reply, engine) = oneshot::channel();
+            self.send(shard, Message::Stop(reply)).await;
+            stopped.push(engine);
+        }
+
+        let mut engine: Option<Engine> = None;
+        for shard in stopped {
+            let shard = shard.await.expect("shard task stopped");
+            match &mut engine {
+                Some(engine) => engine.merge(shard),
+                None => engine = Some(shard)
+            }
+        }
+        engine.expect("there is always at least one shard")
+    }
+
+    /// Send a message to the task owning a shard, waiting if its queue is full.
+    async fn send(&self, shard: usize, message: Message) {
+        if self.senders[shard].send(message).await.is_err() {
+            panic!("shard task stopped");
+        }
+    }
+
+    /// Queue a function to run against the engine of a shard, returning a receiver for its result.
+    async fn run<T, F>(&self, shard: usize, run: F) -> oneshot::Receiver<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Engine) -> T + Send + 'static
+    {
+        let (reply, result) = oneshot::channel();
+        self.send(shard, Message::Run(Box::new(move |engine| drop(reply.send(run(engine)))))).await;
+        result
+    }
+
+    /// Ask a shard to hand its engine over, once it has processed everything queued before.
+    /// Returns a receiver for the engine, and the sender to hand it back through.
+    async fn lend(&self, shard: usize) -> (oneshot::Receiver<Engine>, oneshot::Sender<Engine>) {
+        let (lend, lent) = oneshot::channel();
+        let (back, returned) = oneshot::channel();
+        self.send(shard, Message::Lend(lend, returned)).await;
+        (lent, back)
+    }
+}
+
+impl fmt::Debug for ShardedEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedEngine")
+            .field("shards", &self.senders.len())
+            .field("precision", &self.precision)
+            .finish()
+    }
+}
+
+/// A transaction queued for processing, which resolves to whether it was accepted once its shard has processed it.
+#[derive(Debug)]
+pub struct Pending(oneshot::Receiver<Result<Applied, TransactionError>>);
+
+impl Future for Pending {
+    type Output = Result<Applied, TransactionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.0).poll(cx).map(|result| result.expect("shard task stopped"))
+    }
+}
+
+/// Process every message sent to a shard in order, until the shard is stopped or every handle to it is dropped.
+async fn run_shard(mut engine: Engine, mut receiver: mpsc::Receiver<Message>, observer: Option<Observer>) {
+    while let Some(message) = receiver.recv().await {
+        match message {
+            Message::Process(transaction, reply) => {
+                let result = engine.process(&transaction);
+                if let (Ok(applied), Some(observer)) = (&result, &observer) {
+                    observer(&engine, &transaction, applied);
+                }
+                let _ = reply.send(result);
+            },
+            Message::Run(run) => run(&mut engine),
+            Message::Lend(lend, back) => {
+                engine = match lend.send(engine) {
+                    Ok(()) => back.await.expect("a lent engine is always handed back"),
+                    // The transfer was abandoned before the engine was handed over.
+                    Err(engine) => engine
+                };
+            },
+            Message::Stop(reply) => {
+                let _ = reply.send(engine);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{AmountThreshold, Flags, RiskMonitor};
+
+    fn amount(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    fn transactions() -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        for client in 0..20u16 {
+            let base = client as u32 * 10;
+            transactions.push(Transaction::new(TransactionType::Deposit, client, base + 1, amount("100")));
+            transactions.push(Transaction::new(TransactionType::Withdrawal, client, base + 2, amount("30")));
+            transactions.push(Transaction::transfer(client, (client + 1) % 20, base + 3, amount("50").unwrap()));
+            transactions.push(Transaction::new(TransactionType::Dispute, client, base + 1, None));
+            transactions.push(Transaction::new(TransactionType::Withdrawal, client, base + 4, amount("500")));
+        }
+        transactions
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn matches_sequential_processing() {
+        let mut sequential = Engine::new();
+        let expected = sequential.process_all(transactions());
+
+        let engine = ShardedEngine::spawn(4, Engine::new);
+        let mut pending = Vec::new();
+        for transaction in transactions() {
+            pending.push((transaction.id, engine.submit(transaction).await));
+        }
+
+        let mut rejected = Vec::new();
+        for (id, pending) in pending {
+            if let Err(e) = pending.await {
+                rejected.push((id, e));
+            }
+        }
+        assert_eq!(rejected, expected.into_iter().map(|(transaction, e)| (transaction.id, e)).collect::<Vec<_>>());
+        assert_eq!(engine.process(Transaction::new(TransactionType::Deposit, 3, 11, amount("1"))).await, Err(TransactionError::DuplicateTransaction(11)));
+
+        let snapshot = engine.snapshot().await.unwrap();
+        assert_eq!(snapshot, sequential.snapshot().unwrap());
+
+        let restored = ShardedEngine::spawn(3, Engine::new);
+        restored.restore(snapshot).await.unwrap();
+        assert_eq!(restored.client(7).await.unwrap(), sequential.client(7).unwrap());
+        assert_eq!(restored.process(Transaction::new(TransactionType::Deposit, 1, 71, amount("1"))).await, Err(TransactionError::DuplicateTransaction(71)));
+
+        let merged = engine.into_engine().await;
+        assert_eq!(merged.snapshot().unwrap(), sequential.snapshot().unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn risk_rules_flag_transfers_across_shards() {
+        let flags = Flags::default();
+        let engine = ShardedEngine::spawn(2, || Engine::builder()
+            .observer(RiskMonitor::new(vec![Box::new(AmountThreshold(BigDecimal::from(50)))], flags.clone()))
+            .build());
+
+        engine.process(Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).await.unwrap();
+        engine.process(Transaction::transfer(1, 2, 2, amount("5").unwrap())).await.unwrap();
+        engine.process(Transaction::new(TransactionType::Deposit, 3, 3, amount("100"))).await.unwrap();
+        engine.process(Transaction::transfer(3, 4, 4, amount("60").unwrap())).await.unwrap();
+
+        let flagged = flags.take().into_iter().map(|flag| (flag.type_, flag.client_id, flag.id)).collect::<Vec<_>>();
+        assert_eq!(flagged, vec![(TransactionType::Deposit, 3, 3), (TransactionType::Transfer, 3, 4)]);
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::{HashMap, HashSet}, io, str::FromStr};
+
+use bigdecimal::{BigDecimal, RoundingMode};
+
+use crate::{generate::split_mix64, mapping::FIELDS};
+
+/// The columns of a transaction holding a client id, which are remapped.
+const CLIENT_COLUMNS: &[&str] = &["client", "to"];
+
+/// The columns of a transaction holding an amount, which are perturbed.
+const AMOUNT_COLUMNS: &[&str] = &["amount", "total"];
+
+/// A rewriter of CSV transactions into shareable test data, which remaps every client id, perturbs every amount and
+/// strips every column that is not a field of a transaction, such as a `memo`.
+///
+/// Transaction ids are kept, so disputes, captures and the like still reference the transaction they did. Every amount of
+/// a client is scaled by the same factor, kept to its number of decimal places, so a withdrawal of the funds a client
+/// holds still fits them, but for rounding and transfers between clients scaled by different factors.
+#[derive(Clone, Debug)]
+pub struct Anonymizer {
+    /// The state of the random number generator.
+    state: u64,
+
+    /// How far, as a percentage, the amounts of a client may be scaled either way.
+    perturbation: BigDecimal,
+
+    /// The id each client is remapped to, alongside the factor its amounts are scaled by.
+    clients: HashMap<u16, (u16, BigDecimal)>,
+
+    /// Every id a client has been remapped to.
+    used: HashSet<u16>
+}
+
+impl Anonymizer {
+    /// Create an anonymizer scaling the amounts of each client by up to the percentage either way, where the same seed
+    /// always remaps and scales the same input the same way.
+    pub fn new(perturbation: BigDecimal, seed: u64) -> Self {
+        Self {
+            state: seed,
+            perturbation,
+            clients: HashMap::new(),
+            used: HashSet::new()
+        }
+    }
+
+    /// The id a client is remapped to, and the factor its amounts are scaled by, drawing both the first time it is seen.
+    fn client(&mut self, id: u16) -> &(u16, BigDecimal) {
+        if !self.clients.contains_key(&id) {
+            // NOTE: There are as many ids as clients, so an unused id is always left to draw.
+            let remapped = loop {
+                let remapped = split_mix64(&mut self.state) as u16;
+                if self.used.insert(remapped) {
+                    break remapped;
+                }
+            };
+            let offset = BigDecimal::from(split_mix64(&mut self.state) % 2_000_001) - BigDecimal::from(1_000_000);
+            let factor = BigDecimal::from(1) + &self.perturbation * offset / BigDecimal::from(100_000_000);
+            self.clients.insert(id, (remapped, factor));
+        }
+        &self.clients[&id]
+    }
+
+    /// The number of clients remapped so far.
+    pub fn clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Rewrite every row of a CSV source of transactions, writing only the columns that are fields of a transaction,
+    /// and returning the number of rows written. The header and the client id of every row must be valid.
+    pub fn rewrite<R: io::Read, W: io::Write>(&mut self, reader: R, writer: W) -> io::Result<u64> {
+        let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers()?.clone();
+        let kept = headers.iter().enumerate()
+            .filter(|(_, header)| FIELDS.contains(header))
+            .collect::<Vec<_>>();
+        let client = kept.iter().find(|(_, header)| *header == "client").map(|(index, _)| *index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "a transaction source needs a client column"))?;
+
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(kept.iter().map(|(_, header)| header))?;
+
+        let mut rows = 0;
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let line = record.position().map_or(0, |position| position.line());
+            let owner = record[client].parse::<u16>()
+                .map_err(|_| invalid(line, format!("invalid client id '{}'", &record[client])))?;
+            let factor = self.client(owner).1.clone();
+
+            let mut row = Vec::with_capacity(kept.len());
+            for &(index, header) in &kept {
+                let value = &record[index];
+                let value = match header {
+                    _ if value.is_empty() => String::new(),
+                    _ if CLIENT_COLUMNS.contains(&header) => {
+                        let id = value.parse::<u16>().map_err(|_| invalid(line, format!("invalid client id '{}'", value)))?;
+                        self.client(id).0.to_string()
+                    },
+                    _ if AMOUNT_COLUMNS.contains(&header) => {
+                        let amount = BigDecimal::from_str(value).map_err(|_| invalid(line, format!("invalid {} '{}'", header, value)))?;
+                        (&amount * &factor).with_scale_round(amount.fractional_digit_count().max(0), RoundingMode::HalfEven).to_plain_string()
+                    },
+                    _ => value.to_string()
+                };
+                row.push(value);
+            }
+            writer.write_record(&row)?;
+            rows += 1;
+        }
+        writer.flush()?;
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType, transactions_from_reader};
+
+    #[test]
+    fn anonymize_keeping_the_structure() {
+        let csv = "\
+type, client, tx, amount, to, memo
+deposit, 7, 1, 100.00, , rent
+deposit, 8, 2, 20.5, , salary
+withdrawal, 7, 3, 100.00, ,
+transfer, 8, 4, 1, 7,
+dispute, 7, 1, , ,
+chargeback, 7, 1, , ,
+";
+        let mut output = Vec::new();
+        let mut anonymizer = Anonymizer::new(BigDecimal::from(10), 42);
+        assert_eq!(anonymizer.rewrite(csv.as_bytes(), &mut output).unwrap(), 6);
+        assert_eq!(anonymizer.clients(), 2);
+
+        let original = transactions_from_reader(csv.as_bytes()).unwrap();
+        let anonymized = transactions_from_reader(output.as_slice()).unwrap();
+        assert!(String::from_utf8_lossy(&output).starts_with("type,client,tx,amount,to\n"));
+        assert_eq!(anonymized.iter().map(|transaction| transaction.id()).collect::<Vec<_>>(), vec![1, 2, 3, 4, 1, 1]);
+        assert!(anonymized.iter().all(|transaction| transaction.metadata().is_empty()));
+
+        // NOTE: Every transaction of a client is remapped to the same client, including the destination of a transfer.
+        let clients = anonymized.iter().map(|transaction| transaction.client_id()).collect::<Vec<_>>();
+        assert_eq!([clients[2], clients[4], clients[5]], [clients[0]; 3]);
+        assert_eq!(anonymized[3].destination(), Some(clients[0]));
+        assert_ne!(clients[0], clients[1]);
+
+        for (original, anonymized) in original.iter().zip(&anonymized).filter(|(original, _)| original.amount().is_some()) {
+            let (original, anonymized) = (original.amount().unwrap(), anonymized.amount().unwrap());
+            assert!((anonymized - original).abs() <= original / BigDecimal::from(10));
+        }
+
+        let outcomes = |transactions: &[Transaction]| {
+            let mut engine = Engine::new();
+            transactions.iter().map(|transaction| engine.process(transaction).is_ok()).collect::<Vec<_>>()
+        };
+        assert_eq!(outcomes(&anonymized), outcomes(&original));
+        assert_eq!(anonymized[5].type_(), TransactionType::Chargeback);
+    }
+}
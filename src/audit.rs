@@ -0,0 +1,325 @@
+use std::{error, fmt, fs::{File, OpenOptions}, io::{self, BufRead, Write}, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Applied, Transaction, TransactionError};
+
+/// The hash the first entry of an audit log chains from.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// An enumeration of the events recorded in an audit log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// A deposit was credited.
+    DepositApplied,
+
+    /// A withdrawal was debited.
+    WithdrawalApplied,
+
+    /// Funds were transferred to another client.
+    TransferApplied,
+
+    /// Funds were converted from one currency to another.
+    ConversionApplied,
+
+    /// Funds were moved from one sub-account to another.
+    FundsMoved,
+
+    /// A transaction was disputed, and its amount is held.
+    DisputeOpened,
+
+    /// A dispute was resolved, and the held amount released.
+    DisputeResolved,
+
+    /// A dispute ended in a chargeback, and the account was locked.
+    AccountLocked,
+
+    /// The account was unlocked by an operator.
+    AccountUnlocked,
+
+    /// The account was frozen by an operator.
+    AccountFrozen,
+
+    /// The account was unfrozen by an operator.
+    AccountUnfrozen,
+
+    /// A chargeback was reversed by a representment, restoring the original transaction.
+    ChargebackReversed,
+
+    /// Accrued interest was credited.
+    InterestPosted,
+
+    /// An authorization placed a hold on funds.
+    AuthorizationHeld,
+
+    /// An authorization was captured, and its held amount debited.
+    AuthorizationCaptured,
+
+    /// An authorization was voided, and its held amount released.
+    AuthorizationVoided,
+
+    /// A transaction was scheduled, to be applied once it falls due.
+    TransactionScheduled,
+
+    /// A balance assertion matched the account.
+    BalanceAsserted,
+
+    /// A transaction was rejected.
+    Rejected,
+}
+
+impl AuditEvent {
+    /// The event recorded for a transaction, given what it applied or why it was rejected.
+    pub fn of(result: &Result<Applied, TransactionError>) -> Self {
+        match result {
+            Ok(Applied::Deposited { .. }) => Self::DepositApplied,
+            Ok(Applied::Withdrew { .. }) => Self::WithdrawalApplied,
+            Ok(Applied::Transferred { .. }) => Self::TransferApplied,
+            Ok(Applied::Converted { .. }) => Self::ConversionApplied,
+            Ok(Applied::Moved { .. }) => Self::FundsMoved,
+            Ok(Applied::Disputed { .. }) => Self::DisputeOpened,
+            Ok(Applied::Resolved { .. }) => Self::DisputeResolved,
+            Ok(Applied::ChargedBack { .. }) => Self::AccountLocked,
+            Ok(Applied::Unlocked) => Self::AccountUnlocked,
+            Ok(Applied::Frozen) => Self::AccountFrozen,
+            Ok(Applied::Unfrozen) => Self::AccountUnfrozen,
+            Ok(Applied::Represented { .. }) => Self::ChargebackReversed,
+            Ok(Applied::Interest { .. }) => Self::InterestPosted,
+            Ok(Applied::Authorized { .. }) => Self::AuthorizationHeld,
+            Ok(Applied::Captured { .. }) => Self::AuthorizationCaptured,
+            Ok(Applied::Voided { .. }) => Self::AuthorizationVoided,
+            Ok(Applied::Scheduled { .. }) => Self::TransactionScheduled,
+            Ok(Applied::Asserted) => Self::BalanceAsserted,
+            Err(_) => Self::Rejected,
+        }
+    }
+}
+
+/// An entry of the audit log, which carries the hash of the entry before it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The position of the entry within the log, starting at 1.
+    pub sequence: u64,
+
+    /// What happened.
+    pub event: AuditEvent,
+
+    /// The transaction that caused the event.
+    pub transaction: Transaction,
+
+    /// What the transaction applied to the accounts, if it was accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied: Option<Applied>,
+
+    /// Why the transaction was rejected, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    /// The hash of the previous entry, or [`GENESIS_HASH`] for the first.
+    pub previous: String,
+
+    /// The SHA-256 hash of every other field of this entry, as hex.
+    pub hash: String
+}
+
+impl AuditEntry {
+    /// Compute the hash of the entry, over its JSON form with the hash itself left empty.
+    pub fn digest(&self) -> String {
+        let unhashed = Self { hash: String::new(), ..self.clone() };
+        hex::encode(Sha256::digest(serde_json::to_vec(&unhashed).expect("audit entries always serialize")))
+    }
+}
+
+/// An enumeration of the reasons an audit log can fail to verify.
+#[derive(Debug)]
+pub enum AuditError {
+    /// The log could not be read.
+    Io(io::Error),
+
+    /// An entry is malformed, on the given line.
+    Format(u64, serde_json::Error),
+
+    /// An entry does not chain from the hash of the entry before it.
+    BrokenChain(u64),
+
+    /// The contents of an entry do not match its hash.
+    Tampered(u64),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Format(line, e) => write!(f, "malformed audit entry on line {}: {}", line, e),
+            Self::BrokenChain(sequence) => write!(f, "audit entry {} does not follow the entry before it", sequence),
+            Self::Tampered(sequence) => write!(f, "audit entry {} does not match its hash", sequence),
+        }
+    }
+}
+
+impl error::Error for AuditError {}
+
+impl From<io::Error> for AuditError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// A tamper-evident audit log, written as one JSON entry per line, with each entry chained to the one before by its hash.
+/// Unlike the journal, rejected transactions are recorded too.
+#[derive(Debug)]
+pub struct AuditLog<W: Write> {
+    /// Where the entries are written.
+    writer: W,
+
+    /// The sequence number of the last entry.
+    sequence: u64,
+
+    /// The hash of the last entry.
+    previous: String
+}
+
+impl AuditLog<File> {
+    /// Open an audit log for appending, creating it if it does not exist.
+    /// The chain carries on from the last entry already in the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let last = match File::open(&path) {
+            Ok(file) => io::BufReader::new(file).lines()
+                .filter(|line| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+                .last()
+                .transpose()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e)
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        let mut log = Self::new(file);
+        if let Some(last) = last {
+            let last = serde_json::from_str::<AuditEntry>(&last)?;
+            log.sequence = last.sequence;
+            log.previous = last.hash;
+        }
+        Ok(log)
+    }
+}
+
+impl<W: Write> AuditLog<W> {
+    /// Create an audit log that writes to the start of a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            sequence: 0,
+            previous: GENESIS_HASH.to_string()
+        }
+    }
+
+    /// Append the event caused by a processed transaction, whether it was accepted or rejected.
+    pub fn append(&mut self, transaction: &Transaction, result: &Result<Applied, TransactionError>) -> io::Result<()> {
+        let mut entry = AuditEntry {
+            sequence: self.sequence + 1,
+            event: AuditEvent::of(result),
+            transaction: transaction.clone(),
+            applied: result.as_ref().ok().cloned(),
+            reason: result.as_ref().err().map(TransactionError::to_string),
+            previous: self.previous.clone(),
+            hash: String::new()
+        };
+        entry.hash = entry.digest();
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.writer.flush()?;
+
+        self.sequence = entry.sequence;
+        self.previous = entry.hash;
+        Ok(())
+    }
+
+    /// Consume the log, returning its writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Check every entry of an audit log matches its hash, and chains from the entry before it.
+/// Returns the number of entries verified, alongside the number that were rejected transactions.
+pub fn verify_audit<R: io::Read>(reader: R) -> Result<(u64, u64), AuditError> {
+    let mut previous = GENESIS_HASH.to_string();
+    let (mut verified, mut rejected) = (0, 0);
+
+    for (number, line) in io::BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str::<AuditEntry>(&line)
+            .map_err(|e| AuditError::Format(number as u64 + 1, e))?;
+
+        if entry.previous != previous {
+            return Err(AuditError::BrokenChain(entry.sequence));
+        }
+        if entry.digest() != entry.hash {
+            return Err(AuditError::Tampered(entry.sequence));
+        }
+
+        verified += 1;
+        if entry.event == AuditEvent::Rejected {
+            rejected += 1;
+        }
+        previous = entry.hash;
+    }
+
+    Ok((verified, rejected))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    fn audit_log() -> String {
+        let transactions = [
+            Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10").unwrap())),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from_str("40").unwrap())),
+            Transaction::new(TransactionType::Dispute, 1, 1, None),
+            Transaction::new(TransactionType::Chargeback, 1, 1, None),
+        ];
+
+        let mut engine = Engine::new();
+        let mut log = AuditLog::new(Vec::new());
+        for transaction in &transactions {
+            log.append(transaction, &engine.process(transaction)).unwrap();
+        }
+        String::from_utf8(log.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn verify_hash_chain() {
+        let log = audit_log();
+        assert_eq!(verify_audit(log.as_bytes()).unwrap(), (4, 1));
+
+        let entries = log.lines().map(|line| serde_json::from_str::<AuditEntry>(line).unwrap()).collect::<Vec<_>>();
+        assert_eq!(entries.iter().map(|entry| entry.event).collect::<Vec<_>>(), vec![AuditEvent::DepositApplied, AuditEvent::Rejected, AuditEvent::DisputeOpened, AuditEvent::AccountLocked]);
+        assert_eq!(entries[1].reason.as_deref(), Some("insufficient funds"));
+        assert_eq!(entries[0].previous, GENESIS_HASH);
+        assert_eq!(entries[1].previous, entries[0].hash);
+
+        let tampered = log.replacen(r#""amount":"10""#, r#""amount":"1000""#, 1);
+        assert!(matches!(verify_audit(tampered.as_bytes()), Err(AuditError::Tampered(1))));
+
+        let removed = log.lines().filter(|line| !line.contains(r#""sequence":2,"#)).collect::<Vec<_>>().join("\n");
+        assert!(matches!(verify_audit(removed.as_bytes()), Err(AuditError::BrokenChain(3))));
+    }
+}
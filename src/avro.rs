@@ -0,0 +1,274 @@
+use std::{collections::HashMap, io};
+
+use avro_schema::{
+    read::fallible_streaming_iterator::FallibleStreamingIterator,
+    schema::{BytesLogical, FixedLogical, LongLogical, Record as AvroRecord, Schema}
+};
+use bigdecimal::{BigDecimal, num_bigint::BigInt};
+use chrono::DateTime;
+use serde_json::{Map, Value};
+
+use crate::{Record, RecordError, Transaction, transaction::JsonTransaction};
+
+/// The byte every message of the Confluent wire format starts with, before the id of its schema.
+const CONFLUENT_MAGIC: u8 = 0;
+
+/// Read every transaction from an Avro object container file, keeping track of the position of each record in the file,
+/// starting at 1, as its line.
+///
+/// Each record is mapped to a transaction by the names of its fields, which are the same as the columns of the CSV
+/// input, such as `type`, `client`, `tx` and `amount`, with any other field kept as metadata. An amount may be a string,
+/// a number, or a decimal logical type, and a timestamp a string or a timestamp logical type. A record that is not a valid transaction is returned as an
+/// error in place of its record, while a file that can not be decoded fails entirely.
+pub fn records_from_avro_reader<R: io::Read>(mut reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let metadata = avro_schema::read::read_metadata(&mut reader).map_err(|e| invalid(format!("invalid avro file: {}", e)))?;
+    let schema = Schema::Record(metadata.record);
+    let mut blocks = avro_schema::read::block_iterator(reader, metadata.compression, metadata.marker);
+
+    let mut records = Vec::new();
+    while let Some(block) = blocks.next().map_err(|e| invalid(format!("invalid avro block: {}", e)))? {
+        let mut data = block.data.as_slice();
+        for _ in 0..block.number_of_rows {
+            let line = records.len() as u64 + 1;
+            let start = data;
+            let value = decode(&schema, &mut data).map_err(|e| invalid(format!("record {}: {}", line, e)))?;
+
+            records.push(transaction(value).map(|transaction| Record { line, transaction }).map_err(|message| RecordError {
+                line,
+                column: None,
+                field: None,
+                message,
+                record: hex::encode(&start[..start.len() - data.len()])
+            }));
+        }
+    }
+
+    Ok(records)
+}
+
+/// A client of a Confluent schema registry, decoding messages of the Confluent wire format with the schemas it holds.
+/// Each schema is only fetched once, the first time a message written with it is decoded.
+#[derive(Debug)]
+pub struct SchemaRegistry {
+    /// The URL of the registry, such as `http://localhost:8081`.
+    url: String,
+
+    /// Every schema fetched so far, keyed by id.
+    schemas: HashMap<u32, Schema>
+}
+
+impl SchemaRegistry {
+    /// Create a client of the schema registry at a URL.
+    pub fn new<U: Into<String>>(url: U) -> Self {
+        Self {
+            url: url.into().trim_end_matches('/').to_string(),
+            schemas: HashMap::new()
+        }
+    }
+
+    /// Decode a transaction from a message of the Confluent wire format, which is a zero byte, the id of the schema
+    /// it was written with as a big-endian 32-bit integer, and then the record.
+    pub fn decode(&mut self, message: &[u8]) -> Result<Transaction, String> {
+        let (id, mut data) = match message {
+            [CONFLUENT_MAGIC, a, b, c, d, data @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]), data),
+            _ => return Err("not a message of the Confluent wire format".to_string())
+        };
+
+        if !self.schemas.contains_key(&id) {
+            let schema = self.fetch(id)?;
+            self.schemas.insert(id, schema);
+        }
+        transaction(decode(&self.schemas[&id], &mut data)?)
+    }
+
+    /// Fetch a schema by id.
+    fn fetch(&self, id: u32) -> Result<Schema, String> {
+        let url = format!("{}/schemas/ids/{}", self.url, id);
+        let response = ureq::get(&url).call()
+            .map_err(|e| format!("unable to fetch schema {} from '{}': {}", id, url, e))?;
+        let body = serde_json::from_reader::<_, Value>(response.into_reader())
+            .map_err(|e| format!("unable to read schema {} from '{}': {}", id, url, e))?;
+
+        let schema = body.get("schema").and_then(Value::as_str)
+            .ok_or_else(|| format!("'{}' did not respond with a schema", url))?;
+        serde_json::from_str(schema).map_err(|e| format!("schema {} is invalid: {}", id, e))
+    }
+}
+
+/// Map a decoded record to a transaction by the names of its fields.
+fn transaction(value: Value) -> Result<Transaction, String> {
+    serde_json::from_value::<JsonTransaction>(value).map(Into::into).map_err(|e| e.to_string())
+}
+
+/// Decode a single datum of a schema as JSON, advancing past it.
+fn decode(schema: &Schema, data: &mut &[u8]) -> Result<Value, String> {
+    Ok(match schema {
+        Schema::Null => Value::Null,
+        Schema::Boolean => Value::Bool(take(data, 1)?[0] != 0),
+        Schema::Int(_) => Value::from(long(data)?),
+        Schema::Long(Some(LongLogical::TimestampMillis)) => timestamp(DateTime::from_timestamp_millis(long(data)?))?,
+        Schema::Long(Some(LongLogical::TimestampMicros)) => timestamp(DateTime::from_timestamp_micros(long(data)?))?,
+        Schema::Long(_) => Value::from(long(data)?),
+        Schema::Float => Value::from(f32::from_le_bytes(take(data, 4)?.try_into().unwrap())),
+        Schema::Double => Value::from(f64::from_le_bytes(take(data, 8)?.try_into().unwrap())),
+        Schema::Bytes(Some(BytesLogical::Decimal(_, scale))) => {
+            let length = length(data)?;
+            decimal(take(data, length)?, *scale)
+        },
+        Schema::Bytes(None) => {
+            let length = length(data)?;
+            Value::from(hex::encode(take(data, length)?))
+        },
+        Schema::String(_) => {
+            let length = length(data)?;
+            Value::from(String::from_utf8(take(data, length)?.to_vec()).map_err(|_| "invalid UTF-8 string".to_string())?)
+        },
+        Schema::Record(record) => self::record(record, data)?,
+        Schema::Enum(symbols) => {
+            let index = long(data)?;
+            let symbol = usize::try_from(index).ok().and_then(|index| symbols.symbols.get(index))
+                .ok_or_else(|| format!("enum index {} is out of range", index))?;
+            Value::from(symbol.as_str())
+        },
+        Schema::Array(item) => Value::Array(blocks(data, |data| decode(item, data))?),
+        Schema::Map(value) => Value::Object(blocks(data, |data| {
+            let length = length(data)?;
+            let key = String::from_utf8(take(data, length)?.to_vec()).map_err(|_| "invalid UTF-8 map key".to_string())?;
+            Ok((key, decode(value, data)?))
+        })?.into_iter().collect()),
+        Schema::Union(variants) => {
+            let index = long(data)?;
+            let variant = usize::try_from(index).ok().and_then(|index| variants.get(index))
+                .ok_or_else(|| format!("union index {} is out of range", index))?;
+            decode(variant, data)?
+        },
+        Schema::Fixed(fixed) => match fixed.logical {
+            Some(FixedLogical::Decimal(_, scale)) => decimal(take(data, fixed.size)?, scale),
+            _ => Value::from(hex::encode(take(data, fixed.size)?))
+        },
+    })
+}
+
+/// Decode every field of a record as an object.
+fn record(record: &AvroRecord, data: &mut &[u8]) -> Result<Value, String> {
+    let mut object = Map::new();
+    for field in &record.fields {
+        let value = decode(&field.schema, data).map_err(|e| format!("field '{}': {}", field.name, e))?;
+        // NOTE: A missing optional field is left out, rather than written as null, as the CSV input leaves it empty.
+        if !value.is_null() {
+            object.insert(field.name.clone(), value);
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+/// Decode the items of an array or map, which are written in blocks, each preceded by its number of items.
+/// A negative count is followed by the size of the block in bytes, and a count of zero ends the items.
+fn blocks<T, F: FnMut(&mut &[u8]) -> Result<T, String>>(data: &mut &[u8], mut item: F) -> Result<Vec<T>, String> {
+    let mut items = Vec::new();
+    loop {
+        let count = match long(data)? {
+            0 => return Ok(items),
+            count if count < 0 => {
+                long(data)?;
+                count.unsigned_abs()
+            },
+            count => count.unsigned_abs()
+        };
+        for _ in 0..count {
+            items.push(item(data)?);
+        }
+    }
+}
+
+/// Decode a decimal from its unscaled value, as a big-endian two's complement integer, as a string.
+fn decimal(bytes: &[u8], scale: usize) -> Value {
+    Value::from(BigDecimal::new(BigInt::from_signed_bytes_be(bytes), scale as i64).to_plain_string())
+}
+
+/// Write a timestamp as RFC 3339.
+fn timestamp(timestamp: Option<DateTime<chrono::Utc>>) -> Result<Value, String> {
+    timestamp.map(|timestamp| Value::from(timestamp.to_rfc3339()))
+        .ok_or_else(|| "timestamp is out of range".to_string())
+}
+
+/// Decode a zigzag encoded variable length integer.
+fn long(data: &mut &[u8]) -> Result<i64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = take(data, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err("integer is too long".to_string())
+}
+
+/// Decode the length of a string, bytes or map key.
+fn length(data: &mut &[u8]) -> Result<usize, String> {
+    let length = long(data)?;
+    usize::try_from(length).map_err(|_| format!("length {} is negative", length))
+}
+
+/// Take a number of bytes.
+fn take<'a>(data: &mut &'a [u8], length: usize) -> Result<&'a [u8], String> {
+    if data.len() < length {
+        return Err("unexpected end of data".to_string());
+    }
+    let (taken, rest) = data.split_at(length);
+    *data = rest;
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use avro_schema::{file::CompressedBlock, write::{encode::zigzag_encode, write_block, write_metadata}};
+
+    use super::*;
+    use crate::TransactionType;
+
+    #[test]
+    fn avro_container_files_and_confluent_messages() {
+        let schema = r#"{"type": "record", "name": "Transaction", "fields": [
+            {"name": "type", "type": {"type": "enum", "name": "Type", "symbols": ["deposit", "withdrawal"]}},
+            {"name": "client", "type": "int"},
+            {"name": "tx", "type": "long"},
+            {"name": "amount", "type": ["null", {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}]},
+            {"name": "timestamp", "type": ["null", {"type": "long", "logicalType": "timestamp-millis"}]}
+        ]}"#;
+        let Schema::Record(record) = serde_json::from_str(schema).unwrap() else {
+            unreachable!();
+        };
+
+        let row = |type_: i64, tx: i64, cents: &[u8]| {
+            let mut row = Vec::new();
+            for value in [type_, 1, tx, 1, cents.len() as i64] {
+                zigzag_encode(value, &mut row).unwrap();
+            }
+            row.extend_from_slice(cents);
+            zigzag_encode(1, &mut row).unwrap();
+            zigzag_encode(1_704_186_000_000, &mut row).unwrap();
+            row
+        };
+        let mut file = Vec::new();
+        write_metadata(&mut file, record, None).unwrap();
+        write_block(&mut file, &CompressedBlock::new(2, [row(0, 1, &[0x04, 0x1a]), row(1, 2, &[])].concat())).unwrap();
+
+        let records = records_from_avro_reader(file.as_slice()).unwrap();
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.transaction, Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10.50").unwrap()))
+            .with_timestamp(DateTime::from_str("2024-01-02T09:00:00Z").unwrap()));
+        assert_eq!(records[1].as_ref().unwrap().transaction.type_(), TransactionType::Withdrawal);
+
+        let mut registry = SchemaRegistry::new("http://localhost:8081");
+        registry.schemas.insert(7, serde_json::from_str(schema).unwrap());
+        let message = [&[CONFLUENT_MAGIC, 0, 0, 0, 7][..], &row(0, 3, &[0x01])].concat();
+        assert_eq!(registry.decode(&message).unwrap().amount(), Some(&BigDecimal::from_str("0.01").unwrap()));
+        assert!(registry.decode(b"{}").is_err());
+    }
+}
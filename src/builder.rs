@@ -0,0 +1,311 @@
+use chrono::TimeDelta;
+
+use crate::{BalanceHistory, Engine, EngineObserver, FeeSchedule, FreezePolicy, Interest, Ledger, Limits, LockPolicy, MemoryStorage, OverdraftPolicy, Ownership, Precision, Retention, Roster, Storage, VelocityPolicy, observer::Observers};
+#[cfg(feature = "scripting")]
+use crate::Script;
+
+/// A builder for an [`Engine`], configuring each of its policies and the storage backend it keeps accounts in.
+///
+/// Every setting starts at the same default as [`Engine::new`], so only the policies that differ need to be given.
+#[derive(Debug, Default)]
+pub struct EngineBuilder<S = MemoryStorage> {
+    /// The storage backend holding every client and transaction.
+    storage: S,
+
+    /// Whether operator-only transactions, such as unlock, are accepted.
+    admin: bool,
+
+    /// The number of decimal places every amount is rounded to.
+    precision: Precision,
+
+    /// How far below zero available funds may go.
+    overdraft: OverdraftPolicy,
+
+    /// The largest amounts a transaction may move and an account may hold.
+    limits: Limits,
+
+    /// The limits on the funds that may leave each client's account, if any.
+    velocity: Option<VelocityPolicy>,
+
+    /// The known clients, whose accounts are reported even without any activity, if any are known.
+    roster: Option<Roster>,
+
+    /// The owners of every joint account, if any are shared.
+    ownership: Option<Ownership>,
+
+    /// The tenant whose books the engine keeps, or `None` for the default books.
+    tenant: Option<String>,
+
+    /// Which transactions a locked account still accepts.
+    lock_policy: LockPolicy,
+
+    /// Which transactions an account frozen by an operator still accepts.
+    freeze_policy: FreezePolicy,
+
+    /// How long after a transaction it can still be disputed, or `None` for no limit.
+    dispute_window: Option<TimeDelta>,
+
+    /// Whether a representment unlocks the account its chargeback locked.
+    unlock_on_representment: bool,
+
+    /// The fees charged on transactions, and the account they are credited to, if any.
+    fees: Option<FeeSchedule>,
+
+    /// The interest earned on available funds, if any.
+    interest: Option<Interest>,
+
+    /// Whether transactions that happened before the latest accepted transaction are rejected.
+    enforce_order: bool,
+
+    /// Which processed transactions are kept.
+    retention: Retention,
+
+    /// Whether the engine's invariants are checked after every transaction.
+    check_invariants: bool,
+
+    /// The double-entry ledger every movement of funds is posted to, if one is kept.
+    ledger: Option<Ledger>,
+
+    /// The state of every client after each change, if a history of balances is kept.
+    history: Option<BalanceHistory>,
+
+    /// Whether the activity of each client's account is counted.
+    activity: bool,
+
+    /// The script deciding whether each transaction is accepted, rejected or flagged before it is applied, if any.
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+
+    /// The observers notified of every processed transaction.
+    observers: Observers
+}
+
+impl EngineBuilder {
+    /// Create a new builder, with every setting at its default and accounts kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Storage> EngineBuilder<S> {
+    /// Keep accounts and transactions in another storage backend, rather than in memory.
+    pub fn storage<T: Storage>(self, storage: T) -> EngineBuilder<T> {
+        EngineBuilder {
+            storage,
+            admin: self.admin,
+            precision: self.precision,
+            overdraft: self.overdraft,
+            limits: self.limits,
+            velocity: self.velocity,
+            roster: self.roster,
+            ownership: self.ownership,
+            tenant: self.tenant,
+            lock_policy: self.lock_policy,
+            freeze_policy: self.freeze_policy,
+            dispute_window: self.dispute_window,
+            unlock_on_representment: self.unlock_on_representment,
+            fees: self.fees,
+            interest: self.interest,
+            enforce_order: self.enforce_order,
+            retention: self.retention,
+            check_invariants: self.check_invariants,
+            ledger: self.ledger,
+            history: self.history,
+            activity: self.activity,
+            #[cfg(feature = "scripting")]
+            script: self.script,
+            observers: self.observers
+        }
+    }
+
+    /// Accept operator-only transactions, such as unlock.
+    pub fn admin(mut self, admin: bool) -> Self {
+        self.admin = admin;
+        self
+    }
+
+    /// Round every amount to this precision as it is processed.
+    pub fn precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// How far below zero withdrawals, transfers and conversions may take available funds.
+    pub fn overdraft(mut self, overdraft: OverdraftPolicy) -> Self {
+        self.overdraft = overdraft;
+        self
+    }
+
+    /// The largest amount a transaction may move, and the most funds an account may hold in a currency.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// The largest amount a client may withdraw at once, and the most withdrawals and outflow it may make in a day.
+    pub fn velocity(mut self, velocity: Option<VelocityPolicy>) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// Report the account of every known client, even without any activity, and reject transactions of clients that are not known if the roster does.
+    pub fn roster(mut self, roster: Option<Roster>) -> Self {
+        self.roster = roster;
+        self
+    }
+
+    /// Apply the transactions of every owner of a joint account to its shared balance.
+    pub fn ownership(mut self, ownership: Option<Ownership>) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Keep the books of a tenant, rather than the default books.
+    pub fn tenant(mut self, tenant: Option<String>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// Which transactions a locked account still accepts.
+    pub fn lock_policy(mut self, lock_policy: LockPolicy) -> Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    /// Accept the transactions a freeze policy permits on frozen accounts.
+    pub fn freeze_policy(mut self, freeze_policy: FreezePolicy) -> Self {
+        self.freeze_policy = freeze_policy;
+        self
+    }
+
+    /// Reject disputes of transactions that happened longer ago than the window, or accept them at any age with `None`.
+    pub fn dispute_window(mut self, dispute_window: Option<TimeDelta>) -> Self {
+        self.dispute_window = dispute_window;
+        self
+    }
+
+    /// Unlock the account when a representment reverses its chargeback.
+    pub fn unlock_on_representment(mut self, unlock_on_representment: bool) -> Self {
+        self.unlock_on_representment = unlock_on_representment;
+        self
+    }
+
+    /// Charge the fees of a schedule on every transaction, crediting them to its fee account.
+    pub fn fees(mut self, fees: Option<FeeSchedule>) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Accrue interest on available funds daily, posting it at the end of each interest period.
+    pub fn interest(mut self, interest: Option<Interest>) -> Self {
+        self.interest = interest;
+        self
+    }
+
+    /// Reject any transaction with a timestamp earlier than the latest accepted transaction.
+    pub fn enforce_order(mut self, enforce_order: bool) -> Self {
+        self.enforce_order = enforce_order;
+        self
+    }
+
+    /// Which processed transactions are kept.
+    pub fn retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Check the engine's invariants after every transaction.
+    pub fn check_invariants(mut self, check_invariants: bool) -> Self {
+        self.check_invariants = check_invariants;
+        self
+    }
+
+    /// Post every movement of funds to a double-entry ledger.
+    pub fn ledger(mut self, ledger: Option<Ledger>) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Keep a history of the balances of every client, so the state of an account can be queried as of any instant.
+    pub fn history(mut self, history: Option<BalanceHistory>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Count the transactions of each client that were accepted and rejected, its open disputes, and its latest activity.
+    pub fn activity(mut self, activity: bool) -> Self {
+        self.activity = activity;
+        self
+    }
+
+    /// Run every transaction past a script before it is applied, which may reject it or flag it for review.
+    #[cfg(feature = "scripting")]
+    pub fn script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Notify an observer of every transaction the engine processes, after any observers already given.
+    pub fn observer<O: EngineObserver + 'static>(mut self, observer: O) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Build the engine.
+    pub fn build(self) -> Engine<S> {
+        let engine = Engine::with_storage(self.storage)
+            .with_admin(self.admin)
+            .with_precision(self.precision)
+            .with_overdraft(self.overdraft)
+            .with_limits(self.limits)
+            .with_velocity(self.velocity)
+            .with_roster(self.roster)
+            .with_ownership(self.ownership)
+            .with_tenant(self.tenant)
+            .with_lock_policy(self.lock_policy)
+            .with_freeze_policy(self.freeze_policy)
+            .with_dispute_window(self.dispute_window)
+            .with_unlock_on_representment(self.unlock_on_representment)
+            .with_fees(self.fees)
+            .with_interest(self.interest)
+            .with_enforce_order(self.enforce_order)
+            .with_retention(self.retention)
+            .with_check_invariants(self.check_invariants)
+            .with_ledger(self.ledger)
+            .with_history(self.history)
+            .with_activity(self.activity)
+            .with_observers(self.observers);
+
+        #[cfg(feature = "scripting")]
+        let engine = engine.with_script(self.script);
+        engine
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Applied, Rounding, SpillStorage, Transaction, TransactionError, TransactionType};
+
+    #[test]
+    fn build_with_pluggable_policies() {
+        let mut engine = EngineBuilder::new()
+            .precision(Precision::new(2, Rounding::HalfUp))
+            .overdraft(OverdraftPolicy::Limit(BigDecimal::from(5)))
+            .storage(SpillStorage::in_temp_dir(1).unwrap())
+            .build();
+
+        let amount = |value: &str| Some(BigDecimal::from_str(value).unwrap());
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, amount("10"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 2, 2, amount("15"))), Ok(Applied::Withdrew { amount: BigDecimal::from(15), fee: None }));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 2, 3, amount("0.01"))), Err(TransactionError::InsufficientFunds));
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 4, amount("5"))).unwrap();
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.iter().map(|client| (client.id(), client.available())).collect::<Vec<_>>(), vec![(1, BigDecimal::from(-5)), (2, BigDecimal::from(-5))]);
+    }
+}
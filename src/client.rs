@@ -0,0 +1,461 @@
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+
+use crate::{Fixed, MAX_INTEGER_DIGITS, Transaction, TransactionError, TransactionType, precision::integer_digits};
+
+/// The representation balances are kept in, which is a fixed-point number with the `fixed-point` feature.
+#[cfg(not(feature = "fixed-point"))]
+pub(crate) type Amount = BigDecimal;
+
+/// The representation balances are kept in, which is a fixed-point number with the `fixed-point` feature.
+#[cfg(feature = "fixed-point")]
+pub(crate) type Amount = Fixed;
+
+/// The arithmetic needed to keep a balance, with every operation able to fail if the result can not be represented.
+pub(crate) trait Arithmetic: Sized + PartialOrd {
+    /// An amount of zero.
+    fn zero() -> Self;
+
+    /// Convert a decimal amount, as submitted in a transaction.
+    fn from_decimal(amount: &BigDecimal) -> Result<Self, TransactionError>;
+
+    /// Convert the amount back to a decimal.
+    fn as_decimal(&self) -> BigDecimal;
+
+    /// Add two amounts.
+    fn add(&self, other: &Self) -> Result<Self, TransactionError>;
+
+    /// Subtract an amount.
+    fn sub(&self, other: &Self) -> Result<Self, TransactionError>;
+}
+
+impl Arithmetic for BigDecimal {
+    fn zero() -> Self {
+        <BigDecimal as bigdecimal::Zero>::zero()
+    }
+
+    fn from_decimal(amount: &BigDecimal) -> Result<Self, TransactionError> {
+        Ok(amount.clone())
+    }
+
+    fn as_decimal(&self) -> BigDecimal {
+        self.clone()
+    }
+
+    // NOTE: A balance is kept to the same range as any amount, so adding up large amounts can not grow it without bound.
+    fn add(&self, other: &Self) -> Result<Self, TransactionError> {
+        in_range(self + other)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, TransactionError> {
+        in_range(self - other)
+    }
+}
+
+/// Convert a decimal amount to the representation balances are kept in.
+pub(crate) fn to_amount(amount: &BigDecimal) -> Result<Amount, TransactionError> {
+    Amount::from_decimal(amount)
+}
+
+/// Reject a balance with more integer digits than any amount may have.
+fn in_range(amount: BigDecimal) -> Result<BigDecimal, TransactionError> {
+    match integer_digits(&amount) > MAX_INTEGER_DIGITS {
+        true => Err(TransactionError::AmountOutOfRange),
+        false => Ok(amount)
+    }
+}
+
+impl Arithmetic for Fixed {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn from_decimal(amount: &BigDecimal) -> Result<Self, TransactionError> {
+        Self::try_from(amount)
+    }
+
+    fn as_decimal(&self) -> BigDecimal {
+        self.to_decimal()
+    }
+
+    fn add(&self, other: &Self) -> Result<Self, TransactionError> {
+        self.checked_add(*other).ok_or(TransactionError::AmountOutOfRange)
+    }
+
+    fn sub(&self, other: &Self) -> Result<Self, TransactionError> {
+        self.checked_sub(*other).ok_or(TransactionError::AmountOutOfRange)
+    }
+}
+
+/// The funds a client holds in a single currency of one of its sub-accounts.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Balance {
+    /// The sub-account holding the funds, such as `escrow`, or `None` for the main account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+
+    /// The currency of the funds, or `None` for the default currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+
+    /// The total funds that are available for trading, staking, withdrawal, etc.
+    available: Amount,
+
+    /// The total funds that are held for dispute.
+    held: Amount,
+
+    /// The total funds that are available or held.
+    total: Amount
+}
+
+impl Balance {
+    /// Create a new empty balance in a currency.
+    pub fn new(currency: Option<String>) -> Self {
+        Self {
+            account: None,
+            currency,
+            available: Amount::zero(),
+            held: Amount::zero(),
+            total: Amount::zero()
+        }
+    }
+
+    /// Hold the funds in a sub-account, or `None` for the main account.
+    pub fn in_account(mut self, account: Option<String>) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// The sub-account holding the funds, or `None` for the main account.
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    /// The currency of the funds, or `None` for the default currency.
+    pub fn currency(&self) -> Option<&str> {
+        self.currency.as_deref()
+    }
+
+    /// The total funds that are available for trading, staking, withdrawal, etc.
+    pub fn available(&self) -> BigDecimal {
+        self.available.as_decimal()
+    }
+
+    /// The total funds that are held for dispute.
+    pub fn held(&self) -> BigDecimal {
+        self.held.as_decimal()
+    }
+
+    /// The total funds that are available or held.
+    pub fn total(&self) -> BigDecimal {
+        self.total.as_decimal()
+    }
+
+    /// Create a balance holding existing funds, with the total being the sum of the available and held funds.
+    pub(crate) fn from_parts(currency: Option<String>, available: &BigDecimal, held: &BigDecimal) -> Result<Self, TransactionError> {
+        let available = Amount::from_decimal(available)?;
+        let held = Amount::from_decimal(held)?;
+        let total = available.add(&held)?;
+        Ok(Self { account: None, currency, available, held, total })
+    }
+
+    /// Whether the balance has never held any funds, or has been emptied.
+    pub fn is_empty(&self) -> bool {
+        let zero = Amount::zero();
+        self.available == zero && self.held == zero && self.total == zero
+    }
+
+}
+
+/// A structure to represent a specific client's account.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Client {
+    /// The id associated with the client.
+    id: u16,
+
+    /// The funds held in each currency, starting with the default currency.
+    balances: Vec<Balance>,
+
+    /// Whether the account is locked.
+    locked: bool,
+
+    /// Whether the account was frozen by an operator, which is separate from the lock a chargeback places.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    frozen: bool,
+
+    /// Every client operating the account, if it is a joint account.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    owners: Vec<u16>
+}
+
+impl Client {
+    /// Create a new client with an empty balance in the default currency.
+    pub fn new(id: u16) -> Self {
+        Self {
+            id,
+            balances: vec![Balance::new(None)],
+            locked: false,
+            frozen: false,
+            owners: Vec::new()
+        }
+    }
+
+    /// Create a client from an existing balance in the default currency, as written by an older snapshot.
+    pub(crate) fn from_parts(id: u16, available: Amount, held: Amount, total: Amount, locked: bool) -> Self {
+        Self {
+            id,
+            balances: vec![Balance { account: None, currency: None, available, held, total }],
+            locked,
+            frozen: false,
+            owners: Vec::new()
+        }
+    }
+
+    /// Create a client holding existing balances, with an empty balance in the default currency of the main account
+    /// leading if there is none.
+    pub(crate) fn from_balances(id: u16, mut balances: Vec<Balance>, locked: bool) -> Self {
+        match balances.iter().position(|balance| balance.account().is_none() && balance.currency().is_none()) {
+            Some(index) => balances[..=index].rotate_right(1),
+            None => balances.insert(0, Balance::new(None))
+        }
+        Self { id, balances, locked, frozen: false, owners: Vec::new() }
+    }
+
+    /// The id associated with the client.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// The funds that are available for trading, staking, withdrawal, etc. in the default currency.
+    pub fn available(&self) -> BigDecimal {
+        self.balances[0].available()
+    }
+
+    /// The funds that are held for dispute in the default currency.
+    pub fn held(&self) -> BigDecimal {
+        self.balances[0].held()
+    }
+
+    /// The funds that are available or held in the default currency.
+    pub fn total(&self) -> BigDecimal {
+        self.balances[0].total()
+    }
+
+    /// Whether the account is locked.
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whether the account is frozen.
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// The balance of the main account in a currency, or `None` for the default currency, if the client has ever held it.
+    pub fn balance(&self, currency: Option<&str>) -> Option<&Balance> {
+        self.balance_in(None, currency)
+    }
+
+    /// The balance of a sub-account, or `None` for the main account, in a currency, if the client has ever held it.
+    pub fn balance_in(&self, account: Option<&str>, currency: Option<&str>) -> Option<&Balance> {
+        self.balances.iter().find(|balance| balance.account() == account && balance.currency() == currency)
+    }
+
+    /// Every balance of the client, starting with the default currency of the main account.
+    pub fn balances(&self) -> &[Balance] {
+        &self.balances
+    }
+
+    /// Get the balance of a sub-account in a currency, creating it if the client has never held it.
+    fn balance_mut(&mut self, account: Option<&str>, currency: Option<&str>) -> &mut Balance {
+        match self.balances.iter().position(|balance| balance.account() == account && balance.currency() == currency) {
+            Some(index) => &mut self.balances[index],
+            None => {
+                self.balances.push(Balance::new(currency.map(str::to_string)).in_account(account.map(str::to_string)));
+                self.balances.last_mut().unwrap()
+            }
+        }
+    }
+
+    // NOTE: If any of the following fail part way, the client is left partially updated, and must be discarded.
+
+    /// Credit the amount to the available and total funds of a sub-account in the currency.
+    pub(crate) fn deposit(&mut self, account: Option<&str>, currency: Option<&str>, amount: &Amount) -> Result<(), TransactionError> {
+        let balance = self.balance_mut(account, currency);
+
+        balance.available = balance.available.add(amount)?;
+        balance.total = balance.total.add(amount)?;
+        Ok(())
+    }
+
+    /// Add every balance of another copy of the account, such as that kept by another shard.
+    pub(crate) fn combine(&mut self, other: &Client) -> Result<(), TransactionError> {
+        for balance in &other.balances {
+            let combined = self.balance_mut(balance.account(), balance.currency());
+            combined.available = combined.available.add(&balance.available)?;
+            combined.held = combined.held.add(&balance.held)?;
+            combined.total = combined.total.add(&balance.total)?;
+        }
+        Ok(())
+    }
+
+    /// Debit the amount from the available and total funds of a sub-account in the currency, letting the available funds
+    /// go no further below zero than the overdraft. This fails, leaving the account untouched, if the amount is greater
+    /// than the available funds of that sub-account in that currency plus the overdraft.
+    pub(crate) fn withdraw(&mut self, account: Option<&str>, currency: Option<&str>, amount: &Amount, overdraft: &Amount) -> Result<(), TransactionError> {
+        let zero = Amount::zero();
+        let available = self.balance_in(account, currency).map_or(&zero, |balance| &balance.available);
+
+        if *amount > available.add(overdraft)? {
+            return Err(TransactionError::InsufficientFunds);
+        }
+
+        let balance = self.balance_mut(account, currency);
+
+        balance.available = balance.available.sub(amount)?;
+        balance.total = balance.total.sub(amount)?;
+        Ok(())
+    }
+
+    /// Move the amount from the available funds of one sub-account to those of another, in the currency.
+    /// This fails, leaving the account untouched, if the amount is greater than the available funds it is moved from.
+    pub(crate) fn move_funds(&mut self, from: Option<&str>, to: Option<&str>, currency: Option<&str>, amount: &Amount) -> Result<(), TransactionError> {
+        let mut moved = self.clone();
+        moved.withdraw(from, currency, amount, &Amount::zero())?;
+        moved.deposit(to, currency, amount)?;
+        *self = moved;
+        Ok(())
+    }
+
+    /// Hold the amount of an authorization, moving it from the available to the held funds of a sub-account in the
+    /// currency until it is captured or voided. This fails, leaving the account untouched, if the amount is greater than
+    /// the available funds of that sub-account in that currency plus the overdraft.
+    pub(crate) fn authorize(&mut self, account: Option<&str>, currency: Option<&str>, amount: &Amount, overdraft: &Amount) -> Result<(), TransactionError> {
+        let zero = Amount::zero();
+        let available = self.balance_in(account, currency).map_or(&zero, |balance| &balance.available);
+
+        if *amount > available.add(overdraft)? {
+            return Err(TransactionError::InsufficientFunds);
+        }
+
+        let balance = self.balance_mut(account, currency);
+
+        balance.available = balance.available.sub(amount)?;
+        balance.held = balance.held.add(amount)?;
+        Ok(())
+    }
+
+    /// Settle a captured authorization, debiting its held amount from the held and total funds.
+    pub(crate) fn capture(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        balance.held = balance.held.sub(&amount)?;
+        balance.total = balance.total.sub(&amount)?;
+        Ok(())
+    }
+
+    /// Release the held amount of a voided authorization back to the available funds.
+    pub(crate) fn void(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        balance.held = balance.held.sub(&amount)?;
+        balance.available = balance.available.add(&amount)?;
+        Ok(())
+    }
+
+    /// Hold the amount of a previous transaction until the dispute is settled.
+    /// A disputed deposit moves funds from available to held, while a disputed withdrawal adds the withdrawn funds to held.
+    pub(crate) fn dispute(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        if target.type_ == TransactionType::Withdrawal {
+            // The withdrawn funds are claimed back by the client, but are held until the dispute is settled.
+            balance.held = balance.held.add(&amount)?;
+            balance.total = balance.total.add(&amount)?;
+        } else {
+            balance.available = balance.available.sub(&amount)?;
+            balance.held = balance.held.add(&amount)?;
+        }
+        Ok(())
+    }
+
+    /// Release the held amount of a disputed transaction, leaving the original transaction in place.
+    /// A resolved deposit returns funds to available, while a resolved withdrawal removes them from the account again.
+    pub(crate) fn resolve(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        if target.type_ == TransactionType::Withdrawal {
+            // The withdrawal stands, so the held funds leave the account again.
+            balance.held = balance.held.sub(&amount)?;
+            balance.total = balance.total.sub(&amount)?;
+        } else {
+            balance.held = balance.held.sub(&amount)?;
+            balance.available = balance.available.add(&amount)?;
+        }
+        Ok(())
+    }
+
+    /// Reverse a disputed transaction using the held amount, and lock the account.
+    /// A charged back deposit removes the funds from the account, while a charged back withdrawal returns them to available.
+    pub(crate) fn chargeback(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        if target.type_ == TransactionType::Withdrawal {
+            // The withdrawal is reversed, so the held funds are returned to the client.
+            balance.held = balance.held.sub(&amount)?;
+            balance.available = balance.available.add(&amount)?;
+        } else {
+            balance.held = balance.held.sub(&amount)?;
+            balance.total = balance.total.sub(&amount)?;
+        }
+        self.locked = true;
+        Ok(())
+    }
+
+    /// Reverse the chargeback of a transaction, restoring the original transaction, without touching the locked flag.
+    /// A represented deposit credits the funds to available again, while a represented withdrawal debits them again.
+    pub(crate) fn represent(&mut self, target: &Transaction) -> Result<(), TransactionError> {
+        let amount = Amount::from_decimal(target.amount.as_ref().unwrap())?;
+        let balance = self.balance_mut(target.account(), target.currency());
+
+        if target.type_ == TransactionType::Withdrawal {
+            balance.available = balance.available.sub(&amount)?;
+            balance.total = balance.total.sub(&amount)?;
+        } else {
+            balance.available = balance.available.add(&amount)?;
+            balance.total = balance.total.add(&amount)?;
+        }
+        Ok(())
+    }
+
+    /// Clear the locked flag, so that transactions are processed again.
+    pub(crate) fn unlock(&mut self) {
+        self.locked = false;
+    }
+
+    /// Set or clear the frozen flag.
+    pub(crate) fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Every client operating the account, in order, or none if it is not a joint account.
+    pub fn owners(&self) -> &[u16] {
+        &self.owners
+    }
+
+    /// Set every client operating the account.
+    pub(crate) fn set_owners(&mut self, owners: Vec<u16>) {
+        self.owners = owners;
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
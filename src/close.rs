@@ -0,0 +1,66 @@
+use chrono::NaiveDate;
+
+use crate::{Client, Storage, Tenants, TransactionError};
+
+/// The closing balances of the books of every tenant at the end of a day, as reported for that day.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailyClose {
+    /// The day that was closed.
+    pub day: NaiveDate,
+
+    /// Every client account of the books of each tenant, or `None` for the default books, with the default books first.
+    pub books: Vec<(Option<String>, Vec<Client>)>
+}
+
+impl DailyClose {
+    /// Close the day in the books of every tenant, as [`Engine::close_day`](crate::Engine::close_day) does.
+    pub fn close<S: Storage>(tenants: &mut Tenants<'_, S>, day: NaiveDate) -> Result<Self, TransactionError> {
+        let books = tenants.iter_mut()
+            .map(|(tenant, engine)| engine.close_day(day).map(|clients| (tenant.map(str::to_string), clients)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { day, books })
+    }
+
+    /// Every client account, alongside the tenant whose books hold it.
+    pub(crate) fn clients(&self) -> impl Iterator<Item = (Option<&str>, &Client)> {
+        self.books.iter().flat_map(|(tenant, clients)| clients.iter().map(move |client| (tenant.as_deref(), client)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta};
+
+    use super::*;
+    use crate::{Engine, OutputFormat, Precision, Transaction, TransactionType};
+
+    #[test]
+    fn close_each_day_with_its_balances() {
+        let at = |days: i64| DateTime::from_timestamp(days * 86_400 + 3_600, 0).unwrap();
+        let day = |days: i64| at(days).date_naive();
+
+        let mut tenants = Tenants::from(Engine::new());
+        tenants.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())).with_timestamp(at(0))).unwrap();
+        tenants.process(&Transaction::new(TransactionType::Deposit, 2, 2, Some(4.into())).with_timestamp(at(0)).with_schedule(at(1) - TimeDelta::hours(2), None)).unwrap();
+        let first = DailyClose::close(&mut tenants, day(0)).unwrap();
+        tenants.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, Some(3.into())).with_timestamp(at(1))).unwrap();
+        let second = DailyClose::close(&mut tenants, day(1)).unwrap();
+
+        // NOTE: The deposit scheduled for the last hour of the first day is applied when it closes.
+        assert_eq!(first.books[0].1.iter().map(|client| client.id()).collect::<Vec<_>>(), vec![1, 2]);
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_closes(&mut output, &[first, second.clone()], Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+date,id,available,held,total,locked
+1970-01-01,1,10.0000,0.0000,10.0000,false
+1970-01-01,2,4.0000,0.0000,4.0000,false
+1970-01-02,1,7.0000,0.0000,7.0000,false
+1970-01-02,2,4.0000,0.0000,4.0000,false
+");
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_close(&mut output, &second, Precision::default()).unwrap();
+        assert!(String::from_utf8(output).unwrap().starts_with("id,available,held,total,locked\n1,7.0000"));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{io, sync::Arc};
+
+use arrow::{array::{ArrayRef, BooleanArray, Date32Array, Decimal128Array, StringArray, UInt16Array, UInt32Array, UInt64Array}, datatypes::{DataType, Field, Schema}, ipc::writer::FileWriter, record_batch::RecordBatch};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use parquet::arrow::ArrowWriter;
+
+use crate::{MAIN_ACCOUNT, output::Account};
+
+/// The largest number of digits an Arrow 128-bit decimal can hold.
+const DECIMAL_PRECISION: u8 = 38;
+
+/// Convert an amount, already rounded to the scale, to the unscaled integer of a fixed point decimal.
+fn unscaled(amount: &BigDecimal, scale: i64) -> io::Result<i128> {
+    let (digits, exponent) = amount.with_scale(scale).into_bigint_and_exponent();
+    debug_assert_eq!(exponent, scale);
+
+    digits.to_i128()
+        .filter(|digits| digits.unsigned_abs() < 10u128.pow(DECIMAL_PRECISION as u32))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("amount {} is too large for a decimal column", amount)))
+}
+
+/// Build a single record batch of every account, with amounts as decimals of the given scale.
+fn record_batch(accounts: &[Account], scale: i64) -> io::Result<RecordBatch> {
+    let decimal_scale = i8::try_from(scale)
+        .ok()
+        .filter(|scale| (0..=DECIMAL_PRECISION as i8).contains(scale))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("a scale of {} can not be written as a decimal column", scale)))?;
+    let decimal = DataType::Decimal128(DECIMAL_PRECISION, decimal_scale);
+
+    let amounts = |amount: for<'r> fn(&'r Account<'_>) -> &'r BigDecimal| -> io::Result<ArrayRef> {
+        let values = accounts.iter()
+            .map(|account| unscaled(amount(account), scale))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Decimal128Array::from(values)
+            .with_precision_and_scale(DECIMAL_PRECISION, decimal_scale)
+            .map(|array| Arc::new(array) as ArrayRef)
+            .map_err(io::Error::other)
+    };
+
+    let mut fields = vec![
+        Field::new("tenant", DataType::Utf8, true),
+        Field::new("id", DataType::UInt16, false),
+        Field::new("account", DataType::Utf8, true),
+        Field::new("currency", DataType::Utf8, true),
+        Field::new("available", decimal.clone(), false),
+        Field::new("held", decimal.clone(), false),
+        Field::new("total", decimal, false),
+        Field::new("locked", DataType::Boolean, false),
+    ];
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(accounts.iter().map(|account| account.tenant.filter(|tenant| !tenant.is_empty())).collect::<StringArray>()),
+        Arc::new(accounts.iter().map(|account| account.id).collect::<UInt16Array>()),
+        Arc::new(accounts.iter().map(|account| account.account.filter(|account| *account != MAIN_ACCOUNT)).collect::<StringArray>()),
+        // NOTE: Unlike the text formats, the default currency is always written as a null.
+        Arc::new(accounts.iter().map(|account| account.currency.filter(|currency| !currency.is_empty())).collect::<StringArray>()),
+        amounts(|account| &account.available)?,
+        amounts(|account| &account.held)?,
+        amounts(|account| &account.total)?,
+        Arc::new(accounts.iter().map(|account| Some(account.locked)).collect::<BooleanArray>()),
+    ];
+
+    // NOTE: The activity of each account is only written when it is asked for, with a missing timestamp as a null.
+    if accounts.iter().any(|account| account.processed.is_some()) {
+        fields.extend([
+            Field::new("processed", DataType::UInt64, false),
+            Field::new("rejected", DataType::UInt64, false),
+            Field::new("open_disputes", DataType::UInt32, false),
+            Field::new("last_activity", DataType::Utf8, true),
+        ]);
+        columns.extend([
+            Arc::new(accounts.iter().map(|account| account.processed).collect::<UInt64Array>()) as ArrayRef,
+            Arc::new(accounts.iter().map(|account| account.rejected).collect::<UInt64Array>()),
+            Arc::new(accounts.iter().map(|account| account.open_disputes).collect::<UInt32Array>()),
+            Arc::new(accounts.iter().map(|account| account.last_activity.as_deref().filter(|at| !at.is_empty())).collect::<StringArray>()),
+        ]);
+    }
+
+    // NOTE: The day of each row is only written for the closing balances of several days, as days since the epoch.
+    if accounts.iter().any(|account| account.date.is_some()) {
+        fields.insert(0, Field::new("date", DataType::Date32, false));
+        columns.insert(0, Arc::new(accounts.iter().map(|account| account.date.map(|date| date.to_epoch_days())).collect::<Date32Array>()));
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(io::Error::other)
+}
+
+/// Write every account as a Parquet file.
+pub(crate) fn write_parquet<W: io::Write>(mut writer: W, accounts: &[Account], scale: i64) -> io::Result<()> {
+    let batch = record_batch(accounts, scale)?;
+
+    // NOTE: The Parquet writer needs its sink to be `Send`, so the file is built in memory first.
+    let mut buffer = Vec::new();
+    let mut parquet = ArrowWriter::try_new(&mut buffer, batch.schema(), None).map_err(io::Error::other)?;
+    parquet.write(&batch).map_err(io::Error::other)?;
+    parquet.close().map_err(io::Error::other)?;
+
+    writer.write_all(&buffer)?;
+    writer.flush()
+}
+
+/// Write every account as an Arrow IPC file.
+pub(crate) fn write_arrow<W: io::Write>(writer: W, accounts: &[Account], scale: i64) -> io::Result<()> {
+    let batch = record_batch(accounts, scale)?;
+
+    let mut arrow = FileWriter::try_new(writer, &batch.schema()).map_err(io::Error::other)?;
+    arrow.write(&batch).map_err(io::Error::other)?;
+    arrow.finish().map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::ipc::reader::FileReader;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use super::*;
+    use crate::{Engine, OutputFormat, Precision, Transaction, TransactionType};
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, Some("1.5".parse().unwrap()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some("12345678901234.0001".parse().unwrap()))).unwrap();
+        engine
+    }
+
+    fn check(batch: &RecordBatch) {
+        let available = batch.column_by_name("available").unwrap().as_any().downcast_ref::<Decimal128Array>().unwrap();
+
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(available.value_as_string(0), "12345678901234.0001");
+        assert_eq!(available.value_as_string(1), "1.5000");
+    }
+
+    #[test]
+    fn parquet_output() {
+        let mut output = Vec::new();
+        OutputFormat::Parquet.write(&mut output, &engine().clients().unwrap(), Precision::default()).unwrap();
+
+        let mut reader = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(output)).unwrap().build().unwrap();
+        check(&reader.next().unwrap().unwrap());
+    }
+
+    #[test]
+    fn arrow_output() {
+        let mut output = Vec::new();
+        OutputFormat::Arrow.write(&mut output, &engine().clients().unwrap(), Precision::default()).unwrap();
+
+        let mut reader = FileReader::try_new(io::Cursor::new(output), None).unwrap();
+        check(&reader.next().unwrap().unwrap());
+    }
+}
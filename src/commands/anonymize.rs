@@ -0,0 +1,60 @@
+use std::{fs::File, io, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use bigdecimal::BigDecimal;
+use clap::Args;
+use transaction_system::{Anonymizer, decompress};
+
+use super::{CommandError, ErrorKind, write_atomically};
+
+/// Rewrite a CSV input as shareable test data, with client ids remapped, amounts perturbed and other columns stripped.
+#[derive(Args, Debug)]
+pub struct AnonymizeArgs {
+    /// The CSV input file to anonymize, which may be compressed.
+    #[arg(value_name = "INPUT")]
+    input: PathBuf,
+
+    /// How far, as a percentage, the amounts of each client may be scaled either way.
+    #[arg(long, value_name = "PERCENT", default_value = "5", value_parser = parse_percentage)]
+    perturbation: BigDecimal,
+
+    /// The seed remapping clients and perturbing amounts, where the same seed always rewrites the same input the same way.
+    /// Without one, a seed is drawn from the clock, so the original ids and amounts can not be worked out from the output.
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// The file to write the anonymized transactions to, instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Parse a percentage of at least 0 and below 100, so no amount is scaled to nothing.
+fn parse_percentage(s: &str) -> Result<BigDecimal, String> {
+    s.parse::<BigDecimal>().ok()
+        .filter(|percentage| (BigDecimal::from(0)..BigDecimal::from(100)).contains(percentage))
+        .ok_or_else(|| format!("invalid percentage '{}', expected a number from 0 up to 100", s))
+}
+
+/// Rewrite every row of the input, keeping transaction ids so disputes still reference the transaction they did,
+/// and print how many rows and clients were rewritten to stderr.
+pub fn run(args: AnonymizeArgs) -> Result<(), CommandError> {
+    let name = args.input.display();
+    let file = File::open(&args.input)
+        .map_err(|e| CommandError::io(&e, format!("unable to open input file '{}': {}", name, e)))?;
+    let source = decompress(io::BufReader::new(file))
+        .map_err(|e| CommandError::io(&e, format!("unable to read input file '{}': {}", name, e)))?;
+
+    let seed = args.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_nanos() as u64));
+    let mut anonymizer = Anonymizer::new(args.perturbation.clone(), seed);
+    let mut rows = 0;
+    let written = match &args.output {
+        Some(output) => write_atomically(output, |writer| anonymizer.rewrite(source, writer).map(|count| rows = count)),
+        None => anonymizer.rewrite(source, io::stdout().lock()).map(|count| rows = count)
+    };
+    written.map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidData => CommandError::new(ErrorKind::Parse, format!("invalid transactions in '{}': {}", name, e)),
+        _ => CommandError::io(&e, format!("unable to anonymize '{}': {}", name, e))
+    })?;
+
+    eprintln!("{} rows of {} clients anonymized", rows, anonymizer.clients());
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use clap::Args;
+use serde::Serialize;
+use transaction_system::{AccountChange, AccountDiff, MAIN_ACCOUNT, diff_accounts};
+
+use super::{CommandError, PrecisionArgs, read_accounts};
+
+/// An enumeration of the formats the differences between two sets of accounts can be printed in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// An aligned table, with a row for each difference.
+    #[default]
+    Table,
+
+    /// A single JSON array of difference objects.
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown diff format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for DiffFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Compare two account outputs, and print how each account changed.
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// The earlier CSV account file.
+    #[arg(value_name = "OLD")]
+    old: PathBuf,
+
+    /// The later CSV account file.
+    #[arg(value_name = "NEW")]
+    new: PathBuf,
+
+    /// The format to print the differences in (table or json).
+    #[arg(long, default_value_t)]
+    format: DiffFormat,
+
+    #[command(flatten)]
+    precision: PrecisionArgs,
+}
+
+/// A difference as printed, with the changes in funds written to the precision.
+#[derive(Debug, Serialize)]
+struct Row {
+    client: u16,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+
+    change: &'static str,
+    available: String,
+    held: String,
+    total: String,
+
+    /// Whether the account was newly locked or unlocked, if either.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    locked: Option<&'static str>
+}
+
+/// Print the balance changes, newly locked or unlocked accounts, and clients that appeared or disappeared between two account files.
+pub fn run(args: DiffArgs) -> Result<(), CommandError> {
+    let precision = args.precision.precision();
+    let old = read_accounts(&args.old)?;
+    let new = read_accounts(&args.new)?;
+
+    let rows = diff_accounts(&old, &new).into_iter()
+        .map(|diff: AccountDiff| Row {
+            client: diff.client,
+            account: diff.account,
+            currency: diff.currency,
+            change: match diff.change {
+                AccountChange::Appeared => "appeared",
+                AccountChange::Disappeared => "disappeared",
+                AccountChange::Changed => "changed",
+            },
+            available: precision.format(&diff.available),
+            held: precision.format(&diff.held),
+            total: precision.format(&diff.total),
+            locked: diff.locked.map(|locked| if locked { "locked" } else { "unlocked" })
+        })
+        .collect::<Vec<_>>();
+
+    match args.format {
+        DiffFormat::Json => println!("{}", serde_json::to_string(&rows).map_err(|e| e.to_string())?),
+        DiffFormat::Table => {
+            // NOTE: The sub-account column is only printed when any balance of a sub-account changed.
+            let accounts = rows.iter().any(|row| row.account.is_some());
+            let account = |account: &str| match accounts {
+                true => format!("{:<10}  ", account),
+                false => String::new()
+            };
+            println!("{:>8}  {}{:<8}  {:<11}  {:>20}  {:>20}  {:>20}  locked", "client", account("account"), "currency", "change", "available", "held", "total");
+            for row in &rows {
+                println!("{:>8}  {}{:<8}  {:<11}  {:>20}  {:>20}  {:>20}  {}",
+                    row.client,
+                    account(row.account.as_deref().unwrap_or(MAIN_ACCOUNT)),
+                    row.currency.as_deref().unwrap_or_default(),
+                    row.change,
+                    row.available,
+                    row.held,
+                    row.total,
+                    row.locked.unwrap_or_default());
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{fmt, io};
+
+use transaction_system::{AuditError, JournalError, SnapshotError, TransactionError};
+
+/// An enumeration of the classes of failure a command can end with, each exiting with its own code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Any other failure, such as invalid arguments or a failure to write the output.
+    Other,
+
+    /// A file does not exist.
+    NotFound,
+
+    /// A file could not be read or written due to its permissions.
+    PermissionDenied,
+
+    /// An input, snapshot or journal is malformed.
+    Parse,
+
+    /// A transaction broke one of the engine's invariants, replaying a journal diverged from what was recorded,
+    /// an audit log was tampered with, or merged accounts conflict.
+    Invariant,
+
+    /// A transaction was rejected while failing on the first rejection, as in strict mode.
+    Rejected,
+}
+
+impl ErrorKind {
+    /// The code the process exits with.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Other => 1,
+            Self::NotFound => 2,
+            Self::PermissionDenied => 3,
+            Self::Parse => 4,
+            Self::Invariant => 5,
+            Self::Rejected => 6,
+        }
+    }
+
+    /// The class of an I/O error, where it has one of its own.
+    pub fn of_io(e: &io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Self::NotFound,
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            _ => Self::Other
+        }
+    }
+}
+
+/// An error that stops a command, with the class of failure deciding the exit code.
+#[derive(Debug)]
+pub struct CommandError {
+    /// The class of failure.
+    pub kind: ErrorKind,
+
+    /// A description of what failed, to report to stderr.
+    pub message: String
+}
+
+impl CommandError {
+    /// Create a new error of a class of failure.
+    pub fn new<M: Into<String>>(kind: ErrorKind, message: M) -> Self {
+        Self { kind, message: message.into() }
+    }
+
+    /// Create a new error for a failed file operation, classed by the I/O error.
+    pub fn io<M: Into<String>>(e: &io::Error, message: M) -> Self {
+        Self::new(ErrorKind::of_io(e), message)
+    }
+
+    /// Create a new error for a snapshot that could not be read or written.
+    pub fn snapshot<M: Into<String>>(e: &SnapshotError, message: M) -> Self {
+        let kind = match e {
+            SnapshotError::Io(e) => ErrorKind::of_io(e),
+            SnapshotError::InvalidMagic | SnapshotError::UnsupportedVersion(_) | SnapshotError::Format(_) => ErrorKind::Parse,
+        };
+        Self::new(kind, message)
+    }
+
+    /// Create a new error for a journal that could not be replayed.
+    pub fn journal<M: Into<String>>(e: &JournalError, message: M) -> Self {
+        let kind = match e {
+            JournalError::Io(e) => ErrorKind::of_io(e),
+            JournalError::Format(..) => ErrorKind::Parse,
+            JournalError::Diverged(..) => ErrorKind::Invariant,
+        };
+        Self::new(kind, message)
+    }
+
+    /// Create a new error for a transaction that stopped the run, as it was rejected in strict mode or broke an invariant.
+    pub fn rejected<M: Into<String>>(e: &TransactionError, message: M) -> Self {
+        let kind = match e.is_violation() {
+            true => ErrorKind::Invariant,
+            false => ErrorKind::Rejected
+        };
+        Self::new(kind, message)
+    }
+
+    /// Create a new error for an audit log that failed to verify.
+    pub fn audit<M: Into<String>>(e: &AuditError, message: M) -> Self {
+        let kind = match e {
+            AuditError::Io(e) => ErrorKind::of_io(e),
+            AuditError::Format(..) => ErrorKind::Parse,
+            AuditError::BrokenChain(_) | AuditError::Tampered(_) => ErrorKind::Invariant,
+        };
+        Self::new(kind, message)
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Other, message)
+    }
+}
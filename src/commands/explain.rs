@@ -0,0 +1,119 @@
+use clap::Args;
+use transaction_system::{Balance, Client, Precision};
+
+use super::{CommandError, InputArgs};
+
+/// Process every transaction, and print the lifecycle of a single one.
+#[derive(Args, Debug)]
+pub struct ExplainArgs {
+    /// The id of the transaction to explain.
+    #[arg(long, value_name = "ID")]
+    tx: u32,
+
+    #[command(flatten)]
+    pub input: InputArgs,
+}
+
+/// Process every transaction in order, printing each one that carries the id being explained.
+/// This covers the transaction itself, every dispute, resolve and chargeback of it, and any later reuse of its id,
+/// alongside whether each was accepted and how it changed the balances of the clients involved.
+pub fn run(args: ExplainArgs) -> Result<(), CommandError> {
+    let input = &args.input;
+    let precision = input.policy.precision.precision();
+    let mut tenants = input.tenants(|builder| builder)?;
+
+    let mut found = false;
+    let skipped = input.skipped()?;
+    for path in input.paths()? {
+        for record in input.read(&path, &skipped)? {
+            let record = record?;
+            let transaction = &record.transaction;
+            if transaction.id() != args.tx {
+                match tenants.process(transaction) {
+                    Err(e) if e.is_violation() => return Err(CommandError::rejected(&e, format!("transaction {} on line {} of '{}' broke an invariant: {}", transaction.id(), record.line, path.display(), e))),
+                    _ => continue
+                }
+            }
+            let engine = tenants.engine_mut(transaction.tenant()).map_err(|e| e.to_string())?;
+
+            if !found {
+                println!("transaction {}", args.tx);
+                found = true;
+            }
+
+            // NOTE: The fee account is listed too, unless it is already one of the clients of the transaction.
+            let fee_account = input.policy.fees.fee_account.filter(|&account| account != transaction.client_id() && transaction.destination() != Some(account));
+            let clients = [Some(transaction.client_id()), transaction.destination(), fee_account];
+            let before = clients.map(|id| id.and_then(|id| engine.client(id).ok().flatten()));
+            let result = engine.process(transaction);
+
+            let amount = transaction.amount().map(|amount| format!(" of {}", amount.to_plain_string())).unwrap_or_default();
+            // NOTE: Each tenant keeps its own transaction ids, so the same id may be explained in the books of several.
+            let amount = match transaction.tenant() {
+                Some(tenant) => format!("{} in tenant '{}'", amount, tenant),
+                None => amount
+            };
+            match &result {
+                Ok(applied) => match applied.fee() {
+                    Some(fee) => println!("{}:{}: {} by client {}{} accepted, with a fee of {}", path.display(), record.line, transaction.type_(), transaction.client_id(), amount, precision.format(fee)),
+                    None => println!("{}:{}: {} by client {}{} accepted", path.display(), record.line, transaction.type_(), transaction.client_id(), amount)
+                },
+                Err(e) => println!("{}:{}: {} by client {}{} rejected: {}", path.display(), record.line, transaction.type_(), transaction.client_id(), amount, e),
+            }
+
+            for (id, before) in clients.into_iter().zip(before) {
+                let Some(id) = id else {
+                    continue;
+                };
+
+                let after = engine.client(id).map_err(|e| e.to_string())?;
+                for change in changes(before.as_ref(), after.as_ref(), precision) {
+                    println!("  client {}: {}", id, change);
+                }
+            }
+        }
+    }
+
+    if !found {
+        return Err(format!("transaction {} does not appear in the input", args.tx).into());
+    }
+
+    if input.lenient {
+        skipped.report();
+    }
+    Ok(())
+}
+
+/// Describe how a client changed, with a line for each balance that changed and one if it was locked or unlocked.
+fn changes(before: Option<&Client>, after: Option<&Client>, precision: Precision) -> Vec<String> {
+    let balances = |client: Option<&Client>| client.map(|client| client.balances().to_vec()).unwrap_or_default();
+    let (before_balances, after_balances) = (balances(before), balances(after));
+
+    let mut changes = Vec::new();
+    for balance in &after_balances {
+        let empty = Balance::new(balance.currency().map(str::to_string)).in_account(balance.account().map(str::to_string));
+        let previous = before_balances.iter()
+            .find(|previous| previous.account() == balance.account() && previous.currency() == balance.currency())
+            .unwrap_or(&empty);
+        if previous == balance {
+            continue;
+        }
+
+        let currency = format!("{}{}",
+            balance.account().map(|account| format!("{} ", account)).unwrap_or_default(),
+            balance.currency().map(|currency| format!("{} ", currency)).unwrap_or_default());
+        changes.push(format!("{}available {} -> {}, held {} -> {}, total {} -> {}",
+            currency,
+            precision.format(&previous.available()), precision.format(&balance.available()),
+            precision.format(&previous.held()), precision.format(&balance.held()),
+            precision.format(&previous.total()), precision.format(&balance.total())));
+    }
+
+    let locked = |client: Option<&Client>| client.is_some_and(Client::locked);
+    match (locked(before), locked(after)) {
+        (false, true) => changes.push("locked".to_string()),
+        (true, false) => changes.push("unlocked".to_string()),
+        _ => {}
+    }
+    changes
+}
@@ -0,0 +1,67 @@
+use std::{io, path::PathBuf};
+
+use chrono::NaiveDate;
+use clap::Args;
+use transaction_system::{ExportFormat, Ledger};
+
+use super::{CommandError, InputArgs, single_tenant, write_atomically};
+
+/// Process every transaction, and write every movement of funds as plain-text accounting entries or bank statements.
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// The format to write (beancount or ledger, or mt940 or camt053 with the statements feature).
+    #[arg(long, value_name = "FORMAT", default_value_t)]
+    export_format: ExportFormat,
+
+    /// The file to write the entries to, instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// The commodity amounts in the default currency are written in.
+    #[arg(long, value_name = "COMMODITY", default_value = "USD")]
+    commodity: String,
+
+    /// The date of the entries of transactions without a timestamp, as YYYY-MM-DD.
+    #[arg(long, value_name = "DATE", default_value = "1970-01-01")]
+    undated: NaiveDate,
+}
+
+/// Process every transaction, posting every movement of funds to a double-entry ledger, and then write each movement
+/// as an entry of one posting pair. Rejected transactions move no funds, so they are left out.
+pub fn run(args: ExportArgs) -> Result<(), CommandError> {
+    let input = &args.input;
+
+    let mut tenants = input.tenants(|builder| builder.ledger(Some(Ledger::new())))?;
+    let mut skipped = input.skipped()?;
+    let mut rejected = 0;
+    for path in input.paths()? {
+        for record in input.read(&path, &skipped)? {
+            let record = record?;
+            if let Err(e) = tenants.process(&record.transaction) {
+                if input.strict || e.is_violation() {
+                    return Err(CommandError::rejected(&e, format!("transaction {} on line {} of '{}' was rejected: {}", record.transaction.id(), record.line, path.display(), e)));
+                }
+
+                rejected += 1;
+            }
+        }
+    }
+    skipped.rejected = rejected;
+    if input.lenient {
+        skipped.report();
+    }
+    tenants.iter_mut().try_for_each(|(_, engine)| input.policy.interest.settle(engine))?;
+    let engine = single_tenant(&tenants, "exported")?;
+
+    let entries = engine.ledger().map(Ledger::entries).unwrap_or_default();
+    let write = |writer: &mut dyn io::Write| args.export_format.write(writer, entries, engine.precision(), &args.commodity, args.undated);
+    match &args.output {
+        Some(output) => write_atomically(output, |writer| write(writer))
+            .map_err(|e| CommandError::io(&e, format!("unable to write {} entries to '{}': {}", args.export_format, output.display(), e))),
+        None => write(&mut io::stdout().lock())
+            .map_err(|e| format!("unable to write {} entries: {}", args.export_format, e).into())
+    }
+}
@@ -0,0 +1,137 @@
+use std::{fs::File, io, path::PathBuf};
+
+use bigdecimal::BigDecimal;
+use clap::Args;
+use transaction_system::{Transaction, TransactionType, decompress, filter_rows};
+
+use super::{CommandError, CsvArgs, ErrorKind};
+
+/// Write the rows of a CSV input matching every filter given to stdout, as they are, without applying any of them.
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// The CSV input file to search, which may be compressed.
+    #[arg(value_name = "INPUT")]
+    input: PathBuf,
+
+    /// Only keep the rows of this client, which may be given more than once to keep those of any of them.
+    #[arg(long, value_name = "ID")]
+    client: Vec<u16>,
+
+    /// Only keep the rows of this type of transaction, which may be given more than once to keep those of any of them.
+    #[arg(long = "type", value_name = "TYPE")]
+    type_: Vec<TransactionType>,
+
+    /// Only keep the rows with an amount of at least this much.
+    #[arg(long, value_name = "AMOUNT")]
+    min_amount: Option<BigDecimal>,
+
+    /// Only keep the rows with an amount of at most this much.
+    #[arg(long, value_name = "AMOUNT")]
+    max_amount: Option<BigDecimal>,
+
+    #[command(flatten)]
+    csv: CsvArgs,
+}
+
+impl FilterArgs {
+    /// Whether a transaction matches every filter given.
+    /// A transaction without an amount never matches an amount filter.
+    fn matches(&self, transaction: &Transaction) -> bool {
+        let client = self.client.is_empty() || self.client.contains(&transaction.client_id());
+        let type_ = self.type_.is_empty() || self.type_.contains(&transaction.type_());
+        let amount = (self.min_amount.is_none() && self.max_amount.is_none()) || transaction.amount()
+            .is_some_and(|amount| self.min_amount.as_ref().is_none_or(|min| amount >= min) && self.max_amount.as_ref().is_none_or(|max| amount <= max));
+        client && type_ && amount
+    }
+}
+
+/// Stream every row of the input, writing those that match to stdout under the same header, in the order they were read.
+/// Each row is matched by the transaction it holds, read in the dialect and with the column mapping given, and a row
+/// that can not be read as a transaction never matches any filter.
+pub fn run(args: FilterArgs) -> Result<(), CommandError> {
+    let name = args.input.display();
+    let file = File::open(&args.input)
+        .map_err(|e| CommandError::io(&e, format!("unable to open input file '{}': {}", name, e)))?;
+    let source = decompress(io::BufReader::new(file))
+        .map_err(|e| CommandError::io(&e, format!("unable to read input file '{}': {}", name, e)))?;
+
+    let mapping = args.csv.column_mapping()?;
+    let (read, matched) = filter_rows(source, io::stdout().lock(), &args.csv.dialect(), &mapping, |transaction| args.matches(transaction))
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => CommandError::new(ErrorKind::Parse, format!("unable to read '{}': {}", name, e)),
+            _ => CommandError::io(&e, format!("unable to write the matching rows: {}", e))
+        })?;
+    eprintln!("{} of {} rows matched", matched, read);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        filter: FilterArgs
+    }
+
+    /// The ids of the transactions of the rows matching the filters, and every row written.
+    fn filter(csv: &str, filters: &[&str]) -> (Vec<u32>, String) {
+        let args = Cli::parse_from(["filter", "input.csv"].iter().chain(filters)).filter;
+        let mut output = Vec::new();
+        let mapping = args.csv.column_mapping().unwrap();
+        filter_rows(csv.as_bytes(), &mut output, &args.csv.dialect(), &mapping, |transaction| args.matches(transaction)).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let ids = output.lines().skip(1)
+            .map(|row| row.split([',', ';']).nth(2).unwrap().trim().parse().unwrap())
+            .collect();
+        (ids, output)
+    }
+
+    const CSV: &str = "\
+type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 2.5
+withdrawal, 1, 3, 5
+dispute, 1, 1,
+deposit, one, 4, 1.0
+";
+
+    #[test]
+    fn filter_by_client_type_and_amount() {
+        assert_eq!(filter(CSV, &["--client", "1"]).0, vec![1, 3, 1]);
+        assert_eq!(filter(CSV, &["--client", "1", "--client", "2"]).0, vec![1, 2, 3, 1]);
+        assert_eq!(filter(CSV, &["--type", "deposit"]).0, vec![1, 2]);
+        assert_eq!(filter(CSV, &["--min-amount", "5"]).0, vec![1, 3]);
+        assert_eq!(filter(CSV, &["--min-amount", "2", "--max-amount", "5"]).0, vec![2, 3]);
+
+        // NOTE: The malformed row never matches, not even without any filter, but every other row is written as it was read.
+        let (ids, output) = filter(CSV, &[]);
+        assert_eq!(ids, vec![1, 2, 3, 1]);
+        assert!(output.starts_with("type,client,tx,amount\ndeposit, 1, 1, 10.0\n"));
+    }
+
+    #[test]
+    fn combine_filters() {
+        assert_eq!(filter(CSV, &["--client", "1", "--type", "withdrawal", "--type", "dispute"]).0, vec![3, 1]);
+        assert_eq!(filter(CSV, &["--client", "1", "--type", "deposit", "--max-amount", "5"]).0, Vec::<u32>::new());
+        assert_eq!(filter(CSV, &["--type", "deposit", "--type", "withdrawal", "--max-amount", "5"]).0, vec![2, 3]);
+    }
+
+    #[test]
+    fn filter_in_another_dialect_and_mapping() {
+        let mapping = std::env::temp_dir().join(format!("filter-mapping-{}.csv", std::process::id()));
+        fs::write(&mapping, "field,input,value\ntype,kind,\nclient,customer,\ntype,credit,deposit\n").unwrap();
+
+        let csv = "kind;customer;tx;amount;memo\ncredit;1;1;\"1.000,50\";\"rent; june\"\nwithdrawal;1;2;2,5;\ncredit;2;3;7;\n";
+        let (ids, output) = filter(csv, &["--delimiter", ";", "--amount-format", "comma", "--column-map", mapping.to_str().unwrap(), "--type", "deposit", "--min-amount", "5"]);
+        assert_eq!(ids, vec![1, 3]);
+        assert_eq!(output, "kind;customer;tx;amount;memo\ncredit;1;1;1.000,50;\"rent; june\"\ncredit;2;3;7;\n");
+        fs::remove_file(&mapping).unwrap();
+    }
+}
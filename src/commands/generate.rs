@@ -0,0 +1,88 @@
+use std::{fs::File, io, path::PathBuf};
+
+use clap::Args;
+use transaction_system::Generator;
+
+use super::CommandError;
+
+/// Write a reproducible, synthetic workload of transactions as CSV.
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    /// The number of clients to spread transactions across.
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    clients: u16,
+
+    /// The number of transactions to write, optionally with a k or M suffix.
+    #[arg(long, value_name = "N", default_value = "1000", value_parser = parse_count)]
+    transactions: u64,
+
+    /// The chance of each transaction disputing an earlier deposit, and of settling an open dispute.
+    #[arg(long, value_name = "RATE", default_value_t = 0.01, value_parser = parse_rate)]
+    dispute_rate: f64,
+
+    /// The seed of the workload, where the same seed always writes the same transactions.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// The file to write the transactions to, instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+/// Parse a count such as `1000`, `10k` or `10M`.
+fn parse_count(s: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match s.strip_suffix(['k', 'K']) {
+        Some(digits) => (digits, 1_000),
+        None => match s.strip_suffix('M') {
+            Some(digits) => (digits, 1_000_000),
+            None => (s, 1)
+        }
+    };
+
+    digits.parse::<u64>().ok()
+        .and_then(|count| count.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid count '{}'", s))
+}
+
+/// Parse a probability between 0 and 1.
+fn parse_rate(s: &str) -> Result<f64, String> {
+    s.parse::<f64>().ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+        .ok_or_else(|| format!("invalid rate '{}', expected a number between 0 and 1", s))
+}
+
+pub fn run(args: GenerateArgs) -> Result<(), CommandError> {
+    let writer: Box<dyn io::Write> = match &args.output {
+        Some(output) => Box::new(File::create(output)
+            .map_err(|e| CommandError::io(&e, format!("unable to create '{}': {}", output.display(), e)))?),
+        None => Box::new(io::stdout().lock())
+    };
+    let mut writer = csv::Writer::from_writer(io::BufWriter::new(writer));
+
+    let written = writer.write_record(["type", "client", "tx", "amount"])
+        .and_then(|_| Generator::new(args.clients, args.dispute_rate, args.seed)
+            .take(args.transactions as usize)
+            .try_for_each(|transaction| writer.write_record([
+                transaction.type_().to_string(),
+                transaction.client_id().to_string(),
+                transaction.id().to_string(),
+                transaction.amount().map(|amount| amount.to_plain_string()).unwrap_or_default()
+            ])))
+        .and_then(|_| Ok(writer.flush()?));
+
+    written.map_err(|e| format!("unable to write transactions: {}", e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_counts() {
+        assert_eq!(parse_count("1000"), Ok(1000));
+        assert_eq!(parse_count("10k"), Ok(10_000));
+        assert_eq!(parse_count("10M"), Ok(10_000_000));
+        assert!(parse_count("ten").is_err());
+        assert!(parse_rate("1.5").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf};
+
+use bigdecimal::BigDecimal;
+use clap::Args;
+use transaction_system::{Engine, Snapshot};
+
+use super::{CommandError, ErrorKind, OutputArgs, PrecisionArgs, read_accounts};
+
+/// Combine the account output of several runs over disjoint clients, such as shards, into one.
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    /// The CSV account files to merge, as written by previous runs.
+    #[arg(value_name = "ACCOUNTS", required = true)]
+    files: Vec<PathBuf>,
+
+    #[command(flatten)]
+    precision: PrecisionArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+/// Read the accounts of every file, failing if any client appears in more than one, then write them as one.
+/// A summary of the merged accounts, with the funds in each currency added up again, is printed to stderr.
+pub fn run(args: MergeArgs) -> Result<(), CommandError> {
+    let precision = args.precision.precision();
+
+    let mut owners = HashMap::new();
+    let mut clients = Vec::new();
+    for file in &args.files {
+        for client in read_accounts(file)? {
+            if let Some(owner) = owners.insert(client.id(), file) {
+                return Err(CommandError::new(ErrorKind::Invariant, format!("client {} is in both '{}' and '{}'", client.id(), owner.display(), file.display())));
+            }
+            clients.push(client);
+        }
+    }
+
+    let mut funds = BTreeMap::<Option<&str>, [BigDecimal; 3]>::new();
+    for balance in clients.iter().flat_map(|client| client.balances()) {
+        let [available, held, total] = funds.entry(balance.currency()).or_default();
+        *available += balance.available();
+        *held += balance.held();
+        *total += balance.total();
+    }
+
+    eprintln!("files: {}", args.files.len());
+    eprintln!("clients: {}", clients.len());
+    eprintln!("locked: {}", clients.iter().filter(|client| client.locked()).count());
+    for (currency, [available, held, total]) in &funds {
+        match currency {
+            Some(currency) => eprintln!("funds ({}):", currency),
+            None => eprintln!("funds:")
+        }
+        eprintln!("  available: {}", precision.format(available));
+        eprintln!("  held: {}", precision.format(held));
+        eprintln!("  total: {}", precision.format(total));
+    }
+
+    let mut engine = Engine::new().with_precision(precision);
+    engine.restore(Snapshot { clients, ..Snapshot::default() })
+        .map_err(|e| e.to_string())?;
+    args.output.write(&engine)
+}
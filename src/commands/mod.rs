@@ -0,0 +1,1186 @@
+use std::{cell::{Cell, RefCell}, fs::{self, File}, io::{self, Write}, iter, path::{Path, PathBuf}};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
+use clap::Args;
+#[cfg(feature = "scripting")]
+use transaction_system::Script;
+use transaction_system::{Activity, AmountFormat, AmountThreshold, BalanceHistory, Client, ColumnMapping, CsvDialect, CsvRecords, DEFAULT_SCALE, DailyClose, DeadLetter, DisputeCount, Encoding, Engine, EngineBuilder, ExcessPrecision, FeeSchedule, Flags, FreezePolicy, InputFormat, Interest, InterestPeriod, Limits, LockPolicy, OutputFormat, OverdraftPolicy, Ownership, Precision, Record, RecordError, QuoteStyle, RapidCycle, Rounding, RiskMonitor, Roster, Rule, Storage, Tenants, VelocityPolicy, WithdrawalLimits, accounts_from_reader, decompress, merkle_root, reorder};
+
+pub mod anonymize;
+pub mod diff;
+mod error;
+pub mod explain;
+pub mod export;
+pub mod filter;
+pub mod generate;
+pub mod merge;
+pub mod process;
+pub mod reconcile;
+pub mod replay;
+#[cfg(feature = "grpc")]
+pub mod serve;
+pub mod split;
+pub mod stats;
+pub mod validate;
+pub mod verify;
+pub mod verify_audit;
+
+pub use error::{CommandError, ErrorKind};
+
+/// The arguments shared by every command that reads transactions.
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    /// The input files to read transactions from, in order. Glob patterns are expanded.
+    /// HTTP(S) and `s3://bucket/key` URLs are streamed when built with the http and s3 features.
+    #[cfg_attr(not(feature = "kafka"), arg(value_name = "INPUT", required_unless_present = "input"))]
+    #[cfg_attr(feature = "kafka", arg(value_name = "INPUT", required_unless_present_any = ["input", "source"]))]
+    paths: Vec<String>,
+
+    /// An input file to read transactions from, as an alternative to the positional arguments.
+    #[arg(short, long, value_name = "FILE", conflicts_with = "paths")]
+    input: Vec<String>,
+
+    /// Consume transactions from a streaming source, such as `kafka://broker/topic`, instead of input files.
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "URL", conflicts_with_all = ["paths", "input"])]
+    pub source: Option<transaction_system::KafkaUrl>,
+
+    /// The input format (csv or jsonl, and iso20022, protobuf or avro when built with those features), detected from each file extension by default.
+    #[arg(short, long)]
+    format: Option<InputFormat>,
+
+    /// The text encoding of the input (utf-8, utf-16le, utf-16be or latin-1). A byte order mark is always skipped,
+    /// and UTF-8 input that starts with a UTF-16 one is read as UTF-16.
+    #[arg(long, value_name = "ENCODING", default_value_t)]
+    encoding: Encoding,
+
+    #[command(flatten)]
+    pub csv: CsvArgs,
+
+    /// Fail on the first malformed row or rejected transaction, reporting its line number.
+    /// A malformed row exits with code 4, and a rejected transaction with code 6.
+    #[arg(long, conflicts_with = "lenient")]
+    pub strict: bool,
+
+    #[command(flatten)]
+    pub policy: PolicyArgs,
+
+    /// Skip malformed rows rather than failing, and print a summary of every skipped row to stderr.
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// A CSV file to write every malformed row skipped in lenient mode to, exactly as it was read,
+    /// alongside its line and why it could not be read, so it can be repaired and resubmitted.
+    #[arg(long, value_name = "FILE", requires = "lenient")]
+    dead_letter: Option<PathBuf>,
+
+    /// Sort the transactions of each input file by timestamp, letting each move ahead of at most N transactions before it.
+    #[arg(long, value_name = "N")]
+    reorder_window: Option<usize>,
+
+    /// Stop reading input after the first record of this transaction id, replaying only the input up to it and writing the
+    /// state at that point, such as to find which record corrupted a balance. No later row or input file is read.
+    #[arg(long, value_name = "N", conflicts_with = "stop_after_line")]
+    stop_after_tx: Option<u32>,
+
+    /// Stop reading input after this line, replaying only the input up to it and writing the state at that point.
+    /// With more than one input file, the file to stop in must be named, as FILE:N, and every input file before it is
+    /// read whole. No later row or input file is read, and following the input ends there.
+    #[arg(long, value_name = "[FILE:]N", value_parser = parse_stop_line)]
+    stop_after_line: Option<StopLine>,
+}
+
+/// The arguments setting how CSV input is read, shared by every command that reads it.
+#[derive(Args, Debug)]
+pub struct CsvArgs {
+    /// A CSV file with `field`, `input` and `value` columns, mapping the columns of CSV input, and the values in them,
+    /// to the fields of a transaction. A row such as `type,txn_type,` reads the `txn_type` column as the `type` field,
+    /// and a row such as `type,credit,deposit` reads `credit` in the `type` field as `deposit`.
+    #[arg(long, value_name = "FILE")]
+    column_map: Option<PathBuf>,
+
+    /// The character separating the fields of CSV input, such as `;` or `\t` for a tab.
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = parse_csv_char)]
+    delimiter: u8,
+
+    /// The character fields of CSV input are quoted with.
+    #[arg(long, value_name = "CHAR", default_value = "\"", value_parser = parse_csv_char)]
+    quote: u8,
+
+    /// Read quotes in CSV input as any other character.
+    #[arg(long, conflicts_with = "quote")]
+    no_quoting: bool,
+
+    /// How amounts in CSV input are written: strict for plain numbers only, point for amounts such as `$1,234.56`,
+    /// or comma for amounts such as `1 234,56 €`. A currency symbol or code and grouping separators are then ignored.
+    #[arg(long, value_name = "FORMAT", default_value_t)]
+    amount_format: AmountFormat,
+}
+
+impl CsvArgs {
+    /// The mapping of the columns of CSV input to the fields of a transaction, which maps nothing unless one is given.
+    pub fn column_mapping(&self) -> Result<ColumnMapping, CommandError> {
+        match &self.column_map {
+            Some(path) => read_csv(path, "column mapping", ColumnMapping::read),
+            None => Ok(ColumnMapping::default())
+        }
+    }
+
+    /// The delimiter and quoting of CSV input.
+    pub fn dialect(&self) -> CsvDialect {
+        CsvDialect::default()
+            .with_delimiter(self.delimiter)
+            .with_quote((!self.no_quoting).then_some(self.quote))
+            .with_amount_format(self.amount_format)
+    }
+}
+
+/// The arguments setting the policies of the engine every transaction is processed by.
+#[derive(Args, Debug)]
+pub struct PolicyArgs {
+    /// Accept operator-only transactions, such as unlocking an account. As accounts can then be frozen,
+    /// the accounts are always written with a `frozen` column.
+    #[arg(long)]
+    pub admin: bool,
+
+    /// Which transactions a locked account still accepts (reject-all, allow-deposits, allow-disputes or allow-all).
+    #[arg(long, value_name = "POLICY", default_value_t)]
+    pub lock_policy: LockPolicy,
+
+    /// Which transactions an account frozen by an operator still accepts (reject-all, allow-deposits or block-withdrawals).
+    #[arg(long, value_name = "POLICY", default_value_t)]
+    pub freeze_policy: FreezePolicy,
+
+    #[command(flatten)]
+    pub precision: PrecisionArgs,
+
+    #[command(flatten)]
+    pub fees: FeeArgs,
+
+    #[command(flatten)]
+    pub interest: InterestArgs,
+
+    /// Reject any transaction with a timestamp earlier than the latest accepted transaction.
+    #[arg(long)]
+    pub enforce_order: bool,
+
+    /// Unlock an account when a representment reverses the chargeback that locked it.
+    #[arg(long)]
+    pub unlock_on_representment: bool,
+
+    /// Reject disputes of transactions that happened more than this many days before the dispute.
+    #[arg(long, value_name = "DAYS")]
+    pub dispute_window: Option<u32>,
+
+    /// Let withdrawals, transfers and conversions take available funds up to this far below zero.
+    #[arg(long, value_name = "AMOUNT")]
+    pub overdraft_limit: Option<BigDecimal>,
+
+    /// Reject deposits, withdrawals, transfers and conversions of more than this amount.
+    #[arg(long, value_name = "AMOUNT")]
+    pub max_amount: Option<BigDecimal>,
+
+    /// Reject transactions that would leave an account holding more than this amount in any one currency.
+    #[arg(long, value_name = "AMOUNT")]
+    pub max_balance: Option<BigDecimal>,
+
+    /// Reject withdrawals of more than this amount.
+    #[arg(long, value_name = "AMOUNT")]
+    pub max_withdrawal: Option<BigDecimal>,
+
+    /// Reject withdrawals once a client has made this many in a day.
+    #[arg(long, value_name = "N")]
+    pub max_withdrawals_per_day: Option<u32>,
+
+    /// Reject withdrawals and transfers that would take more than this amount out of a client's account in a day.
+    #[arg(long, value_name = "AMOUNT")]
+    pub max_daily_outflow: Option<BigDecimal>,
+
+    /// A CSV file with `client`, `max_withdrawal`, `max_withdrawals_per_day` and `max_daily_outflow` columns,
+    /// overriding the withdrawal limits of individual clients. An empty limit is taken from the options above.
+    #[arg(long, value_name = "FILE")]
+    withdrawal_limits: Option<PathBuf>,
+
+    /// A CSV file of every known client, with a `client` column and optional `name` and `tier` columns.
+    /// The account of every known client is written, with zero balances if it has no activity.
+    #[arg(long, value_name = "FILE")]
+    clients: Option<PathBuf>,
+
+    /// Reject transactions of clients that are not in the file of known clients, rather than opening an account for them.
+    #[arg(long, requires = "clients")]
+    reject_unknown_clients: bool,
+
+    /// A CSV file of the owners of every joint account, with `account` and `client` columns and a row for each owner.
+    /// Transactions of any owner are applied to the shared balance, and the account is written with its owners.
+    #[arg(long, value_name = "FILE")]
+    owners: Option<PathBuf>,
+
+    /// The tenant whose isolated books hold every transaction read without a `tenant` column of its own,
+    /// rather than the default books.
+    #[arg(long, value_name = "NAME")]
+    pub tenant: Option<String>,
+
+    /// Check the engine's invariants after every transaction, stopping with the offending transaction and account if one breaks.
+    #[arg(long)]
+    pub check_invariants: bool,
+}
+
+impl PolicyArgs {
+    /// Make builders for engines with every policy set by the arguments, such as one for each shard,
+    /// reading the fee schedule, withdrawal limits, known clients and account owners only once. Fails if any can not be read.
+    pub fn engines(&self) -> Result<impl Fn() -> EngineBuilder + '_, CommandError> {
+        let fees = self.fees.schedule()?;
+        let velocity = self.velocity()?;
+        let roster = self.roster()?;
+        let ownership = self.ownership()?;
+
+        Ok(move || Engine::builder()
+            .admin(self.admin)
+            .precision(self.precision.precision())
+            .overdraft(self.overdraft_limit.clone().map_or(OverdraftPolicy::Deny, OverdraftPolicy::Limit))
+            .limits(Limits { max_amount: self.max_amount.clone(), max_balance: self.max_balance.clone() })
+            .velocity(velocity.clone())
+            .roster(roster.clone())
+            .ownership(ownership.clone())
+            .tenant(self.tenant.clone())
+            .lock_policy(self.lock_policy)
+            .freeze_policy(self.freeze_policy)
+            .dispute_window(self.dispute_window())
+            .unlock_on_representment(self.unlock_on_representment)
+            .fees(fees.clone())
+            .interest(self.interest.interest())
+            // NOTE: A history of balances is only kept when the accounts are written as of an earlier time.
+            .history(self.interest.as_of.map(|_| BalanceHistory::new()))
+            .enforce_order(self.enforce_order)
+            .check_invariants(self.check_invariants))
+    }
+
+    /// Keep the books of every tenant in an engine with every policy set by the arguments, further configured by the given
+    /// function, starting with the books of the tenant given by the arguments. The engine of any other tenant is made when
+    /// its first transaction is processed. Fails if the fee schedule, withdrawal limits or known clients can not be read.
+    pub fn tenants<'a, F>(&'a self, configure: F) -> Result<Tenants<'a>, CommandError>
+    where
+        F: Fn(EngineBuilder) -> EngineBuilder + 'a
+    {
+        let engines = self.engines()?;
+        let mut tenants = Tenants::new(move |tenant| Ok(configure(engines().tenant(tenant.map(str::to_string))).build()));
+        tenants.engine_mut(self.tenant.as_deref()).map_err(|e| e.to_string())?;
+        Ok(tenants)
+    }
+
+    /// The known clients read from their file, if one was given.
+    pub fn roster(&self) -> Result<Option<Roster>, CommandError> {
+        match &self.clients {
+            Some(path) => read_csv(path, "known clients", Roster::read)
+                .map(|roster| Some(roster.with_reject_unknown(self.reject_unknown_clients))),
+            None => Ok(None)
+        }
+    }
+
+    /// The owners of every joint account read from their file, if one was given.
+    pub fn ownership(&self) -> Result<Option<Ownership>, CommandError> {
+        match &self.owners {
+            Some(path) => read_csv(path, "account owners", Ownership::read).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// The withdrawal limits set by the arguments, with those of individual clients read from their file, if any are set.
+    pub fn velocity(&self) -> Result<Option<VelocityPolicy>, CommandError> {
+        let limits = WithdrawalLimits {
+            max_amount: self.max_withdrawal.clone(),
+            max_per_day: self.max_withdrawals_per_day,
+            max_daily_outflow: self.max_daily_outflow.clone()
+        };
+
+        match &self.withdrawal_limits {
+            Some(path) => read_csv(path, "withdrawal limits", |reader| VelocityPolicy::new(limits).read_clients(reader)).map(Some),
+            None if limits.is_empty() => Ok(None),
+            None => Ok(Some(VelocityPolicy::new(limits)))
+        }
+    }
+
+    /// How long after a transaction it can still be disputed, or `None` for no limit.
+    pub fn dispute_window(&self) -> Option<TimeDelta> {
+        self.dispute_window.map(|days| TimeDelta::days(days.into()))
+    }
+}
+
+/// The arguments controlling how amounts are rounded, as they are processed and written.
+#[derive(Args, Debug)]
+pub struct PrecisionArgs {
+    /// The number of decimal places every amount is rounded to.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_SCALE, value_parser = parse_scale)]
+    scale: i64,
+
+    /// How amounts with more decimal places are rounded (half-up, down or bankers).
+    #[arg(long, value_name = "MODE", default_value_t)]
+    rounding: Rounding,
+
+    /// Whether a submitted amount with more decimal places is rejected or rounded (reject or round).
+    #[arg(long, value_name = "POLICY", default_value_t)]
+    excess_precision: ExcessPrecision,
+}
+
+impl PrecisionArgs {
+    /// The precision policy described by the arguments.
+    pub fn precision(&self) -> Precision {
+        Precision::new(self.scale, self.rounding).with_excess(self.excess_precision)
+    }
+}
+
+/// The arguments configuring the fees charged on transactions.
+#[derive(Args, Debug)]
+pub struct FeeArgs {
+    /// A CSV fee schedule with `type`, `tier`, `flat` and `percent` columns, charging each fee on top of the amount
+    /// of a deposit, withdrawal, transfer or conversion. A fee with an empty tier applies to clients without a fee of their own tier.
+    #[arg(long, value_name = "FILE", requires = "fee_account")]
+    fees: Option<PathBuf>,
+
+    /// The client every fee is credited to.
+    #[arg(long, value_name = "ID", requires = "fees")]
+    fee_account: Option<u16>,
+
+    /// A CSV file with `client` and `tier` columns, placing each client in a tier of the fee schedule.
+    #[arg(long, value_name = "FILE", requires = "fees")]
+    client_tiers: Option<PathBuf>,
+}
+
+impl FeeArgs {
+    /// Whether any fees are charged.
+    pub fn is_set(&self) -> bool {
+        self.fees.is_some()
+    }
+
+    /// The fee schedule described by the arguments, if fees are charged.
+    pub fn schedule(&self) -> Result<Option<FeeSchedule>, CommandError> {
+        let (Some(fees), Some(account)) = (&self.fees, self.fee_account) else {
+            return Ok(None);
+        };
+
+        let mut schedule = read_csv(fees, "fees", |reader| FeeSchedule::new(account).read_fees(reader))?;
+        if let Some(tiers) = &self.client_tiers {
+            schedule = read_csv(tiers, "client tiers", |reader| schedule.read_tiers(reader))?;
+        }
+        Ok(Some(schedule))
+    }
+}
+
+/// The arguments configuring the interest accrued on available funds, and the processing date the engine is advanced to
+/// once every transaction has been processed.
+#[derive(Args, Debug)]
+pub struct InterestArgs {
+    /// Accrue interest on the available funds of every unlocked account at this yearly rate, as a percentage,
+    /// of which a 365th is accrued at the end of each day. Days pass as transactions with later timestamps are processed.
+    #[arg(long, value_name = "PERCENT", value_parser = parse_rate)]
+    interest_rate: Option<BigDecimal>,
+
+    /// How often accrued interest is posted to the accounts (daily or monthly).
+    #[arg(long, value_name = "PERIOD", default_value_t, requires = "interest_rate")]
+    interest_period: InterestPeriod,
+
+    /// Once every transaction has been processed, accrue and post interest up to this RFC 3339 time,
+    /// such as `2024-02-01T00:00:00Z` to post the interest of every day of January.
+    #[arg(long, value_name = "TIME", requires = "interest_rate")]
+    interest_until: Option<DateTime<Utc>>,
+
+    /// Write the accounts as they were at this RFC 3339 time, or the start of this day, such as `2024-03-01`, rather than
+    /// once every transaction has been processed. When it is later than every transaction, every scheduled transaction due
+    /// by then is applied, and any interest is accrued and posted up to it.
+    #[arg(long, value_name = "TIME", value_parser = parse_time)]
+    as_of: Option<DateTime<Utc>>,
+}
+
+impl InterestArgs {
+    /// Whether any interest is accrued.
+    pub fn is_set(&self) -> bool {
+        self.interest_rate.is_some()
+    }
+
+    /// The time the accounts are written as of, if they are not written as they are once every transaction has been processed.
+    pub fn as_of(&self) -> Option<DateTime<Utc>> {
+        self.as_of
+    }
+
+    /// The interest described by the arguments, if any is accrued.
+    pub fn interest(&self) -> Option<Interest> {
+        self.interest_rate.clone().map(|rate| Interest { rate, period: self.interest_period })
+    }
+
+    /// Advance the engine to the processing date and the time interest is posted until, if either was given,
+    /// once every transaction has been processed.
+    pub fn settle<S: Storage>(&self, engine: &mut Engine<S>) -> Result<(), CommandError> {
+        for until in [self.as_of, self.interest_until].into_iter().flatten() {
+            let made = engine.advance(until)
+                .map_err(|e| format!("unable to advance to {}: {}", until.to_rfc3339(), e))?;
+
+            // NOTE: Rejected occurrences of scheduled transactions are only recorded by the observers, such as the audit log.
+            let rejected = made.iter().filter(|(_, result)| result.is_err()).count();
+            tracing::info!(applied = made.len() - rejected, rejected, "advanced to {}", until.to_rfc3339());
+        }
+        Ok(())
+    }
+}
+
+/// The arguments configuring the risk rules that flag suspicious transactions for review without blocking them,
+/// and the script that may reject or flag each transaction.
+#[derive(Args, Debug)]
+pub struct RiskArgs {
+    /// Flag every deposit, withdrawal, transfer or conversion of at least this amount.
+    #[arg(long, value_name = "AMOUNT", requires = "flagged")]
+    flag_amount: Option<BigDecimal>,
+
+    /// Flag every withdrawal or transfer made within this many seconds of the client's last deposit.
+    /// Only transactions with a timestamp are checked.
+    #[arg(long, value_name = "SECS", requires = "flagged")]
+    flag_rapid_cycle: Option<u32>,
+
+    /// Flag every dispute a client opens once it has opened more than this many.
+    #[arg(long, value_name = "N", requires = "flagged")]
+    flag_disputes: Option<u32>,
+
+    /// A Rhai script defining `fn check(tx, account)`, run before each transaction is applied with the transaction and its
+    /// client's account, which returns `accept()`, `reject(reason)` or `flag(reason)`.
+    #[cfg(feature = "scripting")]
+    #[arg(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+
+    /// A CSV file to write every flagged transaction to, alongside the rule that flagged it and why.
+    #[arg(long, value_name = "FILE")]
+    flagged: Option<PathBuf>,
+}
+
+impl RiskArgs {
+    /// Screen the engines of a run by the rules and script described by the arguments, recording their flags to one report.
+    pub fn screening(&self) -> Result<Screening<'_>, CommandError> {
+        let flags = Flags::default();
+
+        #[cfg(feature = "scripting")]
+        let script = match &self.script {
+            Some(path) => {
+                let source = fs::read_to_string(path)
+                    .map_err(|e| CommandError::io(&e, format!("unable to read script '{}': {}", path.display(), e)))?;
+                let script = Script::compile(&source, flags.clone())
+                    .map_err(|e| CommandError::new(ErrorKind::Parse, format!("invalid script '{}': {}", path.display(), e)))?;
+                Some(script)
+            },
+            None => None
+        };
+
+        Ok(Screening {
+            args: self,
+            flags,
+            #[cfg(feature = "scripting")]
+            script
+        })
+    }
+
+    /// A monitor checking the rules described by the arguments, recording their flags to the shared flags, if any are given.
+    fn monitor(&self, flags: &Flags) -> Option<RiskMonitor> {
+        let mut rules = Vec::<Box<dyn Rule>>::new();
+        if let Some(amount) = &self.flag_amount {
+            rules.push(Box::new(AmountThreshold(amount.clone())));
+        }
+        if let Some(seconds) = self.flag_rapid_cycle {
+            rules.push(Box::new(RapidCycle::new(TimeDelta::seconds(seconds.into()))));
+        }
+        if let Some(max) = self.flag_disputes {
+            rules.push(Box::new(DisputeCount::new(max)));
+        }
+
+        match rules.is_empty() {
+            true => None,
+            false => Some(RiskMonitor::new(rules, flags.clone()))
+        }
+    }
+}
+
+/// The risk rules and script screening every engine of a run, such as each shard, recording their flags to one report.
+pub struct Screening<'a> {
+    args: &'a RiskArgs,
+    flags: Flags,
+
+    #[cfg(feature = "scripting")]
+    script: Option<Script>
+}
+
+impl Screening<'_> {
+    /// Screen an engine by the risk rules and script, if any.
+    pub fn attach<S: Storage>(&self, builder: EngineBuilder<S>) -> EngineBuilder<S> {
+        #[cfg(feature = "scripting")]
+        let builder = builder.script(self.script.clone());
+
+        match self.args.monitor(&self.flags) {
+            Some(monitor) => builder.observer(monitor),
+            None => builder
+        }
+    }
+
+    /// Write every transaction flagged so far to the report, if one was given.
+    pub fn write(&self) -> Result<(), CommandError> {
+        let Some(path) = &self.args.flagged else {
+            return Ok(());
+        };
+
+        let flags = self.flags.take();
+        let written = csv::Writer::from_path(path)
+            .and_then(|mut writer| {
+                flags.iter().try_for_each(|flag| writer.serialize(flag))?;
+                Ok(writer.flush()?)
+            });
+        match written {
+            Ok(()) => {
+                tracing::info!(flagged = flags.len(), "wrote flagged transactions to '{}'", path.display());
+                Ok(())
+            },
+            Err(_) => Err(format!("unable to write flagged transactions to '{}'", path.display()).into())
+        }
+    }
+}
+
+/// A line of an input file to stop reading after, in the only input file unless one is named.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StopLine {
+    /// The input file to stop in, or `None` for the only one.
+    file: Option<PathBuf>,
+
+    /// The line to stop after.
+    line: u64
+}
+
+/// Parse a line to stop reading after, optionally preceded by the input file to stop in, such as `input.csv:120`.
+fn parse_stop_line(s: &str) -> Result<StopLine, String> {
+    let (file, line) = match s.rsplit_once(':') {
+        Some((file, line)) => (Some(PathBuf::from(file)), line),
+        None => (None, s)
+    };
+    match line.parse() {
+        Ok(line) if file.as_ref().is_none_or(|file| !file.as_os_str().is_empty()) => Ok(StopLine { file, line }),
+        _ => Err(format!("invalid line '{}', expected a line number, optionally preceded by a file, as FILE:N", s))
+    }
+}
+
+/// Parse a single ASCII character separating or quoting CSV fields, where `\t` and `tab` stand for a tab.
+fn parse_csv_char(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "\t" | "tab" => Ok(b'\t'),
+        s if s.len() == 1 && s.is_ascii() && s != "\n" && s != "\r" => Ok(s.as_bytes()[0]),
+        _ => Err(format!("'{}' is not a single ASCII character", s))
+    }
+}
+
+/// Parse an RFC 3339 time, or a day as YYYY-MM-DD, which stands for the start of that day.
+fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|time| time.to_utc())
+        .or_else(|e| match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(day) => Ok(day.and_time(NaiveTime::MIN).and_utc()),
+            Err(_) => Err(e.to_string())
+        })
+}
+
+/// Parse the number of decimal places amounts are rounded to, which can be no more than balances are kept to.
+fn parse_scale(s: &str) -> Result<i64, String> {
+    let scale = s.parse::<i64>().map_err(|e| e.to_string())?;
+
+    // NOTE: Fixed-point balances can not hold any more decimal places, so every amount with more would be rejected.
+    #[cfg(feature = "fixed-point")]
+    if scale > transaction_system::FIXED_SCALE {
+        return Err(format!("this build keeps balances to {} decimal places, so amounts can not be rounded to {}", transaction_system::FIXED_SCALE, scale));
+    }
+    Ok(scale)
+}
+
+/// Parse a yearly interest rate, which can not be negative.
+fn parse_rate(s: &str) -> Result<BigDecimal, String> {
+    match s.parse::<BigDecimal>() {
+        Ok(rate) if rate < 0 => Err(format!("interest rate '{}' is negative", s)),
+        Ok(rate) => Ok(rate),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// How long the first retry of a failed webhook delivery waits, with each retry after it waiting twice as long.
+#[cfg(all(feature = "http", any(feature = "grpc", feature = "kafka")))]
+const WEBHOOK_BACKOFF: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// The arguments configuring the webhooks notified as transactions are processed.
+#[cfg(all(feature = "http", any(feature = "grpc", feature = "kafka")))]
+#[derive(Args, Debug)]
+pub struct WebhookArgs {
+    /// POST a JSON notification to this URL whenever a chargeback locks an account. Can be given more than once.
+    #[arg(long = "webhook", value_name = "URL")]
+    urls: Vec<String>,
+
+    /// How many times a failed webhook delivery is retried, waiting twice as long before each retry as the one before.
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    webhook_retries: u32,
+}
+
+#[cfg(all(feature = "http", any(feature = "grpc", feature = "kafka")))]
+impl WebhookArgs {
+    /// Whether no webhooks were given.
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// Start delivering notifications to the webhooks, if any were given.
+    pub fn spawn(&self, precision: Precision) -> Option<(transaction_system::webhook::Webhook, transaction_system::webhook::WebhookWorker)> {
+        (!self.is_empty()).then(|| transaction_system::webhook::Webhook::spawn(self.urls.clone(), precision, self.webhook_retries, WEBHOOK_BACKOFF))
+    }
+}
+
+/// The records read from an input file, yielded as they are read, where an error ends the read.
+pub type Records<'a> = Box<dyn Iterator<Item = Result<Record, CommandError>> + 'a>;
+
+/// A tally of the rows that were skipped while processing.
+/// It is shared by every input file being read, so reading stops for all of them once it stops for one.
+#[derive(Debug, Default)]
+pub struct Skipped {
+    /// The number of rows that could not be read as a transaction.
+    malformed: Cell<usize>,
+
+    /// The number of transactions that were rejected by the engine.
+    pub rejected: usize,
+
+    /// Where every malformed row is written as it is skipped, if anywhere.
+    dead_letters: RefCell<Option<(PathBuf, csv::Writer<File>)>>,
+
+    /// Whether reading stopped early, at the record given by `--stop-after-tx` or `--stop-after-line`, so every row after it is skipped.
+    stopped: Cell<bool>,
+}
+
+impl Skipped {
+    /// The number of rows that could not be read as a transaction.
+    pub fn malformed(&self) -> usize {
+        self.malformed.get()
+    }
+
+    /// Whether reading stopped early, at the record given by `--stop-after-tx` or `--stop-after-line`.
+    pub fn stopped(&self) -> bool {
+        self.stopped.get()
+    }
+
+    /// Count a malformed row that was skipped, writing it to the dead-letter file if there is one.
+    pub fn skip(&self, path: &Path, error: &RecordError) -> Result<(), CommandError> {
+        self.malformed.set(self.malformed.get() + 1);
+
+        let mut dead_letters = self.dead_letters.borrow_mut();
+        let Some((dead_letter, writer)) = &mut *dead_letters else {
+            return Ok(());
+        };
+        // NOTE: Each row is flushed as it is written, so a run that fails later still keeps every row skipped before it.
+        writer.serialize(DeadLetter { file: Some(path.display().to_string()), ..DeadLetter::new(error) })
+            .and_then(|_| Ok(writer.flush()?))
+            .map_err(|e| format!("unable to write malformed rows to '{}': {}", dead_letter.display(), e).into())
+    }
+
+    /// Print a summary of the skipped rows to stderr.
+    pub fn report(&self) {
+        eprintln!("Skipped {} rows: {} malformed, {} rejected", self.malformed() + self.rejected, self.malformed(), self.rejected);
+    }
+}
+
+impl InputArgs {
+    /// An empty tally of skipped rows, which writes malformed rows to the dead-letter file if one was given.
+    pub fn skipped(&self) -> Result<Skipped, CommandError> {
+        let dead_letters = match &self.dead_letter {
+            Some(path) => {
+                let writer = csv::Writer::from_path(path)
+                    .map_err(|e| format!("unable to create dead-letter file '{}': {}", path.display(), e))?;
+                Some((path.clone(), writer))
+            },
+            None => None
+        };
+        Ok(Skipped { dead_letters: RefCell::new(dead_letters), ..Skipped::default() })
+    }
+
+    /// Make builders for engines with every policy set by the arguments, such as one for each shard. See [`PolicyArgs::engines`].
+    pub fn engines(&self) -> Result<impl Fn() -> EngineBuilder + '_, CommandError> {
+        self.policy.engines()
+    }
+
+    /// Keep the books of every tenant in an engine with every policy set by the arguments. See [`PolicyArgs::tenants`].
+    pub fn tenants<'a, F>(&'a self, configure: F) -> Result<Tenants<'a>, CommandError>
+    where
+        F: Fn(EngineBuilder) -> EngineBuilder + 'a
+    {
+        self.policy.tenants(configure)
+    }
+
+    /// The paths of every input file, in the order they should be processed.
+    /// Glob patterns are expanded in alphabetical order, and must match at least one file.
+    pub fn paths(&self) -> Result<Vec<PathBuf>, CommandError> {
+        #[cfg(feature = "kafka")]
+        if let Some(source) = &self.source {
+            return Err(format!("source '{}' can only be consumed by the process command", source).into());
+        }
+
+        let mut paths = Vec::new();
+
+        for pattern in self.paths.iter().chain(&self.input) {
+            // NOTE: A URL is never expanded, as a query string would look like a pattern.
+            if !pattern.contains(['*', '?', '[']) || pattern.contains("://") {
+                paths.push(PathBuf::from(pattern));
+                continue;
+            }
+
+            let matches = glob::glob(pattern)
+                .map_err(|e| format!("invalid input pattern '{}': {}", pattern, e))?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("unable to expand input pattern '{}': {}", pattern, e))?;
+
+            if matches.is_empty() {
+                return Err(CommandError::new(ErrorKind::NotFound, format!("input pattern '{}' does not match any files", pattern)));
+            }
+
+            paths.extend(matches);
+        }
+
+        // NOTE: A line number alone would be ambiguous, as every input file has a line of that number.
+        match self.stop_after_line.as_ref().map(|stop| &stop.file) {
+            Some(None) if paths.len() > 1 => {
+                return Err("--stop-after-line must name the file to stop in, as FILE:N, when reading more than one input file".to_string().into());
+            },
+            Some(Some(file)) if !paths.contains(file) => {
+                return Err(format!("--stop-after-line names '{}', which is not an input file", file.display()).into());
+            },
+            _ => {}
+        }
+
+        Ok(paths)
+    }
+
+    /// Read every record from an input file, as it is read, so only a block of a CSV file is held at a time.
+    /// Malformed rows fail the read, unless running in lenient mode, where they are skipped and counted.
+    /// With a reorder window, the records are sorted by timestamp within it, once the whole file has been read.
+    pub fn read<'a>(&'a self, path: &Path, skipped: &'a Skipped) -> Result<Records<'a>, CommandError> {
+        if skipped.stopped() {
+            return Ok(Box::new(iter::empty()));
+        }
+
+        let name = path.to_string_lossy();
+        let format = self.format
+            .or_else(|| match name.split_once("://") {
+                Some((_, location)) => InputFormat::from_path(location.split(['?', '#']).next().unwrap_or_default()),
+                None => InputFormat::from_path(path)
+            })
+            .unwrap_or_default();
+
+        let source = open(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => CommandError::io(&e, format!("input file '{}' does not exist", name)),
+            io::ErrorKind::PermissionDenied => CommandError::io(&e, format!("input file '{}' is not readable", name)),
+            _ => CommandError::io(&e, format!("unable to open input file '{}': {}", name, e))
+        })?;
+
+        let mapping = self.column_mapping()?;
+        let rows = decompress(io::BufReader::new(source))
+            .map(|reader| match format.is_text() {
+                true => self.encoding.decode(reader),
+                false => reader
+            })
+            .and_then(|reader| -> io::Result<Box<dyn Iterator<Item = _>>> {
+                match format {
+                    // NOTE: Only CSV is read a block at a time, as it is the only format large inputs come in.
+                    InputFormat::Csv => Ok(Box::new(CsvRecords::new(reader, self.dialect(), mapping)?)),
+                    format => Ok(Box::new(format.read(reader)?.into_iter().map(Ok)))
+                }
+            })
+            .map_err(|e| CommandError::new(ErrorKind::Parse, format!("input file '{}' has an invalid format: {}", name, e)))?;
+
+        let records = self.records(path, rows, skipped);
+        match self.reorder_window {
+            Some(window) => Ok(Box::new(reorder(records.collect::<Result<_, _>>()?, window).into_iter().map(Ok))),
+            None => Ok(records)
+        }
+    }
+
+    /// The mapping of the columns of CSV input to the fields of a transaction. See [`CsvArgs::column_mapping`].
+    pub fn column_mapping(&self) -> Result<ColumnMapping, CommandError> {
+        self.csv.column_mapping()
+    }
+
+    /// The delimiter and quoting of CSV input.
+    pub fn dialect(&self) -> CsvDialect {
+        self.csv.dialect()
+    }
+
+    /// Yield the records read from an input file, up to the record reading stops after, if any.
+    /// Malformed rows fail the read, unless running in lenient mode, where they are skipped and counted.
+    /// No row is read after the stop, nor after a row fails the read.
+    pub fn records<'a, I>(&'a self, path: &Path, rows: I, skipped: &'a Skipped) -> Records<'a>
+    where
+        I: IntoIterator<Item = io::Result<Result<Record, RecordError>>>,
+        I::IntoIter: 'a
+    {
+        let stop_line = self.stop_after_line.as_ref()
+            .filter(|stop| stop.file.as_deref().is_none_or(|file| file == path))
+            .map(|stop| stop.line);
+        let path = path.to_path_buf();
+        let mut rows = rows.into_iter();
+        let mut failed = false;
+
+        Box::new(iter::from_fn(move || loop {
+            // NOTE: Rows after the stop are never looked at, so a malformed one can not fail the read.
+            if failed || skipped.stopped() {
+                return None;
+            }
+            let row = match rows.next()? {
+                Ok(row) => row,
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(CommandError::new(ErrorKind::Parse, format!("input file '{}' has an invalid format: {}", path.display(), e))));
+                }
+            };
+            let line = match &row {
+                Ok(record) => record.line,
+                Err(e) => e.line
+            };
+            if let Some(stop) = stop_line.filter(|&stop| line > stop) {
+                eprintln!("Stopped after line {} of '{}'", stop, path.display());
+                skipped.stopped.set(true);
+                return None;
+            }
+
+            let id = row.as_ref().ok().map(|record| record.transaction.id());
+            if id.is_some_and(|id| self.stop_after_tx == Some(id)) || stop_line == Some(line) {
+                eprintln!("Stopped after line {} of '{}'", line, path.display());
+                skipped.stopped.set(true);
+            }
+
+            match row {
+                Ok(mut record) => {
+                    if let (Some(tenant), None) = (&self.policy.tenant, record.transaction.tenant()) {
+                        record.transaction = record.transaction.with_tenant(tenant.as_str());
+                    }
+                    return Some(Ok(record));
+                },
+                Err(e) if self.lenient => {
+                    tracing::warn!(file = %path.display(), "skipped malformed row at {}", e);
+                    if let Err(e) = skipped.skip(&path, &e) {
+                        failed = true;
+                        return Some(Err(e));
+                    }
+                },
+                Err(e) => {
+                    failed = true;
+                    return Some(Err(CommandError::new(ErrorKind::Parse, format!("input file '{}' is malformed at {}", path.display(), e))));
+                }
+            }
+        }))
+    }
+}
+
+/// Open an input file, or stream a remote source if the path is a URL.
+fn open(path: &Path) -> io::Result<Box<dyn io::Read>> {
+    match path.to_str() {
+        #[cfg(feature = "http")]
+        Some(url) if transaction_system::remote::is_remote(url) => Ok(transaction_system::remote::open(url)?),
+        #[cfg(not(feature = "http"))]
+        Some(url) if url.starts_with("http://") || url.starts_with("https://") || url.starts_with("s3://") => {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "reading remote sources requires the http or s3 feature"))
+        },
+        Some(url) if url.contains("://") => Err(io::Error::new(io::ErrorKind::Unsupported, "unsupported input source")),
+        _ => Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// The arguments shared by every command that writes the final state of each account.
+#[derive(Args, Debug)]
+pub struct OutputArgs {
+    /// The file to write the accounts to, instead of stdout.
+    /// The file is only replaced once every account has been written, so a failed run never leaves partial output.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// The output format (csv, json or jsonl, and parquet or arrow when built with the arrow feature).
+    #[arg(long, default_value_t)]
+    output_format: OutputFormat,
+
+    /// The character separating the fields of CSV output, such as `;` or `\t` for a tab.
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = parse_csv_char)]
+    output_delimiter: u8,
+
+    /// When fields of CSV output are quoted (necessary, always, non-numeric or never).
+    #[arg(long, value_name = "STYLE", default_value_t)]
+    quote_style: QuoteStyle,
+
+    /// Print the Merkle root of the final accounts to stderr, so two runs can be compared without diffing their output.
+    #[arg(long)]
+    merkle_root: bool,
+
+    /// Write the activity of each account after its funds, in `processed`, `rejected`, `open_disputes` and
+    /// `last_activity` columns. The activity is always that at the end of the run.
+    #[arg(long)]
+    extended_output: bool,
+}
+
+impl OutputArgs {
+    /// The dialect CSV output is written in, always with the `frozen` column when accounts can be frozen.
+    fn dialect(&self, frozen: bool) -> CsvDialect {
+        CsvDialect::default()
+            .with_delimiter(self.output_delimiter)
+            .with_quote_style(self.quote_style)
+            .with_frozen_column(frozen)
+    }
+
+    /// Whether the engines need to count the activity of each account, for the extended output.
+    pub fn activity(&self) -> bool {
+        self.extended_output
+    }
+
+    /// Write the accounts of the books of each tenant in the output format, with the activity of each account if the
+    /// extended output was asked for, and whether each is frozen when accounts can be frozen.
+    fn write_books<W: io::Write>(&self, writer: W, books: &[(Option<String>, Vec<Client>)], activity: &[Book], precision: Precision, frozen: bool) -> io::Result<()> {
+        match self.extended_output {
+            true => self.output_format.write_activity_with(writer, activity, precision, &self.dialect(frozen)),
+            false => self.output_format.write_books_with(writer, books, precision, &self.dialect(frozen))
+        }
+    }
+
+    /// Write every client account in the books of every tenant, with a `tenant` column when there is any tenant other
+    /// than the default books, and amounts written to the engines' precision.
+    /// Each account is written as it was at the given time, if any, from the history of balances, and the `frozen` column
+    /// is always written when `frozen` is set, as when accounts can be frozen.
+    pub fn write_tenants<S: Storage>(&self, tenants: &Tenants<'_, S>, as_of: Option<DateTime<Utc>>, frozen: bool) -> Result<(), CommandError> {
+        let precision = tenants.iter().next().map_or_else(Precision::default, |(_, engine)| engine.precision());
+        let books = tenants.iter()
+            .map(|(tenant, engine)| match as_of {
+                Some(at) => engine.accounts_at(at),
+                None => engine.accounts()
+            }.map(|clients| (tenant.map(str::to_string), clients)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        if self.merkle_root {
+            for (tenant, clients) in &books {
+                match tenant {
+                    Some(tenant) => eprintln!("merkle root of tenant '{}': {}", tenant, merkle_root(clients, precision)),
+                    None => eprintln!("merkle root: {}", merkle_root(clients, precision))
+                }
+            }
+        }
+
+        let activity = match self.extended_output {
+            true => books.iter().zip(tenants.iter())
+                .map(|((tenant, clients), (_, engine))| {
+                    let clients = clients.iter()
+                        .map(|client| (client.clone(), engine.activity(client.id()).cloned().unwrap_or_else(|| Activity::new(client.id()))))
+                        .collect();
+                    (tenant.clone(), clients)
+                })
+                .collect(),
+            false => Vec::new()
+        };
+
+        match &self.output {
+            Some(output) => write_atomically(output, |writer| self.write_books(writer, &books, &activity, precision, frozen))
+                .map_err(|e| CommandError::io(&e, format!("unable to write accounts as {} to '{}': {}", self.output_format, output.display(), e))),
+            None => self.write_books(io::stdout().lock(), &books, &activity, precision, frozen)
+                .map_err(|e| format!("unable to write accounts as {}: {}", self.output_format, e).into())
+        }
+    }
+
+    /// Write the closing balances of a day to a file of its own, in the output format, always with the `frozen` column
+    /// when accounts can be frozen.
+    pub fn write_close(&self, path: &Path, close: &DailyClose, precision: Precision, frozen: bool) -> Result<(), CommandError> {
+        let dialect = self.dialect(frozen);
+        write_atomically(path, |writer| self.output_format.write_close_with(writer, close, precision, &dialect))
+            .map_err(|e| CommandError::io(&e, format!("unable to write the closing balances of {} to '{}': {}", close.day, path.display(), e)))
+    }
+
+    /// Write the closing balances of every day to a single file, in the output format, with a `date` column, and always
+    /// with the `frozen` column when accounts can be frozen.
+    pub fn write_closes(&self, path: &Path, closes: &[DailyClose], precision: Precision, frozen: bool) -> Result<(), CommandError> {
+        let dialect = self.dialect(frozen);
+        write_atomically(path, |writer| self.output_format.write_closes_with(writer, closes, precision, &dialect))
+            .map_err(|e| CommandError::io(&e, format!("unable to write closing balances to '{}': {}", path.display(), e)))
+    }
+
+    /// The extension of a file written in the output format, such as `csv`.
+    pub fn extension(&self) -> String {
+        self.output_format.to_string()
+    }
+
+    /// Write every client account of an engine, with amounts written to the engine's precision.
+    pub fn write<S: Storage>(&self, engine: &Engine<S>) -> Result<(), CommandError> {
+        let clients = engine.accounts().map_err(|e| e.to_string())?;
+        if self.merkle_root {
+            eprintln!("merkle root: {}", merkle_root(&clients, engine.precision()));
+        }
+
+        let activity = match self.extended_output {
+            true => vec![(None, engine.accounts_with_activity().map_err(|e| e.to_string())?)],
+            false => Vec::new()
+        };
+        let books = [(None, clients)];
+        match &self.output {
+            Some(output) => write_atomically(output, |writer| self.write_books(writer, &books, &activity, engine.precision(), engine.freezes()))
+                .map_err(|e| CommandError::io(&e, format!("unable to write accounts as {} to '{}': {}", self.output_format, output.display(), e))),
+            None => self.write_books(io::stdout().lock(), &books, &activity, engine.precision(), engine.freezes())
+                .map_err(|e| format!("unable to write accounts as {}: {}", self.output_format, e).into())
+        }
+    }
+}
+
+/// The client accounts of the books of a tenant, or `None` for the default books, each alongside its activity.
+type Book = (Option<String>, Vec<(Client, Activity)>);
+
+/// The engine keeping the only books the input was processed in, failing if it holds the transactions of more than one
+/// tenant, whose accounts can not be told apart by what the command writes.
+pub fn single_tenant<'t>(tenants: &'t Tenants<'_>, what: &str) -> Result<&'t Engine, CommandError> {
+    tenants.single().ok_or_else(|| {
+        let names = tenants.iter().map(|(tenant, _)| tenant.map_or_else(|| "the default books".to_string(), |tenant| format!("'{}'", tenant)));
+        format!("only the books of a single tenant can be {}, but the input holds transactions of {}", what, names.collect::<Vec<_>>().join(", ")).into()
+    })
+}
+
+/// Read the client accounts of a CSV file, as written by the account output.
+pub fn read_accounts(path: &Path) -> Result<Vec<Client>, CommandError> {
+    read_csv(path, "accounts", accounts_from_reader)
+}
+
+/// Read a CSV file of some kind, failing with a parse error if its contents are invalid.
+fn read_csv<T, F>(path: &Path, what: &str, read: F) -> Result<T, CommandError>
+where
+    F: FnOnce(io::BufReader<File>) -> io::Result<T>
+{
+    File::open(path)
+        .and_then(|file| read(io::BufReader::new(file)))
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::InvalidData => CommandError::new(ErrorKind::Parse, format!("invalid {} in '{}': {}", what, path.display(), e)),
+            _ => CommandError::io(&e, format!("unable to read {} from '{}': {}", what, path.display(), e))
+        })
+}
+
+/// Write a file through a partial file alongside it, which is renamed over the file only once it has been written in full.
+/// The partial file is removed if anything fails, so the file is either left untouched or replaced entirely.
+pub fn write_atomically<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut io::BufWriter<File>) -> io::Result<()>
+{
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    let partial = path.with_file_name(name);
+
+    let written = File::create(&partial)
+        .and_then(|file| {
+            let mut writer = io::BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+        })
+        .and_then(|_| fs::rename(&partial, path));
+
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_writes_leave_the_file_untouched() {
+        let path = std::env::temp_dir().join(format!("accounts-{}.csv", std::process::id()));
+        fs::write(&path, "previous").unwrap();
+
+        let failed = write_atomically(&path, |writer| {
+            writer.write_all(b"client,available")?;
+            Err(io::Error::other("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+        assert!(!path.with_file_name(format!("accounts-{}.csv.partial", std::process::id())).exists());
+
+        write_atomically(&path, |writer| writer.write_all(b"client,available")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "client,available");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn malformed_rows_are_written_to_the_dead_letter_file() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            input: InputArgs
+        }
+
+        let input = std::env::temp_dir().join(format!("malformed-{}.csv", std::process::id()));
+        let dead_letter = input.with_extension("dead.csv");
+        fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,one,2,0.5\nrefund,1,3\n").unwrap();
+
+        let cli = <Cli as clap::Parser>::parse_from(["process", input.to_str().unwrap(), "--lenient", "--dead-letter", dead_letter.to_str().unwrap()]);
+        let skipped = cli.input.skipped().unwrap();
+        let records = cli.input.read(&input, &skipped).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(skipped.malformed(), 2);
+        drop(skipped);
+
+        let written = fs::read_to_string(&dead_letter).unwrap();
+        let mut lines = written.lines();
+        assert_eq!(lines.next(), Some("file,line,column,field,error,record"));
+        assert!(lines.next().unwrap().ends_with(",3,2,client,invalid digit found in string,\"deposit,one,2,0.5\""));
+        assert!(lines.next().unwrap().contains(",4,"));
+        fs::remove_file(&input).unwrap();
+        fs::remove_file(&dead_letter).unwrap();
+    }
+
+    #[test]
+    fn reading_stops_after_a_transaction_or_line() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            input: InputArgs
+        }
+
+        let input = std::env::temp_dir().join(format!("prefix-{}.csv", std::process::id()));
+        fs::write(&input, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,0.5\ndeposit,one,3,0.5\n").unwrap();
+        let read = |stop: &str, at: &str| {
+            let cli = <Cli as clap::Parser>::parse_from(["process", input.to_str().unwrap(), stop, at]);
+            let skipped = cli.input.skipped().unwrap();
+            let records = cli.input.read(&input, &skipped).unwrap().map(|record| record.unwrap().transaction.id()).collect::<Vec<_>>();
+            let again = cli.input.read(&input, &skipped).unwrap().count();
+            (records, again)
+        };
+
+        // NOTE: The malformed row after the stop is never read, so it does not fail the read.
+        assert_eq!(read("--stop-after-tx", "1"), (vec![1], 0));
+        assert_eq!(read("--stop-after-line", "3"), (vec![1, 2], 0));
+        fs::remove_file(&input).unwrap();
+    }
+
+    #[test]
+    fn stop_after_a_line_of_the_named_file() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            input: InputArgs
+        }
+
+        let first = std::env::temp_dir().join(format!("stop-first-{}.csv", std::process::id()));
+        let second = first.with_file_name(format!("stop-second-{}.csv", std::process::id()));
+        fs::write(&first, "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,0.5\n").unwrap();
+        fs::write(&second, "type,client,tx,amount\ndeposit,2,3,1.0\ndeposit,2,4,0.5\n").unwrap();
+        let (first_name, second_name) = (first.to_str().unwrap(), second.to_str().unwrap());
+        let read = |stop: &str| {
+            let cli = <Cli as clap::Parser>::parse_from(["process", first_name, second_name, "--stop-after-line", stop]);
+            let skipped = cli.input.skipped().unwrap();
+            cli.input.paths().map(|paths| paths.iter()
+                .flat_map(|path| cli.input.read(path, &skipped).unwrap())
+                .map(|record| record.unwrap().transaction.id())
+                .collect::<Vec<_>>())
+        };
+
+        // NOTE: Every file before the one named is read whole, however far past the line it goes.
+        assert_eq!(read(&format!("{}:2", second_name)).unwrap(), vec![1, 2, 3]);
+        assert_eq!(read(&format!("{}:2", first_name)).unwrap(), vec![1]);
+        assert!(read("2").is_err());
+        assert!(read("other.csv:2").is_err());
+        assert!(parse_stop_line(":2").is_err());
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+}
@@ -0,0 +1,791 @@
+use std::{fs::{self, File}, io, iter, path::{Path, PathBuf}, thread, time::{Duration, Instant}};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Args;
+use transaction_system::{AuditLog, DailyClose, Encoding, InputFormat, Journal, Ledger, MemoryStorage, Precision, Rejection, Retention, Snapshot, SnapshotError, SpillStorage, Storage, StorageError, Tail, Tenants, process_parallel_tenants};
+
+use super::{CommandError, ErrorKind, InputArgs, OutputArgs, Records, RiskArgs, Screening, Skipped, read_accounts, write_atomically};
+
+/// Process every transaction and write the final state of each account.
+#[derive(Args, Debug)]
+pub struct ProcessArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// A CSV file to write every rejected transaction to, alongside the reason it was rejected.
+    #[arg(long, value_name = "FILE")]
+    rejects: Option<PathBuf>,
+
+    #[command(flatten)]
+    risk: RiskArgs,
+
+    /// Post every movement of funds to a double-entry ledger of the client accounts and the engine's own suspense,
+    /// chargeback loss and interest accounts, and write its trial balance to this CSV file.
+    #[arg(long, value_name = "FILE")]
+    trial_balance: Option<PathBuf>,
+
+    /// Write every transaction still disputed once processing ends to this CSV file, with the amount it holds and its age in
+    /// whole days, as of the latest transaction, so the held funds of each client can be traced to their disputes.
+    #[arg(long, value_name = "FILE")]
+    open_disputes: Option<PathBuf>,
+
+    /// Write the closing balances of every client at the end of each day to this file, in the output format, with a `date`
+    /// column holding the day of each row. A day is closed once a transaction of a later day arrives, and the last once every
+    /// input has been processed, applying everything that fell due and all the interest earned by the end of the day.
+    #[arg(long, value_name = "FILE", conflicts_with = "shards")]
+    close_of_day: Option<PathBuf>,
+
+    /// Write the closing balances of every client at the end of each day to a file of its own in this directory, named after
+    /// the day, such as `2024-01-31.csv`, as each day is closed.
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["shards", "close_of_day"])]
+    close_of_day_dir: Option<PathBuf>,
+
+    /// Process transactions across this many worker threads, sharded by client id,
+    /// or across this many async tasks when consuming a streaming source.
+    #[arg(long, value_name = "N", conflicts_with_all = ["journal", "audit"])]
+    shards: Option<usize>,
+
+    /// Keep at most this many transactions in memory, spilling older ones to a temporary file until a dispute needs them.
+    #[arg(long, value_name = "N", conflicts_with = "shards")]
+    spill_after: Option<usize>,
+
+    /// The directory to write spilled transactions to, instead of the system's temporary directory.
+    #[arg(long, value_name = "DIR", requires = "spill_after")]
+    spill_dir: Option<PathBuf>,
+
+    /// Keep every client and transaction in Redis at this URL, such as `redis://localhost:6379/0`, rather than in memory,
+    /// so several instances can share their accounts.
+    #[cfg(feature = "redis")]
+    #[arg(long, value_name = "URL", conflicts_with_all = ["shards", "spill_after"])]
+    redis: Option<String>,
+
+    /// The prefix of every key kept in Redis.
+    #[cfg(feature = "redis")]
+    #[arg(long, value_name = "PREFIX", default_value = transaction_system::DEFAULT_REDIS_PREFIX, requires = "redis")]
+    redis_prefix: String,
+
+    /// Expire every transaction kept in Redis this many seconds after it was last written, unless it is disputed,
+    /// after which it can no longer be disputed.
+    #[cfg(feature = "redis")]
+    #[arg(long, value_name = "SECS", requires = "redis")]
+    redis_ttl: Option<u64>,
+
+    /// Keep every client and transaction in PostgreSQL at this URL, such as `postgres://user@localhost/transactions`,
+    /// committing each transaction's writes alongside its audit record and the line of its input file, and skipping the
+    /// lines already committed by an earlier run.
+    #[cfg(feature = "postgres")]
+    #[arg(long, value_name = "URL", conflicts_with_all = ["shards", "spill_after", "follow"])]
+    postgres: Option<String>,
+
+    /// Which transactions to keep once processed: "all", or only those that can still be disputed ("disputable").
+    #[arg(long, value_name = "POLICY", default_value_t = Retention::All)]
+    retention: Retention,
+
+    /// Resume from the engine state saved in a snapshot file, before processing any input.
+    #[arg(long, value_name = "FILE")]
+    snapshot_in: Option<PathBuf>,
+
+    /// Seed the client accounts from a CSV file of accounts, as written by a previous run, before processing any input.
+    /// Only balances are carried over, so transactions from before it can not be disputed.
+    #[arg(long, value_name = "FILE", conflicts_with = "snapshot_in")]
+    initial_balances: Option<PathBuf>,
+
+    /// Save the engine state to a snapshot file once every input has been processed.
+    #[arg(long, value_name = "FILE")]
+    snapshot_out: Option<PathBuf>,
+
+    /// Append every accepted transaction and what it applied to a journal file, as it is processed.
+    #[arg(long, value_name = "FILE")]
+    journal: Option<PathBuf>,
+
+    /// Append every processed transaction, accepted or rejected, to a hash-chained audit log, as it is processed.
+    #[arg(long, value_name = "FILE")]
+    audit: Option<PathBuf>,
+
+    /// Serve Prometheus metrics at `/metrics` on this address while processing.
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDR", conflicts_with = "shards")]
+    metrics: Option<String>,
+
+    /// The consumer group offsets are committed to, when consuming a streaming source.
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "NAME", default_value = "transaction-system")]
+    group: String,
+
+    /// Save a snapshot and commit offsets after this many messages, when consuming a streaming source.
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    checkpoint_interval: u64,
+
+    /// Decode every message of a streaming source as Avro in the Confluent wire format, with the schemas of the registry
+    /// at this URL, such as `http://localhost:8081`, rather than as JSON.
+    #[cfg(all(feature = "kafka", feature = "avro"))]
+    #[arg(long, value_name = "URL")]
+    schema_registry: Option<String>,
+
+    /// Keep reading the input file once every transaction in it has been processed, processing transactions as they are
+    /// appended to it like `tail -f`, and writing the accounts again whenever any arrive. Only uncompressed CSV can be followed.
+    /// Following ends once reading stops, at the record given by `--stop-after-tx` or `--stop-after-line`.
+    #[arg(long, conflicts_with_all = ["shards", "reorder_window"])]
+    follow: bool,
+
+    /// How often a followed input file is checked for new transactions, in seconds.
+    #[arg(long, value_name = "SECS", default_value_t = 1, requires = "follow")]
+    follow_interval: u64,
+
+    /// Stop consuming a streaming source, or following an input file, once no transaction has arrived for this many seconds,
+    /// rather than running forever.
+    #[arg(long, value_name = "SECS")]
+    idle_timeout: Option<u64>,
+
+    #[cfg(all(feature = "kafka", feature = "http"))]
+    #[command(flatten)]
+    webhooks: super::WebhookArgs,
+}
+
+pub fn run(args: ProcessArgs) -> Result<(), CommandError> {
+    if args.input.policy.fees.is_set() && args.shards.is_some_and(|shards| shards > 1) {
+        return Err("--fees can not be charged with more than one shard, as the fee account would be split across the shards".to_string().into());
+    }
+    if args.input.policy.interest.is_set() && args.shards.is_some_and(|shards| shards > 1) {
+        return Err("--interest-rate can not be accrued with more than one shard, as each shard would only see its own days pass".to_string().into());
+    }
+
+    let skipped = args.input.skipped()?;
+    let screening = args.risk.screening()?;
+
+    #[cfg(feature = "kafka")]
+    if args.input.source.is_some() && (args.close_of_day.is_some() || args.close_of_day_dir.is_some()) {
+        return Err("--close-of-day is not supported when consuming a streaming source".to_string().into());
+    }
+
+    #[cfg(feature = "kafka")]
+    if let Some(source) = &args.input.source {
+        // NOTE: The snapshot is written at every checkpoint, alongside the offsets it was taken at.
+        let (engine, rejections) = run_stream(&args, source, &skipped, &screening)?;
+        let mut tenants = Tenants::from(engine);
+        settle(&args, &mut tenants)?;
+        return finish(&args, &tenants, rejections, skipped, &screening);
+    }
+
+    #[cfg(all(feature = "kafka", feature = "http"))]
+    if !args.webhooks.is_empty() {
+        return Err("--webhook is only supported when consuming a streaming source".to_string().into());
+    }
+
+    #[cfg(feature = "redis")]
+    if let Some(url) = &args.redis {
+        // NOTE: The keys of each tenant other than the default books are kept under a prefix of their own.
+        let storage = |tenant: Option<&str>| transaction_system::RedisStorage::connect(url)
+            .map(|storage| storage
+                .with_prefix(match tenant {
+                    Some(tenant) => format!("{}:tenant:{}", args.redis_prefix, tenant),
+                    None => args.redis_prefix.clone()
+                })
+                .with_ttl(args.redis_ttl.map(std::time::Duration::from_secs)));
+
+        let (mut tenants, rejections) = run_sequential(&args, storage, &skipped, &screening)?;
+        settle(&args, &mut tenants)?;
+        return complete(&args, &tenants, rejections, skipped, &screening);
+    }
+
+    #[cfg(feature = "postgres")]
+    if let Some(url) = &args.postgres {
+        let storage = |tenant: Option<&str>| match tenant == args.input.policy.tenant.as_deref() {
+            true => transaction_system::PostgresStorage::connect(url),
+            false => Err(StorageError("only the books of a single tenant can be kept in PostgreSQL".to_string()))
+        };
+
+        let (mut tenants, rejections) = run_sequential(&args, storage, &skipped, &screening)?;
+        settle(&args, &mut tenants)?;
+        return complete(&args, &tenants, rejections, skipped, &screening);
+    }
+
+    match (args.shards, args.spill_after) {
+        (Some(_), _) if args.snapshot_in.is_some() || args.initial_balances.is_some() =>
+            Err("--shards can not start from --snapshot-in or --initial-balances when processing input files".to_string().into()),
+        (Some(shards), _) => {
+            let (mut tenants, rejections) = run_parallel(&args, shards, &skipped, &screening)?;
+            settle(&args, &mut tenants)?;
+            complete(&args, &tenants, rejections, skipped, &screening)
+        },
+        (None, Some(capacity)) => {
+            let directory = args.spill_dir.clone().unwrap_or_else(std::env::temp_dir);
+            let storage = |_: Option<&str>| SpillStorage::new(capacity, &directory)
+                .map_err(|e| StorageError(format!("unable to create a spill file in '{}': {}", directory.display(), e)));
+
+            let (mut tenants, rejections) = run_sequential(&args, storage, &skipped, &screening)?;
+            settle(&args, &mut tenants)?;
+            complete(&args, &tenants, rejections, skipped, &screening)
+        },
+        (None, None) => {
+            let (mut tenants, rejections) = run_sequential(&args, |_| Ok(MemoryStorage::default()), &skipped, &screening)?;
+            settle(&args, &mut tenants)?;
+            complete(&args, &tenants, rejections, skipped, &screening)
+        }
+    }
+}
+
+/// Advance the books of every tenant to the times given by the arguments, if any.
+fn settle<S: Storage>(args: &ProcessArgs, tenants: &mut Tenants<'_, S>) -> Result<(), CommandError> {
+    tenants.iter_mut().try_for_each(|(_, engine)| args.input.policy.interest.settle(engine))
+}
+
+/// Save the engine state to the snapshot file, if one was given, and then finish the run.
+fn complete<S: Storage>(args: &ProcessArgs, tenants: &Tenants<'_, S>, rejections: Vec<Rejection>, skipped: Skipped, screening: &Screening) -> Result<(), CommandError> {
+    if let Some(snapshot_out) = &args.snapshot_out {
+        let engine = tenants.single()
+            .ok_or_else(|| format!("unable to write snapshot to '{}': a snapshot can only hold the books of a single tenant", snapshot_out.display()))?;
+        let snapshot = engine.snapshot()
+            .map_err(|e| format!("unable to write snapshot to '{}': {}", snapshot_out.display(), e))?;
+        write_snapshot(&snapshot, snapshot_out)?;
+    }
+
+    finish(args, tenants, rejections, skipped, screening)
+}
+
+/// Report the skipped rows, and write the rejected and flagged transactions and the final state of each account.
+fn finish<S: Storage>(args: &ProcessArgs, tenants: &Tenants<'_, S>, rejections: Vec<Rejection>, mut skipped: Skipped, screening: &Screening) -> Result<(), CommandError> {
+    if args.input.lenient {
+        skipped.rejected = rejections.len();
+        skipped.report();
+    }
+
+    if let Some(rejects) = &args.rejects {
+        let written = csv::Writer::from_path(rejects)
+            .and_then(|mut writer| {
+                rejections.iter().try_for_each(|rejection| writer.serialize(rejection))?;
+                Ok(writer.flush()?)
+            });
+
+        if written.is_err() {
+            return Err(format!("unable to write rejected transactions to '{}'", rejects.display()).into());
+        }
+    }
+    screening.write()?;
+    write_trial_balance(args, tenants)?;
+    write_open_disputes(args, tenants)?;
+
+    // NOTE: A followed file has its accounts written as each batch is processed, which already covers the last.
+    match args.follow {
+        true => Ok(()),
+        false => args.output.write_tenants(tenants, args.input.policy.interest.as_of(), freezes(tenants))
+    }
+}
+
+/// A new ledger, if a trial balance is written.
+fn ledger(args: &ProcessArgs) -> Option<Ledger> {
+    args.trial_balance.as_ref().map(|_| Ledger::new())
+}
+
+/// Write the trial balance of the ledger of every tenant's engine, if one was asked for,
+/// with a `tenant` column when there is any tenant other than the default books.
+fn write_trial_balance<S: Storage>(args: &ProcessArgs, tenants: &Tenants<'_, S>) -> Result<(), CommandError> {
+    let Some(path) = &args.trial_balance else {
+        return Ok(());
+    };
+
+    let multi_tenant = tenants.is_multi_tenant();
+    let written = csv::Writer::from_path(path)
+        .and_then(|mut writer| {
+            let header = ["tenant", "account", "currency", "balance"];
+            writer.write_record(&header[usize::from(!multi_tenant)..])?;
+            for (tenant, engine) in tenants.iter() {
+                let (Some(ledger), precision) = (engine.ledger(), engine.precision()) else {
+                    continue;
+                };
+                for row in ledger.trial_balance() {
+                    let record = [tenant.unwrap_or_default().to_string(), row.account.to_string(), row.currency.unwrap_or_default(), precision.format(&row.balance)];
+                    writer.write_record(&record[usize::from(!multi_tenant)..])?;
+                }
+            }
+            Ok(writer.flush()?)
+        });
+    written.map_err(|_| format!("unable to write the trial balance to '{}'", path.display()).into())
+}
+
+/// Write every dispute still open in the books of every tenant's engine, if they were asked for,
+/// with a `tenant` column when there is any tenant other than the default books.
+fn write_open_disputes<S: Storage>(args: &ProcessArgs, tenants: &Tenants<'_, S>) -> Result<(), CommandError> {
+    let Some(path) = &args.open_disputes else {
+        return Ok(());
+    };
+
+    let multi_tenant = tenants.is_multi_tenant();
+    let books = tenants.iter()
+        .map(|(tenant, engine)| engine.open_disputes().map(|disputes| (tenant, engine.precision(), disputes)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("unable to list the open disputes: {}", e))?;
+    let written = csv::Writer::from_path(path)
+        .and_then(|mut writer| {
+            let header = ["tenant", "client", "tx", "currency", "amount", "age_days"];
+            writer.write_record(&header[usize::from(!multi_tenant)..])?;
+            for (tenant, precision, disputes) in &books {
+                for dispute in disputes {
+                    let age = dispute.age.map(|age| age.num_days().to_string()).unwrap_or_default();
+                    let record = [tenant.unwrap_or_default().to_string(), dispute.client.to_string(), dispute.transaction.to_string(), dispute.currency.clone().unwrap_or_default(), precision.format(&dispute.amount), age];
+                    writer.write_record(&record[usize::from(!multi_tenant)..])?;
+                }
+            }
+            Ok(writer.flush()?)
+        });
+    written.map_err(|_| format!("unable to write the open disputes to '{}'", path.display()).into())
+}
+
+/// Read the state to start from, either a snapshot file or the accounts of the initial balances file, if either was given.
+fn initial_state(args: &ProcessArgs) -> Result<Option<Snapshot>, CommandError> {
+    if let Some(snapshot_in) = &args.snapshot_in {
+        return read_snapshot(snapshot_in).map(Some);
+    }
+    let Some(path) = &args.initial_balances else {
+        return Ok(None);
+    };
+
+    Ok(Some(Snapshot { clients: read_accounts(path)?, ..Snapshot::default() }))
+}
+
+/// Read a snapshot file.
+fn read_snapshot(path: &Path) -> Result<Snapshot, CommandError> {
+    File::open(path)
+        .map_err(SnapshotError::Io)
+        .and_then(|file| Snapshot::read(io::BufReader::new(file)))
+        .map_err(|e| CommandError::snapshot(&e, format!("unable to read snapshot from '{}': {}", path.display(), e)))
+}
+
+/// Write a snapshot file, replacing any previous snapshot only once the new one has been written in full.
+fn write_snapshot(snapshot: &Snapshot, path: &Path) -> Result<(), CommandError> {
+    write_atomically(path, |writer| snapshot.write(writer).map_err(io::Error::other))
+        .map_err(|e| CommandError::io(&e, format!("unable to write snapshot to '{}': {}", path.display(), e)))
+}
+
+/// The records read from an input file at once, alongside the path of the file.
+type Batch<'a> = Result<(PathBuf, Records<'a>), CommandError>;
+
+/// Process every transaction in order on the current thread, in the isolated books of its tenant,
+/// each kept on top of a storage backend made for it.
+/// The snapshot or initial balances, if any, are restored to the books of the tenant given by the arguments.
+fn run_sequential<'a, S, F>(args: &'a ProcessArgs, mut storage: F, skipped: &Skipped, screening: &'a Screening) -> Result<(Tenants<'a, S>, Vec<Rejection>), CommandError>
+where
+    S: Storage,
+    F: FnMut(Option<&str>) -> Result<S, StorageError> + 'a
+{
+    let engines = args.input.engines()?;
+    let mut tenants = Tenants::new(move |tenant| {
+        let builder = engines()
+            .storage(storage(tenant)?)
+            .retention(args.retention)
+            .ledger(ledger(args))
+            .activity(args.output.activity())
+            .tenant(tenant.map(str::to_string));
+        Ok(screening.attach(builder).build())
+    });
+    let books = |tenant: Option<&str>, e: StorageError| match tenant {
+        Some(tenant) => format!("unable to keep the books of tenant '{}': {}", tenant, e),
+        None => format!("unable to keep the default books: {}", e)
+    };
+
+    let home = args.input.policy.tenant.as_deref();
+    let engine = tenants.engine_mut(home).map_err(|e| books(home, e))?;
+    if let Some(snapshot) = initial_state(args)? {
+        engine.restore(snapshot)
+            .map_err(|e| format!("unable to restore the initial state: {}", e))?;
+    }
+    let mut journal = match &args.journal {
+        Some(path) => Some(Journal::open(path).map_err(|e| format!("unable to open journal '{}': {}", path.display(), e))?),
+        None => None
+    };
+    let mut audit = match &args.audit {
+        Some(path) => Some(AuditLog::open(path).map_err(|e| CommandError::io(&e, format!("unable to open audit log '{}': {}", path.display(), e)))?),
+        None => None
+    };
+
+    #[cfg(feature = "metrics")]
+    let metrics = match &args.metrics {
+        Some(addr) => {
+            let metrics = transaction_system::Metrics::new();
+            metrics.serve(addr.as_str()).map_err(|e| format!("unable to serve metrics on '{}': {}", addr, e))?;
+            Some(metrics)
+        },
+        None => None
+    };
+
+    let batches: Box<dyn Iterator<Item = Batch>> = match args.follow {
+        true => Box::new(follow(args, skipped)?),
+        false => Box::new(args.input.paths()?.into_iter().map(|path| {
+            let records = args.input.read(&path, skipped)?;
+            Ok((path, records))
+        }))
+    };
+
+    let mut closing = Closing::new(args)?;
+    let mut rejections = Vec::new();
+    for batch in batches {
+        let (path, records) = batch?;
+        let source = path.display().to_string();
+        let committed = tenants.engine_mut(home)
+            .map_err(|e| books(home, e))?
+            .storage()
+            .offset(&source)
+            .map_err(|e| format!("unable to read the offset of '{}': {}", source, e))?;
+
+        // NOTE: A storage that tracks offsets already committed the lines up to its offset in an earlier run.
+        for record in records {
+            let record = record?;
+            if committed.is_some_and(|line| record.line <= line) {
+                continue;
+            }
+            closing.pass(&mut tenants, record.transaction.timestamp())?;
+            let tenant = record.transaction.tenant();
+            let engine = tenants.engine_mut(tenant).map_err(|e| books(tenant, e))?;
+            engine.storage_mut().track_offset(&source, record.line)
+                .map_err(|e| format!("unable to track the offset of '{}': {}", source, e))?;
+
+            #[cfg(feature = "metrics")]
+            let started = std::time::Instant::now();
+
+            let result = engine.process(&record.transaction);
+
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &metrics {
+                metrics.observe(&record.transaction, &result, started.elapsed());
+            }
+
+            // NOTE: A transaction is journalled as it was applied, so that of an owner of a joint account is replayed
+            // onto the shared account even without the owners.
+            if let (Some(journal), Ok(applied)) = (&mut journal, &result) {
+                journal.append(&engine.resolve(&record.transaction), applied)
+                    .map_err(|e| format!("unable to append to journal: {}", e))?;
+            }
+            if let Some(audit) = &mut audit {
+                audit.append(&record.transaction, &result)
+                    .map_err(|e| format!("unable to append to audit log: {}", e))?;
+            }
+
+            if let Err(e) = result {
+                // NOTE: A broken invariant always stops the run, as the engine can not be trusted after it.
+                if args.input.strict || e.is_violation() {
+                    return Err(CommandError::rejected(&e, format!("transaction {} on line {} of '{}' was rejected: {}", record.transaction.id(), record.line, path.display(), e)));
+                }
+
+                rejections.push(Rejection {
+                    file: Some(path.display().to_string()),
+                    ..Rejection::new(record.line, &record.transaction, &e)
+                });
+            }
+        }
+
+        if args.follow {
+            args.output.write_tenants(&tenants, args.input.policy.interest.as_of(), freezes(&tenants))?;
+        }
+    }
+    closing.finish(&mut tenants)?;
+    Ok((tenants, rejections))
+}
+
+/// The days closed as transactions of later days arrive, when the closing balances of each day are written.
+struct Closing<'a> {
+    args: &'a ProcessArgs,
+
+    /// The day still open, or `None` before any transaction with a timestamp.
+    day: Option<NaiveDate>,
+
+    /// Every day closed so far, when they are written to a single file.
+    closes: Vec<DailyClose>
+}
+
+impl<'a> Closing<'a> {
+    /// Start closing days, making the directory each day is written to, if one was given.
+    fn new(args: &'a ProcessArgs) -> Result<Self, CommandError> {
+        if let Some(dir) = &args.close_of_day_dir {
+            fs::create_dir_all(dir)
+                .map_err(|e| CommandError::io(&e, format!("unable to create the closing balance directory '{}': {}", dir.display(), e)))?;
+        }
+        Ok(Self { args, day: None, closes: Vec::new() })
+    }
+
+    /// Whether the closing balances of each day are written.
+    fn is_set(&self) -> bool {
+        self.args.close_of_day.is_some() || self.args.close_of_day_dir.is_some()
+    }
+
+    /// Close every day before that of the timestamp, if it is later than the day still open.
+    /// A transaction without a timestamp, or with an earlier one, happens on the day still open.
+    fn pass<S: Storage>(&mut self, tenants: &mut Tenants<'_, S>, timestamp: Option<DateTime<Utc>>) -> Result<(), CommandError> {
+        let Some(today) = timestamp.map(|timestamp| timestamp.date_naive()).filter(|_| self.is_set()) else {
+            return Ok(());
+        };
+
+        let mut day = *self.day.get_or_insert(today);
+        while day < today {
+            self.close(tenants, day)?;
+            match day.succ_opt() {
+                Some(next) => day = next,
+                None => break
+            }
+        }
+        self.day = Some(day);
+        Ok(())
+    }
+
+    /// Close a day in the books of every tenant, writing its closing balances to a file of its own if they are written per day.
+    fn close<S: Storage>(&mut self, tenants: &mut Tenants<'_, S>, day: NaiveDate) -> Result<(), CommandError> {
+        let close = DailyClose::close(tenants, day)
+            .map_err(|e| format!("unable to close {}: {}", day, e))?;
+        match &self.args.close_of_day_dir {
+            Some(dir) => self.args.output.write_close(&dir.join(format!("{}.{}", day, self.args.output.extension())), &close, precision(tenants), freezes(tenants)),
+            None => {
+                self.closes.push(close);
+                Ok(())
+            }
+        }
+    }
+
+    /// Close the day still open, and then write the closing balances of every day to a single file, if one was given.
+    fn finish<S: Storage>(mut self, tenants: &mut Tenants<'_, S>) -> Result<(), CommandError> {
+        if let Some(day) = self.day.take() {
+            self.close(tenants, day)?;
+        }
+        match &self.args.close_of_day {
+            Some(path) => self.args.output.write_closes(path, &self.closes, precision(tenants), freezes(tenants)),
+            None => Ok(())
+        }
+    }
+}
+
+/// Whether the accounts in the books of any tenant can be frozen.
+fn freezes<S: Storage>(tenants: &Tenants<'_, S>) -> bool {
+    tenants.iter().any(|(_, engine)| engine.freezes())
+}
+
+/// The precision the books of every tenant are kept to.
+fn precision<S: Storage>(tenants: &Tenants<'_, S>) -> Precision {
+    tenants.iter().next().map_or_else(Precision::default, |(_, engine)| engine.precision())
+}
+
+/// Follow an input file as transactions are appended to it, yielding the records read each time any arrive,
+/// starting with every record already in it, until it goes idle or reading stops.
+fn follow<'a>(args: &'a ProcessArgs, skipped: &'a Skipped) -> Result<impl Iterator<Item = Batch<'a>> + 'a, CommandError> {
+    let path = match args.input.paths()?.as_slice() {
+        [path] => path.clone(),
+        _ => return Err("--follow requires exactly one input file".to_string().into())
+    };
+
+    let name = path.to_string_lossy().into_owned();
+    if name.contains("://") || args.input.format.or_else(|| InputFormat::from_path(&path)).unwrap_or_default() != InputFormat::Csv {
+        return Err(format!("unable to follow '{}', only local CSV files can be followed", name).into());
+    }
+    if args.input.encoding != Encoding::Utf8 {
+        return Err(format!("unable to follow '{}', only UTF-8 files can be followed", name).into());
+    }
+    let mapping = args.input.column_mapping()?;
+    let mut tail = File::open(&path)
+        .map(|file| Tail::new(file).with_dialect(args.input.dialect()).with_mapping(mapping))
+        .map_err(|e| CommandError::io(&e, format!("unable to open input file '{}': {}", name, e)))?;
+
+    let interval = Duration::from_secs(args.follow_interval);
+    let idle_timeout = args.idle_timeout.map(Duration::from_secs);
+    let mut last_record = Instant::now();
+    let mut first = true;
+
+    Ok(iter::from_fn(move || loop {
+        // NOTE: Once reading stops, following ends too, rather than waiting for the file to go idle.
+        if skipped.stopped() {
+            return None;
+        }
+        let rows = match tail.poll() {
+            Ok(rows) => rows,
+            Err(e) => return Some(Err(CommandError::new(ErrorKind::Parse, format!("input file '{}' has an invalid format: {}", name, e))))
+        };
+
+        // NOTE: The first batch is yielded even when the file is empty, so the accounts are always written at least once.
+        if std::mem::take(&mut first) || !rows.is_empty() {
+            last_record = Instant::now();
+            return Some(Ok((path.clone(), args.input.records(&path, rows.into_iter().map(Ok), skipped))));
+        }
+        if idle_timeout.is_some_and(|timeout| last_record.elapsed() >= timeout) {
+            return None;
+        }
+        thread::sleep(interval);
+    }))
+}
+
+/// Process every transaction across worker threads, sharded by client id.
+/// Input files are still read one at a time, in order.
+fn run_parallel<'a>(args: &'a ProcessArgs, shards: usize, skipped: &Skipped, screening: &'a Screening) -> Result<(Tenants<'a>, Vec<Rejection>), CommandError> {
+    let paths = args.input.paths()?;
+
+    let mut error = None;
+    let records = paths.iter()
+        .flat_map(|path| args.input.read(path, skipped)
+            .unwrap_or_else(|e| Box::new(iter::once(Err(e))))
+            .map(move |record| record.map(|record| (path, record))))
+        .map_while(|record| record.map_err(|e| error = Some(e)).ok())
+        .map(|(path, record)| {
+            // The reason is filled in once the transaction has been rejected.
+            let rejection = Rejection {
+                file: Some(path.display().to_string()),
+                line: record.line,
+                type_: record.transaction.type_(),
+                client_id: record.transaction.client_id(),
+                id: record.transaction.id(),
+                reason: String::new()
+            };
+            (rejection, record.transaction)
+        });
+    let engines = args.input.engines()?;
+    // NOTE: Every shard screens its own clients, recording to the same flags, and keeps the books of each tenant apart.
+    let (mut tenants, rejected) = process_parallel_tenants(records, shards, move |tenant| screening.attach(engines()
+        .tenant(tenant.map(str::to_string))
+        .retention(args.retention)
+        .ledger(ledger(args))
+        .activity(args.output.activity())).build());
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    let home = args.input.policy.tenant.as_deref();
+    tenants.engine_mut(home).map_err(|e| e.to_string())?;
+
+    // NOTE: A broken invariant always stops the run, as the engine can not be trusted after it.
+    let failed = rejected.iter().find(|(_, e)| args.input.strict || e.is_violation());
+    if let Some((rejection, e)) = failed {
+        return Err(CommandError::rejected(e, format!("transaction {} on line {} was rejected: {}", rejection.id, rejection.line, e)));
+    }
+
+    let rejections = rejected.into_iter()
+        .map(|(rejection, e)| Rejection { reason: e.to_string(), ..rejection })
+        .collect::<Vec<_>>();
+    Ok((tenants, rejections))
+}
+
+/// Consume transactions from a streaming source until it goes idle, checkpointing as it goes.
+/// Transactions are applied by an engine sharded across async tasks, so consuming carries on while they are processed.
+/// At each checkpoint the snapshot is saved with the offsets it was taken at, and only then are the offsets committed,
+/// so a restart from the snapshot resumes exactly after the last transaction it includes.
+#[cfg(feature = "kafka")]
+fn run_stream(args: &ProcessArgs, url: &transaction_system::KafkaUrl, skipped: &Skipped, screening: &Screening) -> Result<(transaction_system::Engine, Vec<Rejection>), CommandError> {
+    use transaction_system::{KafkaSource, KafkaSourceError, ShardedEngine};
+
+    let snapshot_out = args.snapshot_out.as_ref()
+        .ok_or_else(|| format!("consuming '{}' requires --snapshot-out to checkpoint progress", url))?;
+    if args.journal.is_some() || args.audit.is_some() {
+        return Err(format!("consuming '{}' does not support --journal or --audit", url).into());
+    }
+
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("unable to start the runtime: {}", e))?;
+
+    #[cfg(feature = "http")]
+    let webhook = args.webhooks.spawn(args.input.policy.precision.precision());
+
+    // NOTE: Polling the source blocks, which only holds up this thread, as the shards run on the runtime's workers.
+    let engines = args.input.engines()?;
+    let result = runtime.block_on(async {
+        let engine = ShardedEngine::spawn(args.shards.unwrap_or(1), || {
+            let builder = screening.attach(engines().retention(args.retention).ledger(ledger(args)).activity(args.output.activity()));
+
+            #[cfg(feature = "http")]
+            let builder = match &webhook {
+                Some((webhook, _)) => builder.observer(webhook.clone()),
+                None => builder
+            };
+            builder.build()
+        });
+
+        let mut offsets = Vec::new();
+        if let Some(mut snapshot) = initial_state(args)? {
+            offsets = std::mem::take(&mut snapshot.offsets);
+            engine.restore(snapshot).await
+                .map_err(|e| format!("unable to restore the initial state: {}", e))?;
+        }
+
+        let source = KafkaSource::connect(url, &args.group, &offsets)
+            .map_err(|e| format!("unable to consume '{}': {}", url, e))?;
+        #[cfg(feature = "avro")]
+        let source = match &args.schema_registry {
+            Some(registry) => source.with_schema_registry(transaction_system::SchemaRegistry::new(registry.as_str())),
+            None => source
+        };
+        let mut source = source;
+
+        let idle_timeout = args.idle_timeout.map(Duration::from_secs);
+        let mut last_message = Instant::now();
+        let mut pending = Vec::new();
+        let mut rejections = Vec::new();
+
+        loop {
+            let (offset, transaction) = match source.poll(Duration::from_secs(1)) {
+                None if idle_timeout.is_some_and(|timeout| last_message.elapsed() >= timeout) => break,
+                None => continue,
+                Some(Ok(message)) => message,
+                Some(Err(e @ KafkaSourceError::Format(..))) if args.input.lenient => {
+                    tracing::warn!("skipped {}", e);
+                    skipped.malformed.set(skipped.malformed.get() + 1);
+                    continue;
+                },
+                Some(Err(e @ KafkaSourceError::Format(..))) => return Err(CommandError::new(ErrorKind::Parse, format!("unable to consume '{}': {}", url, e))),
+                Some(Err(e)) => return Err(format!("unable to consume '{}': {}", url, e).into())
+            };
+            last_message = Instant::now();
+
+            // The reason is filled in once the transaction has been rejected.
+            let rejection = Rejection {
+                file: Some(format!("{}/{}", offset.topic, offset.partition)),
+                line: offset.offset as u64,
+                type_: transaction.type_(),
+                client_id: transaction.client_id(),
+                id: transaction.id(),
+                reason: String::new()
+            };
+            pending.push((rejection, engine.submit(transaction).await));
+
+            if pending.len() as u64 >= args.checkpoint_interval {
+                checkpoint(args, &engine, &source, &mut pending, &mut rejections, snapshot_out, url).await?;
+            }
+        }
+
+        checkpoint(args, &engine, &source, &mut pending, &mut rejections, snapshot_out, url).await?;
+        Ok((engine.into_engine().await, rejections))
+    });
+
+    #[cfg(feature = "http")]
+    if let Some((_, worker)) = webhook {
+        worker.finish();
+    }
+    result
+}
+
+/// Wait for every pending transaction to be processed, recording those that were rejected,
+/// then save the snapshot alongside the offsets reached and commit them.
+#[cfg(feature = "kafka")]
+async fn checkpoint(
+    args: &ProcessArgs,
+    engine: &transaction_system::ShardedEngine,
+    source: &transaction_system::KafkaSource,
+    pending: &mut Vec<(Rejection, transaction_system::Pending)>,
+    rejections: &mut Vec<Rejection>,
+    snapshot_out: &Path,
+    url: &transaction_system::KafkaUrl
+) -> Result<(), CommandError> {
+    for (rejection, pending) in pending.drain(..) {
+        let Err(e) = pending.await else {
+            continue;
+        };
+
+        if args.input.strict || e.is_violation() {
+            return Err(CommandError::rejected(&e, format!("transaction {} at offset {} of '{}' was rejected: {}", rejection.id, rejection.line, rejection.file.unwrap_or_default(), e)));
+        }
+        rejections.push(Rejection { reason: e.to_string(), ..rejection });
+    }
+
+    let mut snapshot = engine.snapshot().await
+        .map_err(|e| format!("unable to write snapshot to '{}': {}", snapshot_out.display(), e))?;
+    snapshot.offsets = source.offsets();
+    write_snapshot(&snapshot, snapshot_out)?;
+
+    source.commit().map_err(|e| format!("unable to commit offsets to '{}': {}", url, e).into())
+}
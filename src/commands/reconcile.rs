@@ -0,0 +1,137 @@
+use std::{fmt, io, path::PathBuf, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use clap::Args;
+use serde::Serialize;
+use transaction_system::{BalanceStatus, MAIN_ACCOUNT, read_statement, reconcile};
+
+use super::{CommandError, ErrorKind, PrecisionArgs, read_accounts, read_csv, write_atomically};
+
+/// An enumeration of the formats a discrepancy report can be written in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A CSV file, with a row for each balance.
+    #[default]
+    Csv,
+
+    /// A single JSON array of balance objects.
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown report format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Compare an account output against an external statement, and report how each balance reconciles.
+#[derive(Args, Debug)]
+pub struct ReconcileArgs {
+    /// The CSV account file, such as the output of an earlier run.
+    #[arg(value_name = "OUTPUT")]
+    output: PathBuf,
+
+    /// The CSV statement file, with a `client` column, optional `account` and `currency` columns, and an `available`
+    /// column, a `total` column, or both.
+    #[arg(value_name = "STATEMENT")]
+    statement: PathBuf,
+
+    /// How far any amount may be from the statement and still match.
+    #[arg(long, value_name = "AMOUNT", default_value = "0")]
+    tolerance: BigDecimal,
+
+    /// The format to write the report in (csv or json).
+    #[arg(long, default_value_t)]
+    format: ReportFormat,
+
+    /// The file to write the report to, instead of stdout.
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    #[command(flatten)]
+    precision: PrecisionArgs,
+}
+
+/// A balance as reported, with its funds written to the precision, and the funds the statement does not report left empty.
+#[derive(Debug, Serialize)]
+struct Row {
+    client: u16,
+    account: String,
+    currency: Option<String>,
+    status: String,
+    available: Option<String>,
+    total: Option<String>,
+    statement_available: Option<String>,
+    statement_total: Option<String>
+}
+
+/// Report every balance of the account output and the statement as matched, mismatched, or missing from either.
+/// The check fails if any balance does not match.
+pub fn run(args: ReconcileArgs) -> Result<(), CommandError> {
+    let precision = args.precision.precision();
+    let accounts = read_accounts(&args.output)?;
+    let statement = read_csv(&args.statement, "statement", read_statement)?;
+
+    let reconciled = reconcile(&accounts, &statement, &args.tolerance);
+    let rows = reconciled.iter()
+        .map(|balance| {
+            let (available, total) = balance.output.as_ref().map_or((None, None), |(available, total)| (Some(available), Some(total)));
+            let (statement_available, statement_total) = balance.statement.as_ref().map_or((None, None), |(available, total)| (available.as_ref(), total.as_ref()));
+            Row {
+                client: balance.client,
+                account: balance.account.clone().unwrap_or_else(|| MAIN_ACCOUNT.to_string()),
+                currency: balance.currency.clone(),
+                status: balance.status.to_string(),
+                available: available.map(|amount| precision.format(amount)),
+                total: total.map(|amount| precision.format(amount)),
+                statement_available: statement_available.map(|amount| precision.format(amount)),
+                statement_total: statement_total.map(|amount| precision.format(amount))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let write = |writer: &mut dyn io::Write| match args.format {
+        ReportFormat::Json => {
+            serde_json::to_writer(&mut *writer, &rows)?;
+            writeln!(writer)
+        },
+        ReportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()
+        }
+    };
+    match &args.report {
+        Some(report) => write_atomically(report, |writer| write(writer))
+            .map_err(|e| CommandError::io(&e, format!("unable to write the {} report to '{}': {}", args.format, report.display(), e)))?,
+        None => write(&mut io::stdout().lock())
+            .map_err(|e| format!("unable to write the {} report: {}", args.format, e))?
+    }
+
+    let count = |status: BalanceStatus| reconciled.iter().filter(|balance| balance.status == status).count();
+    let (matched, mismatched) = (count(BalanceStatus::Matched), count(BalanceStatus::Mismatched));
+    let missing = count(BalanceStatus::MissingFromOutput) + count(BalanceStatus::MissingFromStatement);
+    eprintln!("{} matched, {} missing, {} mismatched", matched, missing, mismatched);
+
+    match missing + mismatched {
+        0 => Ok(()),
+        count => Err(CommandError::new(ErrorKind::Invariant, format!("{} of {} balances do not reconcile with '{}'", count, reconciled.len(), args.statement.display())))
+    }
+}
@@ -0,0 +1,39 @@
+use std::{fs::File, io, path::PathBuf};
+
+use clap::Args;
+use transaction_system::{JournalError, LockPolicy, replay_tenants};
+
+use super::{CommandError, OutputArgs, PolicyArgs};
+
+/// Rebuild the state of each account from a journal, and write it.
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// The journal file to replay.
+    #[arg(value_name = "JOURNAL")]
+    journal: PathBuf,
+
+    /// The policies the journal was written under, which must be the same as when it was written to rebuild the same state.
+    #[command(flatten)]
+    policy: PolicyArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+pub fn run(args: ReplayArgs) -> Result<(), CommandError> {
+    // NOTE: Every journalled transaction was accepted when it was first processed, including any by an operator
+    // and any on a locked account. Every other policy, such as an overdraft limit, must be given again, as must any fees
+    // and interest, as each transaction was journalled with what it applied, and the interest posted between them is not
+    // journalled at all. A transaction the policies no longer accept stops the replay as a divergence.
+    let mut tenants = args.policy.tenants(|builder| builder.admin(true).lock_policy(LockPolicy::AllowAll))?;
+
+    File::open(&args.journal)
+        .map(io::BufReader::new)
+        .map_err(JournalError::Io)
+        .and_then(|reader| replay_tenants(reader, &mut tenants))
+        .map_err(|e| CommandError::journal(&e, format!("unable to replay journal '{}': {}", args.journal.display(), e)))?;
+    tenants.iter_mut().try_for_each(|(_, engine)| args.policy.interest.settle(engine))?;
+
+    // NOTE: Accounts can only have been frozen if the journal was written with --admin, though every engine accepts them.
+    args.output.write_tenants(&tenants, args.policy.interest.as_of(), args.policy.admin)
+}
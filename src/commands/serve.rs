@@ -0,0 +1,103 @@
+use std::net::SocketAddr;
+
+use clap::Args;
+use transaction_system::{ShardedEngine, grpc::LedgerService};
+
+use super::{CommandError, PolicyArgs};
+
+/// Accept transactions over gRPC, applying them as they arrive.
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// The address to listen for gRPC requests on.
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:50051")]
+    listen: SocketAddr,
+
+    #[command(flatten)]
+    policy: PolicyArgs,
+
+    #[cfg(feature = "http")]
+    #[command(flatten)]
+    webhooks: super::WebhookArgs,
+
+    /// Apply transactions across this many async tasks, sharded by client id, instead of one per available CPU,
+    /// or a single one when charging fees or accruing interest.
+    #[arg(long, value_name = "N")]
+    shards: Option<usize>,
+
+    /// The address to accept WebSocket connections on, pushing every account change to them as JSON.
+    #[cfg(feature = "websocket")]
+    #[arg(long, value_name = "ADDR")]
+    events: Option<SocketAddr>,
+
+    /// Apply every request naming a tenant in its `x-tenant` header to the isolated books of that tenant,
+    /// rather than rejecting it.
+    #[arg(long)]
+    tenants: bool,
+}
+
+pub fn run(args: ServeArgs) -> Result<(), CommandError> {
+    let runtime = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("unable to start the runtime: {}", e))?;
+    let _runtime = runtime.enter();
+
+    // NOTE: The fee account, and the days interest accrues over, would otherwise be split across the shards.
+    let single = args.policy.fees.is_set() || args.policy.interest.is_set();
+    if single && args.shards.is_some_and(|shards| shards > 1) {
+        return Err("--fees and --interest-rate can not be applied with more than one shard".to_string().into());
+    }
+    let shards = match single {
+        true => 1,
+        false => args.shards.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from))
+    };
+
+    // NOTE: The server runs until it is stopped, so the arguments every engine is built from live as long as it does.
+    let args: &'static ServeArgs = Box::leak(Box::new(args));
+    let engines = args.policy.engines()?;
+
+    #[cfg(feature = "http")]
+    let webhook = args.webhooks.spawn(args.policy.precision.precision());
+    // NOTE: The server runs until it is stopped, so there is never a point to wait for outstanding notifications.
+    #[cfg(feature = "http")]
+    let webhook = webhook.map(|(webhook, _worker)| webhook);
+
+    // NOTE: Requests without a tenant are applied to the books of the tenant given by the arguments, if any.
+    let engine = move |tenant: Option<&str>| {
+        let builder = engines().tenant(tenant.or(args.policy.tenant.as_deref()).map(str::to_string));
+
+        #[cfg(feature = "http")]
+        let builder = match &webhook {
+            Some(webhook) => builder.observer(webhook.clone()),
+            None => builder
+        };
+        builder.build()
+    };
+
+    #[cfg(feature = "websocket")]
+    let events = match args.events {
+        Some(events) => {
+            let listener = runtime.block_on(tokio::net::TcpListener::bind(events))
+                .map_err(|e| format!("unable to serve events on '{}': {}", events, e))?;
+            let (sender, _) = tokio::sync::broadcast::channel(transaction_system::websocket::EVENT_CAPACITY);
+            runtime.spawn(transaction_system::websocket::serve_events(listener, sender.clone()));
+            Some(sender)
+        },
+        None => None
+    };
+    // NOTE: Every tenant's engine publishes to the same subscribers, with each event naming its tenant.
+    let spawn = move |tenant: Option<&str>| {
+        #[cfg(feature = "websocket")]
+        let observer = events.clone().map(transaction_system::websocket::publisher);
+        #[cfg(not(feature = "websocket"))]
+        let observer = None;
+
+        ShardedEngine::spawn_with(shards, || engine(tenant), observer)
+    };
+
+    let service = LedgerService::new(spawn(None));
+    let service = match args.tenants {
+        true => service.with_tenants(move |tenant| spawn(Some(tenant))),
+        false => service
+    };
+    runtime.block_on(service.serve(args.listen))
+        .map_err(|e| format!("unable to serve on '{}': {}", args.listen, e).into())
+}
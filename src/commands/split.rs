@@ -0,0 +1,95 @@
+use std::{fs::File, io::{self, BufWriter}, path::PathBuf};
+
+use clap::Args;
+use transaction_system::{decompress, shard_for};
+
+use super::{CommandError, ErrorKind};
+
+/// Split a CSV input into a file per shard of clients, to process each on its own and merge the accounts afterwards.
+#[derive(Args, Debug)]
+pub struct SplitArgs {
+    /// The CSV input file to split, which may be compressed.
+    #[arg(value_name = "INPUT")]
+    input: PathBuf,
+
+    /// The number of files to split the input into.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    shards: u16,
+
+    /// The directory to write the shards to, as `<input>.shard-<n>.csv`.
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    output_dir: PathBuf,
+}
+
+/// Write every row of the input to the shard that owns its client, keeping the order of rows within each shard.
+/// Clients are assigned to shards as with `process --shards`, so every transaction of a client lands in the same file.
+/// Transfers to a client of another shard can not be applied by either shard on its own, so they are counted and warned about.
+pub fn run(args: SplitArgs) -> Result<(), CommandError> {
+    let name = args.input.display();
+    let file = File::open(&args.input)
+        .map_err(|e| CommandError::io(&e, format!("unable to open input file '{}': {}", name, e)))?;
+    let source = decompress(io::BufReader::new(file))
+        .map_err(|e| CommandError::io(&e, format!("unable to read input file '{}': {}", name, e)))?;
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(source);
+    let headers = reader.byte_headers()
+        .map_err(|e| CommandError::new(ErrorKind::Parse, format!("unable to read the header of '{}': {}", name, e)))?
+        .clone();
+    let column = |name: &[u8]| headers.iter().position(|header| header == name);
+    let client = column(b"client")
+        .ok_or_else(|| CommandError::new(ErrorKind::Parse, format!("'{}' has no client column", name)))?;
+    let destination = column(b"to");
+
+    let stem = args.input.file_name().unwrap_or_default().to_string_lossy();
+    let stem = stem.split('.').next().unwrap_or_default();
+    let mut shards = (0..args.shards as usize)
+        .map(|shard| {
+            let path = args.output_dir.join(format!("{}.shard-{}.csv", stem, shard));
+            let mut writer = File::create(&path)
+                .map(|file| csv::Writer::from_writer(BufWriter::new(file)))
+                .map_err(|e| CommandError::io(&e, format!("unable to create '{}': {}", path.display(), e)))?;
+            writer.write_byte_record(&headers)
+                .map_err(|e| format!("unable to write to '{}': {}", path.display(), e))?;
+            Ok((path, writer, 0))
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+
+    let id = |record: &csv::ByteRecord, index: usize| std::str::from_utf8(&record[index]).ok()
+        .and_then(|id| id.parse::<u16>().ok());
+
+    let mut crossing = 0;
+    let mut record = csv::ByteRecord::new();
+    loop {
+        match reader.read_byte_record(&mut record) {
+            Ok(true) => {},
+            Ok(false) => break,
+            Err(e) => return Err(CommandError::new(ErrorKind::Parse, format!("unable to read '{}': {}", name, e)))
+        }
+        let line = record.position().map_or(0, |position| position.line());
+        let shard = id(&record, client)
+            .map(|client| shard_for(client, shards.len()))
+            .ok_or_else(|| CommandError::new(ErrorKind::Parse, format!("{}:{}: invalid client id", name, line)))?;
+
+        let other = destination.and_then(|index| id(&record, index));
+        if other.is_some_and(|other| shard_for(other, shards.len()) != shard) {
+            crossing += 1;
+        }
+
+        let (path, writer, count) = &mut shards[shard];
+        writer.write_byte_record(&record)
+            .map_err(|e| format!("unable to write to '{}': {}", path.display(), e))?;
+        *count += 1;
+    }
+
+    for (path, mut writer, count) in shards {
+        writer.flush()
+            .map_err(|e| CommandError::io(&e, format!("unable to write to '{}': {}", path.display(), e)))?;
+        eprintln!("{}: {} records", path.display(), count);
+    }
+    if crossing > 0 {
+        eprintln!("warning: {} transfers are to a client of another shard, which can not be applied correctly while the shards are processed apart", crossing);
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::BigDecimal;
+
+use super::{CommandError, InputArgs};
+
+/// Process every transaction and print a summary of the run.
+pub fn run(args: InputArgs) -> Result<(), CommandError> {
+    let paths = args.paths()?;
+
+    let mut tenants = args.tenants(|builder| builder)?;
+    let mut count = 0;
+    let mut by_type = BTreeMap::new();
+    let mut fees = BTreeMap::<Option<String>, BigDecimal>::new();
+    let mut by_reason = BTreeMap::new();
+    let mut skipped = args.skipped()?;
+    let mut rejected = 0;
+    for path in &paths {
+        for record in args.read(path, &skipped)? {
+            let record = record?;
+            count += 1;
+            *by_type.entry(record.transaction.type_().to_string()).or_insert(0) += 1;
+
+            let result = tenants.process(&record.transaction);
+            if let Some(fee) = result.as_ref().ok().and_then(|applied| applied.fee()) {
+                *fees.entry(record.transaction.currency().map(str::to_string)).or_default() += fee;
+            }
+
+            if let Err(e) = result {
+                if args.strict || e.is_violation() {
+                    return Err(CommandError::rejected(&e, format!("transaction {} on line {} of '{}' was rejected: {}", record.transaction.id(), record.line, path.display(), e)));
+                }
+
+                rejected += 1;
+                *by_reason.entry(e.code()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    skipped.rejected = rejected;
+    tenants.iter_mut().try_for_each(|(_, engine)| args.policy.interest.settle(engine))?;
+
+    println!("files: {}", paths.len());
+    println!("records: {}", count);
+    for (type_, count) in by_type {
+        println!("  {}: {}", type_, count);
+    }
+    println!("malformed: {}", skipped.malformed());
+    println!("rejected: {}", skipped.rejected);
+    for (reason, count) in by_reason {
+        println!("  {}: {}", reason, count);
+    }
+    let clients = tenants.iter()
+        .map(|(_, engine)| engine.clients())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?
+        .concat();
+    if tenants.is_multi_tenant() {
+        println!("tenants: {}", tenants.iter().count());
+    }
+    println!("clients: {}", clients.len());
+    println!("locked: {}", clients.iter().filter(|client| client.locked()).count());
+    println!("frozen: {}", clients.iter().filter(|client| client.frozen()).count());
+    if args.policy.fees.is_set() {
+        let precision = args.policy.precision.precision();
+        match fees.is_empty() {
+            true => println!("fees: {}", precision.format(&BigDecimal::from(0))),
+            false => for (currency, total) in &fees {
+                match currency {
+                    Some(currency) => println!("fees ({}): {}", currency, precision.format(total)),
+                    None => println!("fees: {}", precision.format(total))
+                }
+            }
+        }
+    }
+
+    if args.lenient {
+        skipped.report();
+    }
+    Ok(())
+}
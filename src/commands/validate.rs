@@ -0,0 +1,80 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::Args;
+use transaction_system::{Rejection, TransactionError, validate};
+
+use super::{CommandError, ErrorKind, InputArgs, write_atomically};
+
+/// Parse and validate every transaction, without applying any of them.
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// A CSV file to write every invalid transaction to, alongside the reason it is invalid.
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+}
+
+/// Check every transaction on its own, and against the transactions before it, without applying any of them.
+/// Transactions must not reuse an id, and disputes, resolves and chargebacks must reference an earlier transaction of the same client.
+/// The check fails if any transaction is invalid, unless running in lenient mode.
+pub fn run(args: ValidateArgs) -> Result<(), CommandError> {
+    let input = &args.input;
+    let precision = input.policy.precision.precision();
+
+    let mut count = 0;
+    let mut skipped = input.skipped()?;
+    let mut seen = HashMap::new();
+    let mut invalid = Vec::new();
+    for path in input.paths()? {
+        for record in input.read(&path, &skipped)? {
+            let record = record?;
+            count += 1;
+            let transaction = &record.transaction;
+            let checked = validate(transaction)
+                .and_then(|_| transaction.amount().map_or(Ok(()), |amount| precision.check(amount).map(drop)))
+                .and_then(|_| match (transaction.type_().references_existing(), seen.get(&transaction.id())) {
+                    (true, Some(client_id)) if *client_id == transaction.client_id() => Ok(()),
+                    (true, Some(client_id)) => Err(TransactionError::ForeignTransaction(transaction.id(), *client_id)),
+                    (true, None) => Err(TransactionError::UnknownTransaction(transaction.id())),
+                    (false, Some(_)) => Err(TransactionError::DuplicateTransaction(transaction.id())),
+                    (false, None) => Ok(())
+                });
+
+            // NOTE: As with the engine, an invalid transaction does not claim its id.
+            if let Err(e) = checked {
+                if input.strict {
+                    return Err(CommandError::new(ErrorKind::Parse, format!("transaction {} on line {} of '{}' is invalid: {}", transaction.id(), record.line, path.display(), e)));
+                }
+
+                eprintln!("{}:{}: transaction {} is invalid: {}", path.display(), record.line, transaction.id(), e);
+                invalid.push(Rejection {
+                    file: Some(path.display().to_string()),
+                    ..Rejection::new(record.line, transaction, &e)
+                });
+            } else if !transaction.type_().references_existing() {
+                seen.insert(transaction.id(), transaction.client_id());
+            }
+        }
+    }
+
+    skipped.rejected = invalid.len();
+    println!("{} records, {} invalid", count, skipped.rejected);
+
+    if let Some(report) = &args.report {
+        write_atomically(report, |writer| {
+            let mut writer = csv::Writer::from_writer(writer);
+            invalid.iter().try_for_each(|rejection| writer.serialize(rejection))?;
+            writer.flush()
+        })
+        .map_err(|e| CommandError::io(&e, format!("unable to write validation report to '{}': {}", report.display(), e)))?;
+    }
+
+    if input.lenient {
+        skipped.report();
+    } else if !invalid.is_empty() {
+        return Err(CommandError::new(ErrorKind::Parse, format!("{} of {} records are invalid", invalid.len(), count)));
+    }
+    Ok(())
+}
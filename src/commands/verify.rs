@@ -0,0 +1,92 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use bigdecimal::BigDecimal;
+use clap::Args;
+use transaction_system::{AccountChange, diff_accounts};
+
+use super::{CommandError, ErrorKind, InputArgs, read_accounts, single_tenant};
+
+/// Process every transaction, and check the final accounts match an expected account output.
+#[derive(Args, Debug)]
+pub struct VerifyArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+
+    /// The CSV account file the final accounts are expected to match, such as the output of an earlier run.
+    #[arg(long, value_name = "FILE")]
+    expected: PathBuf,
+
+    /// How far any amount may be from the expected amount and still match.
+    #[arg(long, value_name = "AMOUNT", default_value = "0")]
+    tolerance: BigDecimal,
+}
+
+/// Process every transaction, then report every client whose final account does not match the expected one.
+/// An account matches if every amount is within the tolerance of the expected amount, and it is locked only if expected.
+/// The check fails if any account does not match.
+pub fn run(args: VerifyArgs) -> Result<(), CommandError> {
+    let input = &args.input;
+    let expected = read_accounts(&args.expected)?;
+
+    let mut tenants = input.tenants(|builder| builder)?;
+    let mut skipped = input.skipped()?;
+    let mut rejected = 0;
+    for path in input.paths()? {
+        for record in input.read(&path, &skipped)? {
+            let record = record?;
+            if let Err(e) = tenants.process(&record.transaction) {
+                if input.strict || e.is_violation() {
+                    return Err(CommandError::rejected(&e, format!("transaction {} on line {} of '{}' was rejected: {}", record.transaction.id(), record.line, path.display(), e)));
+                }
+
+                rejected += 1;
+            }
+        }
+    }
+    skipped.rejected = rejected;
+    if input.lenient {
+        skipped.report();
+    }
+    tenants.iter_mut().try_for_each(|(_, engine)| input.policy.interest.settle(engine))?;
+    let engine = single_tenant(&tenants, "verified")?;
+
+    let precision = engine.precision();
+    let actual = engine.clients().map_err(|e| e.to_string())?;
+    let outside = |delta: &BigDecimal| delta.abs() > args.tolerance;
+
+    let mut mismatched = Vec::new();
+    for diff in diff_accounts(&expected, &actual) {
+        let amounts = outside(&diff.available) || outside(&diff.held) || outside(&diff.total);
+        if !amounts && diff.locked.is_none() && diff.change == AccountChange::Changed {
+            continue;
+        }
+
+        let currency = diff.currency.as_deref().map(|currency| format!(" ({})", currency)).unwrap_or_default();
+        let mut reasons = Vec::new();
+        match diff.change {
+            AccountChange::Appeared => reasons.push("is not expected".to_string()),
+            AccountChange::Disappeared => reasons.push("is missing".to_string()),
+            AccountChange::Changed if amounts => reasons.push(format!("differs by available {}, held {}, total {}",
+                precision.format(&diff.available), precision.format(&diff.held), precision.format(&diff.total))),
+            AccountChange::Changed => {}
+        }
+        match diff.locked {
+            Some(true) => reasons.push("is locked, but not expected to be".to_string()),
+            Some(false) => reasons.push("is not locked, but expected to be".to_string()),
+            None => {}
+        }
+        eprintln!("client {}{} {}", diff.client, currency, reasons.join(", and "));
+        if !mismatched.contains(&diff.client) {
+            mismatched.push(diff.client);
+        }
+    }
+
+    let clients = expected.iter().chain(&actual).map(|client| client.id()).collect::<BTreeSet<_>>().len();
+    match mismatched.len() {
+        0 => {
+            println!("{} clients match", clients);
+            Ok(())
+        },
+        count => Err(CommandError::new(ErrorKind::Invariant, format!("{} of {} clients do not match '{}'", count, clients, args.expected.display())))
+    }
+}
@@ -0,0 +1,24 @@
+use std::{fs::File, path::PathBuf};
+
+use clap::Args;
+use transaction_system::{AuditError, verify_audit};
+
+use super::CommandError;
+
+/// Check an audit log is intact, with every entry matching its hash and chained to the one before.
+#[derive(Args, Debug)]
+pub struct VerifyAuditArgs {
+    /// The audit log to verify.
+    #[arg(value_name = "AUDIT_LOG")]
+    audit: PathBuf,
+}
+
+pub fn run(args: VerifyAuditArgs) -> Result<(), CommandError> {
+    let (verified, rejected) = File::open(&args.audit)
+        .map_err(AuditError::Io)
+        .and_then(verify_audit)
+        .map_err(|e| CommandError::audit(&e, format!("audit log '{}' failed verification: {}", args.audit.display(), e)))?;
+
+    println!("{} entries verified, {} of them rejected transactions", verified, rejected);
+    Ok(())
+}
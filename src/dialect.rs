@@ -0,0 +1,325 @@
+use std::{fmt, str::FromStr};
+
+/// An enumeration of when fields of CSV output are quoted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Only fields holding the delimiter, a quote or a newline.
+    #[default]
+    Necessary,
+
+    /// Every field.
+    Always,
+
+    /// Every field that is not a number.
+    NonNumeric,
+
+    /// No field, even one that can then not be read back.
+    Never,
+}
+
+impl FromStr for QuoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "necessary" => Ok(Self::Necessary),
+            "always" => Ok(Self::Always),
+            "non-numeric" => Ok(Self::NonNumeric),
+            "never" => Ok(Self::Never),
+            _ => Err(format!("unknown quote style '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for QuoteStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Necessary => write!(f, "necessary"),
+            Self::Always => write!(f, "always"),
+            Self::NonNumeric => write!(f, "non-numeric"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// An enumeration of how amounts in CSV input are written, so amounts formatted for a locale can be read.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AmountFormat {
+    /// Plain decimal numbers only, such as `1234.56`.
+    #[default]
+    Strict,
+
+    /// Numbers with a decimal point, optionally grouped and with a currency, such as `$1,234.56` or `1'234.56 CHF`.
+    Point,
+
+    /// Numbers with a decimal comma, optionally grouped and with a currency, such as `1 234,56` or `1.234,56 €`.
+    Comma,
+}
+
+impl AmountFormat {
+    /// The characters digits can be grouped with.
+    fn grouping(&self) -> &'static [char] {
+        match self {
+            Self::Strict => &[],
+            Self::Point => &[',', ' ', '\'', '_', '\u{a0}', '\u{202f}'],
+            Self::Comma => &['.', ' ', '\'', '_', '\u{a0}', '\u{202f}'],
+        }
+    }
+
+    /// Rewrite a formatted amount as a plain decimal number, or `None` if it is not an amount in this format.
+    pub fn normalize(&self, amount: &str) -> Option<String> {
+        if *self == Self::Strict {
+            return Some(amount.to_string());
+        }
+
+        // NOTE: A currency can come either side of the sign, as in both `-$5` and `$-5`.
+        let currency = |c: char| c.is_alphabetic() || "$€£¥₹₩₽¢".contains(c) || c.is_whitespace();
+        let amount = amount.trim_matches(currency);
+        let (sign, amount) = match amount.strip_prefix('-') {
+            Some(amount) => ("-", amount),
+            None => ("", amount.strip_prefix('+').unwrap_or(amount))
+        };
+        let amount = amount.trim_start_matches(currency);
+
+        // NOTE: Digits are only grouped before the decimal separator, so `1.234,56` is not read as `1.23456`.
+        let grouping = self.grouping();
+        let mut normalized = sign.to_string();
+        let mut fraction = false;
+        for c in amount.chars() {
+            match c {
+                c if c.is_ascii_digit() => normalized.push(c),
+                ',' if *self == Self::Comma && !fraction => {
+                    fraction = true;
+                    normalized.push('.');
+                },
+                '.' if *self == Self::Point && !fraction => {
+                    fraction = true;
+                    normalized.push('.');
+                },
+                c if grouping.contains(&c) && !fraction => {},
+                _ => return None
+            }
+        }
+        normalized.parse::<bigdecimal::BigDecimal>().ok().map(|_| normalized)
+    }
+
+    /// Rewrite every amount of a row as a plain decimal number, given the fields held by its columns.
+    /// An amount that is not in this format is left as it is, to be reported as malformed.
+    pub(crate) fn normalize_row(&self, fields: &csv::StringRecord, row: &mut csv::StringRecord) {
+        if *self == Self::Strict {
+            return;
+        }
+
+        let position = row.position().cloned();
+        *row = row.iter().zip(fields)
+            .map(|(value, field)| match field {
+                "amount" | "rate" | "total" if !value.is_empty() => self.normalize(value).unwrap_or_else(|| value.to_string()),
+                _ => value.to_string()
+            })
+            .collect();
+        row.set_position(position);
+    }
+}
+
+impl FromStr for AmountFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "point" => Ok(Self::Point),
+            "comma" => Ok(Self::Comma),
+            _ => Err(format!("unknown amount format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for AmountFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Strict => write!(f, "strict"),
+            Self::Point => write!(f, "point"),
+            Self::Comma => write!(f, "comma"),
+        }
+    }
+}
+
+/// The delimiter and quoting of CSV input and output, which are a comma and double quotes by default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// The byte separating fields.
+    delimiter: u8,
+
+    /// The byte fields are quoted with, or `None` if quotes are read as any other character.
+    quote: Option<u8>,
+
+    /// When fields of output are quoted.
+    quote_style: QuoteStyle,
+
+    /// How amounts of input are written.
+    amount_format: AmountFormat,
+
+    /// Whether the `frozen` column of account output is written even when no account is frozen.
+    frozen_column: bool
+}
+
+impl Default for CsvDialect {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: Some(b'"'),
+            quote_style: QuoteStyle::default(),
+            amount_format: AmountFormat::default(),
+            frozen_column: false
+        }
+    }
+}
+
+impl CsvDialect {
+    /// Separate fields with another byte, such as `;` or a tab.
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Quote fields with another byte, or read quotes as any other character with `None`.
+    pub fn with_quote(mut self, quote: Option<u8>) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Quote fields of output in another style.
+    pub fn with_quote_style(mut self, quote_style: QuoteStyle) -> Self {
+        self.quote_style = quote_style;
+        self
+    }
+
+    /// Read amounts of input written in another format.
+    pub fn with_amount_format(mut self, amount_format: AmountFormat) -> Self {
+        self.amount_format = amount_format;
+        self
+    }
+
+    /// Always write the `frozen` column of account output, such as when accounts can be frozen, so the columns written
+    /// do not depend on whether any account happens to be frozen.
+    pub fn with_frozen_column(mut self, frozen_column: bool) -> Self {
+        self.frozen_column = frozen_column;
+        self
+    }
+
+    /// Whether the `frozen` column of account output is always written.
+    pub(crate) fn frozen_column(&self) -> bool {
+        self.frozen_column
+    }
+
+    /// How amounts of input are written.
+    pub(crate) fn amount_format(&self) -> AmountFormat {
+        self.amount_format
+    }
+
+    /// The byte fields are quoted with, if any.
+    pub(crate) fn quote(&self) -> Option<u8> {
+        self.quote
+    }
+
+    /// A reader of this dialect, with whitespace around every field trimmed.
+    pub(crate) fn reader(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder.trim(csv::Trim::All)
+            .delimiter(self.delimiter)
+            .quoting(self.quote.is_some())
+            .quote(self.quote.unwrap_or(b'"'));
+        builder
+    }
+
+    /// A writer of this dialect.
+    pub(crate) fn writer(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder.delimiter(self.delimiter)
+            .quote(self.quote.unwrap_or(b'"'))
+            .quote_style(match (self.quote, self.quote_style) {
+                (None, _) | (_, QuoteStyle::Never) => csv::QuoteStyle::Never,
+                (_, QuoteStyle::Necessary) => csv::QuoteStyle::Necessary,
+                (_, QuoteStyle::Always) => csv::QuoteStyle::Always,
+                (_, QuoteStyle::NonNumeric) => csv::QuoteStyle::NonNumeric
+            });
+        builder
+    }
+
+    /// Unquote every field of a row that is still quoted once trimmed, as a quote only starts a quoted field when it
+    /// comes straight after the delimiter, so `deposit, 1, 1, "2.5"` would otherwise have an amount of `"2.5"`.
+    pub(crate) fn unquote(&self, row: &mut csv::StringRecord) {
+        let Some(quote) = self.quote.map(char::from) else {
+            return;
+        };
+        let quoted = |field: &str| field.len() >= 2 && field.starts_with(quote) && field.ends_with(quote);
+        if !row.iter().any(quoted) {
+            return;
+        }
+
+        let doubled = format!("{}{}", quote, quote);
+        let position = row.position().cloned();
+        *row = row.iter()
+            .map(|field| match quoted(field) {
+                true => field[1..field.len() - 1].replace(&doubled, &quote.to_string()),
+                false => field.to_string()
+            })
+            .collect();
+        row.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{ColumnMapping, Engine, OutputFormat, Precision, records_from_reader_with};
+
+    #[test]
+    fn delimiters_and_quoted_fields() {
+        let csv = "type; client; tx; amount\ndeposit; \"1\"; 1; \"2.5\"\n\"withdrawal\";1;2;\"1.0\"\n";
+        let dialect = CsvDialect::default().with_delimiter(b';');
+        let records = records_from_reader_with(csv.as_bytes(), &dialect, &ColumnMapping::default()).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction.amount(), Some(&BigDecimal::from_str("2.5").unwrap()));
+        assert_eq!(records[1].as_ref().unwrap().transaction.amount(), Some(&BigDecimal::from(1)));
+
+        let tsv = "type\tclient\ttx\tamount\ndeposit\t1\t1\t\"2\"\n";
+        let records = records_from_reader_with(tsv.as_bytes(), &CsvDialect::default().with_delimiter(b'\t'), &ColumnMapping::default()).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction.amount(), Some(&BigDecimal::from(2)));
+
+        let unquoted = "type,client,tx,amount\ndeposit,1,1,\"2\"\n";
+        assert!(records_from_reader_with(unquoted.as_bytes(), &CsvDialect::default().with_quote(None), &ColumnMapping::default()).unwrap()[0].is_err());
+
+        let mut engine = Engine::new();
+        engine.process(&records[0].as_ref().unwrap().transaction).unwrap();
+        let mut output = Vec::new();
+        let dialect = dialect.with_quote_style(QuoteStyle::Always);
+        OutputFormat::Csv.write_with(&mut output, &engine.clients().unwrap(), Precision::default(), &dialect).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().nth(1), Some("\"1\";\"2.0000\";\"0.0000\";\"2.0000\";\"false\""));
+
+        assert_eq!(QuoteStyle::from_str("non-numeric"), Ok(QuoteStyle::NonNumeric));
+    }
+
+    #[test]
+    fn formatted_amounts() {
+        assert_eq!(AmountFormat::Point.normalize("$1,234.56").as_deref(), Some("1234.56"));
+        assert_eq!(AmountFormat::Point.normalize("-$1'234.5 CHF").as_deref(), Some("-1234.5"));
+        assert_eq!(AmountFormat::Comma.normalize("1 234,56 €").as_deref(), Some("1234.56"));
+        assert_eq!(AmountFormat::Comma.normalize("EUR 1.234,5").as_deref(), Some("1234.5"));
+        assert_eq!(AmountFormat::Point.normalize("1.234,56"), None);
+        assert_eq!(AmountFormat::Point.normalize("$"), None);
+
+        let csv = "type;client;tx;amount\ndeposit;1;1;1 234,56 €\nwithdrawal;1;2;1,5\ndeposit;1;3;1,2,3\n";
+        let dialect = CsvDialect::default().with_delimiter(b';').with_amount_format(AmountFormat::Comma);
+        let records = records_from_reader_with(csv.as_bytes(), &dialect, &ColumnMapping::default()).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction.amount(), Some(&BigDecimal::from_str("1234.56").unwrap()));
+        assert_eq!(records[1].as_ref().unwrap().transaction.amount(), Some(&BigDecimal::from_str("1.5").unwrap()));
+        assert!(records[2].is_err());
+
+        let strict = records_from_reader_with(csv.as_bytes(), &dialect.with_amount_format(AmountFormat::Strict), &ColumnMapping::default()).unwrap();
+        assert!(strict.iter().all(Result::is_err));
+    }
+}
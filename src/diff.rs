@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::Client;
+
+/// An enumeration of the ways a client's account can differ between two sets of accounts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AccountChange {
+    /// The client only has an account in the new set.
+    Appeared,
+
+    /// The client only has an account in the old set.
+    Disappeared,
+
+    /// The client has an account in both sets, with different balances or a different locked state.
+    Changed,
+}
+
+/// The difference in one balance of a client between two sets of accounts, with each amount being the new minus the old.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccountDiff {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The sub-account of the balance, or `None` for the main account.
+    pub account: Option<String>,
+
+    /// The currency of the balance, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// How the account differs.
+    pub change: AccountChange,
+
+    /// The change in the available funds.
+    pub available: BigDecimal,
+
+    /// The change in the held funds.
+    pub held: BigDecimal,
+
+    /// The change in the total funds.
+    pub total: BigDecimal,
+
+    /// The new locked state of the account, if it differs. Only the first difference of a client carries it.
+    pub locked: Option<bool>
+}
+
+/// Compare two sets of accounts, such as the output of two runs, ordered by client id.
+/// Every balance that changed is listed, including the funds of a client that appeared or disappeared.
+/// A client whose locked state changed while its balances did not, or that appeared or disappeared without any funds,
+/// is listed with its unchanged default balance.
+pub fn diff_accounts(old: &[Client], new: &[Client]) -> Vec<AccountDiff> {
+    let mut clients = BTreeMap::<u16, (Option<&Client>, Option<&Client>)>::new();
+    for client in old {
+        clients.entry(client.id()).or_default().0 = Some(client);
+    }
+    for client in new {
+        clients.entry(client.id()).or_default().1 = Some(client);
+    }
+
+    let mut diffs = Vec::new();
+    for (id, (old, new)) in clients {
+        let change = match (old, new) {
+            (None, _) => AccountChange::Appeared,
+            (_, None) => AccountChange::Disappeared,
+            _ => AccountChange::Changed
+        };
+
+        // NOTE: The default currency of the main account always leads, so an account that only changed its locked state is listed under it.
+        let mut currencies = Vec::<(Option<&str>, Option<&str>)>::new();
+        for balance in old.into_iter().chain(new).flat_map(|client| client.balances()) {
+            if !currencies.contains(&(balance.account(), balance.currency())) {
+                currencies.push((balance.account(), balance.currency()));
+            }
+        }
+
+        let amounts = |client: Option<&Client>, (account, currency)| client.and_then(|client| client.balance_in(account, currency))
+            .map(|balance| [balance.available(), balance.held(), balance.total()])
+            .unwrap_or_default();
+
+        // NOTE: A client that appeared already locked is newly locked, while the lock of one that disappeared is moot.
+        let locked = match (old, new) {
+            (Some(old), Some(new)) => old.locked() != new.locked(),
+            (None, Some(new)) => new.locked(),
+            _ => false
+        };
+        let start = diffs.len();
+        let mut first = None;
+        for (index, currency) in currencies.into_iter().enumerate() {
+            let [old_available, old_held, old_total] = amounts(old, currency);
+            let [new_available, new_held, new_total] = amounts(new, currency);
+            let diff = AccountDiff {
+                client: id,
+                account: currency.0.map(str::to_string),
+                currency: currency.1.map(str::to_string),
+                change,
+                available: new_available - old_available,
+                held: new_held - old_held,
+                total: new_total - old_total,
+                locked: None
+            };
+
+            let unchanged = diff.available.is_zero() && diff.held.is_zero() && diff.total.is_zero();
+            if !unchanged || (locked && index == 0) {
+                diffs.push(diff);
+            } else if index == 0 {
+                first = Some(diff);
+            }
+        }
+
+        // NOTE: A client that appeared or disappeared with no funds at all is still listed, under its default balance.
+        if diffs.len() == start && change != AccountChange::Changed {
+            diffs.extend(first);
+        }
+        if locked {
+            diffs[start].locked = new.map(Client::locked);
+        }
+    }
+    diffs
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType};
+
+    fn amount(amount: &str) -> BigDecimal {
+        BigDecimal::from_str(amount).unwrap()
+    }
+
+    #[test]
+    fn diff_lists_changed_balances() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(amount("10")))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, Some(amount("5")))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 3, Some(amount("1")))).unwrap();
+        let old = engine.clients().unwrap();
+
+        let mut engine = Engine::new();
+        engine.restore(crate::Snapshot { clients: old.iter().filter(|client| client.id() != 3).cloned().collect(), ..Default::default() }).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 4, Some(amount("2")))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 5, Some(amount("1")))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 5, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 2, 5, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 4, 6, Some(amount("3")))).unwrap();
+        let new = engine.clients().unwrap();
+
+        let diffs = diff_accounts(&old, &new);
+
+        let summary = diffs.iter().map(|diff| (diff.client, diff.change, diff.total.clone(), diff.locked)).collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            (1, AccountChange::Changed, amount("2"), None),
+            (2, AccountChange::Changed, amount("0"), Some(true)),
+            (3, AccountChange::Disappeared, amount("-1"), None),
+            (4, AccountChange::Appeared, amount("3"), None),
+        ]);
+    }
+}
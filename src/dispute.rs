@@ -0,0 +1,71 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::Transaction;
+
+/// A transaction that is disputed, and neither resolved nor charged back yet, whose amount is still held.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenDispute {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The id of the disputed transaction.
+    pub transaction: u32,
+
+    /// The sub-account the amount is held in, or `None` for the main account.
+    pub account: Option<String>,
+
+    /// The currency of the amount, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// The amount held for the dispute.
+    pub amount: BigDecimal,
+
+    /// When the disputed transaction happened, if the source recorded it.
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// How long before the latest accepted transaction the disputed transaction happened, if both have a timestamp.
+    pub age: Option<TimeDelta>
+}
+
+impl OpenDispute {
+    /// The open dispute of a disputed transaction, aged as of the instant, if any.
+    pub(crate) fn of(transaction: &Transaction, now: Option<DateTime<Utc>>) -> Self {
+        Self {
+            client: transaction.client_id(),
+            transaction: transaction.id(),
+            account: transaction.account().map(str::to_string),
+            currency: transaction.currency().map(str::to_string),
+            amount: transaction.amount().cloned().unwrap_or_default(),
+            timestamp: transaction.timestamp(),
+            age: now.zip(transaction.timestamp()).map(|(now, happened)| now - happened)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    #[test]
+    fn list_the_disputes_still_open() {
+        let at = |days: i64| DateTime::from_timestamp(days * 86_400, 0).unwrap();
+
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, Some(10.into())).with_timestamp(at(1))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(5.into())).with_timestamp(at(2)).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, Some(3.into())).with_timestamp(at(3))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 3, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 3, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 4, Some(1.into())).with_timestamp(at(5))).unwrap();
+
+        let disputes = engine.open_disputes().unwrap();
+        assert_eq!(disputes.iter().map(|dispute| (dispute.client, dispute.transaction)).collect::<Vec<_>>(), vec![(1, 2), (2, 1)]);
+        assert_eq!(disputes[0].currency.as_deref(), Some("EUR"));
+        assert_eq!(disputes[0].amount, BigDecimal::from(5));
+        assert_eq!(disputes[1].age, Some(TimeDelta::days(4)));
+    }
+}
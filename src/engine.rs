@@ -0,0 +1,2401 @@
+use std::{borrow::Cow, collections::{BTreeMap, HashMap, HashSet}, fmt, iter, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+
+use crate::{AccruedInterest, Activity, Applied, Balance, BalanceHistory, Client, DailyOutflow, EngineBuilder, EngineObserver, FeeSchedule, Interest, Ledger, LedgerAccount, MemoryStorage, OpenDispute, Ownership, Precision, Retention, Roster, ScheduledTransaction, Snapshot, SnapshotTransaction, Storage, StorageError, Transaction, TransactionError, TransactionStatus, TransactionType, VelocityPolicy, client::{Amount, to_amount}, observer::Observers, validate};
+#[cfg(feature = "scripting")]
+use crate::{Decision, Script};
+
+/// An enumeration of the policies for which transactions a locked account still accepts.
+/// An operator unlock is always accepted, and a locked account never accepts anything else by default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LockPolicy {
+    /// Reject every transaction.
+    #[default]
+    RejectAll,
+
+    /// Accept deposits, and transfers into the account.
+    AllowDeposits,
+
+    /// Accept disputes, resolves and chargebacks, so open disputes can still be settled.
+    AllowDisputes,
+
+    /// Accept every transaction, as if the account were not locked.
+    AllowAll,
+}
+
+impl LockPolicy {
+    /// Whether a locked account accepts a type of transaction.
+    pub fn permits(self, type_: TransactionType) -> bool {
+        match self {
+            Self::RejectAll => false,
+            Self::AllowDeposits => type_ == TransactionType::Deposit,
+            Self::AllowDisputes => matches!(type_, TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback | TransactionType::Representment),
+            Self::AllowAll => true,
+        }
+    }
+}
+
+/// An enumeration of the policies for which transactions an account frozen by an operator still accepts.
+/// An operator unfreeze or unlock, and a representment, are always accepted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FreezePolicy {
+    /// Reject every transaction.
+    RejectAll,
+
+    /// Accept deposits, transfers into the account and interest, but nothing that takes funds out.
+    #[default]
+    AllowDeposits,
+
+    /// Accept everything but withdrawals, transfers out of the account, conversions and authorizations,
+    /// so deposits are still taken and open disputes and authorizations can still be settled.
+    BlockWithdrawals,
+}
+
+impl FreezePolicy {
+    /// Whether a frozen account accepts a type of transaction.
+    pub fn permits(self, type_: TransactionType) -> bool {
+        match self {
+            Self::RejectAll => false,
+            Self::AllowDeposits => matches!(type_, TransactionType::Deposit | TransactionType::Interest),
+            Self::BlockWithdrawals => !matches!(type_, TransactionType::Withdrawal | TransactionType::Transfer | TransactionType::Convert | TransactionType::Authorize),
+        }
+    }
+}
+
+impl FromStr for FreezePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject-all" => Ok(Self::RejectAll),
+            "allow-deposits" => Ok(Self::AllowDeposits),
+            "block-withdrawals" => Ok(Self::BlockWithdrawals),
+            _ => Err(format!("unknown freeze policy '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for FreezePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RejectAll => write!(f, "reject-all"),
+            Self::AllowDeposits => write!(f, "allow-deposits"),
+            Self::BlockWithdrawals => write!(f, "block-withdrawals"),
+        }
+    }
+}
+
+/// An enumeration of the policies for how far below zero withdrawals, transfers and conversions may take available funds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum OverdraftPolicy {
+    /// Reject anything that would take the available funds below zero.
+    #[default]
+    Deny,
+
+    /// Accept anything that leaves the available funds no further below zero than the limit.
+    Limit(BigDecimal),
+}
+
+impl OverdraftPolicy {
+    /// How far below zero the available funds may go.
+    pub fn limit(&self) -> BigDecimal {
+        match self {
+            Self::Deny => BigDecimal::zero(),
+            Self::Limit(limit) => limit.clone(),
+        }
+    }
+
+    /// How far below zero the available funds may go, as balances are kept.
+    fn allowance(&self) -> Result<Amount, TransactionError> {
+        match self {
+            Self::Deny => Ok(Amount::default()),
+            Self::Limit(limit) => to_amount(limit)
+        }
+    }
+}
+
+/// Caps on the amounts the engine accepts, on top of the range every amount is kept to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// The largest amount a single deposit, withdrawal, transfer or conversion may move, or `None` for no cap.
+    pub max_amount: Option<BigDecimal>,
+
+    /// The most funds an account may hold in any one currency, or `None` for no cap.
+    pub max_balance: Option<BigDecimal>
+}
+
+impl FromStr for LockPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject-all" => Ok(Self::RejectAll),
+            "allow-deposits" => Ok(Self::AllowDeposits),
+            "allow-disputes" => Ok(Self::AllowDisputes),
+            "allow-all" => Ok(Self::AllowAll),
+            _ => Err(format!("unknown lock policy '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for LockPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RejectAll => write!(f, "reject-all"),
+            Self::AllowDeposits => write!(f, "allow-deposits"),
+            Self::AllowDisputes => write!(f, "allow-disputes"),
+            Self::AllowAll => write!(f, "allow-all"),
+        }
+    }
+}
+
+/// A transaction the engine made itself as time passed, such as an interest posting or an occurrence of a scheduled transaction,
+/// alongside what it applied or why it was rejected.
+pub type Generated = (Transaction, Result<Applied, TransactionError>);
+
+/// The transaction processing engine, which applies transactions to the client accounts in its storage.
+#[derive(Debug, Default)]
+pub struct Engine<S = MemoryStorage> {
+    /// The storage backend holding every client and transaction.
+    storage: S,
+
+    /// Whether operator-only transactions, such as unlock, are accepted.
+    admin: bool,
+
+    /// The number of decimal places every amount is rounded to.
+    precision: Precision,
+
+    /// Which processed transactions are kept.
+    retention: Retention,
+
+    /// Which transactions a locked account still accepts.
+    lock_policy: LockPolicy,
+
+    /// Which transactions an account frozen by an operator still accepts.
+    freeze_policy: FreezePolicy,
+
+    /// How far below zero available funds may go.
+    overdraft: OverdraftPolicy,
+
+    /// The largest amounts a transaction may move and an account may hold.
+    limits: Limits,
+
+    /// The limits on the funds that may leave each client's account, if any.
+    velocity: Option<VelocityPolicy>,
+
+    /// The funds that left each client's account on the day of its latest withdrawal or transfer.
+    outflows: HashMap<u16, DailyOutflow>,
+
+    /// The known clients, whose accounts are reported even without any activity, if any are known.
+    roster: Option<Roster>,
+
+    /// The owners of every joint account, if any are shared.
+    ownership: Option<Ownership>,
+
+    /// The tenant whose books the engine keeps, or `None` for the default books.
+    tenant: Option<String>,
+
+    /// Whether a representment unlocks the account its chargeback locked.
+    unlock_on_representment: bool,
+
+    /// The fees charged on transactions, and the account they are credited to, if any.
+    fees: Option<FeeSchedule>,
+
+    /// The interest earned on available funds, if any.
+    interest: Option<Interest>,
+
+    /// The first day interest has not yet been accrued for, or `None` before any transaction with a timestamp.
+    accrual_day: Option<NaiveDate>,
+
+    /// The interest accrued on each balance of each client since it was last posted.
+    accrued: BTreeMap<(u16, Option<String>), BigDecimal>,
+
+    /// The scheduled transactions still to fall due, by id.
+    scheduled: BTreeMap<u32, ScheduledTransaction>,
+
+    /// Whether transactions that happened before the latest accepted transaction are rejected.
+    enforce_order: bool,
+
+    /// The latest timestamp of any accepted transaction.
+    latest: Option<DateTime<Utc>>,
+
+    /// How long after a transaction it can still be disputed, or `None` for no limit.
+    dispute_window: Option<TimeDelta>,
+
+    /// Whether the engine's invariants are checked after every transaction, which debug builds always do.
+    check_invariants: bool,
+
+    /// The double-entry ledger every movement of funds is posted to, if one is kept.
+    ledger: Option<Ledger>,
+
+    /// The state of every client after each change, if a history of balances is kept.
+    history: Option<BalanceHistory>,
+
+    /// The activity of each client's account, if it is counted.
+    activity: Option<BTreeMap<u16, Activity>>,
+
+    /// The script deciding whether each transaction is accepted, rejected or flagged before it is applied, if any.
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+
+    /// The observers notified of every processed transaction.
+    observers: Observers
+}
+
+impl Engine {
+    /// Create a new engine with no clients, kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start building an engine, to configure its policies and storage backend.
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::new()
+    }
+
+    /// Merge the clients and transactions of another engine, which must not share any clients with this one but the fee account.
+    pub(crate) fn merge(&mut self, mut other: Engine) {
+        // NOTE: Every shard credits the fees it charges to a fee account of its own, so the fee accounts are added up.
+        let charged = self.fees.as_ref().and_then(|fees| other.storage.take_client(fees.account()));
+        self.storage.merge(other.storage);
+        if let Some(charged) = charged {
+            self.storage.combine_client(charged).expect("the fees charged by every shard are out of range");
+        }
+        self.latest = self.latest.max(other.latest);
+        self.accrual_day = self.accrual_day.max(other.accrual_day);
+        self.accrued.extend(other.accrued);
+        self.outflows.extend(other.outflows);
+        self.scheduled.extend(other.scheduled);
+        if let (Some(ledger), Some(other)) = (&mut self.ledger, other.ledger) {
+            ledger.merge(other);
+        }
+        if let (Some(history), Some(other)) = (&mut self.history, other.history) {
+            history.merge(other);
+        }
+        if let (Some(activity), Some(other)) = (&mut self.activity, other.activity) {
+            activity.extend(other);
+        }
+    }
+}
+
+impl<S: Storage> Engine<S> {
+    /// Create a new engine on top of a storage backend.
+    pub fn with_storage(storage: S) -> Self {
+        Self {
+            storage,
+            admin: false,
+            precision: Precision::default(),
+            retention: Retention::default(),
+            lock_policy: LockPolicy::default(),
+            freeze_policy: FreezePolicy::default(),
+            overdraft: OverdraftPolicy::default(),
+            limits: Limits::default(),
+            velocity: None,
+            outflows: HashMap::new(),
+            roster: None,
+            ownership: None,
+            tenant: None,
+            unlock_on_representment: false,
+            fees: None,
+            interest: None,
+            accrual_day: None,
+            accrued: BTreeMap::new(),
+            scheduled: BTreeMap::new(),
+            enforce_order: false,
+            latest: None,
+            dispute_window: None,
+            check_invariants: false,
+            ledger: None,
+            history: None,
+            activity: None,
+            #[cfg(feature = "scripting")]
+            script: None,
+            observers: Observers::default()
+        }
+    }
+
+    /// Accept operator-only transactions, such as unlock, which are otherwise rejected.
+    pub fn with_admin(mut self, admin: bool) -> Self {
+        self.admin = admin;
+        self
+    }
+
+    /// Round every amount to this precision as it is processed, rather than the default of four decimal places.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Accept the transactions a lock policy permits on locked accounts, rather than rejecting every transaction.
+    pub fn with_lock_policy(mut self, lock_policy: LockPolicy) -> Self {
+        self.lock_policy = lock_policy;
+        self
+    }
+
+    /// Accept the transactions a freeze policy permits on frozen accounts, rather than only deposits.
+    pub fn with_freeze_policy(mut self, freeze_policy: FreezePolicy) -> Self {
+        self.freeze_policy = freeze_policy;
+        self
+    }
+
+    /// Let withdrawals, transfers and conversions take available funds below zero, as far as the policy allows.
+    pub fn with_overdraft(mut self, overdraft: OverdraftPolicy) -> Self {
+        self.overdraft = overdraft;
+        self
+    }
+
+    /// Reject transactions that move more than the largest amount, or take an account above the most funds it may hold.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Reject withdrawals and transfers that break a client's withdrawal limits, counting the funds that leave its account
+    /// each day. A day is that of the transaction's timestamp, or of the latest accepted transaction if it has none.
+    pub fn with_velocity(mut self, velocity: Option<VelocityPolicy>) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// Report the account of every known client, with zero balances if it has no activity,
+    /// and reject transactions of clients that are not known if the roster does.
+    pub fn with_roster(mut self, roster: Option<Roster>) -> Self {
+        self.roster = roster;
+        self
+    }
+
+    /// Apply the transactions of every owner of a joint account to its shared balance, and report the account with its owners.
+    pub fn with_ownership(mut self, ownership: Option<Ownership>) -> Self {
+        self.ownership = ownership;
+        self
+    }
+
+    /// Keep the books of a tenant, rejecting the transactions of every other tenant, rather than the default books.
+    pub fn with_tenant(mut self, tenant: Option<String>) -> Self {
+        self.tenant = tenant;
+        self
+    }
+
+    /// The tenant whose books the engine keeps, or `None` for the default books.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// The owners of every joint account, if any are shared.
+    pub fn ownership(&self) -> Option<&Ownership> {
+        self.ownership.as_ref()
+    }
+
+    /// The transaction as applied to the accounts its client and destination operate.
+    pub fn resolve<'a>(&self, transaction: &'a Transaction) -> Cow<'a, Transaction> {
+        match &self.ownership {
+            Some(ownership) => ownership.resolve(transaction),
+            None => Cow::Borrowed(transaction)
+        }
+    }
+
+    /// Unlock the account when a representment reverses its chargeback, rather than leaving it locked for an operator.
+    pub fn with_unlock_on_representment(mut self, unlock_on_representment: bool) -> Self {
+        self.unlock_on_representment = unlock_on_representment;
+        self
+    }
+
+    /// Charge the fees of a schedule on every transaction, debiting them from the available funds alongside the amount
+    /// and crediting them to the schedule's fee account. A transaction that can not also cover its fee is rejected.
+    /// The fee account is an ordinary client of this engine, so an engine sharded across clients can not charge fees.
+    pub fn with_fees(mut self, fees: Option<FeeSchedule>) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    /// Accrue interest on the available funds of every unlocked account at the end of each day, posting it at the end of
+    /// each interest period. Days pass as transactions with later timestamps arrive, or the engine is advanced.
+    pub fn with_interest(mut self, interest: Option<Interest>) -> Self {
+        self.interest = interest;
+        self
+    }
+
+    /// Reject any transaction with a timestamp earlier than the latest accepted transaction.
+    /// Transactions without a timestamp are never rejected for being out of order.
+    pub fn with_enforce_order(mut self, enforce_order: bool) -> Self {
+        self.enforce_order = enforce_order;
+        self
+    }
+
+    /// Reject disputes of transactions that happened longer ago than the window, rather than accepting them at any age.
+    /// The age is measured to the dispute's timestamp, or the latest accepted transaction if it has none,
+    /// and transactions without a timestamp can always be disputed.
+    pub fn with_dispute_window(mut self, dispute_window: Option<TimeDelta>) -> Self {
+        self.dispute_window = dispute_window;
+        self
+    }
+
+    /// Check the engine's invariants after every transaction, failing it with [`TransactionError::InvariantViolated`],
+    /// which describes the offending transaction and account state, if one is broken. The transaction has already been
+    /// applied by then, so the engine can not be trusted any further. Debug builds always check them.
+    pub fn with_check_invariants(mut self, check_invariants: bool) -> Self {
+        self.check_invariants = check_invariants;
+        self
+    }
+
+    /// Post every movement of funds to a double-entry ledger, which the invariant checks also hold the accounts to.
+    pub fn with_ledger(mut self, ledger: Option<Ledger>) -> Self {
+        self.ledger = ledger;
+        self
+    }
+
+    /// Keep a history of the balances of every client, recording each change, so the state of an account can be queried
+    /// as of any instant.
+    pub fn with_history(mut self, history: Option<BalanceHistory>) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Count the transactions of each client that were accepted and rejected, its open disputes, and its latest activity.
+    pub fn with_activity(mut self, activity: bool) -> Self {
+        self.activity = activity.then(BTreeMap::new);
+        self
+    }
+
+    /// Run every transaction past a script before it is applied, which may reject it or flag it for review.
+    /// A transaction is only flagged once it has been accepted.
+    #[cfg(feature = "scripting")]
+    pub fn with_script(mut self, script: Option<Script>) -> Self {
+        self.script = script;
+        self
+    }
+
+    /// Notify an observer of every transaction this engine processes, after any observers already registered.
+    pub fn with_observer<O: EngineObserver + 'static>(mut self, observer: O) -> Self {
+        self.add_observer(observer);
+        self
+    }
+
+    /// Notify an observer of every transaction this engine processes, after any observers already registered.
+    pub fn add_observer<O: EngineObserver + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    /// Replace the observers of the engine.
+    pub(crate) fn with_observers(mut self, observers: Observers) -> Self {
+        self.observers = observers;
+        self
+    }
+
+    /// The latest timestamp of any accepted transaction.
+    pub fn latest(&self) -> Option<DateTime<Utc>> {
+        self.latest
+    }
+
+    /// The precision every amount is rounded to.
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Whether accounts can be frozen by an operator, which only an engine accepting operator-only transactions allows.
+    pub fn freezes(&self) -> bool {
+        self.admin
+    }
+
+    /// Drop transactions once they can no longer be referenced, rather than keeping every transaction.
+    pub fn with_retention(mut self, retention: Retention) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// The double-entry ledger of the engine, if one is kept.
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.ledger.as_ref()
+    }
+
+    /// The history of the balances of every client, if one is kept.
+    pub fn history(&self) -> Option<&BalanceHistory> {
+        self.history.as_ref()
+    }
+
+    /// The activity of a client's account, if it is counted and the client has had any transaction processed.
+    pub fn activity(&self, client: u16) -> Option<&Activity> {
+        self.activity.as_ref()?.get(&client)
+    }
+
+    /// The storage backend of the engine.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// The storage backend of the engine, to track the offsets of a source in.
+    /// Writing clients or transactions directly bypasses every check of the engine.
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Consume the engine, returning its storage backend.
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// Process a single transaction, creating any client that does not yet exist.
+    /// Returns what was applied, or the reason the transaction was rejected.
+    ///
+    /// A transaction scheduled for later is only checked on its own, and is applied once the engine is advanced past the time it falls due.
+    pub fn process(&mut self, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        // NOTE: A transaction of an owner of a joint account is processed, stored and reported as one of the account itself.
+        let resolved = self.resolve(transaction);
+        let transaction = resolved.as_ref();
+        let span = tracing::debug_span!("transaction", tx = transaction.id, client = transaction.client_id, r#type = ?transaction.type_);
+        let _entered = span.enter();
+
+        // NOTE: Time has passed whether or not the transaction is accepted, so anything that fell due and the interest accrued up to it come first.
+        let advanced = self.storage.begin().map_err(TransactionError::from).and_then(|_| match transaction.timestamp {
+            Some(timestamp) => self.advance(timestamp).map(drop),
+            None => Ok(())
+        });
+
+        let checking = self.check_invariants || cfg!(debug_assertions);
+        let before = if checking { self.touched(transaction) } else { Vec::new() };
+
+        let result = advanced
+            .and_then(|_| self.admit(transaction))
+            .and_then(|_| match transaction.scheduled_for {
+                Some(due) => Ok(self.schedule(transaction, due)),
+                None => self.screen(transaction).and_then(|flag| {
+                    let applied = self.apply(transaction)?;
+                    self.post(transaction, &applied)?;
+                    self.retain(transaction, &applied)?;
+                    self.latest = self.latest.max(transaction.timestamp);
+                    self.flag(transaction, flag);
+                    Ok(applied)
+                })
+            });
+
+        // NOTE: A rejected transaction is committed too, keeping the record of its rejection.
+        let result = match self.storage.commit(transaction, &result) {
+            Ok(()) => result,
+            Err(e) => Err(e.into())
+        };
+        self.processed(transaction, result, before)
+    }
+
+    /// Check the invariants still hold after a transaction was processed, if they are checked,
+    /// then log the outcome and notify the observers. A broken invariant becomes the outcome of the transaction.
+    fn processed(&mut self, transaction: &Transaction, result: Result<Applied, TransactionError>, before: Vec<(TransactionType, u16, Option<Client>)>) -> Result<Applied, TransactionError> {
+        let result = self.checked(transaction, result, before);
+        let result = &result;
+        match result {
+            Ok(applied) => tracing::debug!(?applied, "accepted"),
+            Err(e) => tracing::info!(reason = %e, "rejected")
+        }
+
+        if let Some(activity) = &mut self.activity {
+            activity.entry(transaction.client_id)
+                .or_insert_with(|| Activity::new(transaction.client_id))
+                .count(transaction.timestamp, result);
+        }
+
+        // NOTE: Any fee charged is credited to the fee account, so it is recorded too, unless it did not change.
+        if result.is_ok() {
+            let fee_account = self.fees.as_ref().map(FeeSchedule::account);
+            let clients = iter::once(transaction.client_id).chain(transaction.destination).chain(fee_account).collect::<Vec<_>>();
+            self.remember(transaction.timestamp, clients);
+        }
+
+        if !self.observers.is_empty() {
+            // NOTE: A storage error here only costs the observers the account, the transaction has already been processed.
+            let client = self.storage.get_client(transaction.client_id).ok().flatten();
+            self.observers.notify(transaction, result, client.as_ref());
+        }
+        result.clone()
+    }
+
+    /// Check the invariants still hold for the clients of this engine a transaction touched, if they are checked.
+    /// A broken invariant becomes the outcome of the transaction.
+    fn checked(&self, transaction: &Transaction, result: Result<Applied, TransactionError>, before: Vec<(TransactionType, u16, Option<Client>)>) -> Result<Applied, TransactionError> {
+        match self.check_invariants || cfg!(debug_assertions) {
+            true => self.check_invariants(transaction, &result, before)
+                .map_err(TransactionError::InvariantViolated)
+                .and(result),
+            false => result
+        }
+    }
+
+    /// Check a transaction is well formed and may be applied by this engine at all.
+    fn admit(&self, transaction: &Transaction) -> Result<(), TransactionError> {
+        validate(transaction)?;
+        self.check_order(transaction)?;
+
+        if transaction.type_.is_admin() && !self.admin {
+            return Err(TransactionError::Unauthorized);
+        }
+
+        if transaction.tenant() != self.tenant() {
+            return Err(TransactionError::ForeignTenant(transaction.tenant().map(str::to_string)));
+        }
+
+        // NOTE: A joint account is known when any of its owners is.
+        if let Some(roster) = self.roster.as_ref().filter(|roster| roster.rejects_unknown()) {
+            let known = |client: u16| roster.contains(client)
+                || self.ownership.as_ref().is_some_and(|ownership| ownership.owners(client).into_iter().any(|owner| roster.contains(owner)));
+            if let Some(client) = iter::once(transaction.client_id).chain(transaction.destination).find(|&client| !known(client)) {
+                return Err(TransactionError::UnknownClient(client));
+            }
+        }
+
+        if !transaction.type_.references_existing() && (self.storage.contains_transaction(transaction.id)? || self.scheduled.contains_key(&transaction.id)) {
+            // NOTE: Only accepted transactions are stored, so a rejected transaction can be resubmitted.
+            return Err(TransactionError::DuplicateTransaction(transaction.id));
+        }
+        Ok(())
+    }
+
+    /// Hold a scheduled transaction until its first occurrence falls due.
+    fn schedule(&mut self, transaction: &Transaction, due: DateTime<Utc>) -> Applied {
+        self.scheduled.insert(transaction.id, ScheduledTransaction { transaction: transaction.clone(), occurrences: 0 });
+        self.latest = self.latest.max(transaction.timestamp);
+        Applied::Scheduled { due }
+    }
+
+    /// Apply every occurrence of the scheduled transactions that is due, in the order they fall due,
+    /// and return each alongside what it applied or why it was rejected.
+    /// A recurring transaction is held again until its next occurrence, whether or not this one was accepted.
+    fn apply_due<F: Fn(DateTime<Utc>) -> bool>(&mut self, is_due: F) -> Vec<Generated> {
+        let mut occurrences = Vec::new();
+        loop {
+            let next = self.scheduled.values()
+                .filter_map(|scheduled| Some((scheduled.due()?, scheduled.transaction.id)))
+                .min();
+            let Some((_, id)) = next.filter(|(due, _)| is_due(*due)) else {
+                break;
+            };
+
+            let Some(scheduled) = self.scheduled.get_mut(&id) else {
+                break;
+            };
+            let occurrence = scheduled.occurrence().expect("a due transaction has an occurrence");
+            scheduled.occurrences = scheduled.occurrences.saturating_add(1);
+            if scheduled.due().is_none() {
+                self.scheduled.remove(&id);
+            }
+
+            let result = self.occur(&occurrence);
+            occurrences.push((occurrence, result));
+        }
+        occurrences
+    }
+
+    /// Apply an occurrence of a scheduled transaction, which was already admitted when it was scheduled.
+    /// Every occurrence shares the id of its schedule, so none is kept to be disputed, but the id is still seen as a duplicate.
+    fn occur(&mut self, occurrence: &Transaction) -> Result<Applied, TransactionError> {
+        let span = tracing::debug_span!("occurrence", tx = occurrence.id, client = occurrence.client_id, r#type = ?occurrence.type_);
+        let _entered = span.enter();
+
+        let checking = self.check_invariants || cfg!(debug_assertions);
+        let before = if checking { self.touched(occurrence) } else { Vec::new() };
+
+        let result = self.screen(occurrence).and_then(|flag| {
+            let applied = self.apply(occurrence)?;
+            self.post(occurrence, &applied)?;
+            self.storage.retire_transaction(occurrence.id)?;
+            self.latest = self.latest.max(occurrence.timestamp);
+            self.flag(occurrence, flag);
+            Ok(applied)
+        });
+
+        self.processed(occurrence, result, before)
+    }
+
+    /// Post the movements of an accepted transaction to the ledger, if one is kept.
+    fn post(&mut self, transaction: &Transaction, applied: &Applied) -> Result<(), StorageError> {
+        if self.ledger.is_none() {
+            return Ok(());
+        }
+
+        let target = match transaction.type_.references_existing() {
+            true => self.storage.get_transaction(transaction.id)?,
+            false => None
+        };
+        let fee_account = self.fees.as_ref().map(FeeSchedule::account);
+        if let Some(ledger) = &mut self.ledger {
+            ledger.record(transaction, applied, target.as_ref(), fee_account);
+        }
+        Ok(())
+    }
+
+    /// Run a transaction past the script, if there is one, before it is applied.
+    /// Returns why the script flagged the transaction, if it did, to be recorded once it has been accepted.
+    fn screen(&self, transaction: &Transaction) -> Result<Option<String>, TransactionError> {
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &self.script {
+            let client = self.storage.get_client(transaction.client_id)?;
+            return match script.decide(transaction, client.as_ref()) {
+                Ok(Decision::Accept) => Ok(None),
+                Ok(Decision::Flag(reason)) => Ok(Some(reason)),
+                Ok(Decision::Reject(reason)) => Err(TransactionError::RejectedByScript(reason)),
+                Err(e) => Err(TransactionError::RejectedByScript(format!("the script failed: {}", e)))
+            };
+        }
+
+        let _ = transaction;
+        Ok(None)
+    }
+
+    /// Record an accepted transaction the script flagged.
+    fn flag(&self, transaction: &Transaction, flag: Option<String>) {
+        #[cfg(feature = "scripting")]
+        if let (Some(script), Some(reason)) = (&self.script, flag) {
+            script.flag(transaction, reason);
+        }
+
+        #[cfg(not(feature = "scripting"))]
+        let _ = (transaction, flag);
+    }
+
+    /// Apply a single admitted transaction, without any logging.
+    fn apply(&mut self, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        match transaction.type_ {
+            TransactionType::Deposit => {
+                let (amount, kept) = self.amount(transaction)?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+
+                client.deposit(transaction.account(), transaction.currency(), &kept)?;
+                let fee = self.charge_fee(&mut client, transaction, transaction.currency(), &amount)?;
+                self.check_balance(&client, transaction.account(), transaction.currency())?;
+                self.store(client, transaction, amount.clone())?;
+                self.credit_fee(transaction.currency(), fee.as_ref())?;
+                Ok(Applied::Deposited { amount, fee })
+            },
+            TransactionType::Withdrawal => {
+                let (amount, kept) = self.amount(transaction)?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                self.check_outflow(transaction, &amount)?;
+
+                client.withdraw(transaction.account(), transaction.currency(), &kept, &self.overdraft.allowance()?)?;
+                let fee = self.charge_fee(&mut client, transaction, transaction.currency(), &amount)?;
+                self.store(client, transaction, amount.clone())?;
+                self.credit_fee(transaction.currency(), fee.as_ref())?;
+                self.record_outflow(transaction, &amount);
+                Ok(Applied::Withdrew { amount, fee })
+            },
+            TransactionType::Dispute => {
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                let mut target = self.target(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                let now = transaction.timestamp.or(self.latest);
+                if let (Some(window), Some(now), Some(happened)) = (self.dispute_window, now, target.timestamp) {
+                    if now - happened > window {
+                        return Err(TransactionError::DisputeWindowExpired(target.id));
+                    }
+                }
+
+                client.dispute(&target)?;
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::Disputed { amount })
+            },
+            TransactionType::Resolve => {
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                let mut target = self.target(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                client.resolve(&target)?;
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::Resolved { amount })
+            },
+            TransactionType::Chargeback => {
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                let mut target = self.target(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                client.chargeback(&target)?;
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::ChargedBack { amount })
+            },
+            TransactionType::Representment => {
+                let mut target = self.target(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                // NOTE: A chargeback locks the account, so a representment is accepted whatever the lock policy.
+                let mut client = self.storage.get_client(transaction.client_id)?
+                    .ok_or(TransactionError::UnknownTransaction(target.id))?;
+
+                client.represent(&target)?;
+                let unlocked = self.unlock_on_representment && client.locked();
+                if unlocked {
+                    client.unlock();
+                }
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::Represented { amount, unlocked })
+            },
+            TransactionType::Transfer => self.transfer(transaction),
+            TransactionType::Convert => {
+                let (amount, kept) = self.amount(transaction)?;
+                let rate = transaction.rate.as_ref().ok_or(TransactionError::MissingConversion)?;
+                let converted = self.precision.round(&(&amount * rate))?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+
+                // NOTE: A conversion too small to be represented at the stored precision would destroy funds.
+                if converted <= BigDecimal::zero() {
+                    return Err(TransactionError::NonPositiveAmount);
+                }
+
+                client.withdraw(transaction.account(), transaction.currency(), &kept, &self.overdraft.allowance()?)?;
+                let fee = self.charge_fee(&mut client, transaction, transaction.currency(), &amount)?;
+                client.deposit(transaction.account(), transaction.to_currency(), &to_amount(&converted)?)?;
+                self.check_balance(&client, transaction.account(), transaction.to_currency())?;
+                self.store(client, transaction, amount.clone())?;
+                self.credit_fee(transaction.currency(), fee.as_ref())?;
+                Ok(Applied::Converted { amount, converted, fee })
+            },
+            TransactionType::Interest => {
+                let (amount, kept) = self.amount(transaction)?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+
+                client.deposit(transaction.account(), transaction.currency(), &kept)?;
+                self.check_balance(&client, transaction.account(), transaction.currency())?;
+                self.store(client, transaction, amount.clone())?;
+                Ok(Applied::Interest { amount })
+            },
+            TransactionType::Authorize => {
+                let (amount, kept) = self.amount(transaction)?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+
+                client.authorize(transaction.account(), transaction.currency(), &kept, &self.overdraft.allowance()?)?;
+                self.storage.update_client(client)?;
+                self.storage.insert_transaction(Transaction {
+                    amount: Some(amount.clone()),
+                    status: TransactionStatus::Authorized,
+                    ..transaction.clone()
+                })?;
+                Ok(Applied::Authorized { amount })
+            },
+            TransactionType::Capture => {
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                let mut target = self.authorization(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                client.capture(&target)?;
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::Captured { amount })
+            },
+            TransactionType::Void => {
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+                let mut target = self.authorization(transaction)?;
+                let status = target.status.transition(transaction.type_, target.id)?;
+
+                client.void(&target)?;
+                target.status = status;
+                let amount = target.amount.clone().unwrap();
+                self.update(client, target)?;
+                Ok(Applied::Voided { amount })
+            },
+            TransactionType::Move => {
+                let (amount, kept) = self.amount(transaction)?;
+                let mut client = self.client_for(transaction.client_id, transaction.type_)?;
+
+                client.move_funds(transaction.account(), transaction.to_account(), transaction.currency(), &kept)?;
+                self.check_balance(&client, transaction.to_account(), transaction.currency())?;
+                self.store(client, transaction, amount.clone())?;
+                Ok(Applied::Moved { amount })
+            },
+            TransactionType::Unlock => {
+                let mut client = self.storage.get_client(transaction.client_id)?
+                    .filter(Client::locked)
+                    .ok_or(TransactionError::NotLocked)?;
+
+                client.unlock();
+                self.storage.update_client(client)?;
+                // NOTE: The unlock is stored so its id stays unique, but it carries no amount and can not be disputed.
+                self.storage.insert_transaction(transaction.clone())?;
+                tracing::warn!(client = transaction.client_id, "account unlocked by an operator");
+                Ok(Applied::Unlocked)
+            },
+            TransactionType::Freeze | TransactionType::Unfreeze => {
+                let frozen = transaction.type_ == TransactionType::Freeze;
+                let mut client = self.storage.get_client(transaction.client_id)?
+                    .unwrap_or_else(|| Client::new(transaction.client_id));
+                match (frozen, client.frozen()) {
+                    (true, true) => return Err(TransactionError::AlreadyFrozen),
+                    (false, false) => return Err(TransactionError::NotFrozen),
+                    _ => {}
+                }
+
+                client.set_frozen(frozen);
+                self.storage.update_client(client)?;
+                // NOTE: Like an unlock, the freeze is stored so its id stays unique, but it carries no amount.
+                self.storage.insert_transaction(transaction.clone())?;
+                match frozen {
+                    true => {
+                        tracing::warn!(client = transaction.client_id, "account frozen by an operator");
+                        Ok(Applied::Frozen)
+                    },
+                    false => {
+                        tracing::warn!(client = transaction.client_id, "account unfrozen by an operator");
+                        Ok(Applied::Unfrozen)
+                    }
+                }
+            },
+            TransactionType::AssertBalance => {
+                // NOTE: An assertion only reads the account, so it is checked even when the account is locked or frozen.
+                let client = self.storage.get_client(transaction.client_id)?
+                    .unwrap_or_else(|| Client::new(transaction.client_id));
+                let balance = client.balance_in(transaction.account(), transaction.currency());
+                let actual = |funds: fn(&Balance) -> BigDecimal| self.precision.apply(&balance.map_or_else(BigDecimal::zero, funds));
+
+                let mismatches = [("available", transaction.amount.as_ref(), actual(Balance::available)), ("total", transaction.total.as_ref(), actual(Balance::total))]
+                    .into_iter()
+                    .filter_map(|(funds, expected, actual)| expected.filter(|expected| **expected != actual).map(|expected| (funds, expected.clone(), actual)))
+                    .collect::<Vec<_>>();
+                if !mismatches.is_empty() {
+                    tracing::warn!(client = transaction.client_id, tx = transaction.id, "balance assertion failed");
+                    return Err(TransactionError::BalanceMismatch(mismatches));
+                }
+
+                // NOTE: Like an unlock, the assertion is stored so its id stays unique, but it changes nothing.
+                self.storage.insert_transaction(transaction.clone())?;
+                Ok(Applied::Asserted)
+            }
+        }
+    }
+
+    /// Advance the engine to the time, applying every scheduled transaction that fell due by then and accruing interest
+    /// for every day that ended before it, posting it at the end of each interest period that did.
+    /// Returns every transaction the engine made itself, alongside what it applied or why it was rejected.
+    ///
+    /// Scheduled transactions falling due during a day are applied before the interest of that day is accrued.
+    /// Each interest posting is a synthetic interest transaction with the id 0, timestamped at the end of its period.
+    /// They are passed to the observers like any other transaction, but never stored.
+    pub fn advance(&mut self, until: DateTime<Utc>) -> Result<Vec<Generated>, TransactionError> {
+        let mut made = Vec::new();
+        if let Some(interest) = self.interest.clone() {
+            let today = until.date_naive();
+            let mut day = *self.accrual_day.get_or_insert(today);
+            while day < today {
+                made.extend(self.apply_due(|due| due.date_naive() <= day));
+
+                // NOTE: A locked account is frozen, so it neither accrues interest nor is credited any already accrued.
+                // Only the main account accrues interest, as funds set aside in a sub-account, such as escrow, are not the client's to earn on.
+                for client in self.storage.clients()?.into_iter().filter(|client| !client.locked()) {
+                    for balance in client.balances().iter().filter(|balance| balance.account().is_none() && balance.available() > BigDecimal::zero()) {
+                        *self.accrued.entry((client.id(), balance.currency().map(str::to_string))).or_default() += interest.daily(&balance.available());
+                    }
+                }
+                if interest.period.ends_on(day) {
+                    made.extend(self.post_interest(day)?);
+                }
+
+                match day.succ_opt() {
+                    Some(next) => day = next,
+                    None => break
+                }
+            }
+            self.accrual_day = Some(day);
+        }
+
+        made.extend(self.apply_due(|due| due <= until));
+        Ok(made)
+    }
+
+    /// Close the day, advancing the engine to the start of the next one, so everything that fell due and all the interest
+    /// earned by the end of the day is applied, and return the closing balances of every client account, ordered by client id.
+    pub fn close_day(&mut self, day: NaiveDate) -> Result<Vec<Client>, TransactionError> {
+        if let Some(until) = day.succ_opt().and_then(|next| next.and_hms_opt(0, 0, 0)) {
+            let made = self.advance(until.and_utc())?;
+            tracing::debug!(%day, made = made.len(), "day closed");
+        }
+        Ok(self.accounts()?)
+    }
+
+    /// Credit the interest accrued on every balance at the end of the day, rounded to precision, carrying over any remainder.
+    fn post_interest(&mut self, day: NaiveDate) -> Result<Vec<Generated>, TransactionError> {
+        let timestamp = day.succ_opt()
+            .and_then(|next| next.and_hms_opt(0, 0, 0))
+            .map(|time| time.and_utc());
+
+        let mut postings = Vec::new();
+        for ((id, currency), accrued) in std::mem::take(&mut self.accrued) {
+            let posted = self.precision.apply(&accrued);
+            let client = self.storage.get_client(id)?.filter(|client| !client.locked());
+            let Some(mut client) = client.filter(|_| posted > BigDecimal::zero()) else {
+                self.accrued.insert((id, currency), accrued);
+                continue;
+            };
+            if to_amount(&posted).and_then(|posted| client.deposit(None, currency.as_deref(), &posted)).is_err() {
+                self.accrued.insert((id, currency), accrued);
+                continue;
+            }
+
+            let remainder = &accrued - &posted;
+            self.storage.update_client(client.clone())?;
+            if let Some(history) = &mut self.history {
+                history.record(timestamp, client.clone());
+            }
+            let posting = Transaction {
+                currency: currency.clone(),
+                tenant: self.tenant.clone(),
+                timestamp,
+                ..Transaction::new(TransactionType::Interest, id, 0, Some(posted.clone()))
+            };
+            tracing::debug!(client = id, amount = %posted, "interest posted");
+            let applied = Applied::Interest { amount: posted };
+            if let Some(ledger) = &mut self.ledger {
+                ledger.record(&posting, &applied, None, None);
+            }
+            let applied = Ok(applied);
+            self.observers.notify(&posting, &applied, Some(&client));
+            postings.push((posting, applied));
+
+            if !remainder.is_zero() {
+                self.accrued.insert((id, currency), remainder);
+            }
+        }
+        Ok(postings)
+    }
+
+    /// Process every transaction yielded by the iterator, in order.
+    /// Returns each rejected transaction alongside the reason it was rejected.
+    pub fn process_all<I: IntoIterator<Item = Transaction>>(&mut self, transactions: I) -> Vec<(Transaction, TransactionError)> {
+        let mut rejected = Vec::new();
+        for transaction in transactions {
+            if let Err(e) = self.process(&transaction) {
+                rejected.push((transaction, e));
+            }
+        }
+        rejected
+    }
+
+    /// Get a client by id, which is an empty account for a known client without any activity.
+    pub fn client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+        let client = match self.storage.get_client(id)? {
+            None if self.roster.as_ref().is_some_and(|roster| roster.contains(id) && self.account_of(id) == id) => Some(Client::new(id)),
+            client => client
+        };
+        Ok(client.map(|client| self.owned(client)))
+    }
+
+    /// Get every client known to the engine, including an empty account for each known client without any activity.
+    pub fn clients(&self) -> Result<Vec<Client>, StorageError> {
+        let mut clients = self.storage.clients()?;
+        if let Some(roster) = &self.roster {
+            // NOTE: An owner of a joint account has no account of its own to report.
+            let active = clients.iter().map(Client::id).collect::<HashSet<_>>();
+            clients.extend(roster.ids().filter(|id| !active.contains(id) && self.account_of(*id) == *id).map(Client::new));
+        }
+        Ok(clients.into_iter().map(|client| self.owned(client)).collect())
+    }
+
+    /// The account a client operates, which is its own unless it owns a joint account.
+    fn account_of(&self, client: u16) -> u16 {
+        self.ownership.as_ref().map_or(client, |ownership| ownership.account_of(client))
+    }
+
+    /// A client with every owner of its account, if it is a joint account.
+    fn owned(&self, mut client: Client) -> Client {
+        if let Some(ownership) = &self.ownership {
+            client.set_owners(ownership.owners(client.id()));
+        }
+        client
+    }
+
+    /// Get every transaction that is disputed and not yet settled, ordered by client id and then transaction id, aged as
+    /// of the latest accepted transaction.
+    pub fn open_disputes(&self) -> Result<Vec<OpenDispute>, StorageError> {
+        let mut disputes = self.storage.transactions()?
+            .iter()
+            .filter(|transaction| transaction.status == TransactionStatus::Disputed)
+            .map(|transaction| OpenDispute::of(transaction, self.latest))
+            .collect::<Vec<_>>();
+        disputes.sort_by_key(|dispute| (dispute.client, dispute.transaction));
+        Ok(disputes)
+    }
+
+    /// Get every client account known to the engine, ordered by client id.
+    pub fn accounts(&self) -> Result<Vec<Client>, StorageError> {
+        let mut clients = self.clients()?;
+        clients.sort_by_key(Client::id);
+        Ok(clients)
+    }
+
+    /// Get every client account known to the engine, as [`Engine::accounts`] does, alongside its activity, which is
+    /// empty for a client without any transaction processed or if activity is not counted.
+    pub fn accounts_with_activity(&self) -> Result<Vec<(Client, Activity)>, StorageError> {
+        Ok(self.accounts()?
+            .into_iter()
+            .map(|client| {
+                let activity = self.activity(client.id()).cloned().unwrap_or_else(|| Activity::new(client.id()));
+                (client, activity)
+            })
+            .collect())
+    }
+
+    /// Get a client as of an instant, or `None` if it had no account by then, from the history of balances.
+    /// A client is as it is now if no history is kept, or its account never changed.
+    pub fn balance_at(&self, id: u16, at: DateTime<Utc>) -> Result<Option<Client>, StorageError> {
+        match &self.history {
+            Some(history) if history.contains(id) => Ok(history.balance_at(id, at).cloned()),
+            _ => self.client(id)
+        }
+    }
+
+    /// Get every client account known to the engine as of an instant, as [`Engine::balance_at`] does, ordered by client id.
+    pub fn accounts_at(&self, at: DateTime<Utc>) -> Result<Vec<Client>, StorageError> {
+        let mut clients = Vec::new();
+        for client in self.accounts()? {
+            clients.extend(self.balance_at(client.id(), at)?);
+        }
+        Ok(clients)
+    }
+
+    /// Capture the complete state of the engine, so it can be restored later.
+    pub fn snapshot(&self) -> Result<Snapshot, StorageError> {
+        let clients = self.accounts()?;
+
+        let mut transactions = self.storage.transactions()?
+            .into_iter()
+            .map(|transaction| SnapshotTransaction {
+                status: transaction.status,
+                transaction
+            })
+            .collect::<Vec<_>>();
+        transactions.sort_by_key(|entry| entry.transaction.id);
+
+        let mut retired = self.storage.retired()?;
+        retired.sort_unstable();
+
+        let mut outflows = self.outflows.values().cloned().collect::<Vec<_>>();
+        outflows.sort_by_key(|outflow| outflow.client);
+
+        let accrued = self.accrued.iter()
+            .map(|((client, currency), amount)| AccruedInterest { client: *client, currency: currency.clone(), amount: amount.clone() })
+            .collect();
+
+        let activity = self.activity.iter().flat_map(BTreeMap::values).cloned().collect();
+
+        Ok(Snapshot {
+            clients,
+            transactions,
+            retired,
+            latest: self.latest,
+            accrual_day: self.accrual_day,
+            accrued,
+            outflows,
+            scheduled: self.scheduled.values().cloned().collect(),
+            activity,
+            offsets: Vec::new()
+        })
+    }
+
+    /// Restore the state captured by a snapshot, replacing any clients or transactions with the same ids.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), StorageError> {
+        for client in snapshot.clients {
+            if let Some(ledger) = &mut self.ledger {
+                ledger.open(&client);
+            }
+            // NOTE: Restored accounts are held as of the very start, as the history of how they came to be is not kept.
+            if let Some(history) = &mut self.history {
+                history.record(None, client.clone());
+            }
+            self.storage.update_client(client)?;
+        }
+
+        for entry in snapshot.transactions {
+            self.storage.insert_transaction(Transaction {
+                status: entry.status,
+                ..entry.transaction
+            })?;
+        }
+
+        for id in snapshot.retired {
+            self.storage.retire_transaction(id)?;
+        }
+        self.latest = self.latest.max(snapshot.latest);
+
+        self.accrual_day = self.accrual_day.max(snapshot.accrual_day);
+        for accrued in snapshot.accrued {
+            *self.accrued.entry((accrued.client, accrued.currency)).or_default() += accrued.amount;
+        }
+        for outflow in snapshot.outflows {
+            self.outflows.insert(outflow.client, outflow);
+        }
+        for scheduled in snapshot.scheduled {
+            self.scheduled.insert(scheduled.transaction.id, scheduled);
+        }
+        if let Some(activity) = &mut self.activity {
+            activity.extend(snapshot.activity.into_iter().map(|activity| (activity.client, activity)));
+        }
+        Ok(())
+    }
+
+    /// Get the clients a transaction can change, as they are before it is applied, alongside the type of transaction
+    /// each one sees. The destination of a transfer sees it as a deposit.
+    fn touched(&self, transaction: &Transaction) -> Vec<(TransactionType, u16, Option<Client>)> {
+        let destination = transaction.destination
+            .filter(|_| transaction.type_ == TransactionType::Transfer)
+            .map(|destination| (TransactionType::Deposit, destination));
+
+        [(transaction.type_, transaction.client_id)].into_iter()
+            .chain(destination)
+            .map(|(type_, id)| (type_, id, self.storage.get_client(id).ok().flatten()))
+            .collect()
+    }
+
+    /// Check every client a transaction touched still holds `total == available + held` with nothing negative held,
+    /// and that a locked or frozen account was left untouched unless the lock or freeze policy accepts the transaction.
+    /// Describes the transaction and the account before and after it, if an invariant is broken.
+    fn check_invariants(&self, transaction: &Transaction, result: &Result<Applied, TransactionError>, before: Vec<(TransactionType, u16, Option<Client>)>) -> Result<(), String> {
+        for (type_, id, before) in before {
+            let Ok(Some(after)) = self.storage.get_client(id) else {
+                continue;
+            };
+
+            let broken = after.balances().iter()
+                .find_map(|balance| {
+                    if balance.total() != balance.available() + balance.held() {
+                        Some("total is not the sum of available and held funds")
+                    } else if balance.held() < BigDecimal::zero() {
+                        Some("held funds are negative")
+                    } else {
+                        None
+                    }
+                })
+                .or_else(|| {
+                    let accepted = result.is_ok() && (matches!(type_, TransactionType::Unlock | TransactionType::Representment | TransactionType::Freeze | TransactionType::Unfreeze) || self.lock_policy.permits(type_));
+                    let locked = before.as_ref().filter(|before| before.locked() && !accepted);
+                    locked.filter(|before| *before != &after).map(|_| "a locked account was changed")
+                })
+                .or_else(|| {
+                    let accepted = result.is_ok() && (matches!(type_, TransactionType::Unlock | TransactionType::Representment | TransactionType::Freeze | TransactionType::Unfreeze) || self.freeze_policy.permits(type_));
+                    let frozen = before.as_ref().filter(|before| before.frozen() && !accepted);
+                    frozen.filter(|before| *before != &after).map(|_| "a frozen account was changed")
+                })
+                .map(str::to_string)
+                .or_else(|| self.ledger.as_ref().and_then(|ledger| ledger.check(&after).err()));
+
+            if let Some(broken) = broken {
+                return Err(format!("invariant violated for client {}: {}\n  transaction: {:?}\n  result: {:?}\n  before: {:?}\n  after: {:?}",
+                    id, broken, transaction, result, before, after));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the amount of a transaction, checked against the engine's precision, alongside it as balances are kept,
+    /// so it is converted only once however many balances it changes.
+    fn amount(&self, transaction: &Transaction) -> Result<(BigDecimal, Amount), TransactionError> {
+        let amount = transaction.amount.as_ref()
+            .ok_or(TransactionError::MissingAmount)
+            .and_then(|amount| self.precision.check(amount))?;
+
+        match &self.limits.max_amount {
+            Some(max_amount) if &amount > max_amount => Err(TransactionError::AmountAboveLimit(max_amount.clone())),
+            _ => to_amount(&amount).map(|kept| (amount, kept))
+        }
+    }
+
+    /// Reject a transaction that would leave a client holding more than the most funds an account may hold in a currency.
+    fn check_balance(&self, client: &Client, account: Option<&str>, currency: Option<&str>) -> Result<(), TransactionError> {
+        let Some(max_balance) = &self.limits.max_balance else {
+            return Ok(());
+        };
+        match client.balance_in(account, currency).map(Balance::total) {
+            Some(total) if &total > max_balance => Err(TransactionError::BalanceAboveLimit(max_balance.clone())),
+            _ => Ok(())
+        }
+    }
+
+    /// The day a transaction's outflow counts towards, which is that of its timestamp or of the latest accepted transaction.
+    fn outflow_day(&self, transaction: &Transaction) -> Option<NaiveDate> {
+        transaction.timestamp.or(self.latest).map(|timestamp| timestamp.date_naive())
+    }
+
+    /// Reject a withdrawal or transfer that would break the client's withdrawal limits.
+    fn check_outflow(&self, transaction: &Transaction, amount: &BigDecimal) -> Result<(), TransactionError> {
+        let Some(velocity) = &self.velocity else {
+            return Ok(());
+        };
+        let limits = velocity.limits(transaction.client_id);
+        let withdrawal = transaction.type_ == TransactionType::Withdrawal;
+
+        if let Some(max_amount) = limits.max_amount.as_ref().filter(|max_amount| withdrawal && amount > *max_amount) {
+            return Err(TransactionError::WithdrawalAboveLimit(max_amount.clone()));
+        }
+
+        let day = self.outflow_day(transaction);
+        let today = self.outflows.get(&transaction.client_id).filter(|outflow| outflow.day == day);
+        let withdrawals = today.map_or(0, |today| today.withdrawals);
+        if let Some(max_per_day) = limits.max_per_day.filter(|max_per_day| withdrawal && withdrawals >= *max_per_day) {
+            return Err(TransactionError::TooManyWithdrawals(max_per_day));
+        }
+
+        let outflow = today.map_or_else(|| amount.clone(), |today| &today.outflow + amount);
+        match limits.max_daily_outflow {
+            Some(max_daily_outflow) if outflow > max_daily_outflow => Err(TransactionError::DailyOutflowAboveLimit(max_daily_outflow)),
+            _ => Ok(())
+        }
+    }
+
+    /// Count an accepted withdrawal or transfer against the client's daily limits, starting afresh on a new day.
+    fn record_outflow(&mut self, transaction: &Transaction, amount: &BigDecimal) {
+        if self.velocity.is_none() {
+            return;
+        }
+
+        let day = self.outflow_day(transaction);
+        let fresh = DailyOutflow { client: transaction.client_id, day, ..DailyOutflow::default() };
+        let outflow = self.outflows.entry(transaction.client_id).or_insert_with(|| fresh.clone());
+        if outflow.day != day {
+            *outflow = fresh;
+        }
+        if transaction.type_ == TransactionType::Withdrawal {
+            outflow.withdrawals += 1;
+        }
+        outflow.outflow += amount;
+    }
+
+    /// Get a client by id to apply a type of transaction to, creating it if it does not yet exist.
+    /// Fails if the client is locked, unless the lock policy permits the transaction.
+    fn client_for(&mut self, id: u16, type_: TransactionType) -> Result<Client, TransactionError> {
+        let client = match self.storage.get_client(id)? {
+            Some(client) => client,
+            None => {
+                // A client is created by any transaction that references it, even if the transaction is rejected.
+                let client = Client::new(id);
+                self.storage.update_client(client.clone())?;
+                client
+            }
+        };
+
+        if client.locked() && !self.lock_policy.permits(type_) {
+            return Err(TransactionError::AccountLocked);
+        }
+        if client.frozen() && !self.freeze_policy.permits(type_) {
+            return Err(TransactionError::AccountFrozen);
+        }
+
+        Ok(client)
+    }
+
+    /// Get the transaction referenced by a dispute, resolve, chargeback or representment.
+    /// The referenced transaction must belong to the same client, as transaction ids are unique across every client.
+    fn target(&self, transaction: &Transaction) -> Result<Transaction, TransactionError> {
+        let target = self.storage.get_transaction(transaction.id)?
+            .ok_or(TransactionError::UnknownTransaction(transaction.id))?;
+
+        if target.client_id != transaction.client_id {
+            return Err(TransactionError::ForeignTransaction(target.id, target.client_id));
+        }
+
+        match target.type_ {
+            TransactionType::Deposit | TransactionType::Withdrawal => Ok(target),
+            _ => Err(TransactionError::NotDisputable(target.id))
+        }
+    }
+
+    /// Get the authorization referenced by a capture or void, which must belong to the same client.
+    fn authorization(&self, transaction: &Transaction) -> Result<Transaction, TransactionError> {
+        let target = self.storage.get_transaction(transaction.id)?
+            .ok_or(TransactionError::UnknownTransaction(transaction.id))?;
+
+        if target.client_id != transaction.client_id {
+            return Err(TransactionError::ForeignTransaction(target.id, target.client_id));
+        }
+
+        match target.type_ {
+            TransactionType::Authorize => Ok(target),
+            _ => Err(TransactionError::NotAuthorization(target.id))
+        }
+    }
+
+    /// Check a transaction did not happen before the latest accepted transaction, if chronological order is enforced.
+    fn check_order(&self, transaction: &Transaction) -> Result<(), TransactionError> {
+        match (transaction.timestamp, self.latest) {
+            (Some(timestamp), Some(latest)) if self.enforce_order && timestamp < latest => Err(TransactionError::OutOfOrder),
+            _ => Ok(())
+        }
+    }
+
+    /// Drop the transaction a newly applied one leaves unreferenceable, if the retention policy allows it.
+    /// Transfers, conversions, moves, unlocks, freezes, assertions and interest can never be disputed, and a represented transaction, or a captured
+    /// or voided authorization, is final.
+    fn retain(&mut self, transaction: &Transaction, applied: &Applied) -> Result<(), StorageError> {
+        match (self.retention, applied) {
+            (Retention::All, _) => Ok(()),
+            (_, Applied::Transferred { .. } | Applied::Converted { .. } | Applied::Moved { .. } | Applied::Unlocked | Applied::Frozen | Applied::Unfrozen | Applied::Asserted | Applied::Represented { .. }
+                | Applied::Interest { .. } | Applied::Captured { .. } | Applied::Voided { .. }) => self.storage.retire_transaction(transaction.id),
+            _ => Ok(())
+        }
+    }
+
+    /// Store an updated client alongside the new transaction that updated it, with the amount rounded to precision.
+    fn store(&mut self, client: Client, transaction: &Transaction, amount: BigDecimal) -> Result<(), TransactionError> {
+        self.storage.update_client(client)?;
+        self.storage.insert_transaction(Transaction {
+            amount: Some(amount),
+            ..transaction.clone()
+        })?;
+        Ok(())
+    }
+
+    /// Debit the fee of a transaction moving the amount from the client's available funds in the currency, rounded to
+    /// precision, returning it unless no fee is charged. This fails if the client can not cover the fee.
+    fn charge_fee(&self, client: &mut Client, transaction: &Transaction, currency: Option<&str>, amount: &BigDecimal) -> Result<Option<BigDecimal>, TransactionError> {
+        let fee = self.fees.as_ref()
+            .and_then(|fees| fees.fee(transaction, amount))
+            .map(|fee| self.precision.apply(&fee))
+            .filter(|fee| !fee.is_zero());
+
+        if let Some(fee) = &fee {
+            client.withdraw(transaction.account(), currency, &to_amount(fee)?, &self.overdraft.allowance()?)?;
+        }
+        Ok(fee)
+    }
+
+    /// Credit a charged fee to the fee account, creating it if it does not yet exist.
+    /// The fee account is credited whether or not it is locked, and whatever the most funds an account may hold.
+    fn credit_fee(&mut self, currency: Option<&str>, fee: Option<&BigDecimal>) -> Result<(), TransactionError> {
+        let (Some(fees), Some(fee)) = (&self.fees, fee) else {
+            return Ok(());
+        };
+
+        let mut account = self.storage.get_client(fees.account())?
+            .unwrap_or_else(|| Client::new(fees.account()));
+        account.deposit(None, currency, &to_amount(fee)?)?;
+        self.storage.update_client(account)?;
+        Ok(())
+    }
+
+    /// Store an updated client alongside the referenced transaction of a dispute, resolve or chargeback.
+    fn update(&mut self, client: Client, target: Transaction) -> Result<(), TransactionError> {
+        self.storage.update_client(client)?;
+        self.storage.insert_transaction(target)?;
+        Ok(())
+    }
+
+    /// Move funds from one client to another.
+    /// Every check is done before either account is touched, so a failed transfer leaves both untouched.
+    fn transfer(&mut self, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        let (amount, kept) = self.amount(transaction)?;
+        let destination = transaction.destination.ok_or(TransactionError::MissingDestination)?;
+
+        if destination == transaction.client_id {
+            return Err(TransactionError::InvalidDestination);
+        }
+
+        let mut destination_client = self.client_for(destination, TransactionType::Deposit)
+            .map_err(destination_error)?;
+        let mut source_client = self.client_for(transaction.client_id, transaction.type_)?;
+        self.check_outflow(transaction, &amount)?;
+
+        source_client.withdraw(transaction.account(), transaction.currency(), &kept, &self.overdraft.allowance()?)?;
+        let fee = self.charge_fee(&mut source_client, transaction, transaction.currency(), &amount)?;
+        destination_client.deposit(None, transaction.currency(), &kept)?;
+        self.check_balance(&destination_client, None, transaction.currency())?;
+
+        self.storage.update_client(destination_client)?;
+        self.store(source_client, transaction, amount.clone())?;
+        self.credit_fee(transaction.currency(), fee.as_ref())?;
+        self.record_outflow(transaction, &amount);
+        Ok(Applied::Transferred { amount, destination, fee })
+    }
+
+    /// Move funds from a client of this engine to a client owned by another engine, as in sharded processing.
+    ///
+    /// The transfer is admitted by both engines, then applied, checked, counted and reported by this one as if it had
+    /// processed the transfer on its own, with the invariants of the destination's account checked by the other engine.
+    pub(crate) fn transfer_to<T: Storage>(&mut self, other: &mut Engine<T>, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        let resolved = self.resolve(transaction);
+        let transaction = resolved.as_ref();
+        let span = tracing::debug_span!("transaction", tx = transaction.id, client = transaction.client_id, r#type = ?transaction.type_);
+        let _entered = span.enter();
+
+        // NOTE: Only the storage of this engine records the outcome, so the transfer is recorded once, as within a single engine.
+        let advanced = self.storage.begin().map_err(TransactionError::from).and_then(|_| match transaction.timestamp {
+            Some(timestamp) => self.advance(timestamp).and_then(|_| other.advance(timestamp)).map(drop),
+            None => Ok(())
+        });
+
+        let before = match self.check_invariants || cfg!(debug_assertions) {
+            true => self.touched(transaction),
+            false => Vec::new()
+        };
+        let destination_before = match other.check_invariants || cfg!(debug_assertions) {
+            true => other.touched(transaction),
+            false => Vec::new()
+        };
+
+        let result = advanced
+            .and_then(|_| self.admit(transaction))
+            .and_then(|_| other.admit(transaction))
+            .and_then(|_| match transaction.scheduled_for {
+                Some(_) => Err(TransactionError::ScheduledAcrossShards),
+                None => self.screen(transaction).and_then(|flag| {
+                    let applied = self.transfer_across(other, transaction)?;
+                    self.post(transaction, &applied)?;
+                    self.hand_over(other, transaction);
+                    self.retain(transaction, &applied)?;
+                    self.latest = self.latest.max(transaction.timestamp);
+                    other.latest = other.latest.max(transaction.timestamp);
+                    self.flag(transaction, flag);
+                    Ok(applied)
+                })
+            });
+
+        let result = match self.storage.commit(transaction, &result) {
+            Ok(()) => result,
+            Err(e) => Err(e.into())
+        };
+        let result = other.checked(transaction, result, destination_before);
+        if result.is_ok() {
+            other.remember(transaction.timestamp, transaction.destination);
+        }
+        self.processed(transaction, result, before)
+    }
+
+    /// Move funds from a client of this engine to a client owned by another engine, charging any fee as a transfer within
+    /// this engine would be. Every check is done before either account is touched, so a failed transfer leaves both untouched.
+    fn transfer_across<T: Storage>(&mut self, other: &mut Engine<T>, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        let (amount, kept) = self.amount(transaction)?;
+        let destination = transaction.destination.ok_or(TransactionError::MissingDestination)?;
+
+        let mut destination_client = other.client_for(destination, TransactionType::Deposit)
+            .map_err(destination_error)?;
+        let mut source_client = self.client_for(transaction.client_id, transaction.type_)?;
+        self.check_outflow(transaction, &amount)?;
+
+        source_client.withdraw(transaction.account(), transaction.currency(), &kept, &self.overdraft.allowance()?)?;
+        let fee = self.charge_fee(&mut source_client, transaction, transaction.currency(), &amount)?;
+        destination_client.deposit(None, transaction.currency(), &kept)?;
+        other.check_balance(&destination_client, None, transaction.currency())?;
+
+        other.storage.update_client(destination_client)?;
+        self.store(source_client, transaction, amount.clone())?;
+        // NOTE: The fee is credited to the fee account of this engine, and those of every shard are added up once merged.
+        self.credit_fee(transaction.currency(), fee.as_ref())?;
+        self.record_outflow(transaction, &amount);
+        Ok(Applied::Transferred { amount, destination, fee })
+    }
+
+    /// Hand what the ledger posted to the destination of a transfer over to the ledger of the engine that owns it, if both keep one.
+    fn hand_over<T: Storage>(&mut self, other: &mut Engine<T>, transaction: &Transaction) {
+        if let (Some(ledger), Some(destination_ledger), Some(destination)) = (&mut self.ledger, &mut other.ledger, transaction.destination) {
+            ledger.hand_over(destination_ledger, LedgerAccount::Available(destination), transaction.currency());
+        }
+    }
+
+    /// Record the state of each client in the history of balances, if one is kept, as of the timestamp of the transaction
+    /// that changed it, or of the latest accepted transaction if it has none.
+    fn remember<I: IntoIterator<Item = u16>>(&mut self, timestamp: Option<DateTime<Utc>>, clients: I) {
+        let at = timestamp.or(self.latest);
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        for id in clients {
+            // NOTE: A storage error here only costs the history the change, the transaction has already been processed.
+            if let Ok(Some(client)) = self.storage.get_client(id) {
+                history.record(at, client);
+            }
+        }
+    }
+}
+
+/// The rejection of a transfer whose destination account does not accept it, which is frozen or otherwise locked.
+fn destination_error(error: TransactionError) -> TransactionError {
+    match error {
+        TransactionError::AccountFrozen => TransactionError::DestinationFrozen,
+        _ => TransactionError::DestinationLocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, str::FromStr};
+
+    use bigdecimal::Zero;
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::{ColumnMapping, CsvDialect, OutputFormat, TransactionStatus, accounts_from_reader, records_from_reader_with, transactions_from_reader};
+
+    fn amount(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn simple_deposit() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("100").unwrap());
+        assert_eq!(client.total(), amount("100").unwrap());
+    }
+
+    #[test]
+    fn simple_withdrawal() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("50"))).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("50").unwrap());
+        assert_eq!(client.total(), amount("50").unwrap());
+    }
+
+    #[test]
+    fn simple_dispute() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), BigDecimal::zero());
+        assert_eq!(client.held(), amount("100").unwrap());
+        assert_eq!(client.total(), amount("100").unwrap());
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::Disputed);
+    }
+
+    #[test]
+    fn simple_dispute_to_resolve() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 1, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("100").unwrap());
+        assert_eq!(client.held(), BigDecimal::zero());
+        assert_eq!(client.total(), amount("100").unwrap());
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::Resolved);
+    }
+
+    #[test]
+    fn simple_dispute_to_chargeback() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+        assert!(engine.client(1).unwrap().unwrap().locked());
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::ChargedBack);
+    }
+
+    #[test]
+    fn charged_back_transactions_are_not_disputed_again() {
+        let mut engine = Engine::new().with_lock_policy(LockPolicy::AllowDisputes);
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)), Err(TransactionError::NotDisputed(1)));
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::AlreadyDisputed(1)));
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::AlreadyChargedBack(1)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Resolve, 1, 1, None)), Err(TransactionError::AlreadyChargedBack(1)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)), Err(TransactionError::AlreadyChargedBack(1)));
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), BigDecimal::zero());
+    }
+
+    #[test]
+    fn terminal_transactions_can_not_double_dip() {
+        let mut engine = Engine::new().with_lock_policy(LockPolicy::AllowAll).with_unlock_on_representment(true);
+
+        // Charging a withdrawal back returns the funds, so a second chargeback would pay them out again.
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("60"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)), Err(TransactionError::AlreadyChargedBack(2)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Chargeback, 1, 2, None)), Err(TransactionError::AlreadyChargedBack(2)));
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("100").unwrap());
+
+        engine.process(&Transaction::new(TransactionType::Representment, 1, 2, None)).unwrap();
+        for type_ in [TransactionType::Dispute, TransactionType::Resolve, TransactionType::Chargeback, TransactionType::Representment] {
+            assert_eq!(engine.process(&Transaction::new(type_, 1, 2, None)), Err(TransactionError::AlreadyReversed(2)));
+        }
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("40").unwrap());
+    }
+
+    #[test]
+    fn representment_reverses_a_chargeback() {
+        let charged_back = |unlock_on_representment| {
+            let mut engine = Engine::new().with_unlock_on_representment(unlock_on_representment);
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("5"))).unwrap();
+            assert_eq!(engine.process(&Transaction::new(TransactionType::Representment, 1, 1, None)), Err(TransactionError::NotChargedBack(1)));
+            engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+            engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+            engine
+        };
+
+        let mut engine = charged_back(false);
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Representment, 1, 1, None)), Ok(Applied::Represented { amount: amount("100").unwrap(), unlocked: false }));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Representment, 1, 1, None)), Err(TransactionError::AlreadyReversed(1)));
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("105").unwrap());
+        assert_eq!(client.total(), amount("105").unwrap());
+        assert!(client.locked());
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::Reversed);
+
+        let mut engine = charged_back(true).with_lock_policy(LockPolicy::AllowDisputes);
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Representment, 1, 1, None)), Ok(Applied::Represented { amount: amount("100").unwrap(), unlocked: true }));
+        assert!(!engine.client(1).unwrap().unwrap().locked());
+    }
+
+    #[test]
+    fn authorizations_are_held_until_captured_or_voided() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Authorize, 1, 2, amount("150"))), Err(TransactionError::InsufficientFunds));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Authorize, 1, 2, amount("60"))), Ok(Applied::Authorized { amount: amount("60").unwrap() }));
+        engine.process(&Transaction::new(TransactionType::Authorize, 1, 3, amount("30"))).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!((client.available(), client.held(), client.total()), (amount("10").unwrap(), amount("90").unwrap(), amount("100").unwrap()));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 4, amount("20"))), Err(TransactionError::InsufficientFunds));
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Capture, 1, 2, None)), Ok(Applied::Captured { amount: amount("60").unwrap() }));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Void, 1, 2, None)), Err(TransactionError::AlreadyCaptured(2)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Void, 1, 3, None)), Ok(Applied::Voided { amount: amount("30").unwrap() }));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Capture, 1, 3, None)), Err(TransactionError::AlreadyVoided(3)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Capture, 1, 1, None)), Err(TransactionError::NotAuthorization(1)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)), Err(TransactionError::NotDisputable(2)));
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!((client.available(), client.held(), client.total()), (amount("40").unwrap(), amount("0").unwrap(), amount("40").unwrap()));
+        assert_eq!(engine.storage().get_transaction(2).unwrap().unwrap().status(), TransactionStatus::Captured);
+    }
+
+    #[test]
+    fn unlock_requires_an_operator() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Unlock, 1, 2, None)), Err(TransactionError::Unauthorized));
+
+        let mut engine = engine.with_admin(true);
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Unlock, 1, 2, None)), Ok(Applied::Unlocked));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Unlock, 1, 3, None)), Err(TransactionError::NotLocked));
+
+        assert!(!engine.client(1).unwrap().unwrap().locked());
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 4, amount("5"))).unwrap();
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("5").unwrap());
+    }
+
+    #[test]
+    fn lock_policy_permits_transactions_on_locked_accounts() {
+        let locked = |lock_policy| {
+            let mut engine = Engine::new().with_lock_policy(lock_policy);
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("5"))).unwrap();
+            engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+            engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+            engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+            engine
+        };
+
+        let mut engine = locked(LockPolicy::RejectAll);
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("1"))), Err(TransactionError::AccountLocked));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)), Err(TransactionError::AccountLocked));
+
+        let mut engine = locked(LockPolicy::AllowDeposits);
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("1"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 4, amount("1"))).unwrap();
+        engine.process(&Transaction::transfer(2, 1, 5, amount("1").unwrap())).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)), Err(TransactionError::AccountLocked));
+
+        let mut engine = locked(LockPolicy::AllowDisputes);
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("1"))), Err(TransactionError::AccountLocked));
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("5").unwrap());
+
+        let mut engine = locked(LockPolicy::AllowAll);
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("5"))).unwrap();
+        assert!(engine.client(1).unwrap().unwrap().locked());
+    }
+
+    #[test]
+    fn freeze_is_separate_from_the_chargeback_lock() {
+        let mut engine = Engine::new().with_admin(true);
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("10"))).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Freeze, 1, 3, None)), Ok(Applied::Frozen));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Freeze, 1, 4, None)), Err(TransactionError::AlreadyFrozen));
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 5, amount("1"))).unwrap();
+        engine.process(&Transaction::transfer(2, 1, 6, amount("1").unwrap())).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 7, amount("1"))), Err(TransactionError::AccountFrozen));
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 8, amount("1").unwrap())), Err(TransactionError::AccountFrozen));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::AccountFrozen));
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert!(client.frozen() && !client.locked());
+        assert_eq!(client.available(), amount("12").unwrap());
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Unfreeze, 1, 9, None)), Ok(Applied::Unfrozen));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Unfreeze, 1, 10, None)), Err(TransactionError::NotFrozen));
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 11, amount("1"))).unwrap();
+
+        let mut engine = engine.with_freeze_policy(FreezePolicy::BlockWithdrawals);
+        engine.process(&Transaction::new(TransactionType::Freeze, 2, 12, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 13, amount("1").unwrap())), Ok(Applied::Transferred { amount: amount("1").unwrap(), destination: 2, fee: None }));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 2, 14, amount("1"))), Err(TransactionError::AccountFrozen));
+
+        let mut engine = engine.with_freeze_policy(FreezePolicy::RejectAll);
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 15, amount("1").unwrap())), Err(TransactionError::DestinationFrozen));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Freeze, 3, 16, None)), Ok(Applied::Frozen));
+        assert!(engine.client(3).unwrap().unwrap().frozen());
+    }
+
+    #[test]
+    fn sub_accounts_keep_escrow_apart_from_spendable_funds() {
+        let csv = "type, client, tx, amount, account, to_account\n\
+            deposit, 1, 1, 10, ,\n\
+            deposit, 1, 2, 5, escrow,\n\
+            move, 1, 3, 4, main, escrow\n\
+            withdrawal, 1, 4, 7, ,\n\
+            move, 1, 5, 1, escrow, escrow\n";
+        let records = records_from_reader_with(csv.as_bytes(), &CsvDialect::default(), &ColumnMapping::default()).unwrap();
+        let mut engine = Engine::new().with_ledger(Some(Ledger::default())).with_check_invariants(true);
+        let results = records.into_iter().map(|record| engine.process(&record.unwrap().transaction)).collect::<Vec<_>>();
+        assert_eq!(results[2], Ok(Applied::Moved { amount: amount("4").unwrap() }));
+        assert_eq!(results[3], Err(TransactionError::InsufficientFunds));
+        assert_eq!(results[4], Err(TransactionError::InvalidMove));
+
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("6").unwrap());
+        let escrow = client.balance_in(Some("escrow"), None).unwrap();
+        assert_eq!((escrow.available(), escrow.held()), (amount("4").unwrap(), amount("5").unwrap()));
+        assert_eq!(engine.process(&Transaction::move_between(1, 6, amount("5").unwrap(), Some("escrow"), None)), Err(TransactionError::InsufficientFunds));
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write(&mut output, &engine.clients().unwrap(), Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output.clone()).unwrap(), "id,account,available,held,total,locked\n1,main,6.0000,0.0000,6.0000,false\n1,escrow,4.0000,5.0000,9.0000,false\n");
+        assert_eq!(accounts_from_reader(output.as_slice()).unwrap(), vec![client]);
+    }
+
+    #[test]
+    fn balance_assertions_check_the_account_in_stream() {
+        let csv = "type, client, tx, amount, total\n\
+            deposit, 1, 1, 10,\n\
+            dispute, 1, 1,,\n\
+            assert_balance, 1, 2, 0, 10\n\
+            assert_balance, 1, 3, 10, 9.5\n\
+            assert_balance, 2, 4, 0,\n\
+            assert_balance, 1, 5,,\n";
+        let records = records_from_reader_with(csv.as_bytes(), &CsvDialect::default(), &ColumnMapping::default()).unwrap();
+        let mut engine = Engine::new().with_ledger(Some(Ledger::default())).with_check_invariants(true);
+        let results = records.into_iter().map(|record| engine.process(&record.unwrap().transaction)).collect::<Vec<_>>();
+        assert_eq!(results[2], Ok(Applied::Asserted));
+        assert_eq!(results[3], Err(TransactionError::BalanceMismatch(vec![
+            ("available", amount("10").unwrap(), amount("0").unwrap()),
+            ("total", amount("9.5").unwrap(), amount("10").unwrap()),
+        ])));
+        assert_eq!(results[3].as_ref().unwrap_err().to_string(), "balance does not match: available funds are 0.0000, not 10, total funds are 10.0000, not 9.5");
+        assert_eq!(results[4], Ok(Applied::Asserted));
+        assert_eq!(results[5], Err(TransactionError::MissingAmount));
+
+        // NOTE: An assertion leaves no account behind, and its id can not be reused.
+        assert!(engine.client(2).unwrap().is_none());
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("1"))), Err(TransactionError::DuplicateTransaction(2)));
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+        assert_eq!(engine.process(&Transaction::assert_balance(1, 6, amount("0"), amount("0"))), Ok(Applied::Asserted));
+    }
+
+    #[test]
+    fn check_invariants_after_every_transaction() {
+        let client = serde_json::from_str(r#"{"id":1,"balances":[{"available":"1","held":"0","total":"5"}],"locked":false}"#).unwrap();
+        let mut engine = Engine::new().with_check_invariants(true);
+        engine.restore(Snapshot { clients: vec![client], ..Snapshot::default() }).unwrap();
+
+        let violation = engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("1"))).unwrap_err();
+        assert_eq!(violation.code(), "invariant_violated");
+        assert!(violation.to_string().starts_with("invariant violated for client 1: total is not the sum of available and held funds"));
+    }
+
+    #[test]
+    fn check_invariants_of_transfers_between_engines() {
+        let client = serde_json::from_str(r#"{"id":2,"balances":[{"available":"1","held":"0","total":"5"}],"locked":false}"#).unwrap();
+        let mut engine = Engine::new().with_check_invariants(true);
+        let mut other = Engine::new().with_check_invariants(true);
+        other.restore(Snapshot { clients: vec![client], ..Snapshot::default() }).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+
+        let violation = engine.transfer_to(&mut other, &Transaction::transfer(1, 2, 2, amount("1").unwrap())).unwrap_err();
+        assert!(violation.is_violation());
+        assert!(violation.to_string().starts_with("invariant violated for client 2: total is not the sum of available and held funds"));
+    }
+
+    #[test]
+    fn enforce_chronological_order() {
+        let at = |seconds| DateTime::from_timestamp(seconds, 0).unwrap();
+        let mut engine = Engine::new().with_enforce_order(true);
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10")).with_timestamp(at(20))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("10"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("10")).with_timestamp(at(10))), Err(TransactionError::OutOfOrder));
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 4, amount("5")).with_timestamp(at(20))).unwrap();
+        assert_eq!(engine.latest(), Some(at(20)));
+
+        let mut restored = Engine::new().with_enforce_order(true);
+        restored.restore(engine.snapshot().unwrap()).unwrap();
+        assert_eq!(restored.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("10")).with_timestamp(at(10))), Err(TransactionError::OutOfOrder));
+    }
+
+    #[test]
+    fn disputes_expire_after_the_window() {
+        let at = |days: i64| DateTime::from_timestamp(days * 86_400, 0).unwrap();
+        let mut engine = Engine::new().with_dispute_window(Some(TimeDelta::days(120)));
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10")).with_timestamp(at(0))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("10")).with_timestamp(at(10))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 3, amount("10"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 4, amount("10")).with_timestamp(at(125))).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::DisputeWindowExpired(1)));
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 3, None).with_timestamp(at(500))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 4, None).with_timestamp(at(246))), Err(TransactionError::DisputeWindowExpired(4)));
+    }
+
+    #[test]
+    fn withdrawal_dispute_to_resolve() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("30"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("70").unwrap());
+        assert_eq!(client.held(), amount("30").unwrap());
+        assert_eq!(client.total(), amount("100").unwrap());
+
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 2, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("70").unwrap());
+        assert_eq!(client.held(), BigDecimal::zero());
+        assert_eq!(client.total(), amount("70").unwrap());
+    }
+
+    #[test]
+    fn withdrawal_dispute_to_chargeback() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("30"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("100").unwrap());
+        assert_eq!(client.held(), BigDecimal::zero());
+        assert_eq!(client.total(), amount("100").unwrap());
+        assert!(client.locked());
+    }
+
+    #[test]
+    fn withdrawal_chargeback_never_goes_negative() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("30"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("70"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+
+        // The emptied account is credited with the reversed withdrawal, rather than having held funds taken from it.
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("30").unwrap());
+        assert_eq!(client.held(), BigDecimal::zero());
+        assert_eq!(client.total(), amount("30").unwrap());
+    }
+
+    #[test]
+    fn balances_are_kept_per_currency() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100")).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("10"))).unwrap();
+
+        let result = engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("50")).with_currency("USD"));
+        assert_eq!(result, Err(TransactionError::InsufficientFunds));
+        let result = engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 4, amount("50")));
+        assert_eq!(result, Err(TransactionError::InsufficientFunds));
+
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 5, amount("50")).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        let euros = client.balance(Some("EUR")).unwrap();
+        assert_eq!(euros.available(), amount("-50").unwrap());
+        assert_eq!(euros.held(), amount("100").unwrap());
+        assert_eq!(client.available(), amount("10").unwrap());
+        assert!(client.balance(Some("USD")).is_none());
+    }
+
+    #[test]
+    fn convert_between_currencies() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+
+        let result = engine.process(&Transaction::convert(1, 2, amount("150").unwrap(), None, "EUR", amount("0.9").unwrap()));
+        assert_eq!(result, Err(TransactionError::InsufficientFunds));
+
+        let result = engine.process(&Transaction::convert(1, 3, amount("40").unwrap(), None, "EUR", amount("0.9").unwrap()));
+        assert_eq!(result, Ok(Applied::Converted { amount: amount("40").unwrap(), converted: amount("36").unwrap(), fee: None }));
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), amount("60").unwrap());
+        assert_eq!(client.balance(Some("EUR")).unwrap().available(), amount("36").unwrap());
+
+        // A conversion can not be disputed, only the deposits and withdrawals on either side of it.
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 3, None)), Err(TransactionError::NotDisputable(3)));
+    }
+
+    #[test]
+    fn amounts_are_rounded_to_the_precision() {
+        let mut engine = Engine::new().with_precision(Precision::new(4, crate::Rounding::Down).with_excess(crate::ExcessPrecision::Round));
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("123456789.99999"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("0.00005"))).unwrap();
+
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("123456789.9999").unwrap());
+        assert_eq!(engine.client(2).unwrap().unwrap().available(), BigDecimal::zero());
+
+        let csv = "type, client, tx, amount\ndeposit, 3, 3, 2.00005\ndeposit, 3, 4, 2.00050";
+        let mut engine = Engine::new();
+        let rejected = engine.process_all(transactions_from_reader(csv.as_bytes()).unwrap());
+        assert_eq!(rejected[0].1, TransactionError::ExcessPrecision(4));
+        assert_eq!(engine.client(3).unwrap().unwrap().available(), amount("2.0005").unwrap());
+    }
+
+    #[test]
+    fn withdrawal_insufficient_funds() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+
+        let result = engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("10.5")));
+
+        assert_eq!(result, Err(TransactionError::InsufficientFunds));
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), amount("10").unwrap());
+    }
+
+    #[test]
+    fn rejection_reasons() {
+        let mut engine = Engine::new();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, None)), Err(TransactionError::MissingAmount));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::UnknownTransaction(1)));
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Resolve, 1, 1, None)), Err(TransactionError::NotDisputed(1)));
+
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Err(TransactionError::AlreadyDisputed(1)));
+
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("1"))), Err(TransactionError::AccountLocked));
+    }
+
+    #[test]
+    fn amounts_and_balances_are_capped() {
+        let mut engine = Engine::new().with_limits(Limits { max_amount: amount("100"), max_balance: amount("150") });
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100.0001"))), Err(TransactionError::AmountAboveLimit(BigDecimal::from(100))));
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 3, amount("100"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 4, amount("50.0001"))), Err(TransactionError::BalanceAboveLimit(BigDecimal::from(150))));
+        assert_eq!(engine.process(&Transaction::transfer(2, 1, 5, amount("60").unwrap())), Err(TransactionError::BalanceAboveLimit(BigDecimal::from(150))));
+        assert_eq!(engine.client(2).unwrap().unwrap().available(), BigDecimal::from(100));
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 6, amount("1e300"))), Err(TransactionError::AmountOutOfRange));
+    }
+
+    // NOTE: Fixed-point balances have a narrower range, which already rejects the first deposit.
+    #[cfg(not(feature = "fixed-point"))]
+    #[test]
+    fn balances_stay_in_range() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("9999999999999999999999999999"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("1"))), Err(TransactionError::AmountOutOfRange));
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), amount("9999999999999999999999999999").unwrap());
+    }
+
+    #[test]
+    fn simple_transfer() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::transfer(1, 2, 2, amount("40").unwrap())).unwrap();
+
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), amount("60").unwrap());
+        assert_eq!(engine.client(2).unwrap().unwrap().available(), amount("40").unwrap());
+        assert_eq!(engine.client(2).unwrap().unwrap().total(), amount("40").unwrap());
+    }
+
+    #[test]
+    fn failed_transfer_is_atomic() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("10"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 2, 2, None)).unwrap();
+
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 3, amount("40").unwrap())), Err(TransactionError::DestinationLocked));
+        assert_eq!(engine.process(&Transaction::transfer(1, 3, 4, amount("140").unwrap())), Err(TransactionError::InsufficientFunds));
+        assert_eq!(engine.process(&Transaction::transfer(1, 1, 5, amount("40").unwrap())), Err(TransactionError::InvalidDestination));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Transfer, 1, 6, amount("40"))), Err(TransactionError::MissingDestination));
+
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), amount("100").unwrap());
+        assert_eq!(engine.client(3).unwrap().unwrap().total(), BigDecimal::zero());
+    }
+
+    #[test]
+    fn disputes_reference_own_deposits_and_withdrawals() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::transfer(1, 2, 2, amount("40").unwrap())).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)), Err(TransactionError::NotDisputable(2)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 2, 1, None)), Err(TransactionError::ForeignTransaction(1, 1)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Chargeback, 2, 1, None)), Err(TransactionError::ForeignTransaction(1, 1)));
+    }
+
+    #[test]
+    fn negative_amounts_do_not_change_balances() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, amount("-50.0"))), Err(TransactionError::NonPositiveAmount));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("-50.0"))), Err(TransactionError::NonPositiveAmount));
+
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), amount("100").unwrap());
+    }
+
+    #[test]
+    fn duplicate_transaction_ids() {
+        let mut engine = Engine::new();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("50"))), Err(TransactionError::DuplicateTransaction(1)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, amount("50"))), Err(TransactionError::DuplicateTransaction(1)));
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 1, amount("50").unwrap())), Err(TransactionError::DuplicateTransaction(1)));
+
+        // A rejected transaction does not claim its id.
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("150"))), Err(TransactionError::InsufficientFunds));
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("10"))).unwrap();
+
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), amount("90").unwrap());
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().amount(), amount("100").as_ref());
+    }
+
+    #[test]
+    fn retention_drops_unreferenceable_transactions() {
+        let mut engine = Engine::new().with_retention(Retention::Disputable);
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("10"))).unwrap();
+        engine.process(&Transaction::transfer(1, 3, 3, amount("40").unwrap())).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 2, 2, None)).unwrap();
+
+        // A charged back transaction is kept, as it can still be represented.
+        assert!(engine.storage().get_transaction(1).unwrap().is_some());
+        assert!(engine.storage().get_transaction(2).unwrap().is_some());
+        assert!(engine.storage().get_transaction(3).unwrap().is_none());
+
+        // Dropped transactions keep their ids, and can no longer be referenced.
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 2, 3, amount("5"))), Err(TransactionError::DuplicateTransaction(3)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 3, None)), Err(TransactionError::UnknownTransaction(3)));
+
+        let mut restored = Engine::new().with_retention(Retention::Disputable);
+        restored.restore(engine.snapshot().unwrap()).unwrap();
+        assert_eq!(restored.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("5"))), Err(TransactionError::DuplicateTransaction(2)));
+    }
+
+    #[test]
+    fn csv_example() {
+        let csv = "type,       client,     tx,     amount
+                        deposit,    1,          1,      1.0001
+                        deposit,    2,          2,      2.0001
+                        deposit,    1,          3,      2.0001
+                        withdrawal, 1,          4,      1.5002
+                        withdrawal, 2,          5,      3.0001
+                        dispute,    2,          5,
+                        resolve,    2,          5,
+                        dispute,    1,          3,
+                        chargeback, 1,          3,";
+
+        let transactions = transactions_from_reader(io::BufReader::new(csv.as_bytes())).unwrap();
+        
+        let mut engine = Engine::new();
+        let rejected = engine.process_all(transactions);
+
+        // The second client's withdrawal is rejected, so their dispute and resolve reference an unknown transaction.
+        assert_eq!(rejected.len(), 3);
+        assert_eq!(rejected[0].1, TransactionError::InsufficientFunds);
+
+        assert_eq!(engine.clients().unwrap().len(), 2);
+        assert!(engine.client(1).unwrap().unwrap().locked());
+
+        assert_eq!(engine.client(2).unwrap().unwrap().available(), BigDecimal::from_str("2.0001").unwrap());
+        assert!(!engine.client(2).unwrap().unwrap().locked());
+    }
+
+    #[test]
+    fn csv_transfer() {
+        let csv = "type, client, tx, amount, to
+                   deposit, 1, 1, 5.0,
+                   transfer, 1, 2, 2.5, 2";
+
+        let transactions = transactions_from_reader(io::BufReader::new(csv.as_bytes())).unwrap();
+
+        let mut engine = Engine::new();
+        assert!(engine.process_all(transactions).is_empty());
+
+        assert_eq!(engine.client(2).unwrap().unwrap().available(), amount("2.5").unwrap());
+    }
+
+    /// A transaction between a handful of clients, with ids drawn from a small range so disputes often find their target.
+    fn arbitrary_transaction() -> impl Strategy<Value = Transaction> {
+        let types = prop_oneof![
+            Just(TransactionType::Deposit),
+            Just(TransactionType::Withdrawal),
+            Just(TransactionType::Transfer),
+            Just(TransactionType::Dispute),
+            Just(TransactionType::Resolve),
+            Just(TransactionType::Chargeback),
+        ];
+        let amount = (0i64..1_000_000, 0i64..=4).prop_map(|(value, scale)| BigDecimal::new(value.into(), scale));
+
+        (types, 1u16..4, 1u32..24, amount, 1u16..4).prop_map(|(type_, client_id, id, amount, destination)| match type_ {
+            TransactionType::Transfer => Transaction::transfer(client_id, destination, id, amount),
+            TransactionType::Deposit | TransactionType::Withdrawal => Transaction::new(type_, client_id, id, Some(amount)),
+            _ => Transaction::new(type_, client_id, id, None)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn invariants_hold_for_any_sequence(transactions in prop::collection::vec(arbitrary_transaction(), 0..64)) {
+            let mut engine = Engine::new().with_ledger(Some(Ledger::new()));
+            let mut disputed = false;
+
+            for transaction in &transactions {
+                let locked = engine.clients().unwrap().into_iter().filter(Client::locked).collect::<Vec<_>>();
+                let result = engine.process(transaction);
+                disputed |= result.is_ok() && transaction.type_() == TransactionType::Dispute;
+
+                for client in locked {
+                    prop_assert_eq!(engine.client(client.id()).unwrap(), Some(client));
+                }
+
+                for client in engine.clients().unwrap() {
+                    prop_assert_eq!(engine.ledger().unwrap().check(&client), Ok(()));
+                    for balance in client.balances() {
+                        prop_assert_eq!(balance.total(), balance.available() + balance.held());
+                        prop_assert!(balance.held() >= BigDecimal::zero());
+                        if !disputed {
+                            prop_assert!(balance.available() >= BigDecimal::zero());
+                        }
+                    }
+                }
+            }
+        }
+
+        // NOTE: This runs against whichever backend balances are built with, so checking both against plain decimal
+        // arithmetic checks the fixed-point engine accepts and holds exactly what the decimal one does.
+        #[test]
+        fn balances_match_decimal_arithmetic(transactions in prop::collection::vec(arbitrary_transaction(), 0..64)) {
+            let mut engine = Engine::new();
+            let mut funds = HashMap::<u16, (BigDecimal, BigDecimal)>::new();
+            let mut withdrawals = HashMap::<u32, bool>::new();
+
+            for transaction in &transactions {
+                let client = transaction.client_id;
+                let withdrawal = withdrawals.get(&transaction.id).copied().unwrap_or(false);
+                match engine.process(transaction) {
+                    Ok(Applied::Deposited { amount, .. }) => {
+                        funds.entry(client).or_default().0 += &amount;
+                        withdrawals.insert(transaction.id, false);
+                    },
+                    Ok(Applied::Withdrew { amount, .. }) => {
+                        funds.entry(client).or_default().0 -= &amount;
+                        withdrawals.insert(transaction.id, true);
+                    },
+                    Ok(Applied::Transferred { amount, destination, .. }) => {
+                        funds.entry(client).or_default().0 -= &amount;
+                        funds.entry(destination).or_default().0 += &amount;
+                    },
+                    Ok(Applied::Disputed { amount }) => {
+                        let (available, held) = funds.entry(client).or_default();
+                        if !withdrawal {
+                            *available -= &amount;
+                        }
+                        *held += &amount;
+                    },
+                    Ok(Applied::Resolved { amount }) => {
+                        let (available, held) = funds.entry(client).or_default();
+                        if !withdrawal {
+                            *available += &amount;
+                        }
+                        *held -= &amount;
+                    },
+                    Ok(Applied::ChargedBack { amount }) => {
+                        let (available, held) = funds.entry(client).or_default();
+                        if withdrawal {
+                            *available += &amount;
+                        }
+                        *held -= &amount;
+                    },
+                    Ok(applied) => prop_assert!(false, "unexpected {:?}", applied),
+                    Err(TransactionError::InsufficientFunds) => {
+                        let available = funds.get(&client).map_or_else(BigDecimal::zero, |(available, _)| available.clone());
+                        prop_assert!(transaction.amount.as_ref().unwrap() > &available);
+                    },
+                    Err(_) => {}
+                }
+
+                for client in engine.clients().unwrap() {
+                    let (available, held) = funds.get(&client.id()).cloned().unwrap_or_default();
+                    prop_assert_eq!(client.available(), available.clone());
+                    prop_assert_eq!(client.held(), held.clone());
+                    prop_assert_eq!(client.total(), available + held);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use std::{error, fmt};
+
+use bigdecimal::BigDecimal;
+
+use crate::{MAX_INTEGER_DIGITS, StorageError, TransactionType};
+
+/// An enumeration of the reasons a transaction can be rejected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionError {
+    /// The client's account is locked, so no further transactions are processed.
+    AccountLocked,
+
+    /// The withdrawal amount is greater than the available balance.
+    InsufficientFunds,
+
+    /// A deposit or withdrawal was submitted without an amount.
+    MissingAmount,
+
+    /// A deposit, withdrawal or transfer was submitted with a negative or zero amount.
+    NonPositiveAmount,
+
+    /// The amount has more decimal places than the configured scale.
+    ExcessPrecision(i64),
+
+    /// The amount is too large to be a real balance.
+    AmountOutOfRange,
+
+    /// The amount is larger than the most a single transaction may move, which is given.
+    AmountAboveLimit(BigDecimal),
+
+    /// The transaction would leave the account holding more than the most funds it may hold, which is given.
+    BalanceAboveLimit(BigDecimal),
+
+    /// The withdrawal is larger than the most a single withdrawal of the client may take, which is given.
+    WithdrawalAboveLimit(BigDecimal),
+
+    /// The client has already made the most withdrawals it may make in a day, which is given.
+    TooManyWithdrawals(u32),
+
+    /// The transaction would take more funds out of the account in a day than the client may, which is given.
+    DailyOutflowAboveLimit(BigDecimal),
+
+    /// A conversion was submitted without a target currency or rate.
+    MissingConversion,
+
+    /// A conversion was submitted with a negative or zero rate.
+    NonPositiveRate,
+
+    /// A conversion was submitted with the same source and target currency.
+    InvalidConversion,
+
+    /// A move was submitted with the same source and target sub-account.
+    InvalidMove,
+
+    /// A transfer was submitted without a destination client.
+    MissingDestination,
+
+    /// A transfer was submitted with the source client as its destination.
+    InvalidDestination,
+
+    /// The destination client's account of a transfer is locked.
+    DestinationLocked,
+
+    /// The client's account is frozen, and the freeze policy does not permit the transaction.
+    AccountFrozen,
+
+    /// The destination client's account of a transfer is frozen, and the freeze policy does not permit deposits.
+    DestinationFrozen,
+
+    /// The transaction id was already used by a previously processed transaction, for any client.
+    DuplicateTransaction(u32),
+
+    /// The referenced transaction does not exist for this client.
+    UnknownTransaction(u32),
+
+    /// The referenced transaction belongs to another client, given alongside it, which points to corrupt input.
+    ForeignTransaction(u32, u16),
+
+    /// The referenced transaction is not a deposit or withdrawal, so it cannot be disputed.
+    NotDisputable(u32),
+
+    /// The referenced transaction is already in dispute.
+    AlreadyDisputed(u32),
+
+    /// The referenced transaction happened longer ago than disputes are accepted for.
+    DisputeWindowExpired(u32),
+
+    /// The referenced transaction is not in dispute, so it cannot be resolved or charged back.
+    NotDisputed(u32),
+
+    /// The referenced transaction has not been charged back, so it cannot be represented.
+    NotChargedBack(u32),
+
+    /// The referenced transaction was already charged back, so it can only be represented.
+    AlreadyChargedBack(u32),
+
+    /// The chargeback of the referenced transaction was already reversed, so it is final.
+    AlreadyReversed(u32),
+
+    /// The referenced transaction is not an authorization, so it cannot be captured or voided.
+    NotAuthorization(u32),
+
+    /// The referenced authorization was already captured, so it is final.
+    AlreadyCaptured(u32),
+
+    /// The referenced authorization was already voided, so it is final.
+    AlreadyVoided(u32),
+
+    /// An operator-only transaction was submitted to an engine that does not accept them.
+    Unauthorized,
+
+    /// The transaction involves a client, which is given, that is not in the roster of known clients.
+    UnknownClient(u16),
+
+    /// The transaction belongs to the books of another tenant, which is given, or to the default books if `None`.
+    ForeignTenant(Option<String>),
+
+    /// An unlock was submitted for an account that is not locked.
+    NotLocked,
+
+    /// A balance assertion did not match the account, with every mismatched kind of funds (`available` or `total`)
+    /// given alongside the amount asserted and the actual amount.
+    BalanceMismatch(Vec<(&'static str, BigDecimal, BigDecimal)>),
+
+    /// A freeze was submitted for an account that is already frozen.
+    AlreadyFrozen,
+
+    /// An unfreeze was submitted for an account that is not frozen.
+    NotFrozen,
+
+    /// The transaction happened before the latest transaction already processed, while enforcing chronological order.
+    OutOfOrder,
+
+    /// A validation script rejected the transaction, for the reason given.
+    RejectedByScript(String),
+
+    /// The transaction type can not be scheduled, as only deposits, withdrawals, transfers and conversions can.
+    NotSchedulable(TransactionType),
+
+    /// The transaction recurs, but has no time it is first scheduled for.
+    MissingSchedule,
+
+    /// A scheduled transfer between clients of different shards, which would be applied by the source shard alone.
+    ScheduledAcrossShards,
+
+    /// The storage backend failed to read or write the affected client or transaction.
+    Storage(StorageError),
+
+    /// The transaction was applied, but broke one of the engine's invariants, as described.
+    InvariantViolated(String),
+}
+
+impl TransactionError {
+    /// Whether the transaction broke one of the engine's invariants, so the engine can not be trusted any further.
+    pub fn is_violation(&self) -> bool {
+        matches!(self, Self::InvariantViolated(_))
+    }
+
+    /// A short, stable name for the kind of rejection, without any transaction ids.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AccountLocked => "account_locked",
+            Self::InsufficientFunds => "insufficient_funds",
+            Self::MissingAmount => "missing_amount",
+            Self::NonPositiveAmount => "non_positive_amount",
+            Self::ExcessPrecision(_) => "excess_precision",
+            Self::AmountOutOfRange => "amount_out_of_range",
+            Self::AmountAboveLimit(_) => "amount_above_limit",
+            Self::BalanceAboveLimit(_) => "balance_above_limit",
+            Self::WithdrawalAboveLimit(_) => "withdrawal_above_limit",
+            Self::TooManyWithdrawals(_) => "too_many_withdrawals",
+            Self::DailyOutflowAboveLimit(_) => "daily_outflow_above_limit",
+            Self::MissingConversion => "missing_conversion",
+            Self::NonPositiveRate => "non_positive_rate",
+            Self::InvalidConversion => "invalid_conversion",
+            Self::InvalidMove => "invalid_move",
+            Self::MissingDestination => "missing_destination",
+            Self::InvalidDestination => "invalid_destination",
+            Self::DestinationLocked => "destination_locked",
+            Self::AccountFrozen => "account_frozen",
+            Self::DestinationFrozen => "destination_frozen",
+            Self::DuplicateTransaction(_) => "duplicate_transaction",
+            Self::UnknownTransaction(_) => "unknown_transaction",
+            Self::ForeignTransaction(..) => "foreign_transaction",
+            Self::NotDisputable(_) => "not_disputable",
+            Self::AlreadyDisputed(_) => "already_disputed",
+            Self::DisputeWindowExpired(_) => "dispute_window_expired",
+            Self::NotDisputed(_) => "not_disputed",
+            Self::NotChargedBack(_) => "not_charged_back",
+            Self::AlreadyChargedBack(_) => "already_charged_back",
+            Self::AlreadyReversed(_) => "already_reversed",
+            Self::NotAuthorization(_) => "not_authorization",
+            Self::AlreadyCaptured(_) => "already_captured",
+            Self::AlreadyVoided(_) => "already_voided",
+            Self::Unauthorized => "unauthorized",
+            Self::UnknownClient(_) => "unknown_client",
+            Self::ForeignTenant(_) => "foreign_tenant",
+            Self::NotLocked => "not_locked",
+            Self::BalanceMismatch(_) => "balance_mismatch",
+            Self::AlreadyFrozen => "already_frozen",
+            Self::NotFrozen => "not_frozen",
+            Self::OutOfOrder => "out_of_order",
+            Self::RejectedByScript(_) => "rejected_by_script",
+            Self::NotSchedulable(_) => "not_schedulable",
+            Self::MissingSchedule => "missing_schedule",
+            Self::ScheduledAcrossShards => "scheduled_across_shards",
+            Self::Storage(_) => "storage",
+            Self::InvariantViolated(_) => "invariant_violated",
+        }
+    }
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AccountLocked => write!(f, "account is locked"),
+            Self::InsufficientFunds => write!(f, "insufficient funds"),
+            Self::MissingAmount => write!(f, "missing amount"),
+            Self::NonPositiveAmount => write!(f, "amount must be greater than zero"),
+            Self::ExcessPrecision(scale) => write!(f, "amount has more than {} decimal places", scale),
+            Self::AmountOutOfRange => write!(f, "amount has more than {} integer digits", MAX_INTEGER_DIGITS),
+            Self::AmountAboveLimit(limit) => write!(f, "amount is above the limit of {}", limit.to_plain_string()),
+            Self::BalanceAboveLimit(limit) => write!(f, "balance would be above the limit of {}", limit.to_plain_string()),
+            Self::WithdrawalAboveLimit(limit) => write!(f, "withdrawal is above the limit of {}", limit.to_plain_string()),
+            Self::TooManyWithdrawals(limit) => write!(f, "client has already made the limit of {} withdrawals today", limit),
+            Self::DailyOutflowAboveLimit(limit) => write!(f, "outflow today would be above the limit of {}", limit.to_plain_string()),
+            Self::MissingConversion => write!(f, "missing target currency or rate"),
+            Self::NonPositiveRate => write!(f, "rate must be greater than zero"),
+            Self::InvalidConversion => write!(f, "target currency is the source currency"),
+            Self::InvalidMove => write!(f, "target sub-account is the source sub-account"),
+            Self::MissingDestination => write!(f, "missing destination client"),
+            Self::InvalidDestination => write!(f, "destination client is the source client"),
+            Self::DestinationLocked => write!(f, "destination account is locked"),
+            Self::AccountFrozen => write!(f, "account is frozen"),
+            Self::DestinationFrozen => write!(f, "destination account is frozen"),
+            Self::DuplicateTransaction(id) => write!(f, "transaction {} has already been processed", id),
+            Self::UnknownTransaction(id) => write!(f, "transaction {} does not exist", id),
+            Self::ForeignTransaction(id, owner) => write!(f, "transaction {} belongs to client {}", id, owner),
+            Self::NotDisputable(id) => write!(f, "transaction {} cannot be disputed", id),
+            Self::AlreadyDisputed(id) => write!(f, "transaction {} is already disputed", id),
+            Self::DisputeWindowExpired(id) => write!(f, "transaction {} is too old to be disputed", id),
+            Self::NotDisputed(id) => write!(f, "transaction {} is not disputed", id),
+            Self::NotChargedBack(id) => write!(f, "transaction {} has not been charged back", id),
+            Self::AlreadyChargedBack(id) => write!(f, "transaction {} has already been charged back", id),
+            Self::AlreadyReversed(id) => write!(f, "the chargeback of transaction {} has already been reversed", id),
+            Self::NotAuthorization(id) => write!(f, "transaction {} is not an authorization", id),
+            Self::AlreadyCaptured(id) => write!(f, "authorization {} has already been captured", id),
+            Self::AlreadyVoided(id) => write!(f, "authorization {} has already been voided", id),
+            Self::Unauthorized => write!(f, "transaction requires an operator"),
+            Self::UnknownClient(client) => write!(f, "client {} is not known", client),
+            Self::ForeignTenant(Some(tenant)) => write!(f, "transaction belongs to tenant '{}'", tenant),
+            Self::ForeignTenant(None) => write!(f, "transaction belongs to the default books"),
+            Self::NotLocked => write!(f, "account is not locked"),
+            Self::BalanceMismatch(mismatches) => {
+                write!(f, "balance does not match")?;
+                for (index, (funds, expected, actual)) in mismatches.iter().enumerate() {
+                    let separator = match index {
+                        0 => ":",
+                        _ => ","
+                    };
+                    write!(f, "{} {} funds are {}, not {}", separator, funds, actual.to_plain_string(), expected.to_plain_string())?;
+                }
+                Ok(())
+            },
+            Self::AlreadyFrozen => write!(f, "account is already frozen"),
+            Self::NotFrozen => write!(f, "account is not frozen"),
+            Self::OutOfOrder => write!(f, "timestamp is earlier than the latest processed transaction"),
+            Self::RejectedByScript(reason) => write!(f, "rejected by script: {}", reason),
+            Self::NotSchedulable(type_) => write!(f, "a {} can not be scheduled", type_),
+            Self::MissingSchedule => write!(f, "a recurring transaction must be scheduled"),
+            Self::ScheduledAcrossShards => write!(f, "a scheduled transfer can not be made between clients of different shards"),
+            Self::Storage(e) => write!(f, "{}", e),
+            Self::InvariantViolated(violation) => write!(f, "{}", violation),
+        }
+    }
+}
+
+impl error::Error for TransactionError {}
+
+impl From<StorageError> for TransactionError {
+    fn from(e: StorageError) -> Self {
+        Self::Storage(e)
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::BTreeSet, fmt, io, str::FromStr};
+
+use chrono::NaiveDate;
+
+use crate::{LedgerAccount, LedgerEntry, Precision};
+
+/// An enumeration of the formats the ledger can be exported as.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Beancount, with an `open` directive for every account before its first entry.
+    #[default]
+    Beancount,
+
+    /// ledger-cli.
+    Ledger,
+
+    /// A SWIFT MT940 customer statement message for every client and currency.
+    #[cfg(feature = "statements")]
+    Mt940,
+
+    /// An ISO 20022 camt.053 bank to customer statement message, with a statement for every client and currency.
+    #[cfg(feature = "statements")]
+    Camt053,
+}
+
+impl ExportFormat {
+    /// Write every entry of a ledger as a plain-text accounting transaction of one posting pair, debiting one account and
+    /// crediting the other, or as the statements of every client. Amounts in the default currency are written in the
+    /// given commodity, and an entry without a timestamp is dated on the given day.
+    pub fn write<W: io::Write>(self, mut writer: W, entries: &[LedgerEntry], precision: Precision, commodity: &str, undated: NaiveDate) -> io::Result<()> {
+        #[cfg(feature = "statements")]
+        match self {
+            Self::Mt940 => {
+                for statement in crate::Statement::from_entries(entries) {
+                    statement.write_mt940(&mut writer, precision, commodity, undated)?;
+                }
+                return writer.flush();
+            },
+            Self::Camt053 => return crate::write_camt053(writer, &crate::Statement::from_entries(entries), precision, commodity, undated),
+            _ => {}
+        }
+
+        let date = |entry: &LedgerEntry| entry.timestamp.map_or(undated, |timestamp| timestamp.date_naive());
+
+        if self == Self::Beancount {
+            let accounts = entries.iter().flat_map(|entry| [entry.debit, entry.credit]).collect::<BTreeSet<_>>();
+            let opened = entries.iter().map(date).min().unwrap_or(undated);
+            for account in accounts {
+                writeln!(writer, "{} open {}", opened.format("%Y-%m-%d"), Self::account(account))?;
+            }
+            writeln!(writer)?;
+        }
+
+        for entry in entries {
+            let commodity = entry.currency.as_deref().unwrap_or(commodity);
+            let amount = precision.format(&entry.amount);
+            let narration = format!("{} {}", entry.type_, entry.id);
+            match self {
+                Self::Beancount => writeln!(writer, "{} * \"{}\"", date(entry).format("%Y-%m-%d"), narration)?,
+                _ => writeln!(writer, "{} {}", date(entry).format("%Y/%m/%d"), narration)?,
+            }
+            for (key, value) in &entry.metadata {
+                match self {
+                    Self::Beancount => writeln!(writer, "  {}: \"{}\"", Self::metadata_key(key), value.replace('\\', "\\\\").replace('"', "\\\""))?,
+                    _ => writeln!(writer, "  ; {}: {}", key, value)?,
+                }
+            }
+            writeln!(writer, "  {}  {} {}", Self::account(entry.debit), amount, commodity)?;
+            writeln!(writer, "  {}  -{} {}", Self::account(entry.credit), amount, commodity)?;
+            writeln!(writer)?;
+        }
+        writer.flush()
+    }
+
+    /// A metadata key as Beancount accepts it, which starts with a lowercase letter and holds only letters, digits,
+    /// dashes and underscores.
+    fn metadata_key(key: &str) -> String {
+        let key = key.chars()
+            .map(|c| match c {
+                'a'..='z' | '0'..='9' | '-' | '_' => c,
+                'A'..='Z' => c.to_ascii_lowercase(),
+                _ => '_'
+            })
+            .collect::<String>();
+        match key.chars().next() {
+            Some(first) if first.is_ascii_lowercase() => key,
+            _ => format!("m{}", key)
+        }
+    }
+
+    /// The name of a ledger account in a plain-text accounting file, under the top-level account of its type.
+    fn account(account: LedgerAccount) -> String {
+        match account {
+            LedgerAccount::Available(client) => format!("Liabilities:Clients:Client{}:Available", client),
+            LedgerAccount::Held(client) => format!("Liabilities:Clients:Client{}:Held", client),
+            LedgerAccount::Suspense => "Assets:Suspense".to_string(),
+            LedgerAccount::ChargebackLoss => "Expenses:ChargebackLoss".to_string(),
+            LedgerAccount::InterestExpense => "Expenses:Interest".to_string(),
+            LedgerAccount::OpeningBalances => "Equity:OpeningBalances".to_string(),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "beancount" => Ok(Self::Beancount),
+            "ledger" => Ok(Self::Ledger),
+            #[cfg(feature = "statements")]
+            "mt940" => Ok(Self::Mt940),
+            #[cfg(feature = "statements")]
+            "camt053" => Ok(Self::Camt053),
+            _ => Err(format!("unknown export format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Beancount => write!(f, "beancount"),
+            Self::Ledger => write!(f, "ledger"),
+            #[cfg(feature = "statements")]
+            Self::Mt940 => write!(f, "mt940"),
+            #[cfg(feature = "statements")]
+            Self::Camt053 => write!(f, "camt053"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::{Engine, Ledger, Transaction, TransactionType};
+
+    #[test]
+    fn ledger_entries_are_written_as_plain_text_accounting() {
+        let mut engine = Engine::new().with_ledger(Some(Ledger::new()));
+        let at = DateTime::<Utc>::from_str("2024-01-02T09:00:00Z").unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(100))).with_timestamp(at).with_metadata("Memo", "rent \"May\"")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from(40)))).unwrap();
+
+        let entries = engine.ledger().unwrap().entries();
+        let undated = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+        let mut beancount = Vec::new();
+        ExportFormat::Beancount.write(&mut beancount, entries, engine.precision(), "USD", undated).unwrap();
+        assert_eq!(String::from_utf8(beancount).unwrap(), "\
+2024-01-02 open Liabilities:Clients:Client1:Available
+2024-01-02 open Assets:Suspense
+
+2024-01-02 * \"deposit 1\"
+  memo: \"rent \\\"May\\\"\"
+  Assets:Suspense  100.0000 USD
+  Liabilities:Clients:Client1:Available  -100.0000 USD
+
+2024-01-03 * \"withdrawal 2\"
+  Liabilities:Clients:Client1:Available  40.0000 USD
+  Assets:Suspense  -40.0000 USD
+
+");
+
+        let mut ledger = Vec::new();
+        ExportFormat::Ledger.write(&mut ledger, &entries[..1], engine.precision(), "USD", undated).unwrap();
+        assert_eq!(String::from_utf8(ledger).unwrap(), "2024/01/02 deposit 1\n  ; Memo: rent \"May\"\n  Assets:Suspense  100.0000 USD\n  Liabilities:Clients:Client1:Available  -100.0000 USD\n\n");
+    }
+}
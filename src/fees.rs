@@ -0,0 +1,184 @@
+use std::{collections::HashMap, io, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use serde::Deserialize;
+
+use crate::{Transaction, TransactionType};
+
+/// A fee charged on a transaction, made up of a flat amount and a percentage of the amount moved.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fee {
+    /// The amount charged on every transaction.
+    pub flat: BigDecimal,
+
+    /// The percentage of the amount moved that is charged on top of the flat amount.
+    pub percent: BigDecimal
+}
+
+impl Fee {
+    /// The fee charged on a transaction moving the amount, before it is rounded to any precision.
+    pub fn on(&self, amount: &BigDecimal) -> BigDecimal {
+        &self.flat + amount * &self.percent / BigDecimal::from(100)
+    }
+}
+
+/// A schedule of the fees charged on each type of transaction, optionally varying by the tier of the client,
+/// and the fee account every fee is credited to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// The client every fee is credited to.
+    account: u16,
+
+    /// The fee of each type of transaction, for clients of a tier or, without one, for every other client.
+    fees: HashMap<(TransactionType, Option<String>), Fee>,
+
+    /// The tier of each client that has one.
+    tiers: HashMap<u16, String>
+}
+
+/// A row of a CSV fee schedule, with the amounts left as written until they are checked.
+#[derive(Debug, Deserialize)]
+struct FeeRow {
+    #[serde(rename = "type")]
+    type_: TransactionType,
+
+    #[serde(default)]
+    tier: Option<String>,
+
+    #[serde(default)]
+    flat: Option<String>,
+
+    #[serde(default)]
+    percent: Option<String>
+}
+
+/// A row of a CSV file of client tiers.
+#[derive(Debug, Deserialize)]
+struct TierRow {
+    client: u16,
+    tier: String
+}
+
+impl FeeSchedule {
+    /// Create an empty schedule, crediting any fee to the fee account.
+    pub fn new(account: u16) -> Self {
+        Self { account, ..Self::default() }
+    }
+
+    /// The client every fee is credited to.
+    pub fn account(&self) -> u16 {
+        self.account
+    }
+
+    /// Charge a fee on a type of transaction, for clients of the tier or, with `None`, for clients without a fee of their own tier.
+    pub fn with_fee(mut self, type_: TransactionType, tier: Option<&str>, fee: Fee) -> Self {
+        self.fees.insert((type_, tier.map(str::to_string)), fee);
+        self
+    }
+
+    /// Place a client in a tier.
+    pub fn with_tier(mut self, client: u16, tier: &str) -> Self {
+        self.tiers.insert(client, tier.to_string());
+        self
+    }
+
+    /// Read the fees of a CSV file with `type`, `tier`, `flat` and `percent` columns, adding them to the schedule.
+    /// An empty tier applies to every client, and an empty amount is zero.
+    /// Fees can only be charged on deposits, withdrawals, transfers and conversions.
+    pub fn read_fees<R: io::Read>(mut self, reader: R) -> io::Result<Self> {
+        let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+        let decimal = |line: u64, column: &str, value: Option<&str>| match value {
+            None | Some("") => Ok(BigDecimal::zero()),
+            Some(value) => match BigDecimal::from_str(value) {
+                Ok(value) if value >= BigDecimal::zero() => Ok(value),
+                Ok(_) => Err(invalid(line, format!("{} '{}' is negative", column, value))),
+                Err(e) => Err(invalid(line, format!("invalid {} '{}': {}", column, value, e)))
+            }
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers()?.clone();
+
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let line = record.position().map_or(0, |position| position.line());
+            let row = record.deserialize::<FeeRow>(Some(&headers))
+                .map_err(|e| invalid(line, e.to_string()))?;
+
+            if !matches!(row.type_, TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer | TransactionType::Convert) {
+                return Err(invalid(line, format!("a fee can not be charged on a {}", row.type_)));
+            }
+
+            let fee = Fee {
+                flat: decimal(line, "flat", row.flat.as_deref())?,
+                percent: decimal(line, "percent", row.percent.as_deref())?
+            };
+            let tier = row.tier.filter(|tier| !tier.is_empty());
+            if self.fees.insert((row.type_, tier.clone()), fee).is_some() {
+                return Err(invalid(line, format!("the {} fee{} is given more than once", row.type_, tier.map(|tier| format!(" of tier '{}'", tier)).unwrap_or_default())));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Read the tier of each client from a CSV file with `client` and `tier` columns, adding them to the schedule.
+    pub fn read_tiers<R: io::Read>(mut self, reader: R) -> io::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        for row in reader.deserialize::<TierRow>() {
+            let row = row.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            self.tiers.insert(row.client, row.tier);
+        }
+        Ok(self)
+    }
+
+    /// The fee charged on a transaction moving the amount, before it is rounded, or `None` if it is not charged one.
+    /// The fee of the client's tier is charged if it has one, and otherwise the fee for every client.
+    /// The fee account is never charged a fee.
+    pub fn fee(&self, transaction: &Transaction, amount: &BigDecimal) -> Option<BigDecimal> {
+        if transaction.client_id() == self.account {
+            return None;
+        }
+
+        let tier = self.tiers.get(&transaction.client_id()).cloned();
+        self.fees.get(&(transaction.type_(), tier))
+            .or_else(|| self.fees.get(&(transaction.type_(), None)))
+            .map(|fee| fee.on(amount))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Applied, Engine, TransactionError};
+
+    fn amount(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn fees_are_credited_to_the_fee_account() {
+        let schedule = "type,tier,flat,percent\nwithdrawal,,1,\nwithdrawal,gold,,1\ndeposit,,0.5,\n";
+        let fees = FeeSchedule::new(99)
+            .read_fees(schedule.as_bytes()).unwrap()
+            .read_tiers("client,tier\n2,gold\n".as_bytes()).unwrap();
+        let mut engine = Engine::new().with_fees(Some(fees));
+
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))), Ok(Applied::Deposited { amount: BigDecimal::from(10), fee: amount("0.5") }));
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("5"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 2, 4, amount("50"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 5, amount("4"))), Err(TransactionError::InsufficientFunds));
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.iter().map(|client| (client.id(), client.available())).collect::<Vec<_>>(), vec![
+            (1, BigDecimal::from_str("3.5").unwrap()),
+            (2, BigDecimal::from_str("49").unwrap()),
+            (99, BigDecimal::from_str("2.5").unwrap()),
+        ]);
+    }
+}
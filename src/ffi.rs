@@ -0,0 +1,143 @@
+//! A C API to the engine, so it can be called in-process from other languages. See `include/tx_engine.h`.
+
+use std::{
+    ffi::{CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice
+};
+
+use crate::{Engine, OutputFormat, records_from_reader};
+
+/// An argument was a null pointer.
+pub const TX_ERROR_NULL: i32 = -1;
+
+/// The CSV could not be read at all, such as when its header is malformed.
+pub const TX_ERROR_INPUT: i32 = -2;
+
+/// The engine panicked, and can no longer be used.
+pub const TX_ERROR_PANIC: i32 = -3;
+
+/// An engine, as an opaque handle for C.
+pub struct TxEngine {
+    /// The engine.
+    engine: Engine,
+
+    /// Whether the engine panicked, leaving it in an unknown state.
+    poisoned: bool
+}
+
+/// Create a new engine with the default configuration, to be freed with `tx_engine_free`.
+#[no_mangle]
+pub extern "C" fn tx_engine_new() -> *mut TxEngine {
+    Box::into_raw(Box::new(TxEngine { engine: Engine::new(), poisoned: false }))
+}
+
+/// Process every transaction of a CSV document, with a header row, in order.
+/// Returns the number of rows that were malformed or rejected, or a negative `TX_ERROR_*` code.
+///
+/// # Safety
+///
+/// `engine` must have been returned by `tx_engine_new` and not yet freed, and `csv` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tx_engine_process_csv(engine: *mut TxEngine, csv: *const u8, len: usize) -> i32 {
+    let (Some(engine), false) = (engine.as_mut(), csv.is_null()) else {
+        return TX_ERROR_NULL;
+    };
+    if engine.poisoned {
+        return TX_ERROR_PANIC;
+    }
+
+    let csv = slice::from_raw_parts(csv, len);
+    // NOTE: A panic must not unwind across the C boundary, so it is caught, and the engine is never used again.
+    let processed = panic::catch_unwind(AssertUnwindSafe(|| {
+        let records = records_from_reader(csv).map_err(|_| TX_ERROR_INPUT)?;
+        let failed = records.into_iter()
+            .filter(|record| match record {
+                Ok(record) => engine.engine.process(&record.transaction).is_err(),
+                Err(_) => true
+            })
+            .count();
+        Ok(i32::try_from(failed).unwrap_or(i32::MAX))
+    }));
+
+    match processed {
+        Ok(Ok(failed)) => failed,
+        Ok(Err(code)) => code,
+        Err(_) => {
+            engine.poisoned = true;
+            TX_ERROR_PANIC
+        }
+    }
+}
+
+/// Write every client account as a JSON array, in the same format as the `--output-format json` of the command line,
+/// as a NUL-terminated string to be freed with `tx_engine_string_free`. Returns null if the accounts can not be written.
+///
+/// # Safety
+///
+/// `engine` must have been returned by `tx_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn tx_engine_accounts_json(engine: *const TxEngine) -> *mut c_char {
+    let Some(engine) = engine.as_ref().filter(|engine| !engine.poisoned) else {
+        return ptr::null_mut();
+    };
+
+    let mut json = Vec::new();
+    let written = engine.engine.clients()
+        .map_err(|_| ())
+        .and_then(|clients| OutputFormat::Json.write(&mut json, &clients, engine.engine.precision()).map_err(drop));
+    match written.ok().and_then(|_| CString::new(json).ok()) {
+        Some(json) => json.into_raw(),
+        None => ptr::null_mut()
+    }
+}
+
+/// Free a string returned by `tx_engine_accounts_json`. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `string` must have been returned by `tx_engine_accounts_json` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn tx_engine_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Free an engine. Freeing null does nothing.
+///
+/// # Safety
+///
+/// `engine` must have been returned by `tx_engine_new` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn tx_engine_free(engine: *mut TxEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    #[test]
+    fn process_through_the_c_api() {
+        let csv = "type, client, tx, amount\ndeposit, 1, 1, 10\nwithdrawal, 1, 2, 20\nnonsense\n";
+
+        unsafe {
+            let engine = tx_engine_new();
+            assert_eq!(tx_engine_process_csv(engine, csv.as_ptr(), csv.len()), 2);
+            assert_eq!(tx_engine_process_csv(ptr::null_mut(), csv.as_ptr(), csv.len()), TX_ERROR_NULL);
+
+            let json = tx_engine_accounts_json(engine);
+            let accounts: serde_json::Value = serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            assert_eq!(accounts[0]["id"], 1);
+            assert_eq!(accounts[0]["available"], "10.0000");
+
+            tx_engine_string_free(json);
+            tx_engine_free(engine);
+        }
+    }
+}
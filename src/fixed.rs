@@ -0,0 +1,142 @@
+use std::fmt;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::TransactionError;
+
+/// The number of decimal places a fixed-point amount is kept to.
+pub const FIXED_SCALE: i64 = 4;
+
+/// An amount held as a whole number of ten-thousandths, with overflow-checked arithmetic that never allocates.
+/// Balances are kept in this form when built with the `fixed-point` feature.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fixed(i64);
+
+impl Fixed {
+    /// An amount of zero.
+    pub const ZERO: Self = Self(0);
+
+    /// Create an amount from a whole number of ten-thousandths.
+    pub fn from_units(units: i64) -> Self {
+        Self(units)
+    }
+
+    /// The amount as a whole number of ten-thousandths.
+    pub fn units(self) -> i64 {
+        self.0
+    }
+
+    /// Add two amounts, returning `None` if the result does not fit.
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Subtract an amount, returning `None` if the result does not fit.
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// The amount as a decimal, with exactly four decimal places.
+    pub fn to_decimal(self) -> BigDecimal {
+        BigDecimal::new(self.0.into(), FIXED_SCALE)
+    }
+}
+
+impl TryFrom<&BigDecimal> for Fixed {
+    type Error = TransactionError;
+
+    /// Convert a decimal exactly, failing if it has more than four decimal places or does not fit.
+    fn try_from(amount: &BigDecimal) -> Result<Self, Self::Error> {
+        // NOTE: Every balance operation converts its amount, so one whose digits fit is converted without allocating.
+        let (digits, scale) = amount.as_bigint_and_scale();
+        if let Some(units) = i64::try_from(digits.as_ref()).ok().and_then(|digits| rescale(digits, scale)) {
+            return Ok(Self(units));
+        }
+
+        if amount.normalized().fractional_digit_count() > FIXED_SCALE {
+            return Err(TransactionError::ExcessPrecision(FIXED_SCALE));
+        }
+
+        let (units, _) = amount.with_scale(FIXED_SCALE).into_bigint_and_exponent();
+        i64::try_from(units)
+            .map(Self)
+            .map_err(|_| TransactionError::AmountOutOfRange)
+    }
+}
+
+/// The whole number of ten-thousandths in digits at a scale, if it is exact and fits.
+fn rescale(digits: i64, scale: i64) -> Option<i64> {
+    match scale <= FIXED_SCALE {
+        true => digits.checked_mul(10i64.checked_pow(u32::try_from(FIXED_SCALE - scale).ok()?)?),
+        false => {
+            let divisor = 10i64.checked_pow(u32::try_from(scale - FIXED_SCALE).ok()?)?;
+            (digits % divisor == 0).then(|| digits / divisor)
+        }
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_decimal().to_plain_string())
+    }
+}
+
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_decimal().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let amount = BigDecimal::deserialize(deserializer)?;
+        Self::try_from(&amount).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn convert_exactly() {
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("10.5").unwrap()), Ok(Fixed::from_units(105_000)));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("-0.0001").unwrap()), Ok(Fixed::from_units(-1)));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("1.00001").unwrap()), Err(TransactionError::ExcessPrecision(FIXED_SCALE)));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("1e20").unwrap()), Err(TransactionError::AmountOutOfRange));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("2.500000").unwrap()), Ok(Fixed::from_units(25_000)));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("3e2").unwrap()), Ok(Fixed::from_units(3_000_000)));
+        assert_eq!(Fixed::try_from(&BigDecimal::from_str("1.000000000000000000001").unwrap()), Err(TransactionError::ExcessPrecision(FIXED_SCALE)));
+        assert_eq!(Fixed::from_units(i64::MAX).checked_add(Fixed::from_units(1)), None);
+        assert_eq!(Fixed::from_units(105_000).to_string(), "10.5000");
+    }
+
+    proptest! {
+        #[test]
+        fn matches_bigdecimal(amounts in prop::collection::vec((-1_000_000_000_000i64..1_000_000_000_000, any::<bool>()), 1..32)) {
+            let mut fixed = Fixed::ZERO;
+            let mut decimal = BigDecimal::from(0);
+
+            for (units, add) in amounts {
+                let amount = BigDecimal::new(units.into(), FIXED_SCALE);
+                let converted = Fixed::try_from(&amount).unwrap();
+
+                if add {
+                    fixed = fixed.checked_add(converted).unwrap();
+                    decimal += &amount;
+                } else {
+                    fixed = fixed.checked_sub(converted).unwrap();
+                    decimal -= &amount;
+                }
+
+                prop_assert_eq!(fixed.to_decimal(), decimal.clone());
+                prop_assert_eq!(fixed.cmp(&Fixed::ZERO), decimal.cmp(&BigDecimal::from(0)));
+            }
+        }
+    }
+}
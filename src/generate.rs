@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+
+use bigdecimal::BigDecimal;
+
+use crate::{Transaction, TransactionType};
+
+/// The number of recent deposits kept as candidates for a dispute.
+const DISPUTABLE_DEPOSITS: usize = 4096;
+
+/// The next random number of a SplitMix64 generator, advancing its state.
+pub(crate) fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A generator of a reproducible, synthetic workload of transactions across many clients.
+/// Most transactions are deposits and withdrawals, with a configurable share of disputes that are later resolved or charged back.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    /// The state of the random number generator.
+    state: u64,
+
+    /// The number of clients transactions are spread across.
+    clients: u16,
+
+    /// The chance of each transaction disputing an earlier deposit, and of settling an open dispute.
+    dispute_rate: f64,
+
+    /// The id of the next new transaction.
+    next_id: u32,
+
+    /// Recent deposits that have not been disputed, as the client and transaction id.
+    deposits: VecDeque<(u16, u32)>,
+
+    /// Disputes that have not yet been resolved or charged back.
+    disputes: Vec<(u16, u32)>
+}
+
+impl Generator {
+    /// Create a generator across a number of clients, where the same seed always yields the same transactions.
+    pub fn new(clients: u16, dispute_rate: f64, seed: u64) -> Self {
+        Self {
+            state: seed,
+            clients: clients.max(1),
+            dispute_rate,
+            next_id: 1,
+            deposits: VecDeque::new(),
+            disputes: Vec::new()
+        }
+    }
+
+    /// The next random number, using SplitMix64 so a workload never changes between versions.
+    fn next_u64(&mut self) -> u64 {
+        split_mix64(&mut self.state)
+    }
+
+    /// A random number below the bound.
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// Whether an event with the given probability happens.
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+
+    /// A random amount between 0.0001 and 1000, with four decimal places.
+    fn amount(&mut self) -> BigDecimal {
+        BigDecimal::new((self.below(10_000_000) + 1).into(), 4)
+    }
+}
+
+impl Iterator for Generator {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.disputes.is_empty() && self.chance(self.dispute_rate) {
+            let index = self.below(self.disputes.len() as u64) as usize;
+            let (client_id, id) = self.disputes.swap_remove(index);
+            // NOTE: Most disputes are settled in the client's favour, while a few end in a chargeback that locks the account.
+            let type_ = if self.chance(0.8) { TransactionType::Resolve } else { TransactionType::Chargeback };
+            return Some(Transaction::new(type_, client_id, id, None));
+        }
+
+        if !self.deposits.is_empty() && self.chance(self.dispute_rate) {
+            let index = self.below(self.deposits.len() as u64) as usize;
+            let (client_id, id) = self.deposits.remove(index).unwrap();
+            self.disputes.push((client_id, id));
+            return Some(Transaction::new(TransactionType::Dispute, client_id, id, None));
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1)?;
+        let client_id = self.below(u64::from(self.clients)) as u16 + 1;
+        let amount = self.amount();
+
+        if self.chance(0.6) {
+            if self.deposits.len() == DISPUTABLE_DEPOSITS {
+                self.deposits.pop_front();
+            }
+            self.deposits.push_back((client_id, id));
+            Some(Transaction::new(TransactionType::Deposit, client_id, id, Some(amount)))
+        } else {
+            Some(Transaction::new(TransactionType::Withdrawal, client_id, id, Some(amount)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn workloads_are_reproducible() {
+        let first = Generator::new(10, 0.05, 42).take(1000).collect::<Vec<_>>();
+
+        assert_eq!(first, Generator::new(10, 0.05, 42).take(1000).collect::<Vec<_>>());
+        assert_ne!(first, Generator::new(10, 0.05, 43).take(1000).collect::<Vec<_>>());
+        assert!(first.iter().all(|transaction| (1..=10).contains(&transaction.client_id())));
+        assert!(first.iter().any(|transaction| transaction.type_() == TransactionType::Dispute));
+
+        let mut engine = Engine::new();
+        let rejected = engine.process_all(first);
+        assert!(rejected.iter().all(|(_, e)| !matches!(e, crate::TransactionError::DuplicateTransaction(_))));
+    }
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, fmt, net::SocketAddr, sync::{Arc, Mutex}};
+
+use tokio_stream::StreamExt;
+use tonic::{Request, Response, Status, Streaming, metadata::MetadataMap, transport::Server};
+
+use crate::{ShardedEngine, TENANT_HEADER, Transaction};
+
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/transactions.Ledger.rs"));
+}
+
+pub use generated::{ledger_client::LedgerClient, ledger_server::{Ledger, LedgerServer}};
+pub use crate::protobuf::{AccountReply, BalanceMessage, TransactionMessage};
+
+/// A transaction that was rejected by the engine.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RejectionMessage {
+    /// The id of the transaction.
+    #[prost(uint32, tag = "1")]
+    pub tx: u32,
+
+    /// A human readable reason the transaction was rejected.
+    #[prost(string, tag = "2")]
+    pub reason: String,
+}
+
+/// A summary of every transaction submitted on a stream.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SubmitSummary {
+    /// The number of transactions that were accepted.
+    #[prost(uint64, tag = "1")]
+    pub accepted: u64,
+
+    /// Every transaction that was rejected, in the order it was submitted.
+    #[prost(message, repeated, tag = "2")]
+    pub rejected: Vec<RejectionMessage>,
+}
+
+/// A request for the state of a single client's account.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountRequest {
+    /// The id of the client.
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+}
+
+/// The engines of the isolated books of every tenant a service has had a request for.
+struct TenantEngines {
+    /// The engine of every tenant, keyed by its name.
+    engines: Mutex<HashMap<String, ShardedEngine>>,
+
+    /// Spawn the engine of a tenant, which must already keep the books of that tenant.
+    spawn: Box<dyn Fn(&str) -> ShardedEngine + Send + Sync>
+}
+
+/// A gRPC service that applies submitted transactions to an engine sharded across async tasks.
+///
+/// When tenants are enabled, a request naming its tenant in the [`TENANT_HEADER`] is applied to the isolated books of
+/// that tenant instead, each kept by an engine of its own.
+#[derive(Clone)]
+pub struct LedgerService {
+    /// The engine every request without a tenant is applied to.
+    engine: ShardedEngine,
+
+    /// The engines of the books of every tenant, if tenants are enabled.
+    tenants: Option<Arc<TenantEngines>>
+}
+
+impl LedgerService {
+    /// Create a new service on top of an engine.
+    pub fn new(engine: ShardedEngine) -> Self {
+        Self { engine, tenants: None }
+    }
+
+    /// Apply requests naming a tenant to the books of that tenant, in an engine spawned by the given function the first
+    /// time the tenant is named, which is passed the name of the tenant.
+    pub fn with_tenants<F>(mut self, spawn: F) -> Self
+    where
+        F: Fn(&str) -> ShardedEngine + Send + Sync + 'static
+    {
+        self.tenants = Some(Arc::new(TenantEngines { engines: Mutex::new(HashMap::new()), spawn: Box::new(spawn) }));
+        self
+    }
+
+    /// The engine every request without a tenant is applied to.
+    pub fn engine(&self) -> &ShardedEngine {
+        &self.engine
+    }
+
+    /// The tenant named by the metadata of a request, if any, and the engine keeping its books,
+    /// or why the request names a tenant it can not be applied to.
+    fn route(&self, metadata: &MetadataMap) -> Result<(Option<String>, ShardedEngine), String> {
+        let Some(tenant) = metadata.get(TENANT_HEADER) else {
+            return Ok((None, self.engine.clone()));
+        };
+        let tenant = tenant.to_str().ok()
+            .filter(|tenant| !tenant.is_empty())
+            .ok_or_else(|| format!("the {} header is not a valid tenant", TENANT_HEADER))?;
+        let tenants = self.tenants.as_ref()
+            .ok_or_else(|| "tenants are not enabled on this server".to_string())?;
+
+        let mut engines = tenants.engines.lock().unwrap();
+        let engine = engines.entry(tenant.to_string())
+            .or_insert_with(|| (tenants.spawn)(tenant))
+            .clone();
+        Ok((Some(tenant.to_string()), engine))
+    }
+
+    /// Serve the service on an address until the process is stopped.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), tonic::transport::Error> {
+        Server::builder()
+            .add_service(LedgerServer::new(self))
+            .serve(addr)
+            .await
+    }
+}
+
+#[tonic::async_trait]
+impl Ledger for LedgerService {
+    async fn submit_transactions(&self, request: Request<Streaming<TransactionMessage>>) -> Result<Response<SubmitSummary>, Status> {
+        let (tenant, engine) = self.route(request.metadata()).map_err(Status::invalid_argument)?;
+        let mut stream = request.into_inner();
+        let mut pending = Vec::new();
+
+        // NOTE: Transactions are queued as they arrive, without waiting for each to be processed before reading the next.
+        while let Some(message) = stream.next().await {
+            let transaction = Transaction::try_from(message?).map_err(Status::invalid_argument)?;
+            let transaction = match &tenant {
+                Some(tenant) => transaction.with_tenant(tenant.as_str()),
+                None => transaction
+            };
+            pending.push((transaction.id(), engine.submit(transaction).await));
+        }
+
+        let mut summary = SubmitSummary::default();
+        for (tx, pending) in pending {
+            match pending.await {
+                Ok(_) => summary.accepted += 1,
+                Err(e) => summary.rejected.push(RejectionMessage { tx, reason: e.to_string() })
+            }
+        }
+
+        Ok(Response::new(summary))
+    }
+
+    async fn get_account(&self, request: Request<AccountRequest>) -> Result<Response<AccountReply>, Status> {
+        let (_, engine) = self.route(request.metadata()).map_err(Status::invalid_argument)?;
+        let id = request.into_inner().client;
+        let client = match u16::try_from(id) {
+            Ok(id) => engine.client(id).await.map_err(|e| Status::internal(e.to_string()))?,
+            Err(_) => None
+        };
+        let client = client.ok_or_else(|| Status::not_found(format!("client {} does not exist", id)))?;
+
+        Ok(Response::new(AccountReply::new(&client, engine.precision())))
+    }
+}
+
+impl fmt::Debug for LedgerService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LedgerService")
+            .field("engine", &self.engine)
+            .field("tenants", &self.tenants.as_ref().map(|tenants| tenants.engines.lock().unwrap().keys().cloned().collect::<Vec<_>>()))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+
+    use super::*;
+    use crate::Engine;
+
+    fn message(type_: &str, client: u32, tx: u32, amount: Option<&str>) -> TransactionMessage {
+        TransactionMessage {
+            r#type: type_.to_string(),
+            client,
+            tx,
+            amount: amount.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_and_get_account() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(Server::builder()
+            .add_service(LedgerServer::new(LedgerService::new(ShardedEngine::spawn(2, Engine::new))))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+
+        let mut client = LedgerClient::connect(format!("http://{}", addr)).await.unwrap();
+
+        let transactions = vec![
+            message("deposit", 1, 1, Some("10.5")),
+            message("withdrawal", 1, 2, Some("20")),
+            message("dispute", 1, 1, None),
+        ];
+        let summary = client.submit_transactions(tokio_stream::iter(transactions)).await.unwrap().into_inner();
+
+        assert_eq!(summary.accepted, 2);
+        assert_eq!(summary.rejected, vec![RejectionMessage { tx: 2, reason: "insufficient funds".to_string() }]);
+
+        let account = client.get_account(AccountRequest { client: 1 }).await.unwrap().into_inner();
+        assert_eq!(account.balances[0].held, "10.5000");
+        assert_eq!(account.balances[0].available, "0.0000");
+        assert_eq!(client.get_account(AccountRequest { client: 2 }).await.unwrap_err().code(), tonic::Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn route_requests_by_tenant() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = LedgerService::new(ShardedEngine::spawn(1, Engine::new))
+            .with_tenants(|tenant| {
+                let tenant = tenant.to_string();
+                ShardedEngine::spawn(1, move || Engine::new().with_tenant(Some(tenant.clone())))
+            });
+        tokio::spawn(Server::builder()
+            .add_service(LedgerServer::new(service))
+            .serve_with_incoming(TcpListenerStream::new(listener)));
+
+        let mut client = LedgerClient::connect(format!("http://{}", addr)).await.unwrap();
+        fn tenant<T>(message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.metadata_mut().insert(TENANT_HEADER, "acme".parse().unwrap());
+            request
+        }
+
+        client.submit_transactions(tokio_stream::iter(vec![message("deposit", 1, 1, Some("10"))])).await.unwrap();
+        let summary = client.submit_transactions(tenant(tokio_stream::iter(vec![message("deposit", 1, 1, Some("3"))]))).await.unwrap().into_inner();
+        assert_eq!(summary.accepted, 1);
+
+        assert_eq!(client.get_account(AccountRequest { client: 1 }).await.unwrap().into_inner().balances[0].available, "10.0000");
+        assert_eq!(client.get_account(tenant(AccountRequest { client: 1 })).await.unwrap().into_inner().balances[0].available, "3.0000");
+    }
+}
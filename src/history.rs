@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::Client;
+
+/// The state of a client after a change, alongside the time it was made, or `None` for the very start.
+type Change = (Option<DateTime<Utc>>, Client);
+
+/// The balances of every client over time, recorded as each accepted transaction changes them, so the state of an
+/// account can be queried as of any instant.
+///
+/// A change is recorded at the timestamp of the transaction that made it, or of the latest accepted transaction if it has
+/// none, so the history assumes transactions are processed in the order they happened.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BalanceHistory {
+    /// The state of each client after every change, in the order they were made, keyed by client id.
+    /// A change made before any transaction with a timestamp is held as of the very start.
+    clients: BTreeMap<u16, Vec<Change>>
+}
+
+impl BalanceHistory {
+    /// Create an empty history.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state of a client after a change at the time, unless it is the same as its last recorded state.
+    pub(crate) fn record(&mut self, at: Option<DateTime<Utc>>, client: Client) {
+        let states = self.clients.entry(client.id()).or_default();
+        if states.last().is_none_or(|(_, last)| *last != client) {
+            states.push((at, client));
+        }
+    }
+
+    /// Merge the history of another engine, which must not share any clients with this one.
+    pub(crate) fn merge(&mut self, other: BalanceHistory) {
+        self.clients.extend(other.clients);
+    }
+
+    /// Whether any change to the client was recorded.
+    pub fn contains(&self, client: u16) -> bool {
+        self.clients.contains_key(&client)
+    }
+
+    /// The state of a client as of the instant, which is that after the last change made at or before it,
+    /// or `None` if the client had no account by then.
+    pub fn balance_at(&self, client: u16, at: DateTime<Utc>) -> Option<&Client> {
+        self.clients.get(&client)?
+            .iter()
+            .rev()
+            .find(|(changed, _)| changed.is_none_or(|changed| changed <= at))
+            .map(|(_, client)| client)
+    }
+
+    /// The state of every client with an account as of the instant, ordered by client id.
+    pub fn accounts_at(&self, at: DateTime<Utc>) -> Vec<Client> {
+        self.clients.keys()
+            .filter_map(|&client| self.balance_at(client, at).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, Snapshot, Transaction, TransactionType};
+
+    #[test]
+    fn query_balances_as_of_an_instant() {
+        let at = |hours: i64| DateTime::from_timestamp(hours * 3_600, 0).unwrap();
+
+        let mut engine = Engine::new().with_history(Some(BalanceHistory::new()));
+        engine.restore(Snapshot { clients: vec![Client::new(3)], ..Snapshot::default() }).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())).with_timestamp(at(1))).unwrap();
+        engine.process(&Transaction::transfer(1, 2, 2, 4.into()).with_timestamp(at(2))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        assert!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, Some(50.into())).with_timestamp(at(3))).is_err());
+
+        let available = |client: u16, hours: i64| engine.balance_at(client, at(hours)).unwrap().map(|client| client.available());
+        assert_eq!(available(1, 0), None);
+        assert_eq!(available(1, 1), Some(BigDecimal::from(10)));
+        assert_eq!((available(1, 2), available(2, 2)), (Some(BigDecimal::from(-4)), Some(BigDecimal::from(4))));
+        assert_eq!(available(2, 1), None);
+        assert_eq!(available(3, 0), Some(BigDecimal::from(0)));
+
+        // NOTE: The dispute has no timestamp, so it happened at the time of the transfer before it.
+        assert_eq!(engine.history().unwrap().accounts_at(at(1)).len(), 2);
+        assert_eq!(engine.accounts_at(at(2)).unwrap(), engine.accounts().unwrap());
+        assert_eq!(engine.history().unwrap().balance_at(1, at(2)).unwrap().held(), BigDecimal::from(10));
+    }
+}
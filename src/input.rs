@@ -0,0 +1,942 @@
+use std::{cmp::Reverse, collections::{BinaryHeap, VecDeque}, error, fmt, io, path::Path, str::FromStr};
+
+use rayon::prelude::*;
+
+use crate::{ColumnMapping, CsvDialect, Transaction, mapping::FIELDS, transaction::JsonTransaction};
+
+/// The size a CSV source is split into chunks of, to be parsed in parallel.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// The size a CSV source is read in blocks of, each split into chunks, so only about this much of it is held at a time.
+const BLOCK_SIZE: usize = 16 * CHUNK_SIZE;
+
+/// The bytes every gzip stream starts with.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// The bytes every zstd frame starts with.
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// The extensions of compressed files, which are looked through to detect the format of their contents.
+const COMPRESSED_EXTENSIONS: &[&str] = &["gz", "zst", "zstd"];
+
+/// The byte order mark some tools write at the start of UTF-8 text.
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+
+/// An enumeration of the text encodings input can be written in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, or UTF-16 when the input starts with a UTF-16 byte order mark.
+    #[default]
+    Utf8,
+
+    /// Little-endian UTF-16.
+    Utf16Le,
+
+    /// Big-endian UTF-16.
+    Utf16Be,
+
+    /// ISO 8859-1, read as its superset Windows-1252.
+    Latin1,
+}
+
+impl Encoding {
+    /// Wrap a source of text in this encoding in a reader of it as UTF-8, without any byte order mark it starts with.
+    pub fn decode<'a, R: io::Read + 'a>(self, reader: R) -> Box<dyn io::Read + 'a> {
+        let encoding = match self {
+            Self::Utf8 => None,
+            Self::Utf16Le => Some(encoding_rs::UTF_16LE),
+            Self::Utf16Be => Some(encoding_rs::UTF_16BE),
+            Self::Latin1 => Some(encoding_rs::WINDOWS_1252)
+        };
+
+        // NOTE: UTF-8 is passed through as is, so invalid UTF-8 is still reported against its row rather than replaced.
+        Box::new(encoding_rs_io::DecodeReaderBytesBuilder::new()
+            .encoding(encoding)
+            .utf8_passthru(true)
+            .strip_bom(true)
+            .build(reader))
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "utf-16le" | "utf16le" => Ok(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Self::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Self::Latin1),
+            _ => Err(format!("unknown encoding '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Utf8 => write!(f, "utf-8"),
+            Self::Utf16Le => write!(f, "utf-16le"),
+            Self::Utf16Be => write!(f, "utf-16be"),
+            Self::Latin1 => write!(f, "latin-1"),
+        }
+    }
+}
+
+/// An enumeration of each supported input format.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Comma separated values, with a header row.
+    #[default]
+    Csv,
+
+    /// Newline delimited JSON, with one transaction object per line.
+    Jsonl,
+
+    /// An ISO 20022 pain.001 credit transfer or pain.008 direct debit initiation message.
+    #[cfg(feature = "iso20022")]
+    Iso20022,
+
+    /// A stream of length-delimited protobuf transaction messages.
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+
+    /// An Avro object container file of transaction records.
+    #[cfg(feature = "avro")]
+    Avro,
+}
+
+impl InputFormat {
+    /// Detect the input format from the extension of a path, if it is recognised.
+    /// The extension of a compressed file is skipped, so `transactions.jsonl.gz` is read as JSON Lines.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let path = path.as_ref();
+        let extension = match path.extension()?.to_str()? {
+            extension if COMPRESSED_EXTENSIONS.contains(&extension) => Path::new(path.file_stem()?).extension()?.to_str()?,
+            extension => extension
+        };
+
+        match extension {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            #[cfg(feature = "iso20022")]
+            "xml" => Some(Self::Iso20022),
+            #[cfg(feature = "protobuf")]
+            "pb" | "binpb" => Some(Self::Protobuf),
+            #[cfg(feature = "avro")]
+            "avro" => Some(Self::Avro),
+            _ => None
+        }
+    }
+
+    /// Whether the format is text, which can be written in another encoding, rather than binary.
+    pub fn is_text(self) -> bool {
+        match self {
+            Self::Csv | Self::Jsonl => true,
+            #[cfg(feature = "iso20022")]
+            Self::Iso20022 => true,
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf => false,
+            #[cfg(feature = "avro")]
+            Self::Avro => false,
+        }
+    }
+
+    /// Read every transaction from a source in this format, keeping track of the line each was read from.
+    /// A malformed row is returned as an error in place of its record, while a failure to read the source fails entirely.
+    pub fn read<R: io::Read>(self, reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+        match self {
+            Self::Csv => records_from_reader(reader),
+            Self::Jsonl => records_from_jsonl_reader(reader),
+            #[cfg(feature = "iso20022")]
+            Self::Iso20022 => crate::records_from_iso20022_reader(reader),
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf => crate::protobuf::records_from_protobuf_reader(reader),
+            #[cfg(feature = "avro")]
+            Self::Avro => crate::records_from_avro_reader(reader),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            #[cfg(feature = "iso20022")]
+            "iso20022" | "pain" => Ok(Self::Iso20022),
+            #[cfg(feature = "protobuf")]
+            "protobuf" => Ok(Self::Protobuf),
+            #[cfg(feature = "avro")]
+            "avro" => Ok(Self::Avro),
+            _ => Err(format!("unknown input format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Jsonl => write!(f, "jsonl"),
+            #[cfg(feature = "iso20022")]
+            Self::Iso20022 => write!(f, "iso20022"),
+            #[cfg(feature = "protobuf")]
+            Self::Protobuf => write!(f, "protobuf"),
+            #[cfg(feature = "avro")]
+            Self::Avro => write!(f, "avro"),
+        }
+    }
+}
+
+/// Wrap a source in a decompressor if it starts with the magic bytes of a gzip or zstd stream, and otherwise read it as is.
+/// Concatenated gzip members are read as a single stream.
+pub fn decompress<'a, R: io::BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn io::Read + 'a>> {
+    let magic = reader.fill_buf()?;
+
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(flate2::bufread::MultiGzDecoder::new(reader)))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        Ok(Box::new(zstd::Decoder::with_buffer(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// A transaction alongside the line of the input it was read from.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The line number of the record within the input, starting at 1 for the header.
+    pub line: u64,
+
+    /// The transaction read from the line.
+    pub transaction: Transaction
+}
+
+/// A row of the input that could not be read as a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordError {
+    /// The line number of the row within the input.
+    pub line: u64,
+
+    /// The column the error was found at, if known.
+    /// For CSV this is the position of the field, starting at 1, and for JSON Lines the character within the line.
+    pub column: Option<u64>,
+
+    /// The name of the field that could not be read, if known.
+    pub field: Option<String>,
+
+    /// A human readable description of what is wrong with the row.
+    pub message: String,
+
+    /// The raw contents of the row.
+    pub record: String
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ", column {}", column)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, " (field '{}')", field)?;
+        }
+        write!(f, ": {}; record: `{}`", self.message, self.record)
+    }
+}
+
+impl error::Error for RecordError {}
+
+/// Read every transaction from a CSV source, keeping track of the line each was read from.
+/// Whitespace around fields is trimmed, and rows without an amount are accepted.
+/// A malformed row is returned as an error in place of its record, while a failure to read the source fails entirely.
+///
+/// The source is split into chunks at record boundaries, which are parsed in parallel, and the records are returned
+/// in their original order, exactly as if they had been parsed one after another.
+pub fn records_from_reader<R: io::Read>(reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+    records_from_reader_with(reader, &CsvDialect::default(), &ColumnMapping::default())
+}
+
+/// Read every transaction from a CSV source in a dialect, with its columns and values mapped to the fields of a
+/// transaction, keeping track of the line each was read from. A malformed row is reported against the column it was read from.
+pub fn records_from_reader_with<R: io::Read>(reader: R, dialect: &CsvDialect, mapping: &ColumnMapping) -> io::Result<Vec<Result<Record, RecordError>>> {
+    CsvRecords::new(reader, *dialect, mapping.clone())?.collect()
+}
+
+/// Reads the records of a CSV source in blocks of bounded size, keeping track of the line each was read from,
+/// so only a block of the source, and the records read from it, are held at a time.
+///
+/// Each block is split into chunks at record boundaries, which are parsed in parallel, and its records are yielded
+/// in their original order, exactly as if they had been parsed one after another, before the next block is read.
+/// A malformed row is yielded as an error in place of its record, while a failure to read the source ends the records.
+#[derive(Debug)]
+pub struct CsvRecords<R> {
+    /// The source the records are read from.
+    reader: R,
+
+    /// The bytes read that do not yet make up a complete record.
+    pending: Vec<u8>,
+
+    /// The header row of the source.
+    headers: csv::StringRecord,
+
+    /// The line the next record starts on.
+    line: u64,
+
+    /// The delimiter and quoting of the source.
+    dialect: CsvDialect,
+
+    /// The mapping of the columns of the source to the fields of a transaction.
+    mapping: ColumnMapping,
+
+    /// The records parsed from the last block that are yet to be yielded.
+    records: VecDeque<Result<Record, RecordError>>,
+
+    /// The number of bytes read at a time.
+    block_size: usize,
+
+    /// Whether the whole source has been read.
+    done: bool
+}
+
+impl<R: io::Read> CsvRecords<R> {
+    /// Start reading a source in a dialect, with its columns and values mapped to the fields of a transaction,
+    /// by reading its header row.
+    pub fn new(reader: R, dialect: CsvDialect, mapping: ColumnMapping) -> io::Result<Self> {
+        Self::with_block_size(reader, dialect, mapping, BLOCK_SIZE)
+    }
+
+    /// Start reading a source `block_size` bytes at a time.
+    fn with_block_size(reader: R, dialect: CsvDialect, mapping: ColumnMapping, block_size: usize) -> io::Result<Self> {
+        let mut records = Self {
+            reader,
+            pending: Vec::new(),
+            headers: csv::StringRecord::new(),
+            line: 1,
+            dialect,
+            mapping,
+            records: VecDeque::new(),
+            block_size,
+            done: false
+        };
+
+        // NOTE: The header row is read whole, however many blocks it spans, before any of the body.
+        while !records.read_block()? && record_end(&records.pending, dialect.quote()).is_none() {}
+        // NOTE: A byte order mark would otherwise be read as part of the name of the first column.
+        if records.pending.starts_with(UTF8_BOM) {
+            records.pending.drain(..UTF8_BOM.len());
+        }
+
+        let mut reader = dialect.reader().from_reader(records.pending.as_slice());
+        records.headers = reader.headers()?.clone();
+        let start = reader.position().clone();
+        records.pending.drain(..start.byte() as usize);
+        records.line = start.line();
+        Ok(records)
+    }
+
+    /// Read up to another block of the source, returning whether its end was reached.
+    fn read_block(&mut self) -> io::Result<bool> {
+        let mut block = io::Read::take(&mut self.reader, self.block_size as u64);
+        let read = io::Read::read_to_end(&mut block, &mut self.pending)?;
+        Ok(read == 0)
+    }
+
+    /// Read blocks of the source up to the end of a record, and parse every complete record read so far.
+    fn parse_block(&mut self) -> io::Result<()> {
+        let quote = self.dialect.quote();
+        let end = loop {
+            let eof = self.read_block()?;
+            match record_end(&self.pending, quote) {
+                _ if eof => {
+                    self.done = true;
+                    break self.pending.len();
+                },
+                Some(end) => break end,
+                None => {}
+            }
+        };
+
+        let rest = self.pending.split_off(end);
+        let block = std::mem::replace(&mut self.pending, rest);
+        let (headers, dialect, mapping) = (&self.headers, &self.dialect, &self.mapping);
+        let records = chunks(&block, self.line, quote).into_par_iter()
+            .map(|(chunk, line)| records_from_chunk(chunk, line, headers, dialect, mapping))
+            .collect::<Vec<_>>();
+
+        self.records.extend(records.into_iter().flatten());
+        self.line += block.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        Ok(())
+    }
+}
+
+impl<R: io::Read> Iterator for CsvRecords<R> {
+    type Item = io::Result<Result<Record, RecordError>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Some(Ok(record));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.parse_block() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+/// The end of the last complete record in part of a CSV source starting at a record boundary, if it holds one.
+/// Newlines within quoted fields never end a record.
+fn record_end(data: &[u8], quote: Option<u8>) -> Option<usize> {
+    let mut quoted = false;
+    let mut end = None;
+    for (index, byte) in data.iter().enumerate() {
+        match byte {
+            byte if Some(*byte) == quote => quoted = !quoted,
+            b'\n' if !quoted => end = Some(index + 1),
+            _ => {}
+        }
+    }
+    end
+}
+
+/// Split the body of a CSV source into chunks of roughly `CHUNK_SIZE` bytes, each ending at the end of a record,
+/// alongside the line each chunk starts on. Newlines within quoted fields never end a chunk.
+fn chunks(body: &[u8], first_line: u64, quote: Option<u8>) -> Vec<(&[u8], u64)> {
+    let mut chunks = Vec::new();
+    let (mut start, mut line, mut lines) = (0, first_line, 0);
+    let mut quoted = false;
+
+    for (index, byte) in body.iter().enumerate() {
+        match byte {
+            byte if Some(*byte) == quote => quoted = !quoted,
+            b'\n' if !quoted => {
+                lines += 1;
+                if index + 1 - start >= CHUNK_SIZE {
+                    chunks.push((&body[start..=index], line));
+                    (start, line, lines) = (index + 1, line + lines, 0);
+                }
+            },
+            b'\n' => lines += 1,
+            _ => {}
+        }
+    }
+
+    if start < body.len() {
+        chunks.push((&body[start..], line));
+    }
+    chunks
+}
+
+/// Read every transaction from a chunk of the body of a CSV source, which starts on the given line.
+fn records_from_chunk(chunk: &[u8], first_line: u64, headers: &csv::StringRecord, dialect: &CsvDialect, mapping: &ColumnMapping) -> Vec<Result<Record, RecordError>> {
+    // NOTE: Rows are checked against the headers here, as without them the reader would check against the first row.
+    let mut reader = dialect.reader()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(chunk);
+
+    let line_of = |position: Option<&csv::Position>| first_line + position.map(csv::Position::line).unwrap_or(1) - 1;
+    let rows = Rows::new(headers, dialect, mapping);
+    let mut row = csv::StringRecord::new();
+    let mut records = Vec::new();
+
+    loop {
+        match reader.read_record(&mut row) {
+            Ok(false) => break,
+            Ok(true) if row.len() != headers.len() => {
+                records.push(Err(RecordError {
+                    line: line_of(row.position()),
+                    column: None,
+                    field: None,
+                    message: format!("found {} fields, but expected {}", row.len(), headers.len()),
+                    record: row.iter().collect::<Vec<_>>().join(",")
+                }));
+            },
+            Ok(true) => {
+                let line = line_of(row.position());
+                records.push(rows.transaction(&mut row)
+                    .map(|transaction| Record { line, transaction })
+                    .map_err(|e| csv_record_error(line, headers, &row, e)));
+            },
+            Err(e) => {
+                let line = line_of(e.position());
+                records.push(Err(csv_record_error(line, headers, &row, e)));
+            }
+        }
+    }
+
+    records
+}
+
+/// Reads the transactions of the rows of a CSV source, with its columns and values mapped to the fields of a transaction.
+struct Rows<'a> {
+    /// The field held by each column of the source.
+    fields: csv::StringRecord,
+
+    /// The columns that are not a field of a transaction, which are kept as its metadata, alongside their names.
+    metadata: Vec<(usize, String)>,
+
+    /// The delimiter and quoting of the source.
+    dialect: &'a CsvDialect,
+
+    /// The mapping of the columns of the source to the fields of a transaction.
+    mapping: &'a ColumnMapping
+}
+
+impl<'a> Rows<'a> {
+    /// Read the rows of a source with the header row.
+    fn new(headers: &csv::StringRecord, dialect: &'a CsvDialect, mapping: &'a ColumnMapping) -> Self {
+        let fields = mapping.fields(headers);
+        // NOTE: Every column that is not a field of a transaction is kept as its metadata, rather than ignored.
+        let metadata = fields.iter().enumerate()
+            .filter(|(_, field)| !FIELDS.contains(field))
+            .map(|(column, field)| (column, field.to_string()))
+            .collect();
+        Self { fields, metadata, dialect, mapping }
+    }
+
+    /// Read the transaction of a row with its fields trimmed, which is left with its values mapped.
+    fn transaction(&self, row: &mut csv::StringRecord) -> Result<Transaction, csv::Error> {
+        self.dialect.unquote(row);
+        self.mapping.map_values(&self.fields, row);
+        self.dialect.amount_format().normalize_row(&self.fields, row);
+
+        let mut transaction = row.deserialize::<Transaction>(Some(&self.fields))?;
+        for (column, field) in &self.metadata {
+            if let Some(value) = row.get(*column).filter(|value| !value.is_empty()) {
+                transaction.metadata.insert(field.clone(), value.to_string());
+            }
+        }
+        Ok(transaction)
+    }
+}
+
+/// Copy every row of a CSV source in a dialect whose transaction matches to a writer in the same dialect, exactly as it
+/// was read and under the same header, with the columns and values of the source mapped to the fields of a transaction.
+/// A row that can not be read as a transaction never matches. Returns the number of rows read and the number written.
+pub fn filter_rows<R, W, F>(reader: R, writer: W, dialect: &CsvDialect, mapping: &ColumnMapping, mut matches: F) -> io::Result<(u64, u64)>
+where
+    R: io::Read,
+    W: io::Write,
+    F: FnMut(&Transaction) -> bool
+{
+    let invalid = |e: csv::Error| io::Error::new(io::ErrorKind::InvalidData, e);
+
+    // NOTE: Only the header is trimmed, so every matching row is written exactly as it was read.
+    let mut reader = dialect.reader()
+        .trim(csv::Trim::Headers)
+        .from_reader(reader);
+    let headers = reader.headers().map_err(invalid)?.clone();
+    let rows = Rows::new(&headers, dialect, mapping);
+    if let Some(field) = ["type", "client", "tx"].into_iter().find(|field| !rows.fields.iter().any(|column| column == *field)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("a transaction source needs a {} column", field)));
+    }
+
+    let mut writer = dialect.writer().from_writer(writer);
+    writer.write_record(&headers)?;
+
+    let (mut read, mut written) = (0, 0);
+    let mut record = csv::ByteRecord::new();
+    while reader.read_byte_record(&mut record).map_err(invalid)? {
+        read += 1;
+        let transaction = csv::StringRecord::from_byte_record(record.clone()).ok()
+            .and_then(|mut row| {
+                row.trim();
+                rows.transaction(&mut row).ok()
+            });
+        if transaction.is_some_and(|transaction| matches(&transaction)) {
+            writer.write_byte_record(&record)?;
+            written += 1;
+        }
+    }
+    writer.flush()?;
+    Ok((read, written))
+}
+
+/// Reads the records of a CSV source as rows are appended to it, like `tail -f`.
+/// Only complete rows are read, so a row that is still being written is held back until its line ends.
+#[derive(Debug)]
+pub struct Tail<R> {
+    /// The source rows are appended to.
+    reader: R,
+
+    /// The bytes read that do not yet make up a complete row.
+    pending: Vec<u8>,
+
+    /// The header row, once it has been read.
+    headers: Option<csv::StringRecord>,
+
+    /// The line the next row starts on.
+    line: u64,
+
+    /// The delimiter and quoting of the source.
+    dialect: CsvDialect,
+
+    /// The mapping of the columns of the source to the fields of a transaction.
+    mapping: ColumnMapping
+}
+
+impl<R: io::Read> Tail<R> {
+    /// Start reading a source from its header row.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Vec::new(),
+            headers: None,
+            line: 1,
+            dialect: CsvDialect::default(),
+            mapping: ColumnMapping::default()
+        }
+    }
+
+    /// Read the source in another delimiter and quoting.
+    pub fn with_dialect(mut self, dialect: CsvDialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Map the columns of the source, and the values in them, to the fields of a transaction.
+    pub fn with_mapping(mut self, mapping: ColumnMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Read every complete row appended since the last poll, keeping track of the line each was read from.
+    /// A malformed row is returned as an error in place of its record, while a failure to read the source fails entirely.
+    pub fn poll(&mut self) -> io::Result<Vec<Result<Record, RecordError>>> {
+        self.reader.read_to_end(&mut self.pending)?;
+
+        let Some(end) = record_end(&self.pending, self.dialect.quote()) else {
+            return Ok(Vec::new());
+        };
+        let rows = self.pending.drain(..end).collect::<Vec<_>>();
+        let (mut body, mut line) = (rows.as_slice(), self.line);
+        self.line += rows.iter().filter(|&&byte| byte == b'\n').count() as u64;
+
+        let headers = match &self.headers {
+            Some(headers) => headers,
+            None => {
+                body = body.strip_prefix(UTF8_BOM).unwrap_or(body);
+                let mut reader = self.dialect.reader().from_reader(body);
+                let headers = reader.headers()?.clone();
+                let start = reader.position().clone();
+
+                body = &body[start.byte() as usize..];
+                line += start.line() - 1;
+                self.headers.insert(headers)
+            }
+        };
+
+        Ok(records_from_chunk(body, line, headers, &self.dialect, &self.mapping))
+    }
+}
+
+/// Read every transaction from a JSON Lines source, keeping track of the line each was read from.
+/// Blank lines are skipped, and a malformed line is returned as an error in place of its record.
+pub fn records_from_jsonl_reader<R: io::Read>(reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+    let mut records = Vec::new();
+
+    for (index, line) in io::BufRead::lines(io::BufReader::new(reader)).enumerate() {
+        let mut line = line?;
+        if index == 0 && line.starts_with('\u{feff}') {
+            line.remove(0);
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let line_number = index as u64 + 1;
+        records.push(serde_json::from_str::<JsonTransaction>(&line)
+            .map(|transaction| Record { line: line_number, transaction: transaction.into() })
+            .map_err(|e| {
+                // NOTE: serde_json appends the position to its message, which would always report line 1 here.
+                let message = e.to_string();
+                let position = format!(" at line {} column {}", e.line(), e.column());
+
+                RecordError {
+                    line: line_number,
+                    column: Some(e.column() as u64).filter(|column| *column > 0),
+                    field: None,
+                    message: message.strip_suffix(&position).unwrap_or(&message).to_string(),
+                    record: line.clone()
+                }
+            }));
+    }
+
+    Ok(records)
+}
+
+/// Describe a CSV row that could not be read, pointing at the offending field where the reader reports one.
+fn csv_record_error(line: u64, headers: &csv::StringRecord, row: &csv::StringRecord, error: csv::Error) -> RecordError {
+    let (field, message) = match error.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => (err.field(), err.kind().to_string()),
+        csv::ErrorKind::UnequalLengths { expected_len, len, .. } => (None, format!("found {} fields, but expected {}", len, expected_len)),
+        csv::ErrorKind::Utf8 { err, .. } => (Some(err.field() as u64), "invalid UTF-8".to_string()),
+        _ => (None, error.to_string())
+    };
+
+    RecordError {
+        line,
+        column: field.map(|field| field + 1),
+        field: field.and_then(|field| headers.get(field as usize)).map(str::to_string),
+        message,
+        record: row.iter().collect::<Vec<_>>().join(",")
+    }
+}
+
+/// Put records into chronological order by their timestamps, buffering at most `window` records at a time.
+/// A record can only move ahead of the `window` records before it, so the result may still be out of order.
+/// A record without a timestamp is ordered as if it had the timestamp of the record before it.
+pub fn reorder(records: Vec<Record>, window: usize) -> Vec<Record> {
+    let mut slots = records.into_iter().map(Some).collect::<Vec<_>>();
+    let mut buffer = BinaryHeap::with_capacity(window + 1);
+    let mut ordered = Vec::with_capacity(slots.len());
+    let mut latest = None;
+
+    for index in 0..slots.len() {
+        latest = slots[index].as_ref().and_then(|record| record.transaction.timestamp()).or(latest);
+        buffer.push(Reverse((latest, index)));
+
+        if buffer.len() > window {
+            let Reverse((_, next)) = buffer.pop().unwrap();
+            ordered.extend(slots[next].take());
+        }
+    }
+
+    while let Some(Reverse((_, next))) = buffer.pop() {
+        ordered.extend(slots[next].take());
+    }
+    ordered
+}
+
+/// Read every transaction from a CSV source, failing on the first malformed row.
+/// Whitespace around fields is trimmed, and rows without an amount are accepted.
+pub fn transactions_from_reader<R: io::Read>(reader: R) -> io::Result<Vec<Transaction>> {
+    records_from_reader(reader)?
+        .into_iter()
+        .map(|record| record
+            .map(|record| record.transaction)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::TransactionType;
+
+    #[test]
+    fn records_track_line_numbers() {
+        let csv = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nwithdrawal, 1, 2, 0.5";
+
+        let records = records_from_reader(csv.as_bytes()).unwrap().into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(records.iter().map(|record| record.line).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(records[1].transaction.id(), 2);
+    }
+
+    #[test]
+    fn malformed_rows_do_not_stop_reading() {
+        let csv = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nrefund, 1, 2, 0.5\ndeposit, 1\nwithdrawal, 1, 4, 0.5";
+
+        let records = records_from_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1].as_ref().unwrap_err().line, 3);
+        assert_eq!(records[2].as_ref().unwrap_err().line, 4);
+        assert_eq!(records[3].as_ref().unwrap().transaction.id(), 4);
+        assert!(transactions_from_reader(csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn parallel_chunks_match_sequential_parsing() {
+        let mut csv = String::from("type, client, tx, amount\n");
+        for id in 1..=100_000 {
+            match id % 1000 {
+                0 => csv.push_str(&format!("deposit, {}\n", id % 7)),
+                500 => csv.push_str(&format!("\"deposit\",{},{},\"1.\n5\"\n", id % 7, id)),
+                _ => csv.push_str(&format!("deposit, {}, {}, {}.{}\n", id % 7, id, id, id % 10_000))
+            }
+        }
+
+        let body = &csv.as_bytes()[csv.find('\n').unwrap() + 1..];
+        assert!(chunks(body, 2, Some(b'"')).len() > 1);
+
+        let records = records_from_reader(csv.as_bytes()).unwrap();
+        let chunk = records_from_chunk(body, 2, &csv::StringRecord::from(vec!["type", "client", "tx", "amount"]), &CsvDialect::default(), &ColumnMapping::default());
+        assert_eq!(records.len(), chunk.len());
+
+        for (parallel, sequential) in records.iter().zip(&chunk) {
+            match (parallel, sequential) {
+                (Ok(parallel), Ok(sequential)) => {
+                    assert_eq!(parallel.line, sequential.line);
+                    assert_eq!(parallel.transaction, sequential.transaction);
+                },
+                (Err(parallel), Err(sequential)) => assert_eq!(parallel, sequential),
+                _ => panic!("parallel and sequential parsing disagree at line {:?}", parallel.as_ref().map(|record| record.line))
+            }
+        }
+        // NOTE: The quoted amount of transaction 500 spans two lines, so every later row starts a line further down.
+        assert_eq!(records[999].as_ref().unwrap_err().line, 1002);
+        assert_eq!(records[1000].as_ref().unwrap().line, 1003);
+    }
+
+    #[test]
+    fn read_in_blocks_across_record_boundaries() {
+        let csv = "\u{feff}type,client,tx,amount,memo\ndeposit,1,1,1.0,\"spans\ntwo lines\"\ndeposit,one,2,0.5,\nwithdrawal,1,3,0.5,\"a, b\"";
+
+        // NOTE: Blocks of a few bytes end within the header, within quoted fields and within every row.
+        let records = CsvRecords::with_block_size(csv.as_bytes(), CsvDialect::default(), ColumnMapping::default(), 3).unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].as_ref().unwrap().transaction, Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("1.0").unwrap())).with_metadata("memo", "spans\ntwo lines"));
+        assert_eq!(records[1].as_ref().unwrap_err().line, 4);
+        assert_eq!(records[2].as_ref().unwrap().line, 5);
+    }
+
+    #[test]
+    fn malformed_rows_describe_the_field() {
+        let csv = "type, client, tx, amount\ndeposit, 1, 1, 1.0\ndeposit, one, 2, 0.5";
+
+        let records = records_from_reader(csv.as_bytes()).unwrap();
+        let error = records[1].as_ref().unwrap_err();
+
+        assert_eq!(error.line, 3);
+        assert_eq!(error.column, Some(2));
+        assert_eq!(error.field.as_deref(), Some("client"));
+        assert_eq!(error.record, "deposit,one,2,0.5");
+
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+{"type": "deposit", "client": "one", "tx": 2}"#;
+
+        let records = InputFormat::Jsonl.read(jsonl.as_bytes()).unwrap();
+        let error = records[1].as_ref().unwrap_err();
+
+        assert_eq!(error.line, 2);
+        assert!(error.column.is_some());
+        assert!(!error.message.contains("line 1"));
+        assert_eq!(error.record, r#"{"type": "deposit", "client": "one", "tx": 2}"#);
+    }
+
+    #[test]
+    fn other_fields_are_kept_as_metadata() {
+        let csv = "type, client, tx, amount, memo, reference\ndeposit, 1, 1, 1.5, rent, 00123\ndispute, 1, 1, , ,\n";
+
+        let records = records_from_reader(csv.as_bytes()).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction, Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("1.5").unwrap()))
+            .with_metadata("memo", "rent")
+            .with_metadata("reference", "00123"));
+        assert!(records[1].as_ref().unwrap().transaction.metadata().is_empty());
+
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0", "memo": "rent", "merchant": 42, "note": null}"#;
+        let records = InputFormat::Jsonl.read(jsonl.as_bytes()).unwrap();
+        let transaction = &records[0].as_ref().unwrap().transaction;
+        assert_eq!(transaction.metadata().iter().map(|(key, value)| (key.as_str(), value.as_str())).collect::<Vec<_>>(), vec![("memo", "rent"), ("merchant", "42")]);
+        assert_eq!(serde_json::from_str::<Transaction>(&serde_json::to_string(transaction).unwrap()).unwrap(), *transaction);
+    }
+
+    #[test]
+    fn byte_order_marks_and_other_encodings() {
+        let csv = "type, client, tx, amount, memo\ndeposit, 1, 1, 1.5, café\n";
+        let expected = Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("1.5").unwrap())).with_metadata("memo", "café");
+        let read = |encoding: Encoding, bytes: Vec<u8>| records_from_reader(encoding.decode(bytes.as_slice())).unwrap().remove(0).unwrap().transaction;
+
+        assert_eq!(records_from_reader([UTF8_BOM, csv.as_bytes()].concat().as_slice()).unwrap()[0].as_ref().unwrap().transaction, expected);
+        let utf16le = [&[0xff, 0xfe][..], &csv.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>()].concat();
+        assert_eq!(read(Encoding::Utf8, utf16le), expected);
+        assert_eq!(read(Encoding::Utf16Be, csv.encode_utf16().flat_map(u16::to_be_bytes).collect()), expected);
+        assert_eq!(read(Encoding::Latin1, csv.chars().map(|c| c as u8).collect()), expected);
+        assert_eq!(Encoding::from_str("UTF-16LE"), Ok(Encoding::Utf16Le));
+    }
+
+    #[test]
+    fn tail_reads_appended_rows() {
+        // NOTE: A deque is drained as it is read, so pushing onto it behaves like appending to a file.
+        let mut tail = Tail::new(VecDeque::new());
+        let append = |tail: &mut Tail<VecDeque<u8>>, data: &str| {
+            tail.reader.extend(data.as_bytes());
+            tail.poll().unwrap()
+        };
+
+        let lines = |records: Vec<Result<Record, RecordError>>| records.iter().map(|record| record.as_ref().map_or_else(|e| e.line, |record| record.line)).collect::<Vec<_>>();
+
+        assert!(append(&mut tail, "type, client, tx, am").is_empty());
+        assert_eq!(lines(append(&mut tail, "ount\ndeposit, 1, 1, 1.0\nwithdrawal, 1")), vec![2]);
+        assert!(append(&mut tail, "").is_empty());
+        assert_eq!(lines(append(&mut tail, ", 2, 0.5\n\"deposit\",2,3,\"1.\n")), vec![3]);
+        assert_eq!(lines(append(&mut tail, "5\"\ndeposit, 2, 4, 1.0\n")), vec![4, 6]);
+    }
+
+    #[test]
+    fn jsonl_records() {
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0001"}
+
+{"type": "transfer", "client": 1, "tx": 2, "amount": 0.5, "to": 2}
+{"type": "dispute", "client": 1, "tx": 1}"#;
+
+        let records = InputFormat::Jsonl.read(jsonl.as_bytes()).unwrap().into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+
+        assert_eq!(records.iter().map(|record| record.line).collect::<Vec<_>>(), vec![1, 3, 4]);
+        assert_eq!(records[0].transaction.amount().unwrap().to_string(), "1.0001");
+        assert_eq!(records[1].transaction.destination(), Some(2));
+        assert_eq!(records[2].transaction.amount(), None);
+    }
+
+    #[test]
+    fn reorder_within_a_window() {
+        let csv = "type, client, tx, amount, timestamp
+                   deposit, 1, 1, 1.0, 2024-01-01T00:00:02Z
+                   deposit, 1, 2, 1.0, 2024-01-01T00:00:01Z
+                   dispute, 1, 2, ,
+                   deposit, 1, 3, 1.0, 2024-01-01T00:00:03Z
+                   deposit, 1, 4, 1.0, 2024-01-01T00:00:00Z";
+
+        let records = records_from_reader(csv.as_bytes()).unwrap().into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(records[0].transaction.timestamp().unwrap().to_rfc3339(), "2024-01-01T00:00:02+00:00");
+        assert_eq!(records[2].transaction.timestamp(), None);
+
+        let lines = |records: Vec<Record>| records.iter().map(|record| record.line).collect::<Vec<_>>();
+        assert_eq!(lines(reorder(records.clone(), 0)), vec![2, 3, 4, 5, 6]);
+        assert_eq!(lines(reorder(records.clone(), 1)), vec![3, 4, 2, 6, 5]);
+        assert_eq!(lines(reorder(records, 4)), vec![6, 3, 4, 2, 5]);
+    }
+
+    #[test]
+    fn decompress_by_magic_bytes() {
+        let csv = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        io::Write::write_all(&mut gzip, csv.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(csv.as_bytes(), 0).unwrap();
+
+        for compressed in [csv.as_bytes(), &gzip, &zstd] {
+            let records = records_from_reader(decompress(compressed).unwrap()).unwrap();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].as_ref().unwrap().transaction.id(), 1);
+        }
+    }
+
+    #[test]
+    fn detect_format_from_path() {
+        assert_eq!(InputFormat::from_path("transactions.csv"), Some(InputFormat::Csv));
+        assert_eq!(InputFormat::from_path("transactions.jsonl"), Some(InputFormat::Jsonl));
+        assert_eq!(InputFormat::from_path("transactions.jsonl.gz"), Some(InputFormat::Jsonl));
+        assert_eq!(InputFormat::from_path("transactions.csv.zst"), Some(InputFormat::Csv));
+        assert_eq!(InputFormat::from_path("transactions.gz"), None);
+        assert_eq!(InputFormat::from_path("transactions"), None);
+    }
+}
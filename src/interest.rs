@@ -0,0 +1,112 @@
+use std::{fmt, str::FromStr};
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// The number of decimal places accrued interest is kept to until it is posted, well beyond any posting precision.
+pub const ACCRUAL_SCALE: i64 = 12;
+
+/// An enumeration of how often accrued interest is posted to the accounts.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InterestPeriod {
+    /// At the end of every day.
+    Daily,
+
+    /// At the end of the last day of every month.
+    #[default]
+    Monthly,
+}
+
+impl InterestPeriod {
+    /// Whether the period ends with the day, so the interest accrued by then is posted.
+    pub fn ends_on(self, day: NaiveDate) -> bool {
+        match self {
+            Self::Daily => true,
+            Self::Monthly => day.succ_opt().is_none_or(|next| next.month() != day.month()),
+        }
+    }
+}
+
+impl FromStr for InterestPeriod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Self::Daily),
+            "monthly" => Ok(Self::Monthly),
+            _ => Err(format!("unknown interest period '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for InterestPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daily => write!(f, "daily"),
+            Self::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+/// The interest earned on available funds, accrued daily and posted at the end of every period.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interest {
+    /// The yearly interest rate, as a percentage, of which a 365th is accrued every day.
+    pub rate: BigDecimal,
+
+    /// How often the accrued interest is posted.
+    pub period: InterestPeriod
+}
+
+impl Interest {
+    /// The interest accrued in a day on the available funds, kept to the accrual scale.
+    pub fn daily(&self, available: &BigDecimal) -> BigDecimal {
+        (available * &self.rate / BigDecimal::from(36500)).with_scale_round(ACCRUAL_SCALE, RoundingMode::Down)
+    }
+}
+
+/// The interest accrued on one balance of a client that has not yet been posted.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccruedInterest {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The currency of the balance, or `None` for the default currency.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+
+    /// The interest accrued since it was last posted.
+    pub amount: BigDecimal
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType};
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::from_str(timestamp).unwrap()
+    }
+
+    #[test]
+    fn interest_is_posted_at_the_end_of_each_month() {
+        let mut engine = Engine::new().with_interest(Some(Interest { rate: BigDecimal::from(10), period: InterestPeriod::Monthly }));
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(3650))).with_timestamp(at("2024-01-01T09:00:00Z"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from(650))).with_timestamp(at("2024-01-15T09:00:00Z"))).unwrap();
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from(3000));
+
+        let postings = engine.advance(at("2024-02-01T00:00:00Z")).unwrap();
+
+        // 14 days at 1 a day, and 17 days at 0.821917808219 a day.
+        assert_eq!(postings.iter().map(|(posting, _)| (posting.type_(), posting.amount().cloned(), posting.timestamp())).collect::<Vec<_>>(), vec![
+            (TransactionType::Interest, Some(BigDecimal::from_str("27.9726").unwrap()), Some(at("2024-02-01T00:00:00Z")))
+        ]);
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from_str("3027.9726").unwrap());
+        assert_eq!(engine.snapshot().unwrap().accrued, vec![
+            AccruedInterest { client: 1, currency: None, amount: BigDecimal::from_str("0.000002739723").unwrap() }
+        ]);
+    }
+}
@@ -0,0 +1,137 @@
+use std::{io, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDate, Utc};
+use roxmltree::{Document, Node};
+
+use crate::{Record, RecordError, Transaction, TransactionType};
+
+/// Read every payment of an ISO 20022 payment initiation message, as either a pain.001 customer credit transfer
+/// initiation or a pain.008 customer direct debit initiation, keeping track of the line each payment starts on.
+///
+/// A credit transfer is a deposit to the client of the creditor account, and a direct debit a withdrawal from the client
+/// of the debtor account, where the client is the identification of the account in `Othr/Id`. The end to end id of the
+/// payment is its transaction id, the currency of its instructed amount its currency, and the requested execution or
+/// collection date of its payment information block its timestamp, at midnight.
+///
+/// A malformed payment is returned as an error in place of its record, while a source that is not a well-formed payment
+/// initiation message fails entirely.
+pub fn records_from_iso20022_reader<R: io::Read>(mut reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+
+    let document = Document::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let (type_, payment, date, account) = match document.root_element().children().find(Node::is_element).map(|node| node.tag_name().name()) {
+        Some("CstmrCdtTrfInitn") => (TransactionType::Deposit, "CdtTrfTxInf", "ReqdExctnDt", "CdtrAcct"),
+        Some("CstmrDrctDbtInitn") => (TransactionType::Withdrawal, "DrctDbtTxInf", "ReqdColltnDt", "DbtrAcct"),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "not a pain.001 or pain.008 payment initiation message"))
+    };
+
+    let mut records = Vec::new();
+    for information in document.descendants().filter(|node| node.has_tag_name("PmtInf")) {
+        let timestamp = child(information, date).map(date_of);
+        for node in information.children().filter(|node| node.has_tag_name(payment)) {
+            let position = document.text_pos_at(node.range().start);
+            let error = |field: &str, message: String| RecordError {
+                line: position.row.into(),
+                column: Some(position.col.into()),
+                field: Some(field.to_string()),
+                message,
+                record: text[node.range()].split_whitespace().collect::<Vec<_>>().join(" ")
+            };
+
+            let client = child(node, account)
+                .and_then(|node| child(node, "Id")).and_then(|node| child(node, "Othr")).and_then(|node| child(node, "Id"))
+                .ok_or_else(|| error(account, "missing account identification".to_string()))
+                .and_then(|node| parse(node, account, &error));
+            let id = child(node, "PmtId").and_then(|node| child(node, "EndToEndId"))
+                .ok_or_else(|| error("EndToEndId", "missing end to end id".to_string()))
+                .and_then(|node| parse(node, "EndToEndId", &error));
+            let amount = node.descendants().find(|node| node.has_tag_name("InstdAmt"))
+                .ok_or_else(|| error("InstdAmt", "missing instructed amount".to_string()))
+                .and_then(|node| parse::<BigDecimal>(node, "InstdAmt", &error).map(|amount| (amount, node.attribute("Ccy"))));
+            let timestamp = timestamp.clone().transpose().map_err(|message| error(date, message));
+
+            records.push(client.and_then(|client| id.and_then(|id| amount.and_then(|(amount, currency)| timestamp.map(|timestamp| {
+                let mut transaction = Transaction::new(type_, client, id, Some(amount));
+                transaction.currency = currency.map(str::to_string);
+                transaction.timestamp = timestamp;
+                Record { line: position.row.into(), transaction }
+            })))));
+        }
+    }
+
+    Ok(records)
+}
+
+/// The first child element of a node with the given name, ignoring its namespace.
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+/// Parse the trimmed text of an element.
+fn parse<T: FromStr>(node: Node, field: &str, error: &impl Fn(&str, String) -> RecordError) -> Result<T, RecordError> {
+    let text = node.text().unwrap_or_default().trim();
+    text.parse().map_err(|_| error(field, format!("invalid value '{}'", text)))
+}
+
+/// The time of a date element at midnight, which is either the date itself, or holds it in a `Dt` or `DtTm` element
+/// as in later versions of the messages.
+fn date_of(node: Node) -> Result<DateTime<Utc>, String> {
+    let value = match (child(node, "Dt"), child(node, "DtTm")) {
+        (Some(date), _) => date.text(),
+        (None, Some(time)) => {
+            let value = time.text().unwrap_or_default().trim();
+            return value.parse().map_err(|_| format!("invalid time '{}'", value));
+        },
+        (None, None) => node.text()
+    }.unwrap_or_default().trim();
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(Default::default()).and_utc())
+        .map_err(|_| format!("invalid date '{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payment_initiation_messages() {
+        let pain001 = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.03">
+  <CstmrCdtTrfInitn>
+    <GrpHdr><MsgId>MSG1</MsgId></GrpHdr>
+    <PmtInf>
+      <ReqdExctnDt>2024-01-02</ReqdExctnDt>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>1</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="EUR">100.50</InstdAmt></Amt>
+        <CdtrAcct><Id><Othr><Id>7</Id></Othr></Id></CdtrAcct>
+      </CdtTrfTxInf>
+      <CdtTrfTxInf>
+        <PmtId><EndToEndId>NOTPROVIDED</EndToEndId></PmtId>
+        <Amt><InstdAmt Ccy="EUR">1</InstdAmt></Amt>
+        <CdtrAcct><Id><Othr><Id>7</Id></Othr></Id></CdtrAcct>
+      </CdtTrfTxInf>
+    </PmtInf>
+  </CstmrCdtTrfInitn>
+</Document>"#;
+        let records = records_from_iso20022_reader(pain001.as_bytes()).unwrap();
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.line, 7);
+        assert_eq!(record.transaction, Transaction::new(TransactionType::Deposit, 7, 1, Some(BigDecimal::from_str("100.50").unwrap()))
+            .with_currency("EUR").with_timestamp(DateTime::from_str("2024-01-02T00:00:00Z").unwrap()));
+        let error = records[1].as_ref().unwrap_err();
+        assert_eq!((error.line, error.field.as_deref(), error.message.as_str()), (12, Some("EndToEndId"), "invalid value 'NOTPROVIDED'"));
+
+        let pain008 = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.008.001.02"><CstmrDrctDbtInitn><PmtInf>
+            <ReqdColltnDt>2024-01-03</ReqdColltnDt>
+            <DrctDbtTxInf><PmtId><EndToEndId>2</EndToEndId></PmtId><InstdAmt Ccy="EUR">40</InstdAmt><DbtrAcct><Id><Othr><Id>7</Id></Othr></Id></DbtrAcct></DrctDbtTxInf>
+        </PmtInf></CstmrDrctDbtInitn></Document>"#;
+        let records = records_from_iso20022_reader(pain008.as_bytes()).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction.type_(), TransactionType::Withdrawal);
+
+        assert!(records_from_iso20022_reader("<Document><CstmrPmtStsRpt/></Document>".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use std::{error, fmt, fs::{File, OpenOptions}, io::{self, BufRead, Write}, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Applied, Engine, Storage, Tenants, Transaction, TransactionError};
+
+/// An entry of the journal, recording an accepted transaction and what it applied.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The position of the entry within the journal, starting at 1.
+    pub sequence: u64,
+
+    /// The accepted transaction.
+    pub transaction: Transaction,
+
+    /// What the transaction applied to the accounts.
+    pub applied: Applied
+}
+
+/// An enumeration of the reasons a journal can fail to be replayed.
+#[derive(Debug)]
+pub enum JournalError {
+    /// The journal could not be read.
+    Io(io::Error),
+
+    /// An entry in the middle of the journal is malformed.
+    Format(u64, serde_json::Error),
+
+    /// Replaying an entry did not produce the outcome that was recorded.
+    Diverged(u64, Result<Box<Applied>, TransactionError>),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Format(line, e) => write!(f, "malformed journal entry on line {}: {}", line, e),
+            Self::Diverged(sequence, Ok(applied)) => write!(f, "journal entry {} replayed differently: {:?}", sequence, applied),
+            Self::Diverged(sequence, Err(e)) => write!(f, "journal entry {} was rejected on replay: {}", sequence, e),
+        }
+    }
+}
+
+impl error::Error for JournalError {}
+
+impl From<io::Error> for JournalError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// An append-only journal, written as one JSON entry per line.
+/// Each entry is flushed as soon as it is appended, so the journal survives the process crashing.
+#[derive(Debug)]
+pub struct Journal<W: Write> {
+    /// Where the entries are written.
+    writer: W,
+
+    /// The sequence number of the last entry.
+    sequence: u64
+}
+
+impl Journal<File> {
+    /// Open a journal file for appending, creating it if it does not exist.
+    /// Sequence numbers carry on from the entries already in the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let sequence = match File::open(&path) {
+            Ok(file) => io::BufReader::new(file).lines().count() as u64,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e)
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(Self {
+            writer: file,
+            sequence
+        })
+    }
+}
+
+impl<W: Write> Journal<W> {
+    /// Create a journal that writes to the start of a writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            sequence: 0
+        }
+    }
+
+    /// Append an accepted transaction and what it applied.
+    pub fn append(&mut self, transaction: &Transaction, applied: &Applied) -> io::Result<()> {
+        let entry = JournalEntry {
+            sequence: self.sequence + 1,
+            transaction: transaction.clone(),
+            applied: applied.clone()
+        };
+
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.writer.flush()?;
+
+        self.sequence = entry.sequence;
+        Ok(())
+    }
+
+    /// Consume the journal, returning its writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Replay every entry of a journal through an engine, checking that each applies exactly as recorded.
+/// A torn final entry, left by a crash part way through a write, is ignored.
+/// Returns the number of entries replayed.
+pub fn replay<R: io::Read, S: Storage>(reader: R, engine: &mut Engine<S>) -> Result<u64, JournalError> {
+    replay_with(reader, |transaction| engine.process(transaction))
+}
+
+/// Replay every entry of a journal in the books of its tenant, as in [`replay`].
+pub fn replay_tenants<R: io::Read, S: Storage>(reader: R, tenants: &mut Tenants<'_, S>) -> Result<u64, JournalError> {
+    replay_with(reader, |transaction| tenants.process(transaction))
+}
+
+/// Replay every entry of a journal by processing its transaction with the given function, as in [`replay`].
+fn replay_with<R, F>(reader: R, mut process: F) -> Result<u64, JournalError>
+where
+    R: io::Read,
+    F: FnMut(&Transaction) -> Result<Applied, TransactionError>
+{
+    let mut reader = io::BufReader::new(reader);
+    let mut line = String::new();
+    let mut number = 0;
+    let mut replayed = 0;
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        number += 1;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = match serde_json::from_str::<JournalEntry>(&line) {
+            Ok(entry) => entry,
+            Err(_) if !line.ends_with('\n') => break,
+            Err(e) => return Err(JournalError::Format(number, e))
+        };
+
+        match process(&entry.transaction) {
+            Ok(applied) if applied == entry.applied => replayed += 1,
+            result => return Err(JournalError::Diverged(entry.sequence, result.map(Box::new)))
+        }
+    }
+
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Ownership, TransactionType};
+
+    fn journal() -> Vec<u8> {
+        let transactions = [
+            Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10").unwrap())),
+            Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from_str("4").unwrap())),
+            Transaction::new(TransactionType::Dispute, 1, 1, None),
+        ];
+
+        let mut engine = Engine::new();
+        let mut journal = Journal::new(Vec::new());
+        for transaction in &transactions {
+            let applied = engine.process(transaction).unwrap();
+            journal.append(transaction, &applied).unwrap();
+        }
+        journal.into_inner()
+    }
+
+    #[test]
+    fn replay_journal() {
+        let mut engine = Engine::new();
+
+        assert_eq!(replay(journal().as_slice(), &mut engine).unwrap(), 3);
+
+        let client = engine.client(1).unwrap().unwrap();
+        assert_eq!(client.available(), BigDecimal::from(-4));
+        assert_eq!(client.held(), BigDecimal::from(10));
+    }
+
+    #[test]
+    fn replay_ignores_torn_entry() {
+        let mut journal = journal();
+        journal.extend_from_slice(br#"{"sequence":4,"transaction":{"type":"dep"#);
+
+        let mut engine = Engine::new();
+
+        assert_eq!(replay(journal.as_slice(), &mut engine).unwrap(), 3);
+    }
+
+    #[test]
+    fn replay_detects_divergence() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, Some(BigDecimal::from(1)))).unwrap();
+
+        assert!(matches!(replay(journal().as_slice(), &mut engine), Err(JournalError::Diverged(1, Err(TransactionError::DuplicateTransaction(1))))));
+    }
+
+    #[test]
+    fn replay_resolved_joint_accounts() {
+        let mut engine = Engine::new().with_ownership(Some(Ownership::default().with_owner(100, 1).with_owner(100, 2)));
+        let mut journal = Journal::new(Vec::new());
+        for transaction in [Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())), Transaction::new(TransactionType::Deposit, 2, 2, Some(5.into()))] {
+            let applied = engine.process(&transaction).unwrap();
+            journal.append(&engine.resolve(&transaction), &applied).unwrap();
+        }
+
+        // NOTE: The resolved transactions are replayed onto the shared account, with or without the owners.
+        let mut replayed = Engine::new();
+        assert_eq!(replay(journal.into_inner().as_slice(), &mut replayed).unwrap(), 2);
+        assert_eq!(replayed.client(100).unwrap().unwrap().available(), BigDecimal::from(15));
+        assert!(replayed.client(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn replay_tenants_apart() {
+        let mut journal = Journal::new(Vec::new());
+        let mut tenants = Tenants::new(|tenant| Ok(Engine::new().with_tenant(tenant.map(str::to_string))));
+        for transaction in [Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())), Transaction::new(TransactionType::Deposit, 1, 1, Some(3.into())).with_tenant("acme")] {
+            let applied = tenants.process(&transaction).unwrap();
+            journal.append(&transaction, &applied).unwrap();
+        }
+        let journal = journal.into_inner();
+
+        assert!(matches!(replay(journal.as_slice(), &mut Engine::new()), Err(JournalError::Diverged(2, Err(TransactionError::ForeignTenant(_))))));
+
+        let mut replayed = Tenants::new(|tenant| Ok(Engine::new().with_tenant(tenant.map(str::to_string))));
+        assert_eq!(replay_tenants(journal.as_slice(), &mut replayed).unwrap(), 2);
+        assert_eq!(replayed.engine(Some("acme")).unwrap().client(1).unwrap().unwrap().available(), BigDecimal::from(3));
+        assert_eq!(replayed.engine(None).unwrap().client(1).unwrap().unwrap().available(), BigDecimal::from(10));
+    }
+}
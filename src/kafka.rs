@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, error, fmt, str::FromStr, time::Duration};
+
+use rdkafka::{
+    ClientConfig, Message, Offset, TopicPartitionList,
+    consumer::{BaseConsumer, CommitMode, Consumer},
+    error::KafkaError
+};
+
+#[cfg(feature = "avro")]
+use crate::SchemaRegistry;
+use crate::{SourceOffset, Transaction, transaction::JsonTransaction};
+
+/// How long to wait for the brokers to describe the topic when connecting.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A Kafka topic to consume transactions from, written as `kafka://broker[,broker...]/topic`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KafkaUrl {
+    /// The comma separated list of brokers to bootstrap from.
+    pub brokers: String,
+
+    /// The topic to consume.
+    pub topic: String
+}
+
+impl FromStr for KafkaUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rest = s.strip_prefix("kafka://")
+            .ok_or_else(|| format!("unsupported source '{}', expected kafka://broker/topic", s))?;
+
+        match rest.split_once('/') {
+            Some((brokers, topic)) if !brokers.is_empty() && !topic.is_empty() && !topic.contains('/') => Ok(Self {
+                brokers: brokers.to_string(),
+                topic: topic.to_string()
+            }),
+            _ => Err(format!("invalid source '{}', expected kafka://broker/topic", s))
+        }
+    }
+}
+
+impl fmt::Display for KafkaUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "kafka://{}/{}", self.brokers, self.topic)
+    }
+}
+
+/// An enumeration of the reasons a message could not be consumed.
+#[derive(Debug)]
+pub enum KafkaSourceError {
+    /// The consumer failed to talk to the brokers.
+    Kafka(KafkaError),
+
+    /// The payload of a message is not a transaction.
+    Format(SourceOffset, String),
+}
+
+impl fmt::Display for KafkaSourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kafka(e) => write!(f, "{}", e),
+            Self::Format(offset, e) => write!(f, "message {} in partition {} of '{}' is malformed: {}", offset.offset, offset.partition, offset.topic, e),
+        }
+    }
+}
+
+impl error::Error for KafkaSourceError {}
+
+impl From<KafkaError> for KafkaSourceError {
+    fn from(e: KafkaError) -> Self {
+        Self::Kafka(e)
+    }
+}
+
+/// A consumer of transactions from every partition of a Kafka topic, with each message holding one JSON transaction,
+/// or one Avro record in the Confluent wire format when a schema registry is given.
+/// Offsets are only committed when asked to, so they can be committed once the state they lead to has been saved.
+pub struct KafkaSource {
+    /// The consumer, manually assigned every partition of the topic.
+    consumer: BaseConsumer,
+
+    /// The offset of the next message to consume in each partition that has been read from.
+    positions: BTreeMap<i32, i64>,
+
+    /// The topic being consumed.
+    topic: String,
+
+    /// The registry of the schemas messages are written with, when they are Avro rather than JSON.
+    #[cfg(feature = "avro")]
+    registry: Option<SchemaRegistry>
+}
+
+impl KafkaSource {
+    /// Connect to the brokers and start consuming every partition of the topic.
+    /// Partitions are resumed from the given offsets, as saved in a snapshot, and otherwise from the group's committed offsets.
+    pub fn connect(url: &KafkaUrl, group: &str, offsets: &[SourceOffset]) -> Result<Self, KafkaError> {
+        let consumer: BaseConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &url.brokers)
+            .set("group.id", group)
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .create()?;
+
+        let metadata = consumer.fetch_metadata(Some(&url.topic), METADATA_TIMEOUT)?;
+        let mut assignment = TopicPartitionList::new();
+        let mut positions = BTreeMap::new();
+
+        for partition in metadata.topics().iter().flat_map(|topic| topic.partitions()) {
+            let saved = offsets.iter().find(|offset| offset.topic == url.topic && offset.partition == partition.id());
+            if let Some(saved) = saved {
+                positions.insert(saved.partition, saved.offset);
+            }
+
+            assignment.add_partition_offset(&url.topic, partition.id(), saved.map_or(Offset::Stored, |saved| Offset::Offset(saved.offset)))?;
+        }
+        consumer.assign(&assignment)?;
+
+        Ok(Self {
+            consumer,
+            positions,
+            topic: url.topic.clone(),
+            #[cfg(feature = "avro")]
+            registry: None
+        })
+    }
+
+    /// Decode every message as an Avro record in the Confluent wire format, with the schemas of the given registry.
+    #[cfg(feature = "avro")]
+    pub fn with_schema_registry(mut self, registry: SchemaRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Wait up to the timeout for the next transaction, alongside the offset of the message it was read from.
+    /// Returns `None` if no message arrived in time. A malformed message still counts as consumed.
+    pub fn poll(&mut self, timeout: Duration) -> Option<Result<(SourceOffset, Transaction), KafkaSourceError>> {
+        let message = match self.consumer.poll(timeout)? {
+            Ok(message) => message,
+            Err(e) => return Some(Err(e.into()))
+        };
+
+        let offset = SourceOffset {
+            topic: message.topic().to_string(),
+            partition: message.partition(),
+            offset: message.offset()
+        };
+        self.positions.insert(offset.partition, offset.offset + 1);
+
+        let payload = message.payload().unwrap_or_default();
+        #[cfg(feature = "avro")]
+        if let Some(registry) = &mut self.registry {
+            return Some(registry.decode(payload)
+                .map(|transaction| (offset.clone(), transaction))
+                .map_err(|e| KafkaSourceError::Format(offset, e)));
+        }
+
+        Some(serde_json::from_slice::<JsonTransaction>(payload)
+            .map(|transaction| (offset.clone(), transaction.into()))
+            .map_err(|e| KafkaSourceError::Format(offset, e.to_string())))
+    }
+
+    /// The offset of the next message to consume in each partition that has been read from.
+    pub fn offsets(&self) -> Vec<SourceOffset> {
+        self.positions.iter()
+            .map(|(partition, offset)| SourceOffset {
+                topic: self.topic.clone(),
+                partition: *partition,
+                offset: *offset
+            })
+            .collect()
+    }
+
+    /// Commit the offsets reached so far to the consumer group, waiting for the brokers to acknowledge them.
+    pub fn commit(&self) -> Result<(), KafkaError> {
+        if self.positions.is_empty() {
+            return Ok(());
+        }
+
+        let mut offsets = TopicPartitionList::new();
+        for (partition, offset) in &self.positions {
+            offsets.add_partition_offset(&self.topic, *partition, Offset::Offset(*offset))?;
+        }
+        self.consumer.commit(&offsets, CommitMode::Sync)
+    }
+}
+
+impl fmt::Debug for KafkaSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KafkaSource")
+            .field("topic", &self.topic)
+            .field("positions", &self.positions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_kafka_urls() {
+        let url = KafkaUrl::from_str("kafka://one:9092,two:9092/transactions").unwrap();
+
+        assert_eq!(url.brokers, "one:9092,two:9092");
+        assert_eq!(url.topic, "transactions");
+        assert_eq!(url.to_string(), "kafka://one:9092,two:9092/transactions");
+
+        assert!(KafkaUrl::from_str("http://localhost/transactions").is_err());
+        assert!(KafkaUrl::from_str("kafka://localhost").is_err());
+        assert!(KafkaUrl::from_str("kafka:///transactions").is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use std::{collections::BTreeMap, fmt};
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+
+use crate::{Applied, Client, Transaction, TransactionType};
+
+/// An enumeration of the accounts of the engine's internal ledger.
+///
+/// Every account is kept per currency, with its balance as its credits less its debits, so the funds the engine
+/// owes its clients are positive, and the balances of every account of a currency always sum to zero.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LedgerAccount {
+    /// The available funds of a client.
+    Available(u16),
+
+    /// The held funds of a client.
+    Held(u16),
+
+    /// Funds moving between the engine and the outside world, such as deposits, withdrawals and the amounts claimed back
+    /// by disputed withdrawals, and the funds exchanged by conversions.
+    Suspense,
+
+    /// Funds returned to clients by chargebacks of their withdrawals, which the engine has not recovered.
+    ChargebackLoss,
+
+    /// Interest credited to clients.
+    InterestExpense,
+
+    /// The balances clients already had when their accounts were restored, rather than built up by transactions.
+    OpeningBalances,
+}
+
+impl fmt::Display for LedgerAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Available(client) => write!(f, "client:{}:available", client),
+            Self::Held(client) => write!(f, "client:{}:held", client),
+            Self::Suspense => write!(f, "suspense"),
+            Self::ChargebackLoss => write!(f, "chargeback_loss"),
+            Self::InterestExpense => write!(f, "interest_expense"),
+            Self::OpeningBalances => write!(f, "opening_balances"),
+        }
+    }
+}
+
+impl Serialize for LedgerAccount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A single movement of funds, debiting one account of the ledger and crediting another by the same amount.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LedgerEntry {
+    /// The id of the transaction that moved the funds, or 0 for a movement the engine made itself.
+    #[serde(rename = "tx")]
+    pub id: u32,
+
+    /// The type of the transaction that moved the funds.
+    #[serde(rename = "type")]
+    pub type_: TransactionType,
+
+    /// When the transaction happened, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// The account the funds left.
+    pub debit: LedgerAccount,
+
+    /// The account the funds arrived in.
+    pub credit: LedgerAccount,
+
+    /// The currency of the amount, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// The amount moved.
+    pub amount: BigDecimal,
+
+    /// The metadata of the transaction that moved the funds.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>
+}
+
+/// A row of the trial balance of a ledger.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TrialBalance {
+    /// The account.
+    pub account: LedgerAccount,
+
+    /// The currency of the balance, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// The credits of the account less its debits.
+    pub balance: BigDecimal
+}
+
+/// A double-entry ledger of every movement of funds between the client accounts and the engine's own accounts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ledger {
+    /// The balance of every account the ledger has posted to, in each currency.
+    balances: BTreeMap<(LedgerAccount, Option<String>), BigDecimal>,
+
+    /// Every movement posted, in the order it was posted.
+    entries: Vec<LedgerEntry>
+}
+
+impl Ledger {
+    /// Create an empty ledger.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every movement posted, in the order it was posted.
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// The credits of an account less its debits, in a currency.
+    pub fn balance(&self, account: LedgerAccount, currency: Option<&str>) -> BigDecimal {
+        self.balances.get(&(account, currency.map(str::to_string))).cloned().unwrap_or_default()
+    }
+
+    /// The balance of every account the ledger has posted to, ordered by account and then currency.
+    pub fn trial_balance(&self) -> Vec<TrialBalance> {
+        self.balances.iter()
+            .map(|((account, currency), balance)| TrialBalance { account: *account, currency: currency.clone(), balance: balance.clone() })
+            .collect()
+    }
+
+    /// Post the movements of an accepted transaction, given the earlier transaction it references, if any,
+    /// and the client every fee is credited to, if fees are charged.
+    pub(crate) fn record(&mut self, transaction: &Transaction, applied: &Applied, target: Option<&Transaction>, fee_account: Option<u16>) {
+        use LedgerAccount::*;
+
+        let client = transaction.client_id;
+        let currency = transaction.currency();
+        let withdrawal = target.is_some_and(|target| target.type_ == TransactionType::Withdrawal);
+        let target_currency = target.and_then(Transaction::currency);
+
+        match applied {
+            Applied::Deposited { amount, .. } => self.post(transaction, Suspense, Available(client), currency, amount),
+            Applied::Withdrew { amount, .. } => self.post(transaction, Available(client), Suspense, currency, amount),
+            Applied::Transferred { amount, destination, .. } => self.post(transaction, Available(client), Available(*destination), currency, amount),
+            Applied::Converted { amount, converted, .. } => {
+                self.post(transaction, Available(client), Suspense, currency, amount);
+                self.post(transaction, Suspense, Available(client), transaction.to_currency(), converted);
+            },
+            Applied::Interest { amount } => self.post(transaction, InterestExpense, Available(client), currency, amount),
+            // NOTE: A disputed withdrawal is claimed back from the outside world, so it is held until the claim is settled.
+            Applied::Disputed { amount } if withdrawal => self.post(transaction, Suspense, Held(client), target_currency, amount),
+            Applied::Disputed { amount } => self.post(transaction, Available(client), Held(client), target_currency, amount),
+            Applied::Resolved { amount } if withdrawal => self.post(transaction, Held(client), Suspense, target_currency, amount),
+            Applied::Resolved { amount } => self.post(transaction, Held(client), Available(client), target_currency, amount),
+            Applied::ChargedBack { amount } if withdrawal => {
+                self.post(transaction, Held(client), Available(client), target_currency, amount);
+                self.post(transaction, ChargebackLoss, Suspense, target_currency, amount);
+            },
+            Applied::ChargedBack { amount } => self.post(transaction, Held(client), Suspense, target_currency, amount),
+            Applied::Represented { amount, .. } if withdrawal => self.post(transaction, Available(client), ChargebackLoss, target_currency, amount),
+            Applied::Represented { amount, .. } => self.post(transaction, Suspense, Available(client), target_currency, amount),
+            Applied::Authorized { amount } => self.post(transaction, Available(client), Held(client), currency, amount),
+            Applied::Captured { amount } => self.post(transaction, Held(client), Suspense, target_currency, amount),
+            Applied::Voided { amount } => self.post(transaction, Held(client), Available(client), target_currency, amount),
+            // NOTE: The ledger keeps a single available account for each client, so a move between its sub-accounts posts nothing.
+            Applied::Moved { .. } | Applied::Unlocked | Applied::Frozen | Applied::Unfrozen | Applied::Asserted | Applied::Scheduled { .. } => {}
+        }
+
+        if let (Some(fee), Some(fee_account)) = (applied.fee(), fee_account) {
+            self.post(transaction, Available(client), Available(fee_account), currency, fee);
+        }
+    }
+
+    /// Post the difference between the balances of a restored client and those the ledger has for it,
+    /// against the opening balances.
+    pub(crate) fn open(&mut self, client: &Client) {
+        for (currency, (available, held)) in funds(client) {
+            let currency = currency.as_deref();
+            for (account, amount) in [(LedgerAccount::Available(client.id()), available), (LedgerAccount::Held(client.id()), held)] {
+                let difference = amount - self.balance(account, currency);
+                let opening = Transaction::new(TransactionType::Deposit, client.id(), 0, None);
+                match difference.cmp(&BigDecimal::zero()) {
+                    std::cmp::Ordering::Greater => self.post(&opening, LedgerAccount::OpeningBalances, account, currency, &difference),
+                    std::cmp::Ordering::Less => self.post(&opening, account, LedgerAccount::OpeningBalances, currency, &-difference),
+                    std::cmp::Ordering::Equal => {}
+                }
+            }
+        }
+    }
+
+    /// Describe how a client's balances differ from those the ledger has for it, if they do.
+    pub(crate) fn check(&self, client: &Client) -> Result<(), String> {
+        for (currency, (account_available, account_held)) in funds(client) {
+            let available = self.balance(LedgerAccount::Available(client.id()), currency.as_deref());
+            let held = self.balance(LedgerAccount::Held(client.id()), currency.as_deref());
+            if available != account_available || held != account_held {
+                return Err(format!("the ledger has available {} and held {}, but the account has available {} and held {}",
+                    available, held, account_available, account_held));
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the balance of an account in a currency over to another ledger, such as that of the shard keeping the account.
+    pub(crate) fn hand_over(&mut self, other: &mut Ledger, account: LedgerAccount, currency: Option<&str>) {
+        let key = (account, currency.map(str::to_string));
+        if let Some(balance) = self.balances.remove(&key) {
+            *other.balances.entry(key).or_default() += balance;
+        }
+    }
+
+    /// Add every movement of another ledger, such as that of another shard.
+    pub(crate) fn merge(&mut self, other: Ledger) {
+        for (key, balance) in other.balances {
+            *self.balances.entry(key).or_default() += balance;
+        }
+        self.entries.extend(other.entries);
+    }
+
+    /// Post a single movement of funds.
+    fn post(&mut self, transaction: &Transaction, debit: LedgerAccount, credit: LedgerAccount, currency: Option<&str>, amount: &BigDecimal) {
+        if amount.is_zero() {
+            return;
+        }
+
+        *self.balances.entry((debit, currency.map(str::to_string))).or_default() -= amount;
+        *self.balances.entry((credit, currency.map(str::to_string))).or_default() += amount;
+        self.entries.push(LedgerEntry {
+            id: transaction.id,
+            type_: transaction.type_,
+            timestamp: transaction.timestamp,
+            debit,
+            credit,
+            currency: currency.map(str::to_string),
+            amount: amount.clone(),
+            metadata: transaction.metadata.clone()
+        });
+    }
+}
+
+/// The available and held funds of a client in each currency, summed across its sub-accounts.
+fn funds(client: &Client) -> BTreeMap<Option<String>, (BigDecimal, BigDecimal)> {
+    let mut funds = BTreeMap::<_, (BigDecimal, BigDecimal)>::new();
+    for balance in client.balances() {
+        let (available, held) = funds.entry(balance.currency().map(str::to_string)).or_default();
+        *available += balance.available();
+        *held += balance.held();
+    }
+    funds
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Engine, Fee, FeeSchedule};
+
+    #[test]
+    fn every_movement_posts_balanced_entries() {
+        let fees = FeeSchedule::new(9).with_fee(TransactionType::Withdrawal, None, Fee { flat: BigDecimal::from(1), percent: BigDecimal::zero() });
+        let mut engine = Engine::new().with_fees(Some(fees)).with_ledger(Some(Ledger::new()));
+
+        let amount = |value: &str| Some(BigDecimal::from_str(value).unwrap());
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("30"))).unwrap();
+        engine.process(&Transaction::transfer(1, 2, 3, BigDecimal::from(20))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 2, None)).unwrap();
+
+        let ledger = engine.ledger().unwrap();
+        let balances = ledger.trial_balance().into_iter().map(|row| (row.account, row.balance)).collect::<Vec<_>>();
+        assert_eq!(balances, vec![
+            (LedgerAccount::Available(1), BigDecimal::from(79)),
+            (LedgerAccount::Available(2), BigDecimal::from(20)),
+            (LedgerAccount::Available(9), BigDecimal::from(1)),
+            (LedgerAccount::Held(1), BigDecimal::zero()),
+            (LedgerAccount::Suspense, BigDecimal::from(-70)),
+            (LedgerAccount::ChargebackLoss, BigDecimal::from(-30)),
+        ]);
+        assert_eq!(balances.iter().map(|(_, balance)| balance).sum::<BigDecimal>(), BigDecimal::zero());
+        for client in engine.clients().unwrap() {
+            assert_eq!(ledger.check(&client), Ok(()));
+        }
+    }
+}
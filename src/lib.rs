@@ -0,0 +1,132 @@
+//! A simple payments engine that reads a series of transactions, updates client accounts,
+//! handles disputes and chargebacks, and reports the final state of each account.
+
+mod activity;
+#[cfg(feature = "actors")]
+mod actor;
+mod anonymize;
+mod audit;
+#[cfg(feature = "avro")]
+mod avro;
+mod builder;
+mod client;
+mod close;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod dialect;
+mod diff;
+mod dispute;
+mod engine;
+mod error;
+mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fees;
+mod fixed;
+mod generate;
+mod history;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod input;
+mod interest;
+#[cfg(feature = "iso20022")]
+mod iso20022;
+mod journal;
+mod ledger;
+mod mapping;
+mod merkle;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod observer;
+mod output;
+mod parallel;
+#[cfg(feature = "postgres")]
+mod postgres_storage;
+mod ownership;
+mod precision;
+mod reconcile;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+#[cfg(feature = "redis")]
+mod redis_storage;
+#[cfg(feature = "http")]
+pub mod remote;
+mod report;
+mod roster;
+mod rules;
+mod schedule;
+#[cfg(feature = "scripting")]
+mod script;
+mod snapshot;
+mod spill;
+#[cfg(feature = "statements")]
+mod statement;
+mod storage;
+mod tenant;
+mod transaction;
+mod validation;
+mod velocity;
+#[cfg(feature = "http")]
+pub mod webhook;
+#[cfg(feature = "websocket")]
+pub mod websocket;
+
+pub use activity::Activity;
+#[cfg(feature = "actors")]
+pub use actor::{Observer, Pending, ShardedEngine};
+pub use anonymize::Anonymizer;
+pub use audit::{AuditEntry, AuditError, AuditEvent, AuditLog, GENESIS_HASH, verify_audit};
+#[cfg(feature = "avro")]
+pub use avro::{SchemaRegistry, records_from_avro_reader};
+pub use builder::EngineBuilder;
+pub use client::{Balance, Client};
+pub use close::DailyClose;
+pub use dialect::{AmountFormat, CsvDialect, QuoteStyle};
+pub use diff::{AccountChange, AccountDiff, diff_accounts};
+pub use dispute::OpenDispute;
+pub use engine::{Engine, FreezePolicy, Generated, Limits, LockPolicy, OverdraftPolicy};
+pub use error::TransactionError;
+pub use export::ExportFormat;
+pub use fees::{Fee, FeeSchedule};
+pub use fixed::{FIXED_SCALE, Fixed};
+pub use generate::Generator;
+pub use history::BalanceHistory;
+pub use input::{CsvRecords, Encoding, InputFormat, Record, RecordError, Tail, decompress, filter_rows, records_from_jsonl_reader, records_from_reader, records_from_reader_with, reorder, transactions_from_reader};
+#[cfg(feature = "iso20022")]
+pub use iso20022::records_from_iso20022_reader;
+pub use interest::{ACCRUAL_SCALE, AccruedInterest, Interest, InterestPeriod};
+pub use journal::{Journal, JournalEntry, JournalError, replay, replay_tenants};
+pub use ledger::{Ledger, LedgerAccount, LedgerEntry, TrialBalance};
+pub use mapping::ColumnMapping;
+pub use merkle::merkle_root;
+#[cfg(feature = "metrics")]
+pub use metrics::Metrics;
+#[cfg(feature = "kafka")]
+pub use kafka::{KafkaSource, KafkaSourceError, KafkaUrl};
+pub use observer::EngineObserver;
+pub use output::{OutputFormat, accounts_from_reader};
+pub use ownership::Ownership;
+pub use parallel::{process_parallel, process_parallel_tenants, process_parallel_with, shard_for};
+#[cfg(feature = "postgres")]
+pub use postgres_storage::PostgresStorage;
+pub use precision::{DEFAULT_SCALE, ExcessPrecision, MAX_INTEGER_DIGITS, Precision, Rounding};
+pub use reconcile::{BalanceStatus, Reconciled, StatementBalance, read_statement, reconcile};
+#[cfg(feature = "redis")]
+pub use redis_storage::{DEFAULT_REDIS_PREFIX, RedisStorage};
+pub use report::{DeadLetter, Rejection};
+pub use roster::{Roster, RosterEntry};
+pub use rules::{AmountThreshold, DisputeCount, Flag, Flags, RapidCycle, RiskMonitor, Rule};
+#[cfg(feature = "scripting")]
+pub use script::{Decision, Script, ScriptError};
+pub use schedule::{Recurrence, ScheduledTransaction};
+pub use snapshot::{SNAPSHOT_MAGIC, SNAPSHOT_VERSION, Snapshot, SnapshotError, SnapshotTransaction, SourceOffset};
+pub use spill::SpillStorage;
+#[cfg(feature = "statements")]
+pub use statement::{Statement, StatementLine, write_camt053};
+pub use storage::{MemoryStorage, Retention, Storage, StorageError};
+pub use tenant::{TENANT_HEADER, Tenants};
+pub use transaction::{Applied, MAIN_ACCOUNT, Transaction, TransactionStatus, TransactionType};
+pub use validation::validate;
+pub use velocity::{DailyOutflow, VelocityPolicy, WithdrawalLimits};
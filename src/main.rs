@@ -1,400 +1,168 @@
-
-use std::{io, fs::File, collections::HashMap};
-
-use bigdecimal::{BigDecimal, Zero};
-use serde::{Deserialize, Serialize};
-
-const PRECISION: u64 = 5;
-
-/// An enumeration of each transaction type.
-#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum TransactionType {
-    /// A deposit is a credit to the client's account.
-    Deposit,
-    
-    /// A withdraw is a debit to the client's account.
-    /// This should fail if the withdrawal amount is greater than the available balance.
-    Withdrawal,
-
-    /// A dispute represents a client's claim that a transaction was erroneous and should be reversed.
-    Dispute,
-
-    /// A resolve represents a resolution to a dispute, releasing the associated held funds.
-    /// Funds that were previously disputed are no longer disputed.
-    Resolve,
-
-    /// A chargeback is the final state of a dispute and represents the client reversing a transaction.
-    /// Funds that were held have now been withdrawn. Lock the account after this.
-    Chargeback,
+use clap::{Parser, Subcommand};
+
+mod commands;
+
+/// How this build keeps balances, which is chosen when it is built rather than when it is run.
+#[cfg(not(feature = "fixed-point"))]
+const DECIMAL_BACKEND: &str = "Balances are kept as arbitrary-precision decimals. The backend is chosen when building rather than when running: \
+a build with the fixed-point feature (`cargo build --features fixed-point`) keeps them as fixed-point numbers of four decimal places \
+instead, which never allocates, but rejects amounts with more decimal places or beyond about 922 trillion.";
+
+/// How this build keeps balances, which is chosen when it is built rather than when it is run.
+#[cfg(feature = "fixed-point")]
+const DECIMAL_BACKEND: &str = "Balances are kept as fixed-point numbers of four decimal places, as this build has the fixed-point feature, \
+so amounts with more decimal places or beyond about 922 trillion are rejected. The backend is chosen when building rather than when \
+running: a build without the feature keeps balances as arbitrary-precision decimals instead.";
+
+/// A simple payments engine that processes transactions and reports the state of each client account.
+#[derive(Parser, Debug)]
+#[command(version, about, after_help = DECIMAL_BACKEND, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Processing arguments, used when no command is given.
+    #[command(flatten)]
+    process: commands::process::ProcessArgs,
 }
 
-/// A structure to represent a transaction.
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Transaction {
-    /// The transaction type.
-    #[serde(rename = "type")]
-    type_: TransactionType,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process every transaction and write the final state of each account.
+    Process(Box<commands::process::ProcessArgs>),
 
-    /// The id of the client.
-    #[serde(rename = "client")]
-    client_id: u16,
+    /// Parse and validate every transaction, without applying any of them.
+    Validate(commands::validate::ValidateArgs),
 
-    /// The id of the transaction.
-    #[serde(rename = "tx")]
-    id: u32,
+    /// Process every transaction and print a summary of the run.
+    Stats(commands::InputArgs),
 
-    /// The amount associated with the transaction.
-    #[serde(default)]
-    amount: Option<BigDecimal>,
+    /// Process every transaction, and print the lifecycle of a single one.
+    Explain(commands::explain::ExplainArgs),
 
-    /// Whether the transaction is in dispute.
-    #[serde(skip)]
-    disputed: bool
-}
+    /// Process every transaction, and write every movement of funds as Beancount or ledger-cli entries.
+    Export(commands::export::ExportArgs),
 
-/// A structure to represent a specific client's account.
-#[derive(Debug, Default, Deserialize, Serialize)]
-pub struct Client {
-    /// The id associated with the client.
-    id: u16,
-    
-    /// The total funds that are available for trading, staking, withdrawal, etc.
-    available: BigDecimal,
+    /// Write a reproducible, synthetic workload of transactions as CSV.
+    Generate(commands::generate::GenerateArgs),
 
-    /// The total funds that are held for dispute.
-    held: BigDecimal,
+    /// Write the rows of a CSV input matching every filter given, without applying any of them.
+    Filter(commands::filter::FilterArgs),
 
-    /// The total funds that are available or held.
-    total: BigDecimal,
+    /// Rewrite a CSV input as shareable test data, with client ids remapped, amounts perturbed and other columns stripped.
+    Anonymize(commands::anonymize::AnonymizeArgs),
 
-    /// Whether the account is locked.
-    locked: bool,
+    /// Split a CSV input into a file per shard of clients, to process each on its own.
+    Split(commands::split::SplitArgs),
 
-    #[serde(skip)]
-    /// The transactions associated with the client.
-    // NOTE: This wouldn't be used in a real system, but is used here to keep things simple.
-    transactions: HashMap<u32, Transaction>
-}
+    /// Combine the account output of several runs over disjoint clients, such as shards, into one.
+    Merge(commands::merge::MergeArgs),
 
-impl Client {
-    fn new(id: u16) -> Self {
-        Self {
-            id,
-            available: BigDecimal::zero().with_prec(PRECISION),
-            held: BigDecimal::zero().with_prec(PRECISION),
-            total: BigDecimal::zero().with_prec(PRECISION),
-            ..Default::default()
-        }
-    }
+    /// Compare two account outputs, and print how each account changed.
+    Diff(commands::diff::DiffArgs),
 
-    fn add_transaction(&mut self, transaction: &Transaction) {
-        assert!(transaction.amount.is_some());
+    /// Rebuild the state of each account from a journal, and write it.
+    Replay(commands::replay::ReplayArgs),
 
-        let transaction = Transaction {
-            type_: transaction.type_,
-            client_id: self.id,
-            id: transaction.id,
-            // BigDecimal is not Copy, so we need to clone the amount. Also, force precision.
-            amount: Some(transaction.amount.as_ref().unwrap().clone().with_prec(PRECISION)),
-            disputed: transaction.disputed
-        };
-
-        self.transactions.insert(transaction.id, transaction);
-    }
+    /// Process every transaction, and check the final accounts match an expected account output.
+    Verify(commands::verify::VerifyArgs),
 
-    pub fn process_transaction(&mut self, transaction: &Transaction) {
-        if self.locked {
-            // NOTE: This wasn't specified, but I made the assumption that a locked account should not have any transactions processed.
-            return;
-        }
+    /// Compare an account output against an external statement, and report how each balance reconciles.
+    Reconcile(commands::reconcile::ReconcileArgs),
 
-        match transaction.type_ {
-            TransactionType::Deposit => {
-                if let Some(amount) = &transaction.amount.as_ref().map(|amount| amount.with_prec(PRECISION)) {
-                    self.available += amount;
-                    self.total += amount;
-
-                    self.add_transaction(transaction);
-                }
-            },
-            TransactionType::Withdrawal => {
-                if let Some(amount) = &transaction.amount.as_ref().map(|amount| amount.with_prec(PRECISION)) {
-                    if amount <= &self.available {
-                        self.available -= amount;
-                        self.total -= amount;
-
-                        self.add_transaction(transaction);
-                    }
-                }
-            },
-            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
-                if let Some(target) = self.transactions.get_mut(&transaction.id) {
-                    match transaction.type_ {
-                        TransactionType::Dispute if !target.disputed => {
-                            let amount = target.amount.as_ref().unwrap();
-
-                            self.available -= amount;
-                            self.held += amount;
-
-                            target.disputed = true;
-                        },
-                        TransactionType::Resolve if target.disputed => {
-                            let amount = target.amount.as_ref().unwrap();
-                            
-                            self.held -= amount;
-                            self.available += amount;
-                                
-                            target.disputed = false;
-                        },
-                        TransactionType::Chargeback if target.disputed => {
-                            let amount = target.amount.as_ref().unwrap();
-
-                            self.held -= amount;
-                            self.total -= amount;
-
-                            self.locked = true;
-                        },
-                        _ => {}
-                    }
-                }
-            }
-        }
-    }
-}
+    /// Check an audit log is intact, with every entry matching its hash and chained to the one before.
+    VerifyAudit(commands::verify_audit::VerifyAuditArgs),
 
-fn transactions_from_reader<R: io::Read>(reader: R) -> io::Result<Vec<Transaction>> {
-    Ok(csv::ReaderBuilder::new()
-        .trim(csv::Trim::All)
-        .from_reader(reader)
-        .deserialize::<Transaction>()
-        .collect::<Result<Vec<_>, _>>()?)
+    /// Accept transactions over gRPC, applying them as they arrive.
+    #[cfg(feature = "grpc")]
+    Serve(commands::serve::ServeArgs),
 }
 
 fn main() {
-    let args = std::env::args().collect::<Vec<_>>();
-
-    if args.len() != 2 {
-        println!("Usage: {} <input_file>", args[0]);
-        std::process::exit(1);
-    }
-
-    let clients = File::open(&args[1])
-        .map(io::BufReader::new)
-        .and_then(transactions_from_reader)
-        .map(|transactions| {
-            let mut clients = HashMap::new();
-            for transaction in transactions {
-                clients.entry(transaction.client_id)
-                    .or_insert_with(|| Client::new(transaction.client_id))
-                    .process_transaction(&transaction);
-            }
-            clients
-        });
-    
-    match clients {
-        Ok(clients) => {
-            let mut writer = csv::Writer::from_writer(std::io::stdout());
-            for client in clients.values() {
-                if writer.serialize(client).is_err() {
-                    println!("Error: unable to serialize client {} to csv", client.id);
-                    std::process::exit(1);
-                }
-            }
-        },
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {
-            println!("Error: input file '{}' does not exist", args[1]);
-            std::process::exit(1);
-        },
-        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-            println!("Error: input file '{}' is not readable", args[1]);
-            std::process::exit(1);
-        },
-        Err(_) => {
-            // TODO: Log the error to stderr, so we can verify that this case is only DeserializerError.
-            println!("Error: input file '{}' has an invalid format", args[1]);
-            std::process::exit(1);
-        },
+    // NOTE: Logs go to stderr so they never mix with the account output, and are filtered by `RUST_LOG`.
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_writer(std::io::stderr)
+        .init();
+
+    // NOTE: Usage errors exit with 1 rather than clap's default of 2, which is reserved for missing files.
+    let cli = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+
+    let result = match cli.command.unwrap_or(Command::Process(Box::new(cli.process))) {
+        Command::Process(args) => commands::process::run(*args),
+        Command::Validate(args) => commands::validate::run(args),
+        Command::Stats(args) => commands::stats::run(args),
+        Command::Explain(args) => commands::explain::run(args),
+        Command::Export(args) => commands::export::run(args),
+        Command::Generate(args) => commands::generate::run(args),
+        Command::Filter(args) => commands::filter::run(args),
+        Command::Anonymize(args) => commands::anonymize::run(args),
+        Command::Split(args) => commands::split::run(args),
+        Command::Merge(args) => commands::merge::run(args),
+        Command::Diff(args) => commands::diff::run(args),
+        Command::Replay(args) => commands::replay::run(args),
+        Command::Verify(args) => commands::verify::run(args),
+        Command::Reconcile(args) => commands::reconcile::run(args),
+        Command::VerifyAudit(args) => commands::verify_audit::run(args),
+        #[cfg(feature = "grpc")]
+        Command::Serve(args) => commands::serve::run(args),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.kind.exit_code());
     }
 }
 
-
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
-
-    use bigdecimal::Zero;
+    use clap::CommandFactory;
 
     use super::*;
 
     #[test]
-    fn simple_deposit() {
-        let amount = BigDecimal::from_str("100").unwrap();
-
-        let mut client = Client::new(1);
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Deposit,
-            client_id: 1,
-            id: 1,
-            amount: Some(amount.clone()),
-            disputed: Default::default()
-        });
-
-        assert_eq!(client.available, amount);
-        assert_eq!(client.total, amount);
+    fn verify_cli() {
+        Cli::command().debug_assert();
     }
 
     #[test]
-    fn simple_withdrawal() {
-        let amount = BigDecimal::from_str("50").unwrap();
-
-        let mut client = Client::new(1);
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Deposit,
-            client_id: 1,
-            id: 1,
-            amount: Some(BigDecimal::from_str("100").unwrap()),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Withdrawal,
-            client_id: 1,
-            id: 2,
-            amount: Some(amount.clone()),
-            disputed: Default::default()
-        });
-
-        assert_eq!(client.available, amount);
-        assert_eq!(client.total, amount);
+    fn positional_input_defaults_to_process() {
+        let cli = Cli::try_parse_from(["transaction-system", "transactions.csv"]).unwrap();
+
+        assert!(cli.command.is_none());
+        assert_eq!(cli.process.input.paths().unwrap(), vec![std::path::PathBuf::from("transactions.csv")]);
     }
 
     #[test]
-    fn simple_dispute() {
-        let amount = BigDecimal::from_str("100").unwrap();
-
-        let mut client = Client::new(1);
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Deposit,
-            client_id: 1,
-            id: 1,
-            amount: Some(amount.clone()),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Dispute,
-            client_id: 1,
-            id: 1,
-            amount: Default::default(),
-            disputed: Default::default()
-        });
-
-        assert_eq!(client.available, BigDecimal::zero().with_prec(PRECISION));
-        assert_eq!(client.held, amount);
-        assert_eq!(client.total, amount);
-        assert!(client.transactions.get(&1).unwrap().disputed);
+    fn multiple_inputs() {
+        let cli = Cli::try_parse_from(["transaction-system", "stats", "-i", "monday.csv", "-i", "tuesday.csv"]).unwrap();
+
+        match cli.command {
+            Some(Command::Stats(args)) => assert_eq!(args.paths().unwrap().len(), 2),
+            _ => panic!("expected the stats command")
+        }
     }
 
     #[test]
-    fn simple_dispute_to_resolve() {
-        let amount = BigDecimal::from_str("100").unwrap();
-
-        let mut client = Client::new(1);
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Deposit,
-            client_id: 1,
-            id: 1,
-            amount: Some(amount.clone()),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Dispute,
-            client_id: 1,
-            id: 1,
-            amount: Default::default(),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Resolve,
-            client_id: 1,
-            id: 1,
-            amount: Default::default(),
-            disputed: Default::default()
-        });
-
-        assert_eq!(client.available, amount);
-        assert_eq!(client.held, BigDecimal::zero().with_prec(PRECISION));
-        assert_eq!(client.total, amount);
-        assert!(!client.transactions.get(&1).unwrap().disputed);
+    fn help_names_the_decimal_backend() {
+        let help = Cli::command().render_help().to_string();
+
+        assert!(help.contains(DECIMAL_BACKEND));
+        assert_eq!(help.contains("as this build has the fixed-point feature"), cfg!(feature = "fixed-point"));
     }
 
     #[test]
-    fn simple_dispute_to_chargeback() {
-        let amount = BigDecimal::from_str("100").unwrap();
-
-        let mut client = Client::new(1);
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Deposit,
-            client_id: 1,
-            id: 1,
-            amount: Some(amount),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Dispute,
-            client_id: 1,
-            id: 1,
-            amount: Default::default(),
-            disputed: Default::default()
-        });
-
-        client.process_transaction(&Transaction {
-            type_: TransactionType::Chargeback,
-            client_id: 1,
-            id: 1,
-            amount: Default::default(),
-            disputed: Default::default()
-        });
-
-        assert!(client.locked);
+    fn scale_fits_the_decimal_backend() {
+        assert!(Cli::try_parse_from(["transaction-system", "--scale", "4", "transactions.csv"]).is_ok());
+        assert_eq!(Cli::try_parse_from(["transaction-system", "--scale", "8", "transactions.csv"]).is_ok(), cfg!(not(feature = "fixed-point")));
     }
 
     #[test]
-    fn csv_example() {
-        let csv = "type,       client,     tx,     amount
-                        deposit,    1,          1,      1.0001
-                        deposit,    2,          2,      2.0001
-                        deposit,    1,          3,      2.0001
-                        withdrawal, 1,          4,      1.5002
-                        withdrawal, 2,          5,      3.0001
-                        dispute,    2,          5,
-                        resolve,    2,          5,
-                        dispute,    1,          3,
-                        chargeback, 1,          3,";
-
-        let transactions = transactions_from_reader(io::BufReader::new(csv.as_bytes())).unwrap();
-        
-        let mut clients: HashMap<u16, Client> = HashMap::new();
-        for transaction in transactions {
-            clients.entry(transaction.client_id)
-                .or_insert_with(|| Client::new(transaction.client_id))
-                .process_transaction(&transaction);
-        }
-
-          
-        assert_eq!(clients.len(), 2);
-        assert!(clients.get(&1).unwrap().locked);
-
-        assert_eq!(clients.get(&2).unwrap().available, BigDecimal::from_str("2.0001").unwrap().with_prec(PRECISION));
-        assert!(!clients.get(&2).unwrap().locked);
+    fn strict_conflicts_with_lenient() {
+        assert!(Cli::try_parse_from(["transaction-system", "validate", "--lenient", "transactions.csv"]).is_ok());
+        assert!(Cli::try_parse_from(["transaction-system", "validate", "--strict", "--lenient", "transactions.csv"]).is_err());
     }
-}
\ No newline at end of file
+}
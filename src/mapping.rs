@@ -0,0 +1,106 @@
+use std::{collections::HashMap, io};
+
+use serde::Deserialize;
+
+/// Every field of a transaction a column can be mapped to.
+pub(crate) const FIELDS: &[&str] = &["type", "client", "tx", "amount", "to", "currency", "to_currency", "rate", "timestamp", "scheduled_for", "recurrence", "account", "to_account", "tenant", "total"];
+
+/// A mapping of the columns of a CSV source, and the values in them, to the fields of a transaction, so a source
+/// written with other headers, such as `txn_type`, `cust_id`, `txn_id` and `value`, can be read as is.
+/// A column that is not mapped is read as the field of the same name, and a value that is not mapped is read as is.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColumnMapping {
+    /// The field each mapped column holds, keyed by the name of the column.
+    columns: HashMap<String, String>,
+
+    /// The value each mapped value stands for, keyed by the field and the value.
+    values: HashMap<(String, String), String>
+}
+
+/// A row of a CSV file of column mappings.
+#[derive(Debug, Deserialize)]
+struct MappingRow {
+    field: String,
+    input: String,
+    #[serde(default)]
+    value: Option<String>
+}
+
+impl ColumnMapping {
+    /// Read a column mapping from a CSV file with `field`, `input` and `value` columns.
+    ///
+    /// A row with an empty `value` reads the `input` column as the field, such as `type,txn_type,`, while a row with a
+    /// `value` reads `input` in the field as that value, such as `type,credit,deposit`.
+    pub fn read<R: io::Read>(reader: R) -> io::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        let mut mapping = Self::default();
+        for row in reader.deserialize::<MappingRow>() {
+            let row = row.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            if !FIELDS.contains(&row.field.as_str()) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown field '{}'", row.field)));
+            }
+
+            match row.value {
+                Some(value) => mapping.values.insert((row.field, row.input), value),
+                None => mapping.columns.insert(row.input, row.field)
+            };
+        }
+        Ok(mapping)
+    }
+
+    /// Read a column as a field.
+    pub fn with_column<C: Into<String>, F: Into<String>>(mut self, column: C, field: F) -> Self {
+        self.columns.insert(column.into(), field.into());
+        self
+    }
+
+    /// Read a value of a field as another value.
+    pub fn with_value<F: Into<String>, I: Into<String>, V: Into<String>>(mut self, field: F, input: I, value: V) -> Self {
+        self.values.insert((field.into(), input.into()), value.into());
+        self
+    }
+
+    /// The fields held by each column of a header row.
+    pub(crate) fn fields(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        headers.iter()
+            .map(|column| self.columns.get(column).map_or(column, String::as_str))
+            .collect()
+    }
+
+    /// Replace every mapped value of a row, given the fields held by its columns.
+    pub(crate) fn map_values(&self, fields: &csv::StringRecord, row: &mut csv::StringRecord) {
+        if self.values.is_empty() {
+            return;
+        }
+
+        let position = row.position().cloned();
+        *row = row.iter().zip(fields)
+            .map(|(value, field)| self.values.get(&(field.to_string(), value.to_string())).map_or(value, String::as_str))
+            .collect();
+        row.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CsvDialect, Transaction, TransactionType, records_from_reader_with};
+
+    #[test]
+    fn map_columns_and_values() {
+        let mapping = ColumnMapping::read("field, input, value\ntype, txn_type,\nclient, cust_id,\ntx, txn_id,\namount, value,\ntype, credit, deposit\ntype, debit, withdrawal\n".as_bytes()).unwrap();
+        let csv = "txn_type, cust_id, txn_id, value\ncredit, 1, 1, 10\ndebit, 1, 2, 4\ndispute, 1, 1,\ncredit, one, 3, 1\n";
+
+        let records = records_from_reader_with(csv.as_bytes(), &CsvDialect::default(), &mapping).unwrap();
+        assert_eq!(records[0].as_ref().unwrap().transaction, Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())));
+        assert_eq!(records[1].as_ref().unwrap().transaction.type_(), TransactionType::Withdrawal);
+        assert_eq!(records[2].as_ref().unwrap().transaction.type_(), TransactionType::Dispute);
+        let error = records[3].as_ref().unwrap_err();
+        assert_eq!((error.line, error.field.as_deref()), (5, Some("cust_id")));
+
+        assert!(ColumnMapping::read("field, input, value\nkind, txn_type,\n".as_bytes()).is_err());
+    }
+}
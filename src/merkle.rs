@@ -0,0 +1,78 @@
+use sha2::{Digest, Sha256};
+
+use crate::{Client, Precision, output::Account};
+
+/// The byte every leaf is hashed with, so a leaf can never be mistaken for an interior node.
+const LEAF_PREFIX: u8 = 0x00;
+
+/// The byte every interior node is hashed with.
+const NODE_PREFIX: u8 = 0x01;
+
+/// Compute the Merkle root of the final state of every client account, as hex.
+///
+/// Each leaf is a row of the account output, ordered by client id and written as
+/// `client,currency,available,held,total,locked` with amounts to the given precision, whatever the output format.
+/// Pairs of nodes are hashed together level by level, with an unpaired node carried up to the next level as it is,
+/// so two runs that produce identical accounts always produce the same root.
+pub fn merkle_root<'a, I: IntoIterator<Item = &'a Client>>(clients: I, precision: Precision) -> String {
+    let mut clients = clients.into_iter().collect::<Vec<_>>();
+    clients.sort_by_key(|client| client.id());
+
+    let mut level = Account::rows(&clients, precision, false).iter()
+        .map(|account| {
+            let row = format!("{},{},{},{},{},{}",
+                account.id,
+                account.currency.unwrap_or_default(),
+                account.available.to_plain_string(),
+                account.held.to_plain_string(),
+                account.total.to_plain_string(),
+                account.locked);
+            Sha256::new().chain_update([LEAF_PREFIX]).chain_update(row).finalize().to_vec()
+        })
+        .collect::<Vec<_>>();
+
+    if level.is_empty() {
+        return hex::encode(Sha256::digest([]));
+    }
+
+    while level.len() > 1 {
+        level = level.chunks(2)
+            .map(|pair| match pair {
+                [left, right] => Sha256::new().chain_update([NODE_PREFIX]).chain_update(left).chain_update(right).finalize().to_vec(),
+                [node] => node.clone(),
+                _ => unreachable!()
+            })
+            .collect();
+    }
+    hex::encode(&level[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType};
+
+    fn engine(order: &[u16]) -> Engine {
+        let mut engine = Engine::new();
+        for &client in order {
+            engine.process(&Transaction::new(TransactionType::Deposit, client, client as u32, Some(BigDecimal::from_str("1.5").unwrap()))).unwrap();
+        }
+        engine
+    }
+
+    #[test]
+    fn root_depends_only_on_the_accounts() {
+        let root = merkle_root(&engine(&[1, 2, 3]).clients().unwrap(), Precision::default());
+        assert_eq!(root.len(), 64);
+        assert_eq!(merkle_root(&engine(&[3, 1, 2]).clients().unwrap(), Precision::default()), root);
+        assert_ne!(merkle_root(&engine(&[1, 2]).clients().unwrap(), Precision::default()), root);
+
+        let mut changed = engine(&[1, 2, 3]);
+        changed.process(&Transaction::new(TransactionType::Withdrawal, 3, 4, Some(BigDecimal::from_str("0.0001").unwrap()))).unwrap();
+        assert_ne!(merkle_root(&changed.clients().unwrap(), Precision::default()), root);
+    }
+}
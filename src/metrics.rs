@@ -0,0 +1,146 @@
+use std::{io, net::ToSocketAddrs, sync::Arc, thread, time::Duration};
+
+use bigdecimal::ToPrimitive;
+use prometheus::{Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder};
+
+use crate::{Applied, Transaction, TransactionError};
+
+/// Prometheus metrics describing the transactions processed by an engine.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    /// The registry every metric is registered with.
+    registry: Registry,
+
+    /// The number of transactions processed, by type and outcome.
+    processed: IntCounterVec,
+
+    /// The number of rejected transactions, by reason.
+    rejected: IntCounterVec,
+
+    /// The number of accounts that are currently locked.
+    locked: IntGauge,
+
+    /// The time taken to process each transaction, in seconds.
+    latency: Histogram,
+
+    /// The amount of each accepted transaction, by type.
+    amounts: HistogramVec,
+}
+
+impl Metrics {
+    /// Create a new set of metrics, each starting at zero.
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let processed = IntCounterVec::new(Opts::new("transactions_processed_total", "The number of transactions processed, by type and outcome."), &["type", "outcome"]).unwrap();
+        let rejected = IntCounterVec::new(Opts::new("transactions_rejected_total", "The number of rejected transactions, by reason."), &["reason"]).unwrap();
+        let locked = IntGauge::new("accounts_locked", "The number of accounts that are currently locked.").unwrap();
+        let latency = Histogram::with_opts(HistogramOpts::new("transaction_processing_seconds", "The time taken to process each transaction.")
+            .buckets(prometheus::exponential_buckets(0.000_001, 4.0, 10).unwrap())).unwrap();
+        let amounts = HistogramVec::new(HistogramOpts::new("transaction_amount", "The amount of each accepted transaction, by type.")
+            .buckets(prometheus::exponential_buckets(1.0, 10.0, 8).unwrap()), &["type"]).unwrap();
+
+        // NOTE: Registration only fails on duplicate names, which can not happen with a fresh registry.
+        registry.register(Box::new(processed.clone())).unwrap();
+        registry.register(Box::new(rejected.clone())).unwrap();
+        registry.register(Box::new(locked.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(amounts.clone())).unwrap();
+
+        Self {
+            registry,
+            processed,
+            rejected,
+            locked,
+            latency,
+            amounts
+        }
+    }
+
+    /// Record the outcome of processing a transaction, and how long it took.
+    pub fn observe(&self, transaction: &Transaction, result: &Result<Applied, TransactionError>, elapsed: Duration) {
+        let type_ = transaction.type_().to_string();
+        self.latency.observe(elapsed.as_secs_f64());
+
+        match result {
+            Ok(applied) => {
+                self.processed.with_label_values(&[&type_, "accepted"]).inc();
+                if let Some(amount) = applied.amount().and_then(ToPrimitive::to_f64) {
+                    self.amounts.with_label_values(&[&type_]).observe(amount);
+                }
+                match applied {
+                    Applied::ChargedBack { .. } => self.locked.inc(),
+                    Applied::Unlocked | Applied::Represented { unlocked: true, .. } => self.locked.dec(),
+                    _ => {}
+                }
+            },
+            Err(e) => {
+                self.processed.with_label_values(&[&type_, "rejected"]).inc();
+                self.rejected.with_label_values(&[e.code()]).inc();
+            }
+        }
+    }
+
+    /// Render every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // NOTE: The text encoder only fails when writing to the buffer fails.
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    /// Serve the metrics over HTTP at `/metrics` from a background thread, for as long as the process runs.
+    pub fn serve<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
+        let server = tiny_http::Server::http(addr)
+            .map_err(|e| io::Error::new(io::ErrorKind::AddrNotAvailable, e))?;
+        let server = Arc::new(server);
+        let metrics = self.clone();
+
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = if request.url() == "/metrics" {
+                    tiny_http::Response::from_string(metrics.render())
+                        .with_header("Content-Type: text/plain; version=0.0.4".parse::<tiny_http::Header>().unwrap())
+                } else {
+                    tiny_http::Response::from_string("not found").with_status_code(404)
+                };
+
+                // NOTE: A client hanging up early is not an error worth stopping the server for.
+                let _ = request.respond(response);
+            }
+        });
+        Ok(())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    #[test]
+    fn counts_outcomes_by_type_and_reason() {
+        let metrics = Metrics::new();
+        let mut engine = Engine::new();
+
+        let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(10)));
+        let withdrawal = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from(20)));
+        for transaction in [&deposit, &withdrawal] {
+            metrics.observe(transaction, &engine.process(transaction), Duration::from_micros(5));
+        }
+
+        let rendered = metrics.render();
+        assert!(rendered.contains(r#"transactions_processed_total{outcome="accepted",type="deposit"} 1"#));
+        assert!(rendered.contains(r#"transactions_processed_total{outcome="rejected",type="withdrawal"} 1"#));
+        assert!(rendered.contains(r#"transactions_rejected_total{reason="insufficient_funds"} 1"#));
+        assert!(rendered.contains("transaction_processing_seconds_count 2"));
+    }
+}
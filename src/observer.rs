@@ -0,0 +1,147 @@
+use std::fmt;
+
+use bigdecimal::BigDecimal;
+
+use crate::{Applied, Client, Transaction, TransactionError, TransactionType};
+
+/// Hooks called by the engine as it processes transactions, so notifications and metrics can be raised from outside
+/// the processing loop. Every hook does nothing by default, so an observer only implements the ones it needs.
+///
+/// Hooks are called once a transaction has been fully applied or rejected, in the order transactions are processed.
+pub trait EngineObserver: Send {
+    /// Called with every transaction that was accepted, and what it applied.
+    fn on_accepted(&mut self, transaction: &Transaction, applied: &Applied) {
+        let _ = (transaction, applied);
+    }
+
+    /// Called with every transaction that was rejected, and the reason it was rejected.
+    fn on_rejected(&mut self, transaction: &Transaction, error: &TransactionError) {
+        let _ = (transaction, error);
+    }
+
+    /// Called when a withdrawal is rejected, such as for insufficient funds.
+    fn on_withdrawal_rejected(&mut self, transaction: &Transaction, error: &TransactionError) {
+        let _ = (transaction, error);
+    }
+
+    /// Called when a dispute holds the amount of an earlier transaction.
+    fn on_dispute_opened(&mut self, transaction: &Transaction, amount: &BigDecimal) {
+        let _ = (transaction, amount);
+    }
+
+    /// Called when a dispute is resolved, releasing the held amount.
+    fn on_dispute_resolved(&mut self, transaction: &Transaction, amount: &BigDecimal) {
+        let _ = (transaction, amount);
+    }
+
+    /// Called when a chargeback reverses a disputed transaction and locks the account, with the account as it now is.
+    fn on_account_locked(&mut self, client: &Client, transaction: &Transaction, amount: &BigDecimal) {
+        let _ = (client, transaction, amount);
+    }
+
+    /// Called when a representment reverses a chargeback, with the account as it now is.
+    fn on_chargeback_reversed(&mut self, client: &Client, transaction: &Transaction, amount: &BigDecimal) {
+        let _ = (client, transaction, amount);
+    }
+
+    /// Called when an account is unlocked, by an operator or a representment, with the account as it now is.
+    fn on_account_unlocked(&mut self, client: &Client, transaction: &Transaction) {
+        let _ = (client, transaction);
+    }
+}
+
+/// The observers registered with an engine, each called in the order it was registered.
+#[derive(Default)]
+pub(crate) struct Observers(Vec<Box<dyn EngineObserver>>);
+
+impl Observers {
+    /// Register another observer.
+    pub(crate) fn push(&mut self, observer: Box<dyn EngineObserver>) {
+        self.0.push(observer);
+    }
+
+    /// Whether no observers are registered.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Call every hook that applies to a processed transaction, on every observer.
+    /// The client is the account the transaction belongs to, as it is after the transaction.
+    pub(crate) fn notify(&mut self, transaction: &Transaction, result: &Result<Applied, TransactionError>, client: Option<&Client>) {
+        for observer in &mut self.0 {
+            match result {
+                Ok(applied) => {
+                    observer.on_accepted(transaction, applied);
+                    match (applied, client) {
+                        (Applied::Disputed { amount }, _) => observer.on_dispute_opened(transaction, amount),
+                        (Applied::Resolved { amount }, _) => observer.on_dispute_resolved(transaction, amount),
+                        (Applied::ChargedBack { amount }, Some(client)) => observer.on_account_locked(client, transaction, amount),
+                        (Applied::Unlocked, Some(client)) => observer.on_account_unlocked(client, transaction),
+                        (Applied::Represented { amount, unlocked }, Some(client)) => {
+                            observer.on_chargeback_reversed(client, transaction, amount);
+                            if *unlocked {
+                                observer.on_account_unlocked(client, transaction);
+                            }
+                        },
+                        _ => {}
+                    }
+                },
+                Err(e) => {
+                    observer.on_rejected(transaction, e);
+                    if transaction.type_ == TransactionType::Withdrawal {
+                        observer.on_withdrawal_rejected(transaction, e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Observers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} observers", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::{Arc, Mutex}};
+
+    use super::*;
+    use crate::Engine;
+
+    /// An observer that records every hook it is called with.
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl EngineObserver for Recorder {
+        fn on_withdrawal_rejected(&mut self, transaction: &Transaction, error: &TransactionError) {
+            self.0.lock().unwrap().push(format!("withdrawal {} rejected: {}", transaction.id(), error));
+        }
+
+        fn on_dispute_opened(&mut self, transaction: &Transaction, amount: &BigDecimal) {
+            self.0.lock().unwrap().push(format!("dispute of {} opened for {}", transaction.id(), amount));
+        }
+
+        fn on_account_locked(&mut self, client: &Client, transaction: &Transaction, _: &BigDecimal) {
+            self.0.lock().unwrap().push(format!("client {} locked by {}, locked: {}", client.id(), transaction.id(), client.locked()));
+        }
+    }
+
+    #[test]
+    fn observers_are_notified() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::builder().observer(Recorder(events.clone())).build();
+
+        let amount = |value: &str| Some(BigDecimal::from_str(value).unwrap());
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))).unwrap();
+        let _ = engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("20")));
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+
+        assert_eq!(*events.lock().unwrap(), vec![
+            "withdrawal 2 rejected: insufficient funds",
+            "dispute of 1 opened for 10.0000",
+            "client 1 locked by 1, locked: true",
+        ]);
+    }
+}
@@ -0,0 +1,515 @@
+use std::{collections::BTreeMap, fmt, io, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{Activity, Balance, Client, CsvDialect, DailyClose, MAIN_ACCOUNT, Precision, Storage, Tenants};
+
+/// A row of the account output, holding a client's funds in a single currency of one of its sub-accounts.
+#[derive(Debug, Serialize)]
+pub(crate) struct Account<'a> {
+    /// The day the row is the closing balance of, only written when the closing balances of several days are written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) date: Option<NaiveDate>,
+
+    /// The tenant whose books hold the row, only written when any tenant other than the default books is written.
+    // NOTE: The default books are written as an empty value, so every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) tenant: Option<&'a str>,
+
+    pub(crate) id: u16,
+
+    /// The sub-account of the row, only written when any client holds funds in a sub-account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) account: Option<&'a str>,
+
+    /// The currency of the row, only written when any client holds funds outside the default currency.
+    // NOTE: The default currency is written as an empty value, so every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) currency: Option<&'a str>,
+
+    #[serde(serialize_with = "plain")]
+    pub(crate) available: BigDecimal,
+
+    #[serde(serialize_with = "plain")]
+    pub(crate) held: BigDecimal,
+
+    #[serde(serialize_with = "plain")]
+    pub(crate) total: BigDecimal,
+
+    pub(crate) locked: bool,
+
+    /// Whether the account is frozen by an operator, only written when any client is frozen or accounts can be frozen.
+    // NOTE: This is separate from `locked`, which is the lock a chargeback places.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) frozen: Option<bool>,
+
+    /// Every client operating a joint account, separated by spaces, only written when any account is joint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) owners: Option<String>,
+
+    /// The number of transactions of the client that were accepted, only written with the activity of each account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) processed: Option<u64>,
+
+    /// The number of transactions of the client that were rejected, only written with the activity of each account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rejected: Option<u64>,
+
+    /// The number of the client's disputes still open, only written with the activity of each account.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) open_disputes: Option<u32>,
+
+    /// The latest timestamp of any transaction of the client, only written with the activity of each account.
+    // NOTE: A client without any transaction with a timestamp is written as an empty value, so every row has the same columns.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) last_activity: Option<String>
+}
+
+/// The client accounts of the books of a tenant, or `None` for the default books, each alongside its activity.
+type Book = (Option<String>, Vec<(Client, Activity)>);
+
+/// A client to split into rows, alongside the day and tenant of the rows, and its activity if that is written.
+type Rows<'a> = (Option<NaiveDate>, Option<&'a str>, &'a Client, Option<&'a Activity>);
+
+/// Write an amount out in full, keeping every decimal place, as with [`Precision::format`].
+fn plain<S: Serializer>(amount: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&amount.to_plain_string())
+}
+
+impl<'a> Account<'a> {
+    /// Split each client into a row per sub-account and currency, with every amount written to the given precision.
+    /// An empty default currency balance of the main account is left out for a client that holds anything else.
+    pub(crate) fn rows(clients: &[&'a Client], precision: Precision, frozen: bool) -> Vec<Self> {
+        let clients = clients.iter().map(|client| (None, *client)).collect::<Vec<_>>();
+        Self::tenant_rows(&clients, precision, frozen)
+    }
+
+    /// Split each client of the books of a tenant into rows, as [`Account::rows`] does, with the tenant of every row
+    /// written when any client is not in the default books.
+    pub(crate) fn tenant_rows(clients: &[(Option<&'a str>, &'a Client)], precision: Precision, frozen: bool) -> Vec<Self> {
+        let clients = clients.iter().map(|&(tenant, client)| (None, tenant, client, None)).collect::<Vec<_>>();
+        Self::dated_rows(&clients, precision, frozen)
+    }
+
+    /// Split each client of the books of a tenant into rows, as [`Account::tenant_rows`] does, with the activity of the
+    /// client on every row.
+    pub(crate) fn activity_rows(clients: &[(Option<&'a str>, &'a Client, &'a Activity)], precision: Precision, frozen: bool) -> Vec<Self> {
+        let clients = clients.iter().map(|&(tenant, client, activity)| (None, tenant, client, Some(activity))).collect::<Vec<_>>();
+        Self::dated_rows(&clients, precision, frozen)
+    }
+
+    /// Split the closing balances of each day into rows, as [`Account::tenant_rows`] does, with the day of every row.
+    pub(crate) fn close_rows(closes: &'a [DailyClose], precision: Precision, frozen: bool) -> Vec<Self> {
+        let clients = closes.iter()
+            .flat_map(|close| close.clients().map(|(tenant, client)| (Some(close.day), tenant, client, None)))
+            .collect::<Vec<_>>();
+        Self::dated_rows(&clients, precision, frozen)
+    }
+
+    /// Split each client into rows, as [`Account::tenant_rows`] does, with the day and activity of every row written
+    /// when they are given, and whether each client is frozen when any is or `frozen` is set.
+    fn dated_rows(clients: &[Rows<'a>], precision: Precision, frozen: bool) -> Vec<Self> {
+        let tenants = clients.iter().any(|(_, tenant, _, _)| tenant.is_some());
+        let currencies = clients.iter()
+            .flat_map(|(_, _, client, _)| client.balances())
+            .any(|balance| balance.currency().is_some());
+        let accounts = clients.iter()
+            .flat_map(|(_, _, client, _)| client.balances())
+            .any(|balance| balance.account().is_some());
+        let frozen = frozen || clients.iter().any(|(_, _, client, _)| client.frozen());
+        let owners = clients.iter().any(|(_, _, client, _)| !client.owners().is_empty());
+
+        clients.iter()
+            .flat_map(|&(date, tenant, client, activity)| client.balances().iter()
+                .filter(move |balance| balance.account().is_some() || balance.currency().is_some() || !balance.is_empty() || client.balances().len() == 1)
+                .map(move |balance| Account {
+                    date,
+                    tenant: tenants.then(|| tenant.unwrap_or_default()),
+                    id: client.id(),
+                    account: accounts.then(|| balance.account().unwrap_or(MAIN_ACCOUNT)),
+                    currency: currencies.then(|| balance.currency().unwrap_or_default()),
+                    available: precision.apply(&balance.available()),
+                    held: precision.apply(&balance.held()),
+                    total: precision.apply(&balance.total()),
+                    locked: client.locked(),
+                    frozen: frozen.then(|| client.frozen()),
+                    owners: owners.then(|| client.owners().iter().map(u16::to_string).collect::<Vec<_>>().join(" ")),
+                    processed: activity.map(|activity| activity.processed),
+                    rejected: activity.map(|activity| activity.rejected),
+                    open_disputes: activity.map(|activity| activity.open_disputes),
+                    last_activity: activity.map(|activity| activity.last_activity.map(|at| at.to_rfc3339()).unwrap_or_default())
+                }))
+            .collect()
+    }
+}
+
+/// A row of the CSV account output as read back, with the amounts left as written until they are checked.
+#[derive(Debug, Deserialize)]
+struct AccountRow {
+    #[serde(alias = "client")]
+    id: u16,
+
+    #[serde(default)]
+    account: Option<String>,
+
+    #[serde(default)]
+    currency: Option<String>,
+
+    available: String,
+
+    held: String,
+
+    /// Checked against the available and held funds, when given.
+    #[serde(default)]
+    total: Option<String>,
+
+    #[serde(default)]
+    locked: bool,
+
+    #[serde(default)]
+    frozen: bool,
+
+    #[serde(default)]
+    owners: Option<String>
+}
+
+/// Read client accounts back from the CSV account output, as written by [`OutputFormat::Csv`].
+/// The `account`, `currency`, `total`, `locked`, `frozen` and `owners` columns are optional, and `client` is accepted in place of `id`.
+/// Each client may have one row per sub-account and currency, and a given total must be the sum of the available and held funds.
+pub fn accounts_from_reader<R: io::Read>(reader: R) -> io::Result<Vec<Client>> {
+    let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+    let decimal = |line: u64, column: &str, value: &str| BigDecimal::from_str(value)
+        .map_err(|e| invalid(line, format!("invalid {} '{}': {}", column, value, e)));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+
+    let mut clients = BTreeMap::<u16, (Vec<Balance>, bool, bool, Vec<u16>)>::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |position| position.line());
+        let row = record.deserialize::<AccountRow>(Some(&headers))
+            .map_err(|e| invalid(line, e.to_string()))?;
+
+        let available = decimal(line, "available", &row.available)?;
+        let held = decimal(line, "held", &row.held)?;
+        if held < BigDecimal::zero() {
+            return Err(invalid(line, format!("held funds of client {} are negative", row.id)));
+        }
+        if let Some(total) = &row.total {
+            if decimal(line, "total", total)? != &available + &held {
+                return Err(invalid(line, format!("total of client {} is not the sum of its available and held funds", row.id)));
+            }
+        }
+
+        let row_owners = row.owners.as_deref().unwrap_or_default().split_whitespace()
+            .map(|owner| owner.parse::<u16>().map_err(|_| invalid(line, format!("invalid owner '{}'", owner))))
+            .collect::<io::Result<Vec<_>>>()?;
+        let (balances, locked, frozen, owners) = clients.entry(row.id).or_insert_with(|| (Vec::new(), row.locked, row.frozen, row_owners.clone()));
+        if *owners != row_owners {
+            return Err(invalid(line, format!("client {} has different owners on different rows", row.id)));
+        }
+        if *locked != row.locked {
+            return Err(invalid(line, format!("client {} is both locked and unlocked", row.id)));
+        }
+        if *frozen != row.frozen {
+            return Err(invalid(line, format!("client {} is both frozen and unfrozen", row.id)));
+        }
+        let account = row.account.filter(|account| !account.is_empty() && account != MAIN_ACCOUNT);
+        if balances.iter().any(|balance| balance.account() == account.as_deref() && balance.currency() == row.currency.as_deref()) {
+            return Err(invalid(line, format!("client {} has more than one row for the same sub-account and currency", row.id)));
+        }
+        let balance = Balance::from_parts(row.currency, &available, &held).map_err(|e| invalid(line, e.to_string()))?;
+        balances.push(balance.in_account(account));
+    }
+
+    Ok(clients.into_iter()
+        .map(|(id, (balances, locked, frozen, owners))| {
+            let mut client = Client::from_balances(id, balances, locked);
+            client.set_frozen(frozen);
+            client.set_owners(owners);
+            client
+        })
+        .collect())
+}
+
+/// An enumeration of each supported output format for the final account state.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Comma separated values, with a header row.
+    #[default]
+    Csv,
+
+    /// A single JSON array of account objects.
+    Json,
+
+    /// Newline delimited JSON, with one account object per line.
+    Jsonl,
+
+    /// Apache Parquet, with amounts as fixed point decimals.
+    #[cfg(feature = "arrow")]
+    Parquet,
+
+    /// The Apache Arrow IPC file format, with amounts as fixed point decimals.
+    #[cfg(feature = "arrow")]
+    Arrow,
+}
+
+impl OutputFormat {
+    /// Write every client account in this format, ordered by client id, with a row for each currency the client holds.
+    /// Amounts are always written as strings with exactly the precision's number of decimal places, so no precision is lost.
+    pub fn write<'a, W, I>(self, writer: W, clients: I, precision: Precision) -> io::Result<()>
+    where
+        W: io::Write,
+        I: IntoIterator<Item = &'a Client>
+    {
+        self.write_with(writer, clients, precision, &CsvDialect::default())
+    }
+
+    /// Write every client account in this format, as [`OutputFormat::write`] does, with CSV written in a dialect.
+    pub fn write_with<'a, W, I>(self, writer: W, clients: I, precision: Precision, dialect: &CsvDialect) -> io::Result<()>
+    where
+        W: io::Write,
+        I: IntoIterator<Item = &'a Client>
+    {
+        let mut clients = clients.into_iter().collect::<Vec<_>>();
+        clients.sort_by_key(|client| client.id());
+        self.write_rows(writer, Account::rows(&clients, precision, dialect.frozen_column()), precision, dialect)
+    }
+
+    /// Write every client account in the books of every tenant, as [`OutputFormat::write`] does, ordered by tenant and
+    /// then client id, with a `tenant` column when there is any tenant other than the default books.
+    pub fn write_tenants<W: io::Write, S: Storage>(self, writer: W, tenants: &Tenants<'_, S>, precision: Precision) -> io::Result<()> {
+        self.write_tenants_with(writer, tenants, precision, &CsvDialect::default())
+    }
+
+    /// Write every client account in the books of every tenant, as [`OutputFormat::write_tenants`] does, with CSV written in a dialect.
+    /// The `frozen` column is always written when the accounts of any tenant can be frozen.
+    pub fn write_tenants_with<W: io::Write, S: Storage>(self, writer: W, tenants: &Tenants<'_, S>, precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        let dialect = dialect.with_frozen_column(dialect.frozen_column() || tenants.iter().any(|(_, engine)| engine.freezes()));
+        let books = tenants.iter()
+            .map(|(tenant, engine)| engine.accounts().map(|clients| (tenant.map(str::to_string), clients)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io::Error::other)?;
+        self.write_books_with(writer, &books, precision, &dialect)
+    }
+
+    /// Write every client account of the books of each tenant, or `None` for the default books, as
+    /// [`OutputFormat::write_tenants`] does, such as the accounts of every tenant as of an earlier instant.
+    pub fn write_books<W: io::Write>(self, writer: W, books: &[(Option<String>, Vec<Client>)], precision: Precision) -> io::Result<()> {
+        self.write_books_with(writer, books, precision, &CsvDialect::default())
+    }
+
+    /// Write every client account of the books of each tenant, as [`OutputFormat::write_books`] does, with CSV written in a dialect.
+    pub fn write_books_with<W: io::Write>(self, writer: W, books: &[(Option<String>, Vec<Client>)], precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        let clients = books.iter()
+            .flat_map(|(tenant, clients)| clients.iter().map(move |client| (tenant.as_deref(), client)))
+            .collect::<Vec<_>>();
+        self.write_rows(writer, Account::tenant_rows(&clients, precision, dialect.frozen_column()), precision, dialect)
+    }
+
+    /// Write every client account of the books of each tenant, as [`OutputFormat::write_books`] does, with the activity
+    /// of each account in the `processed`, `rejected`, `open_disputes` and `last_activity` columns.
+    pub fn write_activity<W: io::Write>(self, writer: W, books: &[Book], precision: Precision) -> io::Result<()> {
+        self.write_activity_with(writer, books, precision, &CsvDialect::default())
+    }
+
+    /// Write every client account with its activity, as [`OutputFormat::write_activity`] does, with CSV written in a dialect.
+    pub fn write_activity_with<W: io::Write>(self, writer: W, books: &[Book], precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        let clients = books.iter()
+            .flat_map(|(tenant, clients)| clients.iter().map(move |(client, activity)| (tenant.as_deref(), client, activity)))
+            .collect::<Vec<_>>();
+        self.write_rows(writer, Account::activity_rows(&clients, precision, dialect.frozen_column()), precision, dialect)
+    }
+
+    /// Write the closing balances of every client account at the end of a day, as [`OutputFormat::write_tenants`] does.
+    pub fn write_close<W: io::Write>(self, writer: W, close: &DailyClose, precision: Precision) -> io::Result<()> {
+        self.write_close_with(writer, close, precision, &CsvDialect::default())
+    }
+
+    /// Write the closing balances of a day, as [`OutputFormat::write_close`] does, with CSV written in a dialect.
+    pub fn write_close_with<W: io::Write>(self, writer: W, close: &DailyClose, precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        self.write_books_with(writer, &close.books, precision, dialect)
+    }
+
+    /// Write the closing balances of several days as one output, ordered by day, with a `date` column holding the day of each row.
+    pub fn write_closes<W: io::Write>(self, writer: W, closes: &[DailyClose], precision: Precision) -> io::Result<()> {
+        self.write_closes_with(writer, closes, precision, &CsvDialect::default())
+    }
+
+    /// Write the closing balances of several days, as [`OutputFormat::write_closes`] does, with CSV written in a dialect.
+    pub fn write_closes_with<W: io::Write>(self, writer: W, closes: &[DailyClose], precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        self.write_rows(writer, Account::close_rows(closes, precision, dialect.frozen_column()), precision, dialect)
+    }
+
+    /// Write every row of the account output in this format.
+    // NOTE: The precision is only needed by the columnar formats.
+    #[cfg_attr(not(feature = "arrow"), allow(unused_variables))]
+    fn write_rows<W: io::Write>(self, writer: W, accounts: Vec<Account>, precision: Precision, dialect: &CsvDialect) -> io::Result<()> {
+        match self {
+            Self::Csv => {
+                let mut writer = dialect.writer().from_writer(writer);
+                for account in accounts {
+                    writer.serialize(account)?;
+                }
+                writer.flush()
+            },
+            Self::Json => {
+                let mut writer = writer;
+                serde_json::to_writer(&mut writer, &accounts)?;
+                writeln!(writer)
+            },
+            Self::Jsonl => {
+                let mut writer = writer;
+                for account in accounts {
+                    serde_json::to_writer(&mut writer, &account)?;
+                    writeln!(writer)?;
+                }
+                Ok(())
+            },
+            #[cfg(feature = "arrow")]
+            Self::Parquet => crate::columnar::write_parquet(writer, &accounts, precision.scale),
+            #[cfg(feature = "arrow")]
+            Self::Arrow => crate::columnar::write_arrow(writer, &accounts, precision.scale),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            #[cfg(feature = "arrow")]
+            "parquet" => Ok(Self::Parquet),
+            #[cfg(feature = "arrow")]
+            "arrow" | "ipc" => Ok(Self::Arrow),
+            _ => Err(format!("unknown output format '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "csv"),
+            Self::Json => write!(f, "json"),
+            Self::Jsonl => write!(f, "jsonl"),
+            #[cfg(feature = "arrow")]
+            Self::Parquet => write!(f, "parquet"),
+            #[cfg(feature = "arrow")]
+            Self::Arrow => write!(f, "arrow"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType};
+
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 1, Some(BigDecimal::from_str("1.5").unwrap()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(BigDecimal::from_str("2").unwrap()))).unwrap();
+        engine
+    }
+
+    #[test]
+    fn json_output() {
+        let mut output = Vec::new();
+        OutputFormat::Json.write(&mut output, &engine().clients().unwrap(), Precision::default()).unwrap();
+
+        let accounts: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(accounts[0]["id"], 1);
+        assert_eq!(accounts[1]["id"], 2);
+        assert_eq!(accounts[1]["available"], "1.5000");
+        assert_eq!(accounts[1]["locked"], false);
+    }
+
+    #[test]
+    fn jsonl_output() {
+        let mut output = Vec::new();
+        OutputFormat::Jsonl.write(&mut output, &engine().clients().unwrap(), Precision::default()).unwrap();
+
+        let lines = String::from_utf8(output).unwrap();
+
+        assert_eq!(lines.lines().count(), 2);
+        assert!(lines.lines().all(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["total"].is_string()));
+    }
+
+    #[test]
+    fn frozen_column_whenever_accounts_can_be_frozen() {
+        let mut engine = Engine::new().with_admin(true);
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(2)))).unwrap();
+        let tenants = Tenants::from(engine);
+
+        // NOTE: No account is frozen, but the columns do not change once one is.
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_tenants(&mut output, &tenants, Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "id,available,held,total,locked,frozen\n1,2.0000,0.0000,2.0000,false,false\n");
+
+        let clients = tenants.single().unwrap().clients().unwrap();
+        let mut output = Vec::new();
+        OutputFormat::Csv.write(&mut output, &clients, Precision::default()).unwrap();
+        assert!(String::from_utf8_lossy(&output).starts_with("id,available,held,total,locked\n"));
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_with(&mut output, &clients, Precision::default(), &CsvDialect::default().with_frozen_column(true)).unwrap();
+        assert!(String::from_utf8_lossy(&output).starts_with("id,available,held,total,locked,frozen\n"));
+    }
+
+    #[test]
+    fn accounts_read_back() {
+        let mut engine = engine();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 3, Some(BigDecimal::from(5))).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 1, None)).unwrap();
+        let mut engine = engine.with_admin(true);
+        engine.process(&Transaction::new(TransactionType::Freeze, 1, 4, None)).unwrap();
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write(&mut output, &engine.clients().unwrap(), Precision::default()).unwrap();
+        assert!(String::from_utf8_lossy(&output).starts_with("id,currency,available,held,total,locked,frozen\n"));
+
+        let mut clients = engine.clients().unwrap();
+        clients.sort_by_key(|client| client.id());
+        assert_eq!(accounts_from_reader(output.as_slice()).unwrap(), clients);
+
+        let accounts = accounts_from_reader("client, available, held\n3, 1.5, 2\n".as_bytes()).unwrap();
+        assert_eq!(accounts[0].total(), BigDecimal::from_str("3.5").unwrap());
+        assert!(!accounts[0].locked());
+
+        let error = accounts_from_reader("id,available,held,total\n1,1,1,3\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: total of client 1 is not the sum of its available and held funds");
+    }
+
+    #[test]
+    fn csv_output_per_currency() {
+        let mut engine = engine();
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 3, Some(BigDecimal::from(5))).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 4, Some(BigDecimal::from(7))).with_currency("USD")).unwrap();
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write(&mut output, &engine.clients().unwrap(), Precision::default()).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\
+id,currency,available,held,total,locked
+1,,2.0000,0.0000,2.0000,false
+1,USD,7.0000,0.0000,7.0000,false
+2,,1.5000,0.0000,1.5000,false
+3,EUR,5.0000,0.0000,5.0000,false
+");
+    }
+}
@@ -0,0 +1,127 @@
+use std::{borrow::Cow, collections::BTreeMap, io};
+
+use serde::Deserialize;
+
+use crate::Transaction;
+
+/// A mapping of clients to the joint accounts they operate, so transactions of any owner of a joint account are
+/// applied to its shared balance. A client that owns no joint account operates the account of its own id.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Ownership {
+    /// The joint account each owner operates, keyed by the id of the owner.
+    accounts: BTreeMap<u16, u16>
+}
+
+/// A row of a CSV file of account owners.
+#[derive(Debug, Deserialize)]
+struct OwnerRow {
+    account: u16,
+    client: u16
+}
+
+impl Ownership {
+    /// Read the owners of every joint account from a CSV file with `account` and `client` columns, with a row for each owner.
+    /// A client may own only one joint account, and the id of a joint account may only be that of one of its own owners.
+    pub fn read<R: io::Read>(reader: R) -> io::Result<Self> {
+        let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers()?.clone();
+
+        let mut ownership = Self::default();
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let line = record.position().map_or(0, |position| position.line());
+            let row = record.deserialize::<OwnerRow>(Some(&headers))
+                .map_err(|e| invalid(line, e.to_string()))?;
+
+            if ownership.accounts.insert(row.client, row.account).is_some() {
+                return Err(invalid(line, format!("client {} owns more than one account", row.client)));
+            }
+        }
+
+        // NOTE: An account whose id is that of an owner of another account would be applied to twice over, so it is refused.
+        if let Some((client, account)) = ownership.accounts.iter().find(|(_, account)| ownership.accounts.get(account).is_some_and(|other| other != *account)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("account {} of client {} is owned by another account", account, client)));
+        }
+        Ok(ownership)
+    }
+
+    /// Add an owner of a joint account.
+    pub fn with_owner(mut self, account: u16, client: u16) -> Self {
+        self.accounts.insert(client, account);
+        self
+    }
+
+    /// The account a client operates, which is its own unless it owns a joint account.
+    pub fn account_of(&self, client: u16) -> u16 {
+        self.accounts.get(&client).copied().unwrap_or(client)
+    }
+
+    /// Every owner of an account, in order, or none if it is not a joint account.
+    pub fn owners(&self, account: u16) -> Vec<u16> {
+        self.accounts.iter()
+            .filter(|(_, owned)| **owned == account)
+            .map(|(client, _)| *client)
+            .collect()
+    }
+
+    /// The transaction as applied to the accounts its client and destination operate.
+    /// The owner that submitted a transaction of a joint account is kept as its `owner` metadata.
+    pub fn resolve<'a>(&self, transaction: &'a Transaction) -> Cow<'a, Transaction> {
+        let account = self.account_of(transaction.client_id);
+        let destination = transaction.destination.map(|destination| self.account_of(destination));
+        if account == transaction.client_id && destination == transaction.destination {
+            return Cow::Borrowed(transaction);
+        }
+
+        let mut resolved = transaction.clone();
+        if account != transaction.client_id {
+            resolved.metadata.insert("owner".to_string(), transaction.client_id.to_string());
+        }
+        resolved.client_id = account;
+        resolved.destination = destination;
+        Cow::Owned(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, OutputFormat, Precision, Storage, TransactionError, TransactionType, process_parallel_with};
+
+    #[test]
+    fn owners_share_the_balance_of_a_joint_account() {
+        let ownership = Ownership::read("account, client\n1, 1\n1, 2\n".as_bytes()).unwrap();
+        assert_eq!((ownership.account_of(2), ownership.account_of(3), ownership.owners(1)), (1, 3, vec![1, 2]));
+
+        let transactions = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into())),
+            Transaction::new(TransactionType::Withdrawal, 2, 2, Some(4.into())),
+            Transaction::new(TransactionType::Dispute, 2, 1, None),
+            Transaction::transfer(2, 1, 3, 1.into()),
+            Transaction::new(TransactionType::Deposit, 3, 4, Some(1.into())),
+        ];
+        let mut engine = Engine::new().with_ownership(Some(ownership.clone()));
+        let results = transactions.iter().map(|transaction| engine.process(transaction)).collect::<Vec<_>>();
+        assert_eq!(results[3], Err(TransactionError::InvalidDestination));
+
+        let account = engine.client(1).unwrap().unwrap();
+        assert_eq!((account.available(), account.held(), account.owners()), ((-4).into(), 10.into(), &[1, 2][..]));
+        assert!(engine.client(2).unwrap().is_none());
+        assert_eq!(engine.storage().get_transaction(2).unwrap().unwrap().metadata().get("owner").map(String::as_str), Some("2"));
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write(&mut output, &engine.clients().unwrap(), Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap().lines().nth(1), Some("1,-4.0000,10.0000,6.0000,false,1 2"));
+
+        let (sharded, rejected) = process_parallel_with(transactions.into_iter().map(|transaction| ((), transaction)), 4, || Engine::new().with_ownership(Some(ownership.clone())));
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(sharded.accounts().unwrap(), engine.accounts().unwrap());
+
+        assert!(Ownership::read("account, client\n1, 2\n1, 2\n".as_bytes()).is_err());
+        assert!(Ownership::read("account, client\n1, 2\n2, 3\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use std::{borrow::Cow, collections::{BTreeMap, HashSet}, sync::{Mutex, mpsc}, thread};
+
+use crate::{Engine, Tenants, Transaction, TransactionError, TransactionType};
+
+/// The number of transactions that can be queued for each shard before the reader blocks.
+const SHARD_CAPACITY: usize = 1024;
+
+/// A message sent from the reader to a shard worker.
+enum Message<C> {
+    /// Process a transaction in the books of a tenant, remembering its position in the input and the caller's context for it.
+    Process(usize, C, Option<String>, Box<Transaction>),
+
+    /// Acknowledge once every previously queued transaction has been processed.
+    Barrier(mpsc::Sender<()>),
+}
+
+/// Get the shard that owns a client.
+pub fn shard_for(client_id: u16, shards: usize) -> usize {
+    client_id as usize % shards
+}
+
+/// Process transactions across a number of worker threads, each owning a disjoint set of clients.
+///
+/// Transactions are routed to shards by client id through bounded channels, so each client's transactions
+/// are still processed in their original order. Each transaction is paired with a caller provided context,
+/// such as its input line, which is returned alongside the reason for every rejected transaction.
+///
+/// Duplicate transaction ids are detected by the reader before routing, so in this mode an id is claimed
+/// by the first transaction that uses it, even if that transaction is later rejected. A transfer between
+/// clients of different shards waits for both shards to catch up, and is then applied across them.
+pub fn process_parallel<C, I>(transactions: I, shards: usize) -> (Engine, Vec<(C, TransactionError)>)
+where
+    C: Send,
+    I: IntoIterator<Item = (C, Transaction)>
+{
+    process_parallel_with(transactions, shards, Engine::new)
+}
+
+/// Process transactions across a number of worker threads, as in [`process_parallel`],
+/// with every shard's engine created by the given function so they share the same configuration.
+pub fn process_parallel_with<C, I, F>(transactions: I, shards: usize, engine: F) -> (Engine, Vec<(C, TransactionError)>)
+where
+    C: Send,
+    I: IntoIterator<Item = (C, Transaction)>,
+    F: Fn() -> Engine
+{
+    // NOTE: Every transaction is kept in the same books, so that of any tenant other than the engine's is rejected.
+    let (books, rejections) = shard(transactions, shards, |_| None, |_| engine());
+
+    let mut merged = engine();
+    for (_, engine) in books.into_iter().flatten() {
+        merged.merge(engine);
+    }
+    (merged, rejections)
+}
+
+/// Process transactions across a number of worker threads, as in [`process_parallel`], keeping the books of each tenant
+/// apart as in [`Tenants`]. The engines of each tenant are created by the given function, which is passed the name of the
+/// tenant, so transaction ids and client ids are only unique within a tenant.
+pub fn process_parallel_tenants<'a, C, I, F>(transactions: I, shards: usize, engine: F) -> (Tenants<'a>, Vec<(C, TransactionError)>)
+where
+    C: Send,
+    I: IntoIterator<Item = (C, Transaction)>,
+    F: Fn(Option<&str>) -> Engine + 'a
+{
+    let (books, rejections) = shard(transactions, shards, |transaction| transaction.tenant().map(str::to_string), &engine);
+
+    let mut tenants = BTreeMap::new();
+    for (tenant, shard) in books.into_iter().flatten() {
+        tenants.entry(tenant).or_insert_with_key(|tenant: &Option<String>| engine(tenant.as_deref())).merge(shard);
+    }
+    (Tenants::with_engines(tenants, move |tenant| Ok(engine(tenant))), rejections)
+}
+
+/// The engine of a shard for each of the books it keeps, keyed by the tenant of the books.
+type Books = BTreeMap<Option<String>, Engine>;
+
+/// Process transactions across a number of worker threads, keeping apart the books each transaction is assigned to,
+/// and return the books of every shard, alongside the rejected transactions in their original order.
+/// Engines are only created on the calling thread, the first time a shard is sent a transaction of a book.
+fn shard<C, I, B, F>(transactions: I, shards: usize, book: B, new_engine: F) -> (Vec<Books>, Vec<(C, TransactionError)>)
+where
+    C: Send,
+    I: IntoIterator<Item = (C, Transaction)>,
+    B: Fn(&Transaction) -> Option<String>,
+    F: Fn(Option<&str>) -> Engine
+{
+    let shards = shards.max(1);
+    let engines = (0..shards).map(|_| Mutex::new(Books::new())).collect::<Vec<_>>();
+
+    let mut rejections = thread::scope(|scope| {
+        let mut senders = Vec::with_capacity(shards);
+        let mut workers = Vec::with_capacity(shards);
+
+        for engine in &engines {
+            let (sender, receiver) = mpsc::sync_channel::<Message<C>>(SHARD_CAPACITY);
+            senders.push(sender);
+            workers.push(scope.spawn(move || {
+                let mut rejections = Vec::new();
+                for message in receiver {
+                    match message {
+                        Message::Process(index, context, book, transaction) => {
+                            let mut books = engine.lock().unwrap();
+                            let engine = books.get_mut(&book).expect("shard engine was not created");
+                            if let Err(e) = engine.process(&transaction) {
+                                rejections.push((index, context, e));
+                            }
+                        },
+                        Message::Barrier(ack) => {
+                            let _ = ack.send(());
+                        }
+                    }
+                }
+                rejections
+            }));
+        }
+
+        let mut rejections = Vec::new();
+        let mut transaction_ids = HashSet::new();
+        let mut created = HashSet::new();
+        let mut create = |shard: usize, book: &Option<String>| {
+            if created.insert((shard, book.clone())) {
+                engines[shard].lock().unwrap().insert(book.clone(), new_engine(book.as_deref()));
+            }
+        };
+
+        // NOTE: Every owner of a joint account is routed to the shard of the account, so they share its balance.
+        let ownership = new_engine(None).ownership().cloned();
+        for (index, (context, transaction)) in transactions.into_iter().enumerate() {
+            let transaction = match ownership.as_ref().map(|ownership| ownership.resolve(&transaction)) {
+                Some(Cow::Owned(resolved)) => resolved,
+                _ => transaction
+            };
+            let book = book(&transaction);
+            if !transaction.type_.references_existing() && !transaction_ids.insert((book.clone(), transaction.id)) {
+                rejections.push((index, context, TransactionError::DuplicateTransaction(transaction.id)));
+                continue;
+            }
+
+            let source = shard_for(transaction.client_id, shards);
+            let destination = transaction.destination
+                .filter(|_| transaction.type_ == TransactionType::Transfer)
+                .map(|destination| shard_for(destination, shards))
+                .filter(|&destination| destination != source);
+            create(source, &book);
+
+            let Some(destination) = destination else {
+                senders[source].send(Message::Process(index, context, book, Box::new(transaction))).expect("shard worker stopped");
+                continue;
+            };
+            create(destination, &book);
+
+            let (ack, acks) = mpsc::channel();
+            for shard in [source, destination] {
+                senders[shard].send(Message::Barrier(ack.clone())).expect("shard worker stopped");
+            }
+            acks.recv().and_then(|_| acks.recv()).expect("shard worker stopped");
+
+            let mut source_books = engines[source].lock().unwrap();
+            let mut destination_books = engines[destination].lock().unwrap();
+            let source_engine = source_books.get_mut(&book).expect("shard engine was not created");
+            let destination_engine = destination_books.get_mut(&book).expect("shard engine was not created");
+            if let Err(e) = source_engine.transfer_to(destination_engine, &transaction) {
+                rejections.push((index, context, e));
+            }
+        }
+
+        drop(senders);
+        for worker in workers {
+            rejections.extend(worker.join().expect("shard worker panicked"));
+        }
+        rejections
+    });
+
+    let books = engines.into_iter()
+        .map(|books| books.into_inner().unwrap())
+        .collect();
+
+    rejections.sort_by_key(|(index, _, _)| *index);
+    (books, rejections.into_iter().map(|(_, context, e)| (context, e)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use bigdecimal::{BigDecimal, Zero};
+
+    use super::*;
+    use crate::{Applied, Client, EngineObserver, Fee, FeeSchedule, Ledger, Roster, RosterEntry};
+
+    /// The id of every transaction reported to an observer, with the code of the reason it was rejected, if it was.
+    type Reported = Arc<Mutex<Vec<(u32, Result<(), String>)>>>;
+
+    /// Records the outcome of every transaction an engine reports, wherever it is processed.
+    struct Recorder(Reported);
+
+    impl EngineObserver for Recorder {
+        fn on_accepted(&mut self, transaction: &Transaction, _: &Applied) {
+            self.0.lock().unwrap().push((transaction.id, Ok(())));
+        }
+
+        fn on_rejected(&mut self, transaction: &Transaction, error: &TransactionError) {
+            self.0.lock().unwrap().push((transaction.id, Err(error.code().to_string())));
+        }
+    }
+
+    fn amount(value: &str) -> Option<BigDecimal> {
+        Some(BigDecimal::from_str(value).unwrap())
+    }
+
+    fn transactions() -> Vec<Transaction> {
+        let mut transactions = Vec::new();
+        for client in 0..20u16 {
+            let base = client as u32 * 10;
+            transactions.push(Transaction::new(TransactionType::Deposit, client, base + 1, amount("100")));
+            transactions.push(Transaction::new(TransactionType::Withdrawal, client, base + 2, amount("30")));
+            transactions.push(Transaction::transfer(client, (client + 1) % 20, base + 3, amount("50").unwrap()));
+            transactions.push(Transaction::new(TransactionType::Dispute, client, base + 1, None));
+            transactions.push(Transaction::new(TransactionType::Withdrawal, client, base + 4, amount("500")));
+        }
+        transactions
+    }
+
+    #[test]
+    fn matches_sequential_processing() {
+        let mut sequential = Engine::new();
+        let expected = sequential.process_all(transactions());
+
+        let (engine, rejections) = process_parallel(transactions().into_iter().map(|transaction| (transaction.id, transaction)), 4);
+
+        assert_eq!(rejections.iter().map(|(id, e)| (*id, e.clone())).collect::<Vec<_>>(), expected.iter().map(|(transaction, e)| (transaction.id, e.clone())).collect::<Vec<_>>());
+
+        for client in sequential.clients().unwrap() {
+            let parallel = engine.client(client.id()).unwrap().unwrap();
+            assert_eq!(parallel.available(), client.available());
+            assert_eq!(parallel.held(), client.held());
+            assert_eq!(parallel.total(), client.total());
+        }
+        assert_eq!(engine.clients().unwrap().len(), sequential.clients().unwrap().len());
+    }
+
+    #[test]
+    fn duplicate_ids_across_shards() {
+        let transactions = vec![
+            (1, Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))),
+            (2, Transaction::new(TransactionType::Deposit, 2, 1, amount("10"))),
+        ];
+
+        let (engine, rejections) = process_parallel(transactions, 2);
+
+        assert_eq!(rejections, vec![(2, TransactionError::DuplicateTransaction(1))]);
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), BigDecimal::from(10));
+    }
+    #[test]
+    fn transfers_across_shards_match_sequential_processing() {
+        let roster = (1..=4).fold(Roster::default().with_reject_unknown(true), |roster, client| roster.with_client(client, RosterEntry::default()));
+        let fees = FeeSchedule::new(9).with_fee(TransactionType::Transfer, None, Fee { flat: BigDecimal::from(1), percent: BigDecimal::zero() });
+        let engine = |reported: &Reported| Engine::builder()
+            .roster(Some(roster.clone()))
+            .fees(Some(fees.clone()))
+            .check_invariants(true)
+            .ledger(Some(Ledger::new()))
+            .activity(true)
+            .observer(Recorder(reported.clone()))
+            .build();
+        let transactions = vec![
+            Transaction::new(TransactionType::Deposit, 1, 1, amount("100")),
+            Transaction::new(TransactionType::Deposit, 4, 2, amount("5")),
+            Transaction::transfer(1, 2, 3, amount("10").unwrap()),
+            Transaction::transfer(1, 5, 4, amount("10").unwrap()),
+            Transaction::transfer(4, 1, 5, amount("5").unwrap()),
+            Transaction::transfer(2, 3, 6, amount("9").unwrap()),
+            Transaction::transfer(3, 2, 7, amount("4").unwrap()),
+        ];
+
+        let sequential_reported = Arc::new(Mutex::new(Vec::new()));
+        let mut sequential = engine(&sequential_reported);
+        let expected = sequential.process_all(transactions.clone());
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let (engine, rejections) = process_parallel_with(transactions.into_iter().map(|transaction| (transaction.id, transaction)), 2, || engine(&reported));
+
+        assert_eq!(rejections, expected.iter().map(|(transaction, e)| (transaction.id, e.clone())).collect::<Vec<_>>());
+        assert_eq!(rejections.iter().map(|(id, e)| (*id, e.code())).collect::<Vec<_>>(), vec![(4, "unknown_client"), (5, "insufficient_funds")]);
+        let clients = |engine: &Engine| {
+            let mut clients = engine.clients().unwrap();
+            clients.sort_by_key(Client::id);
+            clients
+        };
+        assert_eq!(clients(&engine), clients(&sequential));
+        assert_eq!(engine.client(9).unwrap().unwrap().total(), BigDecimal::from(3));
+        assert_eq!(engine.ledger().unwrap().trial_balance(), sequential.ledger().unwrap().trial_balance());
+        for client in 1..=4 {
+            assert_eq!(engine.activity(client), sequential.activity(client));
+        }
+
+        let mut reported = reported.lock().unwrap().clone();
+        reported.sort();
+        assert_eq!(reported, *sequential_reported.lock().unwrap());
+    }
+
+    #[test]
+    fn unknown_clients_are_rejected_across_shards() {
+        let roster = Roster::default().with_reject_unknown(true).with_client(1, RosterEntry::default()).with_client(2, RosterEntry::default());
+        let transactions = vec![
+            (1, Transaction::new(TransactionType::Deposit, 1, 1, amount("100"))),
+            (2, Transaction::transfer(1, 2, 2, amount("10").unwrap())),
+            (3, Transaction::transfer(1, 4, 3, amount("10").unwrap())),
+            (4, Transaction::transfer(3, 2, 4, amount("10").unwrap())),
+        ];
+
+        let (engine, rejections) = process_parallel_with(transactions, 2, || Engine::new().with_roster(Some(roster.clone())));
+
+        assert_eq!(rejections, vec![(3, TransactionError::UnknownClient(4)), (4, TransactionError::UnknownClient(3))]);
+        assert_eq!(engine.client(1).unwrap().unwrap().total(), BigDecimal::from(90));
+        assert_eq!(engine.client(2).unwrap().unwrap().total(), BigDecimal::from(10));
+        assert!(engine.client(3).unwrap().is_none());
+        assert!(engine.client(4).unwrap().is_none());
+    }
+
+    #[test]
+    fn activity_counts_transfers_across_shards() {
+        let at = |seconds| chrono::DateTime::from_timestamp(seconds, 0).unwrap();
+        let roster = Roster::default().with_reject_unknown(true).with_client(1, RosterEntry::default()).with_client(2, RosterEntry::default());
+        let transactions = vec![
+            (1, Transaction::new(TransactionType::Deposit, 1, 1, amount("100")).with_timestamp(at(10))),
+            (2, Transaction::transfer(1, 2, 2, amount("10").unwrap()).with_timestamp(at(20))),
+            (3, Transaction::transfer(1, 4, 3, amount("10").unwrap()).with_timestamp(at(30))),
+        ];
+
+        let (engine, _) = process_parallel_with(transactions, 2, || Engine::new().with_roster(Some(roster.clone())).with_activity(true));
+
+        let activity = engine.activity(1).unwrap();
+        assert_eq!((activity.processed, activity.rejected, activity.last_activity), (2, 1, Some(at(30))));
+        assert!(engine.activity(2).is_none());
+    }
+
+    #[test]
+    fn tenants_keep_isolated_books_across_shards() {
+        let transactions = vec![
+            (1, Transaction::new(TransactionType::Deposit, 1, 1, amount("10"))),
+            (2, Transaction::new(TransactionType::Deposit, 1, 1, amount("3")).with_tenant("acme")),
+            (3, Transaction::transfer(1, 2, 2, amount("2").unwrap()).with_tenant("acme")),
+            (4, Transaction::new(TransactionType::Deposit, 2, 1, amount("1")).with_tenant("acme")),
+        ];
+
+        let (tenants, rejections) = process_parallel_tenants(transactions.clone(), 2, |tenant| Engine::new().with_tenant(tenant.map(str::to_string)));
+
+        assert_eq!(rejections, vec![(4, TransactionError::DuplicateTransaction(1))]);
+        assert_eq!(tenants.engine(None).unwrap().client(1).unwrap().unwrap().total(), BigDecimal::from(10));
+        assert_eq!(tenants.engine(Some("acme")).unwrap().client(1).unwrap().unwrap().total(), BigDecimal::from(1));
+        assert_eq!(tenants.engine(Some("acme")).unwrap().client(2).unwrap().unwrap().total(), BigDecimal::from(2));
+
+        // NOTE: Without tenants, every transaction is kept in the same books, where another tenant's is rejected.
+        let (_, rejections) = process_parallel(transactions, 2);
+        assert!(matches!(rejections[..], [(2, TransactionError::DuplicateTransaction(1)), (3, TransactionError::ForeignTenant(_)), (4, TransactionError::DuplicateTransaction(1))]));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{fmt, str::FromStr};
+
+use bigdecimal::{BigDecimal, RoundingMode, Zero};
+
+use crate::TransactionError;
+
+/// The number of decimal places every amount is kept to, unless configured otherwise.
+pub const DEFAULT_SCALE: i64 = 4;
+
+/// The most integer digits an amount may have, far beyond any real balance while keeping arithmetic on it cheap.
+pub const MAX_INTEGER_DIGITS: i64 = 28;
+
+/// An enumeration of the ways an amount with too many decimal places can be rounded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to the nearest value, with ties rounded away from zero.
+    #[default]
+    HalfUp,
+
+    /// Round towards zero, discarding any extra decimal places.
+    Down,
+
+    /// Round to the nearest value, with ties rounded to the nearest even digit.
+    Bankers,
+}
+
+impl From<Rounding> for RoundingMode {
+    fn from(rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::HalfUp => Self::HalfUp,
+            Rounding::Down => Self::Down,
+            Rounding::Bankers => Self::HalfEven,
+        }
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-up" => Ok(Self::HalfUp),
+            "down" => Ok(Self::Down),
+            "bankers" | "half-even" => Ok(Self::Bankers),
+            _ => Err(format!("unknown rounding mode '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for Rounding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HalfUp => write!(f, "half-up"),
+            Self::Down => write!(f, "down"),
+            Self::Bankers => write!(f, "bankers"),
+        }
+    }
+}
+
+/// An enumeration of what happens to a submitted amount with more decimal places than the scale.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ExcessPrecision {
+    /// Reject the transaction.
+    #[default]
+    Reject,
+
+    /// Round the amount to the scale.
+    Round,
+}
+
+impl FromStr for ExcessPrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "round" => Ok(Self::Round),
+            _ => Err(format!("unknown excess precision policy '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for ExcessPrecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reject => write!(f, "reject"),
+            Self::Round => write!(f, "round"),
+        }
+    }
+}
+
+/// The number of decimal places amounts are kept to, and how any extra places are handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Precision {
+    /// The number of decimal places.
+    pub scale: i64,
+
+    /// How an amount with more decimal places is rounded.
+    pub rounding: Rounding,
+
+    /// Whether a submitted amount with more decimal places is rejected or rounded.
+    pub excess: ExcessPrecision
+}
+
+impl Precision {
+    /// Create a new precision policy, which rejects submitted amounts with more decimal places.
+    pub fn new(scale: i64, rounding: Rounding) -> Self {
+        Self {
+            scale,
+            rounding,
+            excess: ExcessPrecision::default()
+        }
+    }
+
+    /// Choose whether a submitted amount with more decimal places is rejected or rounded.
+    pub fn with_excess(mut self, excess: ExcessPrecision) -> Self {
+        self.excess = excess;
+        self
+    }
+
+    /// Check a submitted amount against the scale, rounding it or rejecting it if it has more decimal places.
+    pub fn check(&self, amount: &BigDecimal) -> Result<BigDecimal, TransactionError> {
+        // NOTE: Trailing zeros are not significant, so `1.50000` is still accepted with four decimal places.
+        if self.excess == ExcessPrecision::Reject && amount.normalized().fractional_digit_count() > self.scale {
+            return Err(TransactionError::ExcessPrecision(self.scale));
+        }
+
+        self.round(amount)
+    }
+
+    /// Round an amount to the configured number of decimal places, rejecting it if it has too many integer digits.
+    pub fn round(&self, amount: &BigDecimal) -> Result<BigDecimal, TransactionError> {
+        let integer_digits = integer_digits(amount);
+        if integer_digits > MAX_INTEGER_DIGITS {
+            return Err(TransactionError::AmountOutOfRange);
+        }
+
+        // NOTE: An exponent such as `1e-999999999` would take forever to round, but is always well below half the smallest unit.
+        if integer_digits < -self.scale - 1 {
+            return Ok(BigDecimal::zero().with_scale(self.scale));
+        }
+
+        Ok(self.apply(amount))
+    }
+
+    /// Round an amount to exactly the configured number of decimal places.
+    /// Unlike a limit on significant digits, this never changes the integer part of a large amount.
+    pub fn apply(&self, amount: &BigDecimal) -> BigDecimal {
+        amount.with_scale_round(self.scale, self.rounding.into())
+    }
+
+    /// Round an amount and write it out in full, always with the configured number of decimal places.
+    // NOTE: Display writes zero without any decimal places, and very large or small amounts in exponent notation.
+    pub fn format(&self, amount: &BigDecimal) -> String {
+        self.apply(amount).to_plain_string()
+    }
+}
+
+/// The number of digits before the decimal point of an amount, which is negative for amounts with leading zeros after it.
+pub(crate) fn integer_digits(amount: &BigDecimal) -> i64 {
+    let normalized = amount.normalized();
+    normalized.digits() as i64 - normalized.fractional_digit_count()
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Self::new(DEFAULT_SCALE, Rounding::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rounding: Rounding, amount: &str) -> String {
+        Precision::new(DEFAULT_SCALE, rounding).format(&amount.parse().unwrap())
+    }
+
+    #[test]
+    fn rounding_boundaries() {
+        assert_eq!(apply(Rounding::HalfUp, "1.00005"), "1.0001");
+        assert_eq!(apply(Rounding::HalfUp, "-1.00005"), "-1.0001");
+        assert_eq!(apply(Rounding::Down, "1.00009"), "1.0000");
+        assert_eq!(apply(Rounding::Bankers, "1.00005"), "1.0000");
+        assert_eq!(apply(Rounding::Bankers, "1.00015"), "1.0002");
+        assert_eq!(apply(Rounding::HalfUp, "0"), "0.0000");
+    }
+
+    #[test]
+    fn reject_excess_precision() {
+        let precision = Precision::default();
+
+        assert_eq!(precision.check(&"1.00001".parse().unwrap()), Err(TransactionError::ExcessPrecision(DEFAULT_SCALE)));
+        assert_eq!(precision.check(&"1.00010".parse().unwrap()), Ok("1.0001".parse().unwrap()));
+        assert_eq!(precision.check(&"100".parse().unwrap()), Ok("100".parse().unwrap()));
+        assert_eq!(precision.with_excess(ExcessPrecision::Round).check(&"1.00001".parse().unwrap()), Ok("1".parse().unwrap()));
+    }
+
+    #[test]
+    fn large_amounts_keep_every_digit() {
+        assert_eq!(apply(Rounding::HalfUp, "123456789012.34567"), "123456789012.3457");
+        assert_eq!(apply(Rounding::Down, "1000000"), "1000000.0000");
+    }
+
+    #[test]
+    fn reject_amounts_out_of_range() {
+        let precision = Precision::default().with_excess(ExcessPrecision::Round);
+
+        assert_eq!(precision.check(&"1e28".parse().unwrap()), Err(TransactionError::AmountOutOfRange));
+        assert_eq!(precision.check(&"1e999999999".parse().unwrap()), Err(TransactionError::AmountOutOfRange));
+        assert!(precision.check(&"9999999999999999999999999999.9999".parse().unwrap()).is_ok());
+        assert_eq!(precision.check(&"1e-999999999".parse().unwrap()), Ok(BigDecimal::zero()));
+        assert_eq!(precision.check(&"0.00005".parse().unwrap()), Ok("0.0001".parse().unwrap()));
+    }
+}
@@ -0,0 +1,211 @@
+//! The protobuf messages of transactions and accounts, as described in `proto/transactions.proto`, and a reader of
+//! length-delimited streams of transactions.
+
+use std::{io, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+
+use crate::{Client, Precision, Record, RecordError, Transaction};
+
+/// A transaction, with the same fields as a row of the CSV input.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionMessage {
+    /// The transaction type, such as `deposit`.
+    #[prost(string, tag = "1")]
+    pub r#type: String,
+
+    /// The id of the client.
+    #[prost(uint32, tag = "2")]
+    pub client: u32,
+
+    /// The id of the transaction.
+    #[prost(uint32, tag = "3")]
+    pub tx: u32,
+
+    /// The amount, as a decimal string so no precision is lost.
+    #[prost(string, optional, tag = "4")]
+    pub amount: Option<String>,
+
+    /// The id of the client receiving the funds of a transfer.
+    #[prost(uint32, optional, tag = "5")]
+    pub to: Option<u32>,
+
+    /// The currency of the amount, or the default currency when not given.
+    #[prost(string, optional, tag = "6")]
+    pub currency: Option<String>,
+
+    /// The currency the amount of a conversion is credited in.
+    #[prost(string, optional, tag = "7")]
+    pub to_currency: Option<String>,
+
+    /// The rate of a conversion, as a decimal string.
+    #[prost(string, optional, tag = "8")]
+    pub rate: Option<String>,
+
+    /// When the transaction happened, as an RFC 3339 timestamp.
+    #[prost(string, optional, tag = "9")]
+    pub timestamp: Option<String>,
+
+    /// The sub-account the transaction applies to, or the main account when not given.
+    #[prost(string, optional, tag = "10")]
+    pub account: Option<String>,
+
+    /// The sub-account the amount of a move is credited to, or the main account when not given.
+    #[prost(string, optional, tag = "11")]
+    pub to_account: Option<String>,
+
+    /// The total funds a balance assertion expects the client to hold, as a decimal string.
+    #[prost(string, optional, tag = "12")]
+    pub total: Option<String>,
+}
+
+/// The funds a client holds in a single currency of one of its sub-accounts.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BalanceMessage {
+    /// The currency of the funds, empty for the default currency.
+    #[prost(string, tag = "1")]
+    pub currency: String,
+
+    /// The funds that are available, as a decimal string.
+    #[prost(string, tag = "2")]
+    pub available: String,
+
+    /// The funds that are held for dispute, as a decimal string.
+    #[prost(string, tag = "3")]
+    pub held: String,
+
+    /// The funds that are available or held, as a decimal string.
+    #[prost(string, tag = "4")]
+    pub total: String,
+
+    /// The sub-account holding the funds, empty for the main account.
+    #[prost(string, tag = "5")]
+    pub account: String,
+}
+
+/// The state of a single client's account.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct AccountReply {
+    /// The id of the client.
+    #[prost(uint32, tag = "1")]
+    pub client: u32,
+
+    /// Every balance of the client, starting with the default currency.
+    #[prost(message, repeated, tag = "2")]
+    pub balances: Vec<BalanceMessage>,
+
+    /// Whether the account is locked.
+    #[prost(bool, tag = "3")]
+    pub locked: bool,
+}
+
+impl TryFrom<TransactionMessage> for Transaction {
+    type Error = String;
+
+    fn try_from(message: TransactionMessage) -> Result<Self, Self::Error> {
+        let decimal = |value: Option<String>, field: &str| value
+            .map(|value| value.parse().map_err(|_| format!("transaction {} has an invalid {}", message.tx, field)))
+            .transpose();
+        let client = |id: u32| u16::try_from(id)
+            .map_err(|_| format!("client {} is out of range", id));
+
+        let mut transaction = Transaction::new(message.r#type.parse()?, client(message.client)?, message.tx, decimal(message.amount.clone(), "amount")?);
+        transaction.destination = message.to.map(client).transpose()?;
+        transaction.currency = message.currency.clone();
+        transaction.to_currency = message.to_currency.clone();
+        transaction.rate = decimal(message.rate.clone(), "rate")?;
+        transaction.total = decimal(message.total.clone(), "total")?;
+        transaction.account = message.account.clone();
+        transaction.to_account = message.to_account.clone();
+        transaction.timestamp = message.timestamp.as_deref()
+            .map(|timestamp| DateTime::<Utc>::from_str(timestamp).map_err(|_| format!("transaction {} has an invalid timestamp", message.tx)))
+            .transpose()?;
+        Ok(transaction)
+    }
+}
+
+impl AccountReply {
+    /// Describe a client's account, with every amount written to the given precision.
+    pub fn new(client: &Client, precision: Precision) -> Self {
+        Self {
+            client: client.id().into(),
+            balances: client.balances().iter()
+                .map(|balance| BalanceMessage {
+                    currency: balance.currency().unwrap_or_default().to_string(),
+                    available: precision.format(&balance.available()),
+                    held: precision.format(&balance.held()),
+                    total: precision.format(&balance.total()),
+                    account: balance.account().unwrap_or_default().to_string()
+                })
+                .collect(),
+            locked: client.locked()
+        }
+    }
+}
+
+/// Read every transaction from a stream of length-delimited `TransactionMessage`s, each preceded by its length as a
+/// varint, keeping track of the position of each message in the stream, starting at 1, as its line.
+/// A message that is not a valid transaction is returned as an error in place of its record, with the bytes of the
+/// message as hex, while a stream cut off part way through a message fails entirely.
+pub fn records_from_protobuf_reader<R: io::Read>(mut reader: R) -> io::Result<Vec<Result<Record, RecordError>>> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    let mut records = Vec::new();
+    let mut remaining = data.as_slice();
+    while !remaining.is_empty() {
+        let line = records.len() as u64 + 1;
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("message {}: {}", line, message));
+
+        let length = prost::decode_length_delimiter(&mut remaining).map_err(|e| invalid(e.to_string()))?;
+        if length > remaining.len() {
+            return Err(invalid(format!("expected {} bytes, but only {} remain", length, remaining.len())));
+        }
+        let (message, rest) = remaining.split_at(length);
+        remaining = rest;
+
+        records.push(TransactionMessage::decode(message)
+            .map_err(|e| e.to_string())
+            .and_then(Transaction::try_from)
+            .map(|transaction| Record { line, transaction })
+            .map_err(|reason| RecordError { line, column: None, field: None, message: reason, record: hex::encode(message) }));
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::TransactionType;
+
+    #[test]
+    fn length_delimited_transactions() {
+        let message = |type_: &str, tx: u32, amount: &str| TransactionMessage {
+            r#type: type_.to_string(),
+            client: 1,
+            tx,
+            amount: Some(amount.to_string()),
+            timestamp: Some("2024-01-02T09:00:00Z".to_string()),
+            ..Default::default()
+        };
+
+        let mut stream = Vec::new();
+        for message in [message("deposit", 1, "10.5"), message("withdrawal", 2, "ten"), message("deposit", 3, "1")] {
+            message.encode_length_delimited(&mut stream).unwrap();
+        }
+        let records = records_from_protobuf_reader(stream.as_slice()).unwrap();
+        let record = records[0].as_ref().unwrap();
+        assert_eq!(record.line, 1);
+        assert_eq!(record.transaction, Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10.5").unwrap()))
+            .with_timestamp(DateTime::from_str("2024-01-02T09:00:00Z").unwrap()));
+        assert_eq!(records[1].as_ref().unwrap_err().message, "transaction 2 has an invalid amount");
+        assert_eq!(records[2].as_ref().unwrap().line, 3);
+
+        stream.truncate(stream.len() - 1);
+        assert!(records_from_protobuf_reader(stream.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::{collections::BTreeMap, fmt, io, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+
+use crate::{Client, MAIN_ACCOUNT};
+
+/// A balance of a client as reported by an external statement, such as that of a bank, with its available funds,
+/// its total funds, or both.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementBalance {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The sub-account of the balance, or `None` for the main account.
+    pub account: Option<String>,
+
+    /// The currency of the balance, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// The available funds, if the statement reports them.
+    pub available: Option<BigDecimal>,
+
+    /// The total funds, if the statement reports them.
+    pub total: Option<BigDecimal>
+}
+
+/// A row of a CSV statement.
+#[derive(Debug, Deserialize)]
+struct StatementRow {
+    client: u16,
+
+    #[serde(default)]
+    account: Option<String>,
+
+    #[serde(default)]
+    currency: Option<String>,
+
+    #[serde(default)]
+    available: Option<String>,
+
+    #[serde(default)]
+    total: Option<String>
+}
+
+/// An enumeration of how a balance of the account output compares to the statement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BalanceStatus {
+    /// The balance is in both, with every amount the statement reports within the tolerance.
+    Matched,
+
+    /// The balance is in both, with an amount the statement reports outside the tolerance.
+    Mismatched,
+
+    /// The balance is only in the statement.
+    MissingFromOutput,
+
+    /// The balance is only in the account output.
+    MissingFromStatement,
+}
+
+impl fmt::Display for BalanceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Matched => write!(f, "matched"),
+            Self::Mismatched => write!(f, "mismatched"),
+            Self::MissingFromOutput => write!(f, "missing_from_output"),
+            Self::MissingFromStatement => write!(f, "missing_from_statement"),
+        }
+    }
+}
+
+/// A balance of a client compared between the account output and the statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reconciled {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The sub-account of the balance, or `None` for the main account.
+    pub account: Option<String>,
+
+    /// The currency of the balance, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// How the balance compares.
+    pub status: BalanceStatus,
+
+    /// The available and total funds of the account output, if it has the balance.
+    pub output: Option<(BigDecimal, BigDecimal)>,
+
+    /// The available and total funds of the statement, where it reports them, if it has the balance.
+    pub statement: Option<(Option<BigDecimal>, Option<BigDecimal>)>
+}
+
+/// Read the balances of an external statement from a CSV file with a `client` column, optional `account` and `currency`
+/// columns, and an `available` column, a `total` column, or both. Only one row may be given for each balance.
+pub fn read_statement<R: io::Read>(reader: R) -> io::Result<Vec<StatementBalance>> {
+    let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    if !headers.iter().any(|header| header == "available" || header == "total") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "a statement needs an available or total column"));
+    }
+
+    let mut balances = Vec::<StatementBalance>::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |position| position.line());
+        let row = record.deserialize::<StatementRow>(Some(&headers))
+            .map_err(|e| invalid(line, e.to_string()))?;
+        let amount = |amount: Option<String>, field: &str| amount
+            .filter(|amount| !amount.is_empty())
+            .map(|amount| BigDecimal::from_str(&amount).map_err(|_| invalid(line, format!("invalid {} '{}'", field, amount))))
+            .transpose();
+
+        let balance = StatementBalance {
+            client: row.client,
+            account: row.account.filter(|account| !account.is_empty() && account != MAIN_ACCOUNT),
+            currency: row.currency.filter(|currency| !currency.is_empty()),
+            available: amount(row.available, "available")?,
+            total: amount(row.total, "total")?
+        };
+        if balances.iter().any(|other| (other.client, &other.account, &other.currency) == (balance.client, &balance.account, &balance.currency)) {
+            return Err(invalid(line, format!("client {} has more than one row for the same balance", balance.client)));
+        }
+        balances.push(balance);
+    }
+    Ok(balances)
+}
+
+/// Compare every balance of the account output against an external statement, ordered by client id, sub-account and
+/// currency. A balance matches if every amount the statement reports is within the tolerance of the output.
+pub fn reconcile(accounts: &[Client], statement: &[StatementBalance], tolerance: &BigDecimal) -> Vec<Reconciled> {
+    type Key = (u16, Option<String>, Option<String>);
+    let mut balances = BTreeMap::<Key, (Option<(BigDecimal, BigDecimal)>, Option<(Option<BigDecimal>, Option<BigDecimal>)>)>::new();
+    for client in accounts {
+        for balance in client.balances() {
+            let key = (client.id(), balance.account().map(str::to_string), balance.currency().map(str::to_string));
+            balances.entry(key).or_default().0 = Some((balance.available(), balance.total()));
+        }
+    }
+    for balance in statement {
+        let key = (balance.client, balance.account.clone(), balance.currency.clone());
+        balances.entry(key).or_default().1 = Some((balance.available.clone(), balance.total.clone()));
+    }
+
+    let within = |expected: &Option<BigDecimal>, actual: &BigDecimal| expected.as_ref().is_none_or(|expected| (actual - expected).abs() <= *tolerance);
+    balances.into_iter()
+        .map(|((client, account, currency), (output, statement))| {
+            let status = match (&output, &statement) {
+                (None, _) => BalanceStatus::MissingFromOutput,
+                (_, None) => BalanceStatus::MissingFromStatement,
+                (Some((available, total)), Some((expected_available, expected_total))) => {
+                    match within(expected_available, available) && within(expected_total, total) {
+                        true => BalanceStatus::Matched,
+                        false => BalanceStatus::Mismatched
+                    }
+                }
+            };
+            Reconciled { client, account, currency, status, output, statement }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, Transaction, TransactionType};
+
+    #[test]
+    fn reconcile_balances_against_a_statement() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, Some(5.into()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 2, 2, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 3, Some(1.into()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 4, Some(2.into())).with_currency("EUR")).unwrap();
+
+        let csv = "client, account, currency, available, total\n1, main, , 10.005,\n2, , , 5, 5\n3, , EUR, , 2\n4, , , 0, 0\n";
+        let statement = read_statement(csv.as_bytes()).unwrap();
+        let reconciled = reconcile(&engine.clients().unwrap(), &statement, &BigDecimal::from_str("0.01").unwrap());
+
+        let summary = reconciled.iter().map(|balance| (balance.client, balance.currency.as_deref(), balance.status)).collect::<Vec<_>>();
+        assert_eq!(summary, vec![
+            (1, None, BalanceStatus::Matched),
+            (2, None, BalanceStatus::Mismatched),
+            (3, None, BalanceStatus::MissingFromStatement),
+            (3, Some("EUR"), BalanceStatus::Matched),
+            (4, None, BalanceStatus::MissingFromOutput),
+        ]);
+        assert_eq!(reconciled[1].output, Some((0.into(), 5.into())));
+
+        assert!(read_statement("client, held\n1, 0\n".as_bytes()).is_err());
+        assert!(read_statement("client, total\n1, 1\n1, 2\n".as_bytes()).is_err());
+    }
+}
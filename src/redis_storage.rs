@@ -0,0 +1,255 @@
+use std::{cell::RefCell, collections::HashMap, fmt, str::FromStr, time::Duration};
+
+use bigdecimal::BigDecimal;
+use redis::{Commands, Connection};
+
+use crate::{Balance, Client, SnapshotTransaction, Storage, StorageError, Transaction, TransactionStatus};
+
+/// The prefix of every key written, unless another is given.
+pub const DEFAULT_REDIS_PREFIX: &str = "transaction-system";
+
+/// A storage backend that keeps everything in Redis, so engines consuming different partitions of a source can share
+/// nothing in memory, yet take over one another's clients when the partitions are reassigned.
+///
+/// Every key starts with a prefix, so several independent sets of accounts can share a server:
+/// - `{prefix}:client:{id}` is a hash of a client's balances, with `available` and `held` fields for the default
+///   currency, `available:{currency}` and `held:{currency}` fields for every other currency, a `locked` field, and a
+///   `frozen` field when the account is frozen.
+/// - `{prefix}:clients` is a set of the id of every client.
+/// - `{prefix}:tx:{id}` is a transaction and its dispute state as JSON, which may expire once it is no longer needed.
+/// - `{prefix}:ids` is a set of the id of every transaction ever stored, so an expired or retired one is still a duplicate.
+/// - `{prefix}:retired` is a set of the id of every retired transaction.
+pub struct RedisStorage {
+    /// The connection to the server, which every command needs mutable access to, even to read.
+    connection: RefCell<Connection>,
+
+    /// The prefix of every key.
+    prefix: String,
+
+    /// How long a transaction is kept once stored, or `None` to keep every transaction until it is retired.
+    ttl: Option<Duration>
+}
+
+impl RedisStorage {
+    /// Connect to a Redis server by URL, such as `redis://localhost:6379/0`.
+    pub fn connect(url: &str) -> Result<Self, StorageError> {
+        let connection = redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(|e| StorageError(format!("unable to connect to '{}': {}", url, e)))?;
+
+        Ok(Self {
+            connection: RefCell::new(connection),
+            prefix: DEFAULT_REDIS_PREFIX.to_string(),
+            ttl: None
+        })
+    }
+
+    /// Use a different prefix for every key.
+    pub fn with_prefix<P: Into<String>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Expire every transaction this long after it was last stored, bounding how long it can be disputed for.
+    /// A disputed transaction never expires, so its dispute can always be settled.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The key of a client's hash of balances.
+    fn client_key(&self, id: u16) -> String {
+        format!("{}:client:{}", self.prefix, id)
+    }
+
+    /// The key of a transaction.
+    fn transaction_key(&self, id: u32) -> String {
+        format!("{}:tx:{}", self.prefix, id)
+    }
+
+    /// The key of a set of ids.
+    fn set_key(&self, name: &str) -> String {
+        format!("{}:{}", self.prefix, name)
+    }
+
+    /// Read the members of a set of ids.
+    fn members<T: FromStr>(&self, name: &str) -> Result<Vec<T>, StorageError> {
+        let members: Vec<String> = self.connection.borrow_mut().smembers(self.set_key(name)).map_err(error)?;
+        members.iter()
+            .map(|member| member.parse().map_err(|_| StorageError(format!("invalid id '{}' in '{}'", member, self.set_key(name)))))
+            .collect()
+    }
+}
+
+impl Storage for RedisStorage {
+    fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+        let fields: HashMap<String, String> = self.connection.borrow_mut().hgetall(self.client_key(id)).map_err(error)?;
+        match fields.is_empty() {
+            true => Ok(None),
+            false => client_from_fields(id, &fields).map(Some)
+        }
+    }
+
+    fn update_client(&mut self, client: Client) -> Result<(), StorageError> {
+        let key = self.client_key(client.id());
+        redis::pipe().atomic()
+            .del(&key).ignore()
+            .hset_multiple(&key, &client_fields(&client)).ignore()
+            .sadd(self.set_key("clients"), client.id()).ignore()
+            .query::<()>(self.connection.get_mut())
+            .map_err(error)
+    }
+
+    fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError> {
+        let value: Option<String> = self.connection.borrow_mut().get(self.transaction_key(id)).map_err(error)?;
+        value.map(|value| decode(id, &value)).transpose()
+    }
+
+    fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), StorageError> {
+        let (id, key) = (transaction.id, self.transaction_key(transaction.id));
+        let ttl = self.ttl.filter(|_| transaction.status != TransactionStatus::Disputed);
+        let value = serde_json::to_string(&SnapshotTransaction { status: transaction.status, transaction })
+            .map_err(|e| StorageError(e.to_string()))?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        match ttl {
+            // NOTE: Redis rejects an expiry of zero seconds, so an expiry of less than a second is kept to a second.
+            Some(ttl) => pipe.set_ex(&key, value, ttl.as_secs().max(1)).ignore(),
+            None => pipe.set(&key, value).ignore()
+        };
+        pipe.sadd(self.set_key("ids"), id).ignore()
+            .query::<()>(self.connection.get_mut())
+            .map_err(error)
+    }
+
+    fn clients(&self) -> Result<Vec<Client>, StorageError> {
+        self.members("clients")?.into_iter()
+            .filter_map(|id| self.get_client(id).transpose())
+            .collect()
+    }
+
+    fn transactions(&self) -> Result<Vec<Transaction>, StorageError> {
+        let ids = self.members::<u32>("ids")?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = ids.iter().map(|id| self.transaction_key(*id)).collect::<Vec<_>>();
+        let values: Vec<Option<String>> = redis::cmd("MGET").arg(keys).query(&mut *self.connection.borrow_mut()).map_err(error)?;
+        ids.into_iter().zip(values)
+            .filter_map(|(id, value)| value.map(|value| decode(id, &value)))
+            .collect()
+    }
+
+    fn contains_transaction(&self, id: u32) -> Result<bool, StorageError> {
+        self.connection.borrow_mut().sismember(self.set_key("ids"), id).map_err(error)
+    }
+
+    fn retire_transaction(&mut self, id: u32) -> Result<(), StorageError> {
+        redis::pipe().atomic()
+            .del(self.transaction_key(id)).ignore()
+            .sadd(self.set_key("retired"), id).ignore()
+            .query::<()>(self.connection.get_mut())
+            .map_err(error)
+    }
+
+    fn retired(&self) -> Result<Vec<u32>, StorageError> {
+        self.members("retired")
+    }
+}
+
+impl fmt::Debug for RedisStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisStorage")
+            .field("prefix", &self.prefix)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+/// Describe a failed command.
+fn error(e: redis::RedisError) -> StorageError {
+    StorageError(format!("redis: {}", e))
+}
+
+/// Read a stored transaction, along with its dispute state.
+fn decode(id: u32, value: &str) -> Result<Transaction, StorageError> {
+    let entry = serde_json::from_str::<SnapshotTransaction>(value)
+        .map_err(|e| StorageError(format!("stored transaction {} is corrupt: {}", id, e)))?;
+    Ok(Transaction { status: entry.status, ..entry.transaction })
+}
+
+/// The fields of the hash of a client's balances, where the balance of a sub-account in a currency is kept as, for
+/// example, `available@escrow:EUR`.
+fn client_fields(client: &Client) -> Vec<(String, String)> {
+    let mut fields = vec![("locked".to_string(), u8::from(client.locked()).to_string())];
+    if client.frozen() {
+        fields.push(("frozen".to_string(), "1".to_string()));
+    }
+    for balance in client.balances() {
+        let suffix = format!("{}{}",
+            balance.account().map(|account| format!("@{}", account)).unwrap_or_default(),
+            balance.currency().map(|currency| format!(":{}", currency)).unwrap_or_default());
+        fields.push((format!("available{}", suffix), balance.available().to_plain_string()));
+        fields.push((format!("held{}", suffix), balance.held().to_plain_string()));
+    }
+    fields
+}
+
+/// Read a client from the fields of the hash of its balances.
+fn client_from_fields(id: u16, fields: &HashMap<String, String>) -> Result<Client, StorageError> {
+    let amount = |field: &str| fields.get(field)
+        .map_or(Ok(BigDecimal::default()), |value| BigDecimal::from_str(value))
+        .map_err(|_| StorageError(format!("client {} has an invalid '{}'", id, field)));
+
+    let mut balances = Vec::new();
+    for field in fields.keys() {
+        let Some(suffix) = field.strip_prefix("available") else {
+            continue;
+        };
+
+        let (account, currency) = match suffix.strip_prefix('@') {
+            Some(rest) => match rest.split_once(':') {
+                Some((account, currency)) => (Some(account), Some(currency)),
+                None => (Some(rest), None)
+            },
+            None => (None, suffix.strip_prefix(':'))
+        };
+        let balance = Balance::from_parts(currency.map(str::to_string), &amount(field)?, &amount(&format!("held{}", suffix))?)
+            .map_err(|e| StorageError(format!("client {} has an invalid balance: {}", id, e)))?;
+        balances.push(balance.in_account(account.map(str::to_string)));
+    }
+    // NOTE: Hash fields come back in no particular order, so the balances are put back in order of their sub-accounts and currencies.
+    balances.sort_by(|a, b| (a.account(), a.currency()).cmp(&(b.account(), b.currency())));
+
+    let mut client = Client::from_balances(id, balances, fields.get("locked").is_some_and(|locked| locked == "1"));
+    client.set_frozen(fields.get("frozen").is_some_and(|frozen| frozen == "1"));
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    #[test]
+    fn clients_round_trip_through_hash_fields() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10.5").unwrap()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(BigDecimal::from(3))).with_currency("EUR")).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 2, None)).unwrap();
+
+        let client = engine.client(1).unwrap().unwrap();
+        let fields = client_fields(&client);
+        assert_eq!(fields.iter().map(|(field, value)| (field.as_str(), BigDecimal::from_str(value).unwrap())).collect::<Vec<_>>(), vec![
+            ("locked", BigDecimal::from(0)),
+            ("available", BigDecimal::from_str("10.5").unwrap()),
+            ("held", BigDecimal::from(0)),
+            ("available:EUR", BigDecimal::from(0)),
+            ("held:EUR", BigDecimal::from(3)),
+        ]);
+        assert_eq!(client_from_fields(1, &fields.into_iter().collect()), Ok(client));
+        assert!(client_from_fields(1, &HashMap::from([("available".to_string(), "ten".to_string())])).is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use std::io;
+
+#[cfg(feature = "s3")]
+use std::{env, time::SystemTime};
+
+#[cfg(feature = "s3")]
+use chrono::DateTime;
+#[cfg(feature = "s3")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "s3")]
+use sha2::{Digest, Sha256};
+
+/// The schemes of the remote sources that can be read, when built with the matching feature.
+const REMOTE_SCHEMES: &[&str] = &["http://", "https://", "s3://"];
+
+/// Whether an input path is the URL of a remote source, rather than a local file.
+pub fn is_remote(path: &str) -> bool {
+    REMOTE_SCHEMES.iter().any(|scheme| path.starts_with(scheme))
+}
+
+/// Open a remote source for reading, streaming its body as it is read rather than downloading it first.
+/// HTTP(S) URLs are fetched with a GET request, and `s3://bucket/key` URLs with a signed request to S3.
+pub fn open(url: &str) -> io::Result<Box<dyn io::Read + Send>> {
+    #[cfg(feature = "s3")]
+    if let Some(location) = url.strip_prefix("s3://") {
+        return S3Object::from_location(location)?.open();
+    }
+
+    if url.starts_with("s3://") {
+        return Err(io::Error::new(io::ErrorKind::Unsupported, "reading from S3 requires the s3 feature"));
+    }
+
+    get(ureq::get(url), url)
+}
+
+/// Send a GET request, returning a reader over the body of a successful response.
+/// A status of 404 is reported as a missing file, and 401 or 403 as a permission error.
+fn get(request: ureq::Request, url: &str) -> io::Result<Box<dyn io::Read + Send>> {
+    match request.call() {
+        Ok(response) => Ok(Box::new(response.into_reader())),
+        Err(ureq::Error::Status(status, _)) => {
+            let kind = match status {
+                404 => io::ErrorKind::NotFound,
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                _ => io::ErrorKind::Other
+            };
+            Err(io::Error::new(kind, format!("'{}' responded with status {}", url, status)))
+        },
+        Err(e) => Err(io::Error::other(e))
+    }
+}
+
+/// An object in an S3 bucket, along with where and how to request it.
+/// Credentials and the region are read from the standard `AWS_*` environment variables, and `AWS_ENDPOINT_URL`
+/// points at an S3 compatible service instead, using path-style requests.
+#[cfg(feature = "s3")]
+#[derive(Debug)]
+struct S3Object {
+    /// The URL of the object.
+    url: String,
+
+    /// The host the request is sent to, which is part of the signature.
+    host: String,
+
+    /// The path of the object, as it appears in the request.
+    path: String,
+
+    /// The region of the bucket.
+    region: String
+}
+
+#[cfg(feature = "s3")]
+impl S3Object {
+    /// Locate an object from the `bucket/key` part of an S3 URL.
+    fn from_location(location: &str) -> io::Result<Self> {
+        let (bucket, key) = location.split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid S3 URL 's3://{}', expected s3://bucket/key", location)))?;
+
+        let region = env::var("AWS_REGION")
+            .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+            .unwrap_or_else(|_| "us-east-1".to_string());
+
+        Ok(Self::new(bucket, key, region, env::var("AWS_ENDPOINT_URL").ok().as_deref()))
+    }
+
+    /// Locate an object in a bucket, on AWS itself or at a custom endpoint.
+    fn new(bucket: &str, key: &str, region: String, endpoint: Option<&str>) -> Self {
+        let key = uri_encode(key, false);
+
+        match endpoint {
+            Some(endpoint) => {
+                let endpoint = endpoint.trim_end_matches('/');
+                let host = endpoint.split_once("://").map_or(endpoint, |(_, host)| host);
+                let path = format!("/{}/{}", uri_encode(bucket, true), key);
+
+                Self {
+                    url: format!("{}{}", endpoint, path),
+                    host: host.to_string(),
+                    path,
+                    region
+                }
+            },
+            None => {
+                let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+                let path = format!("/{}", key);
+
+                Self {
+                    url: format!("https://{}{}", host, path),
+                    host,
+                    path,
+                    region
+                }
+            }
+        }
+    }
+
+    /// Request the object, signing the request if there are credentials, and otherwise reading it anonymously.
+    fn open(&self) -> io::Result<Box<dyn io::Read + Send>> {
+        let request = ureq::get(&self.url);
+
+        let (Ok(access_key), Ok(secret_key)) = (env::var("AWS_ACCESS_KEY_ID"), env::var("AWS_SECRET_ACCESS_KEY")) else {
+            return get(request, &self.url);
+        };
+        let token = env::var("AWS_SESSION_TOKEN").ok();
+
+        let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_err(io::Error::other)?;
+        let now = DateTime::from_timestamp(now.as_secs() as i64, 0).unwrap_or_default();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+        let authorization = self.authorization(&access_key, &secret_key, token.as_deref(), &timestamp);
+        let mut request = request
+            .set("x-amz-content-sha256", UNSIGNED_PAYLOAD)
+            .set("x-amz-date", &timestamp)
+            .set("authorization", &authorization);
+        if let Some(token) = &token {
+            request = request.set("x-amz-security-token", token);
+        }
+
+        get(request, &self.url)
+    }
+
+    /// The AWS Signature Version 4 authorization header of a GET request for the object, made at the timestamp.
+    fn authorization(&self, access_key: &str, secret_key: &str, token: Option<&str>, timestamp: &str) -> String {
+        let date = &timestamp[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+
+        let mut headers = vec![
+            ("host", self.host.as_str()),
+            ("x-amz-content-sha256", UNSIGNED_PAYLOAD),
+            ("x-amz-date", timestamp)
+        ];
+        if let Some(token) = token {
+            headers.push(("x-amz-security-token", token));
+        }
+
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect::<String>();
+        let canonical_request = format!("GET\n{}\n\n{}\n{}\n{}", self.path, canonical_headers, signed_headers, UNSIGNED_PAYLOAD);
+
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", timestamp, scope, hex::encode(Sha256::digest(canonical_request)));
+        let signature = hex::encode(hmac(&signing_key(secret_key, date, &self.region, "s3"), &string_to_sign));
+
+        format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", access_key, scope, signed_headers, signature)
+    }
+}
+
+/// The payload hash sent for a request whose body is not signed.
+#[cfg(feature = "s3")]
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Compute the HMAC-SHA256 of a message.
+#[cfg(feature = "s3")]
+fn hmac(key: &[u8], message: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Derive the key requests to a service are signed with on a date, from the secret access key.
+#[cfg(feature = "s3")]
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac(format!("AWS4{}", secret_key).as_bytes(), date);
+    let key = hmac(&key, region);
+    let key = hmac(&key, service);
+    hmac(&key, "aws4_request")
+}
+
+/// Percent-encode everything but unreserved characters, and slashes unless they are to be encoded too.
+#[cfg(feature = "s3")]
+fn uri_encode(s: &str, encode_slash: bool) -> String {
+    s.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            b'/' if !encode_slash => "/".to_string(),
+            _ => format!("%{:02X}", byte)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_remote_paths() {
+        assert!(is_remote("https://example.com/transactions.csv.gz"));
+        assert!(is_remote("s3://bucket/daily/transactions.csv"));
+        assert!(!is_remote("transactions.csv"));
+        assert!(!is_remote("/data/s3://transactions.csv"));
+    }
+
+    #[cfg(feature = "s3")]
+    #[test]
+    fn sign_s3_requests() {
+        // NOTE: The derived key is the example given in the AWS documentation for signing requests.
+        let key = signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam");
+        assert_eq!(hex::encode(key), "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d");
+
+        let object = S3Object::new("examplebucket", "daily/2024 01.csv", "eu-west-1".to_string(), None);
+        assert_eq!(object.url, "https://examplebucket.s3.eu-west-1.amazonaws.com/daily/2024%2001.csv");
+
+        let object = S3Object::new("examplebucket", "daily.csv", "us-east-1".to_string(), Some("http://localhost:9000/"));
+        assert_eq!(object.url, "http://localhost:9000/examplebucket/daily.csv");
+        assert_eq!(object.host, "localhost:9000");
+
+        let authorization = object.authorization("AKIDEXAMPLE", "secret", None, "20240101T000000Z");
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240101/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="));
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use crate::{RecordError, Transaction, TransactionError, TransactionType};
+
+/// A row of the rejected transactions report.
+#[derive(Clone, Debug, Serialize)]
+pub struct Rejection {
+    /// The input file the transaction was read from, when processing more than one source.
+    pub file: Option<String>,
+
+    /// The line of the input the transaction was read from.
+    pub line: u64,
+
+    /// The transaction type.
+    #[serde(rename = "type")]
+    pub type_: TransactionType,
+
+    /// The id of the client.
+    #[serde(rename = "client")]
+    pub client_id: u16,
+
+    /// The id of the transaction.
+    #[serde(rename = "tx")]
+    pub id: u32,
+
+    /// A human readable reason the transaction was rejected.
+    pub reason: String
+}
+
+impl Rejection {
+    /// Create a report row for a transaction rejected with the given error.
+    pub fn new(line: u64, transaction: &Transaction, error: &TransactionError) -> Self {
+        Self {
+            file: None,
+            line,
+            type_: transaction.type_,
+            client_id: transaction.client_id,
+            id: transaction.id,
+            reason: error.to_string()
+        }
+    }
+}
+
+/// A row of the dead-letter report, holding a malformed row exactly as it was read so it can be repaired and resubmitted.
+#[derive(Clone, Debug, Serialize)]
+pub struct DeadLetter {
+    /// The input file the row was read from.
+    pub file: Option<String>,
+
+    /// The line of the input the row was read from.
+    pub line: u64,
+
+    /// The column of the offending field, starting at 1, if it is known.
+    pub column: Option<u64>,
+
+    /// The name of the offending field, if it is known.
+    pub field: Option<String>,
+
+    /// A human readable reason the row could not be read.
+    pub error: String,
+
+    /// The row as it was read.
+    pub record: String
+}
+
+impl DeadLetter {
+    /// Create a report row for a row that could not be read.
+    pub fn new(error: &RecordError) -> Self {
+        Self {
+            file: None,
+            line: error.line,
+            column: error.column,
+            field: error.field.clone(),
+            error: error.message.clone(),
+            record: error.record.clone()
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, io};
+
+use serde::Deserialize;
+
+/// A known client, as listed in a roster.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RosterEntry {
+    /// The name of the client, if given.
+    pub name: Option<String>,
+
+    /// The tier of the client, such as `retail` or `business`, if given.
+    pub tier: Option<String>
+}
+
+/// The universe of known clients, so accounts without any activity are still reported, with zero balances,
+/// and transactions of clients that are not known can be rejected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Roster {
+    /// Every known client, keyed by client id.
+    clients: BTreeMap<u16, RosterEntry>,
+
+    /// Whether transactions of clients that are not known are rejected.
+    reject_unknown: bool
+}
+
+/// A row of a CSV file of known clients.
+#[derive(Debug, Deserialize)]
+struct RosterRow {
+    client: u16,
+
+    #[serde(default)]
+    name: Option<String>,
+
+    #[serde(default)]
+    tier: Option<String>
+}
+
+impl Roster {
+    /// Read the known clients from a CSV file with a `client` column, and optional `name` and `tier` columns.
+    pub fn read<R: io::Read>(reader: R) -> io::Result<Self> {
+        let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers()?.clone();
+
+        let mut roster = Self::default();
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let line = record.position().map_or(0, |position| position.line());
+            let row = record.deserialize::<RosterRow>(Some(&headers))
+                .map_err(|e| invalid(line, e.to_string()))?;
+
+            let entry = RosterEntry {
+                name: row.name.filter(|name| !name.is_empty()),
+                tier: row.tier.filter(|tier| !tier.is_empty())
+            };
+            if roster.clients.insert(row.client, entry).is_some() {
+                return Err(invalid(line, format!("client {} has more than one row", row.client)));
+            }
+        }
+        Ok(roster)
+    }
+
+    /// Add a known client.
+    pub fn with_client(mut self, client: u16, entry: RosterEntry) -> Self {
+        self.clients.insert(client, entry);
+        self
+    }
+
+    /// Reject transactions of clients that are not known, rather than opening an account for them.
+    pub fn with_reject_unknown(mut self, reject_unknown: bool) -> Self {
+        self.reject_unknown = reject_unknown;
+        self
+    }
+
+    /// Whether transactions of clients that are not known are rejected.
+    pub fn rejects_unknown(&self) -> bool {
+        self.reject_unknown
+    }
+
+    /// Whether a client is known.
+    pub fn contains(&self, client: u16) -> bool {
+        self.clients.contains_key(&client)
+    }
+
+    /// A known client, if it is one.
+    pub fn get(&self, client: u16) -> Option<&RosterEntry> {
+        self.clients.get(&client)
+    }
+
+    /// The id of every known client, in order.
+    pub fn ids(&self) -> impl Iterator<Item = u16> + '_ {
+        self.clients.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, Engine, Transaction, TransactionError, TransactionType};
+
+    #[test]
+    fn known_clients_are_reported_and_unknown_rejected() {
+        let roster = Roster::read("client, name, tier\n1, Alice, retail\n2,,\n".as_bytes()).unwrap();
+        assert_eq!(roster.get(1).and_then(|entry| entry.name.as_deref()), Some("Alice"));
+        assert_eq!(roster.get(2), Some(&RosterEntry::default()));
+
+        let mut engine = Engine::new().with_roster(Some(roster.clone()));
+        engine.process(&Transaction::new(TransactionType::Deposit, 3, 1, Some(5.into()))).unwrap();
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.iter().map(|client| client.id()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(accounts[0], Client::new(1));
+
+        let mut engine = Engine::new().with_roster(Some(roster.with_reject_unknown(true)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 3, 1, Some(5.into()))), Err(TransactionError::UnknownClient(3)));
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(5.into()))).unwrap();
+        assert_eq!(engine.process(&Transaction::transfer(1, 4, 3, 1.into())), Err(TransactionError::UnknownClient(4)));
+        assert_eq!(engine.clients().unwrap().len(), 2);
+
+        assert!(Roster::read("client\n1\n1\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, fmt, sync::{Arc, Mutex}};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{Applied, EngineObserver, Transaction, TransactionType};
+
+/// A risk rule checked against every accepted transaction, which flags suspicious activity for review
+/// without ever blocking a transaction.
+pub trait Rule: Send {
+    /// The name of the rule, recorded with every transaction it flags.
+    fn name(&self) -> &str;
+
+    /// Check an accepted transaction and what it applied, returning why it is suspicious, if it is.
+    fn check(&mut self, transaction: &Transaction, applied: &Applied) -> Option<String>;
+}
+
+/// A row of the flagged transactions report, raised by a rule for compliance to review.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Flag {
+    /// The name of the rule that flagged the transaction.
+    pub rule: String,
+
+    /// The transaction type.
+    #[serde(rename = "type")]
+    pub type_: TransactionType,
+
+    /// The id of the client.
+    #[serde(rename = "client")]
+    pub client_id: u16,
+
+    /// The id of the transaction.
+    #[serde(rename = "tx")]
+    pub id: u32,
+
+    /// A human readable reason the transaction was flagged.
+    pub reason: String
+}
+
+/// The transactions flagged by every monitor sharing it, such as those of each shard.
+#[derive(Clone, Debug, Default)]
+pub struct Flags(Arc<Mutex<Vec<Flag>>>);
+
+impl Flags {
+    /// Take every transaction flagged so far, in the order they were flagged.
+    pub fn take(&self) -> Vec<Flag> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+
+    /// Record a flagged transaction.
+    pub(crate) fn push(&self, flag: Flag) {
+        self.0.lock().unwrap().push(flag);
+    }
+}
+
+/// An observer checking every accepted transaction against a set of rules, recording each flag they raise.
+pub struct RiskMonitor {
+    rules: Vec<Box<dyn Rule>>,
+    flags: Flags
+}
+
+impl RiskMonitor {
+    /// Create a monitor checking the rules, recording their flags to the shared flags.
+    pub fn new(rules: Vec<Box<dyn Rule>>, flags: Flags) -> Self {
+        Self { rules, flags }
+    }
+}
+
+impl EngineObserver for RiskMonitor {
+    fn on_accepted(&mut self, transaction: &Transaction, applied: &Applied) {
+        for rule in &mut self.rules {
+            if let Some(reason) = rule.check(transaction, applied) {
+                self.flags.push(Flag {
+                    rule: rule.name().to_string(),
+                    type_: transaction.type_(),
+                    client_id: transaction.client_id(),
+                    id: transaction.id(),
+                    reason
+                });
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RiskMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.rules.iter().map(|rule| rule.name()).collect::<Vec<_>>();
+        write!(f, "RiskMonitor({})", names.join(", "))
+    }
+}
+
+/// Flags every deposit, withdrawal, transfer or conversion of at least the threshold.
+#[derive(Clone, Debug)]
+pub struct AmountThreshold(pub BigDecimal);
+
+impl Rule for AmountThreshold {
+    fn name(&self) -> &str {
+        "amount_threshold"
+    }
+
+    fn check(&mut self, _: &Transaction, applied: &Applied) -> Option<String> {
+        match applied {
+            Applied::Deposited { amount, .. }
+            | Applied::Withdrew { amount, .. }
+            | Applied::Transferred { amount, .. }
+            | Applied::Converted { amount, .. } if *amount >= self.0 => Some(format!("{} is at least the threshold of {}", amount, self.0)),
+            _ => None
+        }
+    }
+}
+
+/// Flags every withdrawal or transfer out made within a window of the client's last deposit.
+/// Only transactions with a timestamp can be checked.
+#[derive(Clone, Debug)]
+pub struct RapidCycle {
+    window: TimeDelta,
+
+    /// The time, id and amount of the last deposit of each client.
+    deposits: HashMap<u16, (DateTime<Utc>, u32, BigDecimal)>
+}
+
+impl RapidCycle {
+    /// Create a rule flagging funds that leave an account within the window of being deposited.
+    pub fn new(window: TimeDelta) -> Self {
+        Self { window, deposits: HashMap::new() }
+    }
+}
+
+impl Rule for RapidCycle {
+    fn name(&self) -> &str {
+        "rapid_cycle"
+    }
+
+    fn check(&mut self, transaction: &Transaction, applied: &Applied) -> Option<String> {
+        let timestamp = transaction.timestamp()?;
+        match applied {
+            Applied::Deposited { amount, .. } => {
+                self.deposits.insert(transaction.client_id(), (timestamp, transaction.id(), amount.clone()));
+                None
+            },
+            Applied::Withdrew { amount, .. } | Applied::Transferred { amount, .. } => {
+                let (deposited_at, id, deposited) = self.deposits.get(&transaction.client_id())?;
+                let elapsed = timestamp - *deposited_at;
+                (elapsed <= self.window).then(|| format!("{} left the account {} seconds after deposit {} of {}", amount, elapsed.num_seconds(), id, deposited))
+            },
+            _ => None
+        }
+    }
+}
+
+/// Flags every dispute a client opens once it has opened more than the maximum.
+#[derive(Clone, Debug)]
+pub struct DisputeCount {
+    max: u32,
+    disputes: HashMap<u16, u32>
+}
+
+impl DisputeCount {
+    /// Create a rule flagging the disputes of a client beyond the maximum.
+    pub fn new(max: u32) -> Self {
+        Self { max, disputes: HashMap::new() }
+    }
+}
+
+impl Rule for DisputeCount {
+    fn name(&self) -> &str {
+        "dispute_count"
+    }
+
+    fn check(&mut self, transaction: &Transaction, applied: &Applied) -> Option<String> {
+        if !matches!(applied, Applied::Disputed { .. }) {
+            return None;
+        }
+
+        let disputes = self.disputes.entry(transaction.client_id()).or_default();
+        *disputes += 1;
+        (*disputes > self.max).then(|| format!("client has opened {} disputes, more than the maximum of {}", disputes, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::Engine;
+
+    #[test]
+    fn suspicious_transactions_are_flagged_but_not_blocked() {
+        let flags = Flags::default();
+        let rules: Vec<Box<dyn Rule>> = vec![
+            Box::new(AmountThreshold(BigDecimal::from(1000))),
+            Box::new(RapidCycle::new(TimeDelta::minutes(10))),
+            Box::new(DisputeCount::new(1)),
+        ];
+        let mut engine = Engine::builder().observer(RiskMonitor::new(rules, flags.clone())).build();
+
+        let at = |timestamp: &str| DateTime::<Utc>::from_str(timestamp).unwrap();
+        let transaction = |type_, id, amount: Option<u32>, timestamp| Transaction::new(type_, 1, id, amount.map(BigDecimal::from)).with_timestamp(at(timestamp));
+        engine.process(&transaction(TransactionType::Deposit, 1, Some(1500), "2024-01-01T09:00:00Z")).unwrap();
+        engine.process(&transaction(TransactionType::Withdrawal, 2, Some(100), "2024-01-01T09:05:00Z")).unwrap();
+        engine.process(&transaction(TransactionType::Deposit, 3, Some(10), "2024-01-01T10:00:00Z")).unwrap();
+        engine.process(&transaction(TransactionType::Withdrawal, 4, Some(10), "2024-01-01T11:00:00Z")).unwrap();
+        engine.process(&transaction(TransactionType::Dispute, 1, None, "2024-01-02T09:00:00Z")).unwrap();
+        engine.process(&transaction(TransactionType::Dispute, 3, None, "2024-01-02T09:00:00Z")).unwrap();
+
+        let flagged = flags.take().into_iter().map(|flag| (flag.rule, flag.id)).collect::<Vec<_>>();
+        assert_eq!(flagged, vec![
+            ("amount_threshold".to_string(), 1),
+            ("rapid_cycle".to_string(), 2),
+            ("dispute_count".to_string(), 3),
+        ]);
+        assert_eq!(engine.client(1).unwrap().unwrap().held(), BigDecimal::from(1510));
+    }
+}
@@ -0,0 +1,121 @@
+use std::{fmt, str::FromStr};
+
+use chrono::{DateTime, Days, Months, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::Transaction;
+
+/// An enumeration of how often a scheduled transaction recurs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recurrence {
+    /// Every day, at the same time.
+    Daily,
+
+    /// Every week, on the same weekday and time.
+    Weekly,
+
+    /// Every month, on the same day and time, or the last day of a month that is too short.
+    Monthly,
+}
+
+impl Recurrence {
+    /// The time of the nth occurrence after the first, counting the first as the zeroth, or `None` if it is out of range.
+    /// Each is counted from the first, so a monthly schedule starting on the 31st is back on the 31st after a shorter month.
+    pub fn nth(self, first: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Daily => first.checked_add_days(Days::new(n.into())),
+            Self::Weekly => first.checked_add_days(Days::new(u64::from(n) * 7)),
+            Self::Monthly => first.checked_add_months(Months::new(n)),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Self::Daily),
+            "weekly" => Ok(Self::Weekly),
+            "monthly" => Ok(Self::Monthly),
+            _ => Err(format!("unknown recurrence '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Daily => write!(f, "daily"),
+            Self::Weekly => write!(f, "weekly"),
+            Self::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+/// A scheduled transaction waiting for its next occurrence to fall due.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledTransaction {
+    /// The transaction as it was submitted, with the time it is first scheduled for and how often it recurs.
+    #[serde(flatten)]
+    pub transaction: Transaction,
+
+    /// The number of occurrences already applied or rejected.
+    pub occurrences: u32
+}
+
+impl ScheduledTransaction {
+    /// The time the next occurrence falls due, or `None` once there are no more occurrences.
+    pub fn due(&self) -> Option<DateTime<Utc>> {
+        let first = self.transaction.scheduled_for?;
+        match self.transaction.recurrence {
+            Some(recurrence) => recurrence.nth(first, self.occurrences),
+            None => (self.occurrences == 0).then_some(first)
+        }
+    }
+
+    /// The next occurrence, applied as an ordinary transaction with the same id, timestamped at the time it fell due.
+    pub fn occurrence(&self) -> Option<Transaction> {
+        let due = self.due()?;
+        Some(Transaction {
+            timestamp: Some(due),
+            scheduled_for: None,
+            recurrence: None,
+            ..self.transaction.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Applied, Engine, TransactionError, TransactionType};
+
+    fn at(timestamp: &str) -> DateTime<Utc> {
+        DateTime::from_str(timestamp).unwrap()
+    }
+
+    #[test]
+    fn scheduled_transactions_are_applied_once_due() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(100))).with_timestamp(at("2024-01-01T09:00:00Z"))).unwrap();
+
+        let rent = Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from(40))).with_schedule(at("2024-01-31T12:00:00Z"), Some(Recurrence::Monthly));
+        assert_eq!(engine.process(&rent), Ok(Applied::Scheduled { due: at("2024-01-31T12:00:00Z") }));
+        assert_eq!(engine.process(&rent), Err(TransactionError::DuplicateTransaction(2)));
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Dispute, 1, 3, None).with_schedule(at("2024-02-01T00:00:00Z"), None)), Err(TransactionError::NotSchedulable(TransactionType::Dispute)));
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from(100));
+
+        let applied = engine.advance(at("2024-03-31T12:00:00Z")).unwrap();
+        assert_eq!(applied.iter().map(|(transaction, result)| (transaction.timestamp(), result.is_ok())).collect::<Vec<_>>(), vec![
+            (Some(at("2024-01-31T12:00:00Z")), true),
+            (Some(at("2024-02-29T12:00:00Z")), true),
+            (Some(at("2024-03-31T12:00:00Z")), false),
+        ]);
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from(20));
+        assert_eq!(engine.snapshot().unwrap().scheduled.iter().map(ScheduledTransaction::due).collect::<Vec<_>>(), vec![Some(at("2024-04-30T12:00:00Z"))]);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{error, fmt, sync::Arc};
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use rhai::{AST, Dynamic, Map, Scope};
+
+use crate::{Client, Flag, Flags, Transaction};
+
+/// The function a script defines to decide what happens to each transaction.
+const ENTRY_POINT: &str = "check";
+
+/// The most operations a script may run for a single transaction, so a runaway script fails rather than hanging the engine.
+const MAX_OPERATIONS: u64 = 100_000;
+
+/// An enumeration of what a script can decide to do with a transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Apply the transaction as usual.
+    Accept,
+
+    /// Reject the transaction, for the reason given.
+    Reject(String),
+
+    /// Apply the transaction as usual, but flag it for review, for the reason given.
+    Flag(String),
+}
+
+/// The reason a script could not be compiled or run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError(String);
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for ScriptError {}
+
+/// A Rhai script deciding whether to accept, reject or flag each transaction before it is applied.
+///
+/// The script defines `fn check(tx, account)`, which is called with the transaction as a map of its `type`, `client`, `tx`,
+/// `amount`, `currency`, `destination` and `timestamp`, and the account as a map of its `available`, `held` and `total`
+/// funds in the transaction's currency and whether it is `locked`, or `()` for a client without one.
+/// Amounts are floating point, so are only fit for comparing against thresholds.
+/// It returns `accept()`, `reject(reason)` or `flag(reason)`, and returning nothing accepts the transaction.
+#[derive(Clone)]
+pub struct Script {
+    engine: Arc<rhai::Engine>,
+    ast: Arc<AST>,
+
+    /// Where the transactions the script flags are recorded.
+    flags: Flags
+}
+
+impl Script {
+    /// Compile a script, recording the transactions it flags to the shared flags.
+    pub fn compile(source: &str, flags: Flags) -> Result<Self, ScriptError> {
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_type_with_name::<Decision>("Decision")
+            .register_fn("accept", || Decision::Accept)
+            .register_fn("reject", |reason: &str| Decision::Reject(reason.to_string()))
+            .register_fn("flag", |reason: &str| Decision::Flag(reason.to_string()));
+
+        let ast = engine.compile(source).map_err(|e| ScriptError(e.to_string()))?;
+        if !ast.iter_functions().any(|function| function.name == ENTRY_POINT && function.params.len() == 2) {
+            return Err(ScriptError(format!("the script does not define `fn {}(tx, account)`", ENTRY_POINT)));
+        }
+        Ok(Self { engine: Arc::new(engine), ast: Arc::new(ast), flags })
+    }
+
+    /// Decide what happens to a transaction, given the account of its client as it is before the transaction.
+    pub fn decide(&self, transaction: &Transaction, client: Option<&Client>) -> Result<Decision, ScriptError> {
+        let float = |amount: &BigDecimal| Dynamic::from_float(amount.to_f64().unwrap_or_default());
+        let text = |text: Option<String>| text.map_or(Dynamic::UNIT, Dynamic::from);
+
+        let mut tx = Map::new();
+        tx.insert("type".into(), transaction.type_().to_string().into());
+        tx.insert("client".into(), Dynamic::from_int(transaction.client_id().into()));
+        tx.insert("tx".into(), Dynamic::from_int(transaction.id().into()));
+        tx.insert("amount".into(), transaction.amount().map_or(Dynamic::UNIT, float));
+        tx.insert("currency".into(), text(transaction.currency().map(str::to_string)));
+        tx.insert("account".into(), text(transaction.account().map(str::to_string)));
+        tx.insert("destination".into(), transaction.destination().map_or(Dynamic::UNIT, |destination| Dynamic::from_int(destination.into())));
+        tx.insert("timestamp".into(), text(transaction.timestamp().map(|timestamp| timestamp.to_rfc3339())));
+
+        let account = match client {
+            Some(client) => {
+                let [available, held, total] = client.balance_in(transaction.account(), transaction.currency())
+                    .map(|balance| [balance.available(), balance.held(), balance.total()])
+                    .unwrap_or_default();
+
+                let mut account = Map::new();
+                account.insert("available".into(), float(&available));
+                account.insert("held".into(), float(&held));
+                account.insert("total".into(), float(&total));
+                account.insert("locked".into(), client.locked().into());
+                Dynamic::from_map(account)
+            },
+            None => Dynamic::UNIT
+        };
+
+        let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), &self.ast, ENTRY_POINT, (Dynamic::from_map(tx), account))
+            .map_err(|e| ScriptError(e.to_string()))?;
+        if result.is_unit() {
+            return Ok(Decision::Accept);
+        }
+
+        let type_name = result.type_name();
+        result.try_cast::<Decision>()
+            .ok_or_else(|| ScriptError(format!("the script returned {} rather than accept(), reject(reason) or flag(reason)", type_name)))
+    }
+
+    /// Record a transaction the script flagged, once it has been accepted.
+    pub(crate) fn flag(&self, transaction: &Transaction, reason: String) {
+        self.flags.push(Flag {
+            rule: "script".to_string(),
+            type_: transaction.type_(),
+            client_id: transaction.client_id(),
+            id: transaction.id(),
+            reason
+        });
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Script")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Engine, TransactionError, TransactionType};
+
+    #[test]
+    fn scripts_accept_reject_or_flag_transactions() {
+        let source = r#"
+            fn check(tx, account) {
+                if tx.type == "withdrawal" && tx.amount > account.available / 2.0 {
+                    return reject("withdraws more than half the balance");
+                }
+                if tx.amount >= 1000.0 {
+                    return flag("large " + tx.type);
+                }
+            }
+        "#;
+        let flags = Flags::default();
+        let mut engine = Engine::new().with_script(Some(Script::compile(source, flags.clone()).unwrap()));
+
+        let amount = |value: &str| Some(BigDecimal::from_str(value).unwrap());
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, amount("2000"))).unwrap();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, amount("1500"))), Err(TransactionError::RejectedByScript("withdraws more than half the balance".to_string())));
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 3, amount("500"))).unwrap();
+
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from(1500));
+        assert_eq!(flags.take().into_iter().map(|flag| (flag.id, flag.reason)).collect::<Vec<_>>(), vec![(1, "large deposit".to_string())]);
+        assert!(Script::compile("fn validate(tx) { accept() }", Flags::default()).is_err());
+    }
+}
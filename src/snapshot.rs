@@ -0,0 +1,288 @@
+use std::{error, fmt, io};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{AccruedInterest, Activity, Client, DailyOutflow, ScheduledTransaction, Transaction, TransactionStatus, client::Amount};
+
+/// The bytes every snapshot starts with, used to recognise the file.
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"TXSNAP\0\0";
+
+/// The version of the snapshot format written by this build.
+/// Snapshots written by an older version are still read, while newer versions are rejected.
+pub const SNAPSHOT_VERSION: u32 = 8;
+
+/// An enumeration of the reasons a snapshot can fail to be read or written.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The snapshot could not be read or written.
+    Io(io::Error),
+
+    /// The file does not start with the snapshot magic bytes.
+    InvalidMagic,
+
+    /// The snapshot was written by a newer, unsupported version of the format.
+    UnsupportedVersion(u32),
+
+    /// The body of the snapshot is malformed.
+    Format(serde_json::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::InvalidMagic => write!(f, "not a snapshot file"),
+            Self::UnsupportedVersion(version) => write!(f, "snapshot version {} is newer than the supported version {}", version, SNAPSHOT_VERSION),
+            Self::Format(e) => write!(f, "malformed snapshot: {}", e),
+        }
+    }
+}
+
+impl error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Format(e)
+    }
+}
+
+/// A stored transaction, alongside its dispute state, which is not part of the transaction's input format.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredTransaction")]
+pub struct SnapshotTransaction {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+
+    /// Where the transaction is in its dispute lifecycle.
+    pub status: TransactionStatus
+}
+
+/// A stored transaction as read, with the dispute state of either the current or an older version.
+#[derive(Deserialize)]
+struct StoredTransaction {
+    #[serde(flatten)]
+    transaction: Transaction,
+
+    /// The status written since version 4.
+    #[serde(default)]
+    status: Option<TransactionStatus>,
+
+    /// Whether the transaction was in dispute, written before version 4.
+    #[serde(default)]
+    disputed: bool,
+
+    /// Whether the transaction was charged back, written by version 3.
+    #[serde(default)]
+    charged_back: bool
+}
+
+impl From<StoredTransaction> for SnapshotTransaction {
+    fn from(stored: StoredTransaction) -> Self {
+        // NOTE: Before version 3 a charged back transaction was left disputed, so it is read back as disputed.
+        let status = stored.status.unwrap_or(match (stored.disputed, stored.charged_back) {
+            (_, true) => TransactionStatus::ChargedBack,
+            (true, false) => TransactionStatus::Disputed,
+            (false, false) => TransactionStatus::Settled,
+        });
+        Self { transaction: stored.transaction, status }
+    }
+}
+
+/// The position reached in one partition of a streaming source, so processing can resume right after it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceOffset {
+    /// The topic the partition belongs to.
+    pub topic: String,
+
+    /// The partition within the topic.
+    pub partition: i32,
+
+    /// The offset of the next message to consume.
+    pub offset: i64
+}
+
+/// The complete state of an engine, which can be written to disk and later restored to resume processing.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Every client account.
+    pub clients: Vec<Client>,
+
+    /// Every stored transaction, so later disputes and duplicate checks still see them.
+    pub transactions: Vec<SnapshotTransaction>,
+
+    /// The ids of transactions that were dropped once they could no longer be referenced, so they are still seen as duplicates.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub retired: Vec<u32>,
+
+    /// The latest timestamp of any accepted transaction, so chronological order is still enforced after a restore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<DateTime<Utc>>,
+
+    /// The first day interest has not yet been accrued for, so accrual carries on from it after a restore.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accrual_day: Option<NaiveDate>,
+
+    /// The interest accrued on each balance that has not yet been posted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accrued: Vec<AccruedInterest>,
+
+    /// The funds that left each client's account on the day of its latest withdrawal or transfer, so its daily limits
+    /// still count them after a restore.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outflows: Vec<DailyOutflow>,
+
+    /// The scheduled transactions still to fall due, so they are still applied after a restore.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scheduled: Vec<ScheduledTransaction>,
+
+    /// The activity of each client's account, if it was counted, so the counters carry on after a restore.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub activity: Vec<Activity>,
+
+    /// The position reached in each partition of a streaming source, if the snapshot was taken while consuming one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub offsets: Vec<SourceOffset>
+}
+
+/// A client account as written by version 1, before balances were kept per currency.
+#[derive(Deserialize)]
+struct ClientV1 {
+    id: u16,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool
+}
+
+/// The body of a version 1 snapshot.
+#[derive(Deserialize)]
+struct SnapshotV1 {
+    clients: Vec<ClientV1>,
+    transactions: Vec<SnapshotTransaction>
+}
+
+impl From<SnapshotV1> for Snapshot {
+    fn from(snapshot: SnapshotV1) -> Self {
+        Self {
+            clients: snapshot.clients.into_iter()
+                .map(|client| Client::from_parts(client.id, client.available, client.held, client.total, client.locked))
+                .collect(),
+            transactions: snapshot.transactions,
+            retired: Vec::new(),
+            latest: None,
+            accrual_day: None,
+            accrued: Vec::new(),
+            outflows: Vec::new(),
+            scheduled: Vec::new(),
+            activity: Vec::new(),
+            offsets: Vec::new()
+        }
+    }
+}
+
+impl Snapshot {
+    /// Write the snapshot, prefixed with the magic bytes and format version.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), SnapshotError> {
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        serde_json::to_writer(&mut writer, self)?;
+        Ok(writer.flush()?)
+    }
+
+    /// Read a snapshot, checking the magic bytes and that the format version is supported.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => SnapshotError::InvalidMagic,
+            _ => SnapshotError::Io(e)
+        })?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        match version {
+            1 => Ok(serde_json::from_reader::<_, SnapshotV1>(reader)?.into()),
+            _ => Ok(serde_json::from_reader(reader)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    #[test]
+    fn snapshot_and_resume() {
+        let mut engine = Engine::new();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10.5").unwrap()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+
+        let mut bytes = Vec::new();
+        engine.snapshot().unwrap().write(&mut bytes).unwrap();
+
+        let mut resumed = Engine::new();
+        resumed.restore(Snapshot::read(bytes.as_slice()).unwrap()).unwrap();
+
+        assert_eq!(resumed.client(1).unwrap(), engine.client(1).unwrap());
+        resumed.process(&Transaction::new(TransactionType::Resolve, 1, 1, None)).unwrap();
+        assert_eq!(resumed.client(1).unwrap().unwrap().available(), BigDecimal::from_str("10.5").unwrap());
+    }
+
+    #[test]
+    fn reject_unsupported_snapshots() {
+        assert!(matches!(Snapshot::read(&b"type,client,tx,amount"[..]), Err(SnapshotError::InvalidMagic)));
+
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+        bytes.extend_from_slice(b"{}");
+
+        assert!(matches!(Snapshot::read(bytes.as_slice()), Err(SnapshotError::UnsupportedVersion(version)) if version == SNAPSHOT_VERSION + 1));
+    }
+
+    #[test]
+    fn migrate_version_one() {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(br#"{"clients":[{"id":1,"available":"1.5","held":"2","total":"3.5","locked":true}],"transactions":[]}"#);
+
+        let snapshot = Snapshot::read(bytes.as_slice()).unwrap();
+
+        assert_eq!(snapshot.clients[0].held(), BigDecimal::from(2));
+        assert_eq!(snapshot.clients[0].total(), BigDecimal::from_str("3.5").unwrap());
+        assert!(snapshot.clients[0].locked());
+    }
+
+    #[test]
+    fn migrate_dispute_flags() {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend_from_slice(&3u32.to_le_bytes());
+        bytes.extend_from_slice(br#"{"clients":[],"transactions":[
+            {"type":"deposit","client":1,"tx":1,"amount":"1","disputed":false},
+            {"type":"deposit","client":1,"tx":2,"amount":"1","disputed":true},
+            {"type":"deposit","client":1,"tx":3,"amount":"1","disputed":false,"charged_back":true}]}"#);
+
+        let snapshot = Snapshot::read(bytes.as_slice()).unwrap();
+
+        let statuses = snapshot.transactions.iter().map(|entry| entry.status).collect::<Vec<_>>();
+        assert_eq!(statuses, vec![TransactionStatus::Settled, TransactionStatus::Disputed, TransactionStatus::ChargedBack]);
+    }
+}
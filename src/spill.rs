@@ -0,0 +1,230 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering}
+};
+
+use crate::{Client, SnapshotTransaction, Storage, StorageError, Transaction};
+
+/// A counter to give every spill file of this process a unique name.
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// A storage backend that keeps at most a fixed number of transactions in memory, spilling the rest to a file on disk.
+///
+/// Transactions are spilled in the order they were last written, and a spilled transaction is read back from disk
+/// whenever a dispute references it. Only the position of each spilled transaction is kept in memory.
+#[derive(Debug)]
+pub struct SpillStorage {
+    /// The clients, keyed by client id, which are always kept in memory.
+    clients: HashMap<u16, Client>,
+
+    /// The transactions kept in memory, keyed by transaction id, alongside the generation they were written in.
+    transactions: HashMap<u32, (Transaction, u64)>,
+
+    /// The order transactions were written in, as their id and generation, with the oldest first.
+    order: VecDeque<(u32, u64)>,
+
+    /// The generation of the next write.
+    generation: u64,
+
+    /// The most transactions kept in memory at once.
+    capacity: usize,
+
+    /// The offset and length of the latest copy of each spilled transaction within the spill file.
+    spilled: HashMap<u32, (u64, u64)>,
+
+    /// The ids of transactions that were dropped once they could no longer be referenced.
+    retired: HashSet<u32>,
+
+    /// The spill file, which is only ever appended to.
+    file: File,
+
+    /// The length of the spill file.
+    length: u64,
+
+    /// The path of the spill file, which is removed once the storage is dropped.
+    path: PathBuf
+}
+
+impl SpillStorage {
+    /// Create a new empty storage that keeps at most `capacity` transactions in memory, spilling to a new file in a directory.
+    pub fn new<P: AsRef<Path>>(capacity: usize, directory: P) -> io::Result<Self> {
+        let path = directory.as_ref().join(format!("transactions-{}-{}.spill", process::id(), SPILL_FILES.fetch_add(1, Ordering::Relaxed)));
+        let file = File::options().read(true).append(true).create_new(true).open(&path)?;
+
+        Ok(Self {
+            clients: HashMap::new(),
+            transactions: HashMap::new(),
+            order: VecDeque::new(),
+            generation: 0,
+            capacity,
+            spilled: HashMap::new(),
+            retired: HashSet::new(),
+            file,
+            length: 0,
+            path
+        })
+    }
+
+    /// Create a new empty storage that spills to a new file in the system's temporary directory.
+    pub fn in_temp_dir(capacity: usize) -> io::Result<Self> {
+        Self::new(capacity, std::env::temp_dir())
+    }
+
+    /// The number of transactions currently spilled to disk.
+    pub fn spilled(&self) -> usize {
+        self.spilled.len()
+    }
+
+    /// Spill the transactions written longest ago until no more than the capacity are kept in memory.
+    fn evict(&mut self) -> Result<(), StorageError> {
+        while self.transactions.len() > self.capacity {
+            let Some((id, generation)) = self.order.pop_front() else {
+                break;
+            };
+
+            // NOTE: A transaction written again since is further back in the order, so this entry is stale.
+            if self.transactions.get(&id).is_some_and(|(_, latest)| *latest == generation) {
+                let (transaction, _) = self.transactions.remove(&id).unwrap();
+                self.spill(transaction)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Append a transaction to the spill file, replacing any earlier copy.
+    fn spill(&mut self, transaction: Transaction) -> Result<(), StorageError> {
+        let id = transaction.id;
+        let mut line = serde_json::to_vec(&SnapshotTransaction { status: transaction.status, transaction })
+            .map_err(|e| StorageError(e.to_string()))?;
+        line.push(b'\n');
+
+        self.file.write_all(&line).map_err(|e| StorageError(format!("unable to spill transaction {}: {}", id, e)))?;
+        self.spilled.insert(id, (self.length, line.len() as u64));
+        self.length += line.len() as u64;
+        Ok(())
+    }
+
+    /// Read a spilled transaction back from the spill file.
+    fn read(&self, id: u32, offset: u64, length: u64) -> Result<Transaction, StorageError> {
+        let mut line = vec![0; length as usize];
+        let mut file = &self.file;
+
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut line))
+            .map_err(|e| StorageError(format!("unable to read spilled transaction {}: {}", id, e)))?;
+
+        let entry = serde_json::from_slice::<SnapshotTransaction>(&line)
+            .map_err(|e| StorageError(format!("spilled transaction {} is corrupt: {}", id, e)))?;
+        Ok(Transaction { status: entry.status, ..entry.transaction })
+    }
+}
+
+impl Storage for SpillStorage {
+    fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+        Ok(self.clients.get(&id).cloned())
+    }
+
+    fn update_client(&mut self, client: Client) -> Result<(), StorageError> {
+        self.clients.insert(client.id(), client);
+        Ok(())
+    }
+
+    fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError> {
+        if let Some((transaction, _)) = self.transactions.get(&id) {
+            return Ok(Some(transaction.clone()));
+        }
+
+        self.spilled.get(&id)
+            .map(|(offset, length)| self.read(id, *offset, *length))
+            .transpose()
+    }
+
+    fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), StorageError> {
+        // The latest copy now lives in memory, so any spilled copy is out of date.
+        self.spilled.remove(&transaction.id);
+
+        self.generation += 1;
+        self.order.push_back((transaction.id, self.generation));
+        self.transactions.insert(transaction.id, (transaction, self.generation));
+        self.evict()
+    }
+
+    fn clients(&self) -> Result<Vec<Client>, StorageError> {
+        Ok(self.clients.values().cloned().collect())
+    }
+
+    fn transactions(&self) -> Result<Vec<Transaction>, StorageError> {
+        let mut transactions = self.transactions.values()
+            .map(|(transaction, _)| transaction.clone())
+            .collect::<Vec<_>>();
+
+        for (id, (offset, length)) in &self.spilled {
+            transactions.push(self.read(*id, *offset, *length)?);
+        }
+        Ok(transactions)
+    }
+
+    fn contains_transaction(&self, id: u32) -> Result<bool, StorageError> {
+        Ok(self.transactions.contains_key(&id) || self.spilled.contains_key(&id) || self.retired.contains(&id))
+    }
+
+    fn retire_transaction(&mut self, id: u32) -> Result<(), StorageError> {
+        // NOTE: Any entry left in the order is stale now, and is skipped once it is reached.
+        self.transactions.remove(&id);
+        self.spilled.remove(&id);
+        self.retired.insert(id);
+        Ok(())
+    }
+
+    fn retired(&self) -> Result<Vec<u32>, StorageError> {
+        Ok(self.retired.iter().copied().collect())
+    }
+}
+
+impl Drop for SpillStorage {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, TransactionStatus, TransactionType};
+
+    #[test]
+    fn spill_and_page_back_in() {
+        let mut engine = Engine::with_storage(SpillStorage::in_temp_dir(2).unwrap());
+        for id in 1..=5 {
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, id, Some(BigDecimal::from_str("10").unwrap()))).unwrap();
+        }
+        assert_eq!(engine.storage().spilled(), 3);
+
+        // The oldest deposit was spilled, and is read back from disk to be disputed and resolved.
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::Disputed);
+        assert_eq!(engine.client(1).unwrap().unwrap().held(), BigDecimal::from(10));
+
+        for id in 6..=8 {
+            engine.process(&Transaction::new(TransactionType::Deposit, 1, id, Some(BigDecimal::from_str("1").unwrap()))).unwrap();
+        }
+        assert_eq!(engine.storage().get_transaction(1).unwrap().unwrap().status(), TransactionStatus::Disputed);
+        engine.process(&Transaction::new(TransactionType::Resolve, 1, 1, None)).unwrap();
+
+        assert_eq!(engine.client(1).unwrap().unwrap().available(), BigDecimal::from(53));
+        assert_eq!(engine.storage().transactions().unwrap().len(), 8);
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 2, Some(BigDecimal::from(1)))), Err(crate::TransactionError::DuplicateTransaction(2)));
+
+        let path = engine.storage().path.clone();
+        drop(engine);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::BTreeMap, io};
+
+use bigdecimal::{BigDecimal, Signed, Zero};
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{LedgerAccount, LedgerEntry, Precision, TransactionType};
+
+/// The namespace of the camt.053 bank to customer statement messages written.
+const CAMT053_NAMESPACE: &str = "urn:iso:std:iso:20022:tech:xsd:camt.053.001.02";
+
+/// A movement of funds on a client's statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementLine {
+    /// The id of the transaction that moved the funds, or 0 for a movement the engine made itself.
+    pub id: u32,
+
+    /// The type of the transaction that moved the funds.
+    pub type_: TransactionType,
+
+    /// When the transaction happened, if known.
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// The amount credited to the client's funds, which is negative for a debit.
+    pub amount: BigDecimal,
+
+    /// The metadata of the transaction that moved the funds.
+    pub metadata: BTreeMap<String, String>
+}
+
+/// The activity of a client in a single currency, as a bank statement of the client's total funds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Statement {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The currency of the statement, or `None` for the default currency.
+    pub currency: Option<String>,
+
+    /// The funds the client had before any line, such as those of a restored account.
+    pub opening: BigDecimal,
+
+    /// Every movement of the client's total funds, in the order it was posted.
+    pub lines: Vec<StatementLine>,
+
+    /// The funds the client had after every line.
+    pub closing: BigDecimal
+}
+
+impl StatementLine {
+    /// The metadata of the line as `key=value` pairs separated by spaces, if it has any.
+    fn details(&self) -> Option<String> {
+        (!self.metadata.is_empty()).then(|| self.metadata.iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<_>>().join(" "))
+    }
+}
+
+impl Statement {
+    /// Build the statement of every client and currency from the entries of a ledger, ordered by client and then currency.
+    /// Moving funds between a client's own available and held funds leaves its total untouched, so is not a line.
+    pub fn from_entries(entries: &[LedgerEntry]) -> Vec<Self> {
+        let client = |account: LedgerAccount| match account {
+            LedgerAccount::Available(client) | LedgerAccount::Held(client) => Some(client),
+            _ => None
+        };
+
+        let mut statements = BTreeMap::<(u16, Option<String>), Statement>::new();
+        for entry in entries {
+            let (debited, credited) = (client(entry.debit), client(entry.credit));
+            if debited == credited {
+                continue;
+            }
+
+            for (id, amount) in [(debited, -entry.amount.clone()), (credited, entry.amount.clone())] {
+                let Some(id) = id else {
+                    continue;
+                };
+
+                let statement = statements.entry((id, entry.currency.clone())).or_insert_with(|| Statement {
+                    client: id,
+                    currency: entry.currency.clone(),
+                    opening: BigDecimal::zero(),
+                    lines: Vec::new(),
+                    closing: BigDecimal::zero()
+                });
+                statement.closing += &amount;
+                match entry.debit == LedgerAccount::OpeningBalances || entry.credit == LedgerAccount::OpeningBalances {
+                    true => statement.opening += amount,
+                    false => statement.lines.push(StatementLine {
+                        id: entry.id,
+                        type_: entry.type_,
+                        timestamp: entry.timestamp,
+                        amount,
+                        metadata: entry.metadata.clone()
+                    })
+                }
+            }
+        }
+        statements.into_values().collect()
+    }
+
+    /// Write the statement as an MT940 customer statement message, with amounts in the default currency in the given
+    /// commodity, and a line without a timestamp booked on the given day.
+    pub fn write_mt940<W: io::Write>(&self, mut writer: W, precision: Precision, commodity: &str, undated: NaiveDate) -> io::Result<()> {
+        let currency = self.currency.as_deref().unwrap_or(commodity);
+        let mark = |amount: &BigDecimal| if amount.is_negative() { "D" } else { "C" };
+        let amount = |amount: &BigDecimal| precision.format(&amount.abs()).replace('.', ",");
+        let (opened, closed) = self.period(undated);
+
+        writeln!(writer, ":20:STMT{}", self.client)?;
+        writeln!(writer, ":25:CLIENT{}", self.client)?;
+        writeln!(writer, ":28C:1")?;
+        writeln!(writer, ":60F:{}{}{}{}", mark(&self.opening), opened.format("%y%m%d"), currency, amount(&self.opening))?;
+        for line in &self.lines {
+            let date = line.timestamp.map_or(undated, |timestamp| timestamp.date_naive());
+            // NOTE: The transaction type is always NTRF, with the transaction id as the reference for the account owner.
+            writeln!(writer, ":61:{}{}{}{}NTRF{}", date.format("%y%m%d"), date.format("%m%d"), mark(&line.amount), amount(&line.amount), line.id)?;
+            writeln!(writer, ":86:{} {}{}", line.type_, line.id, line.details().map(|details| format!(" {}", details)).unwrap_or_default())?;
+        }
+        writeln!(writer, ":62F:{}{}{}{}", mark(&self.closing), closed.format("%y%m%d"), currency, amount(&self.closing))?;
+        writeln!(writer, "-")
+    }
+
+    /// Write the statement as the `Stmt` element of a camt.053 message, with amounts in the default currency in the given
+    /// commodity, and a line without a timestamp booked on the given day.
+    fn write_camt053<W: io::Write>(&self, mut writer: W, precision: Precision, commodity: &str, undated: NaiveDate) -> io::Result<()> {
+        let currency = escape(self.currency.as_deref().unwrap_or(commodity));
+        let indicator = |amount: &BigDecimal| if amount.is_negative() { "DBIT" } else { "CRDT" };
+        let (opened, closed) = self.period(undated);
+
+        writeln!(writer, "    <Stmt>")?;
+        writeln!(writer, "      <Id>{}-{}</Id>", self.client, currency)?;
+        writeln!(writer, "      <CreDtTm>{}T00:00:00</CreDtTm>", closed.format("%Y-%m-%d"))?;
+        writeln!(writer, "      <Acct><Id><Othr><Id>{}</Id></Othr></Id><Ccy>{}</Ccy></Acct>", self.client, currency)?;
+        for (code, balance, date) in [("OPBD", &self.opening, opened), ("CLBD", &self.closing, closed)] {
+            writeln!(writer, "      <Bal><Tp><CdOrPrtry><Cd>{}</Cd></CdOrPrtry></Tp><Amt Ccy=\"{}\">{}</Amt><CdtDbtInd>{}</CdtDbtInd><Dt><Dt>{}</Dt></Dt></Bal>",
+                code, currency, precision.format(&balance.abs()), indicator(balance), date.format("%Y-%m-%d"))?;
+        }
+        for line in &self.lines {
+            let date = line.timestamp.map_or(undated, |timestamp| timestamp.date_naive()).format("%Y-%m-%d");
+            writeln!(writer, "      <Ntry><Amt Ccy=\"{}\">{}</Amt><CdtDbtInd>{}</CdtDbtInd><Sts>BOOK</Sts><BookgDt><Dt>{}</Dt></BookgDt><ValDt><Dt>{}</Dt></ValDt>\
+                <BkTxCd><Prtry><Cd>{}</Cd></Prtry></BkTxCd><NtryDtls><TxDtls><Refs><EndToEndId>{}</EndToEndId></Refs>{}</TxDtls></NtryDtls></Ntry>",
+                currency, precision.format(&line.amount.abs()), indicator(&line.amount), date, date, line.type_, line.id,
+                line.details().map(|details| format!("<AddtlTxInf>{}</AddtlTxInf>", escape(&details))).unwrap_or_default())?;
+        }
+        writeln!(writer, "    </Stmt>")
+    }
+
+    /// The days the statement opens and closes on, which are those of its first and last lines.
+    fn period(&self, undated: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let dates = self.lines.iter().map(|line| line.timestamp.map_or(undated, |timestamp| timestamp.date_naive()));
+        (dates.clone().min().unwrap_or(undated), dates.max().unwrap_or(undated))
+    }
+}
+
+/// Write every statement as a single camt.053 bank to customer statement message.
+pub fn write_camt053<W: io::Write>(mut writer: W, statements: &[Statement], precision: Precision, commodity: &str, undated: NaiveDate) -> io::Result<()> {
+    let created = statements.iter().map(|statement| statement.period(undated).1).max().unwrap_or(undated).format("%Y-%m-%d");
+
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(writer, "<Document xmlns=\"{}\">", CAMT053_NAMESPACE)?;
+    writeln!(writer, "  <BkToCstmrStmt>")?;
+    writeln!(writer, "    <GrpHdr><MsgId>STMT-{}</MsgId><CreDtTm>{}T00:00:00</CreDtTm></GrpHdr>", created, created)?;
+    for statement in statements {
+        statement.write_camt053(&mut writer, precision, commodity, undated)?;
+    }
+    writeln!(writer, "  </BkToCstmrStmt>")?;
+    writeln!(writer, "</Document>")?;
+    writer.flush()
+}
+
+/// Escape text for an XML element or attribute.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{Engine, Ledger, Transaction};
+
+    #[test]
+    fn statements_list_movements_of_the_total_funds() {
+        let mut engine = Engine::new().with_ledger(Some(Ledger::new()));
+        let at = |timestamp: &str| DateTime::<Utc>::from_str(timestamp).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(100))).with_timestamp(at("2024-01-02T09:00:00Z"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None).with_timestamp(at("2024-01-03T09:00:00Z"))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None).with_timestamp(at("2024-01-04T09:00:00Z"))).unwrap();
+
+        let statements = Statement::from_entries(engine.ledger().unwrap().entries());
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].lines.iter().map(|line| (line.type_, line.amount.clone())).collect::<Vec<_>>(), vec![
+            (TransactionType::Deposit, BigDecimal::from(100)),
+            (TransactionType::Chargeback, BigDecimal::from(-100)),
+        ]);
+
+        let mut mt940 = Vec::new();
+        statements[0].write_mt940(&mut mt940, engine.precision(), "EUR", NaiveDate::MIN).unwrap();
+        assert_eq!(String::from_utf8(mt940).unwrap(), "\
+:20:STMT1
+:25:CLIENT1
+:28C:1
+:60F:C240102EUR0,0000
+:61:2401020102C100,0000NTRF1
+:86:deposit 1
+:61:2401040104D100,0000NTRF1
+:86:chargeback 1
+:62F:C240104EUR0,0000
+-
+");
+
+        let mut camt053 = Vec::new();
+        write_camt053(&mut camt053, &statements, engine.precision(), "EUR", NaiveDate::MIN).unwrap();
+        let camt053 = String::from_utf8(camt053).unwrap();
+        assert!(camt053.contains("<Amt Ccy=\"EUR\">100.0000</Amt><CdtDbtInd>DBIT</CdtDbtInd><Sts>BOOK</Sts><BookgDt><Dt>2024-01-04</Dt></BookgDt>"));
+        assert!(camt053.contains("<Bal><Tp><CdOrPrtry><Cd>CLBD</Cd></CdOrPrtry></Tp><Amt Ccy=\"EUR\">0.0000</Amt>"));
+    }
+}
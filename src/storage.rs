@@ -0,0 +1,331 @@
+use std::{collections::{HashMap, HashSet}, error, fmt, str::FromStr};
+
+use crate::{Applied, Client, Transaction, TransactionError};
+
+/// An error raised by a storage backend, such as a failed read or write.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StorageError(pub String);
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "storage error: {}", self.0)
+    }
+}
+
+impl error::Error for StorageError {}
+
+/// A backend that stores client accounts and the transactions that can be referenced by a dispute.
+///
+/// Values are returned by value and written back explicitly, so a backend is free to keep them anywhere.
+pub trait Storage {
+    /// Get a client by id.
+    fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError>;
+
+    /// Insert or replace a client.
+    fn update_client(&mut self, client: Client) -> Result<(), StorageError>;
+
+    /// Get a transaction by id.
+    fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError>;
+
+    /// Insert or replace a transaction.
+    fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), StorageError>;
+
+    /// Get every client.
+    fn clients(&self) -> Result<Vec<Client>, StorageError>;
+
+    /// Get every transaction.
+    fn transactions(&self) -> Result<Vec<Transaction>, StorageError>;
+
+    /// Whether a transaction with the id was ever stored, even if it has since been retired.
+    fn contains_transaction(&self, id: u32) -> Result<bool, StorageError> {
+        Ok(self.get_transaction(id)?.is_some())
+    }
+
+    /// Drop a transaction that can never be referenced again, remembering only its id so it is still seen as a duplicate.
+    /// Backends that do not support this keep the transaction.
+    fn retire_transaction(&mut self, id: u32) -> Result<(), StorageError> {
+        let _ = id;
+        Ok(())
+    }
+
+    /// Get the id of every retired transaction.
+    fn retired(&self) -> Result<Vec<u32>, StorageError> {
+        Ok(Vec::new())
+    }
+
+    /// Start grouping every write that follows into a single unit, until it is committed.
+    /// Backends without transactions make every write as it happens.
+    fn begin(&mut self) -> Result<(), StorageError> {
+        Ok(())
+    }
+
+    /// Make every write since the unit began durable at once, alongside a record of the transaction that made them and
+    /// its outcome, and any offset tracked since. A backend that failed a write since the unit began discards its writes.
+    fn commit(&mut self, transaction: &Transaction, result: &Result<Applied, TransactionError>) -> Result<(), StorageError> {
+        let _ = (transaction, result);
+        Ok(())
+    }
+
+    /// Get the position a source was last committed at, such as the line of an input file, if it was ever tracked.
+    fn offset(&self, source: &str) -> Result<Option<u64>, StorageError> {
+        let _ = source;
+        Ok(None)
+    }
+
+    /// Track the position of a source, to be committed alongside the next unit, so the transactions up to it are never
+    /// applied twice. Backends that do not support this forget it.
+    fn track_offset(&mut self, source: &str, offset: u64) -> Result<(), StorageError> {
+        let _ = (source, offset);
+        Ok(())
+    }
+}
+
+/// An enumeration of the policies for how long processed transactions are kept.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Retention {
+    /// Keep every transaction, as may be required for compliance.
+    #[default]
+    All,
+
+    /// Only keep transactions that a dispute, resolve or chargeback can still reference.
+    /// Transfers, conversions, unlocks and charged back transactions are dropped, keeping only their ids.
+    Disputable,
+}
+
+impl FromStr for Retention {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::All),
+            "disputable" => Ok(Self::Disputable),
+            _ => Err(format!("unknown retention policy '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Disputable => write!(f, "disputable"),
+        }
+    }
+}
+
+/// The default storage backend, which keeps everything in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    /// The clients, keyed by client id.
+    clients: HashMap<u16, Client>,
+
+    /// The transactions of every client, keyed by transaction id.
+    // NOTE: This wouldn't be used in a real system, but is used here to keep things simple.
+    transactions: HashMap<u32, Transaction>,
+
+    /// The ids of transactions that were dropped once they could no longer be referenced.
+    retired: HashSet<u32>
+}
+
+impl MemoryStorage {
+    /// Create a new empty storage.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove a client, returning it if it was stored.
+    pub(crate) fn take_client(&mut self, id: u16) -> Option<Client> {
+        self.clients.remove(&id)
+    }
+
+    /// Add the balances of a copy of a client kept elsewhere, such as by another shard, to those of the client.
+    pub(crate) fn combine_client(&mut self, client: Client) -> Result<(), TransactionError> {
+        match self.clients.get_mut(&client.id()) {
+            Some(stored) => stored.combine(&client),
+            None => {
+                self.clients.insert(client.id(), client);
+                Ok(())
+            }
+        }
+    }
+
+    /// Merge the contents of another storage, which must not share any clients with this one.
+    pub(crate) fn merge(&mut self, other: MemoryStorage) {
+        debug_assert!(other.clients.keys().all(|id| !self.clients.contains_key(id)));
+
+        self.clients.extend(other.clients);
+        self.transactions.extend(other.transactions);
+        self.retired.extend(other.retired);
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+        Ok(self.clients.get(&id).cloned())
+    }
+
+    fn update_client(&mut self, client: Client) -> Result<(), StorageError> {
+        self.clients.insert(client.id(), client);
+        Ok(())
+    }
+
+    fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError> {
+        Ok(self.transactions.get(&id).cloned())
+    }
+
+    fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), StorageError> {
+        self.transactions.insert(transaction.id, transaction);
+        Ok(())
+    }
+
+    fn clients(&self) -> Result<Vec<Client>, StorageError> {
+        Ok(self.clients.values().cloned().collect())
+    }
+
+    fn transactions(&self) -> Result<Vec<Transaction>, StorageError> {
+        Ok(self.transactions.values().cloned().collect())
+    }
+
+    fn contains_transaction(&self, id: u32) -> Result<bool, StorageError> {
+        Ok(self.transactions.contains_key(&id) || self.retired.contains(&id))
+    }
+
+    fn retire_transaction(&mut self, id: u32) -> Result<(), StorageError> {
+        self.transactions.remove(&id);
+        self.retired.insert(id);
+        Ok(())
+    }
+
+    fn retired(&self) -> Result<Vec<u32>, StorageError> {
+        Ok(self.retired.iter().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{Engine, TransactionError, TransactionType};
+
+    /// A storage backend that can read, but fails to write.
+    #[derive(Default)]
+    struct ReadOnlyStorage(MemoryStorage);
+
+    impl Storage for ReadOnlyStorage {
+        fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+            self.0.get_client(id)
+        }
+
+        fn update_client(&mut self, _: Client) -> Result<(), StorageError> {
+            Err(StorageError("read only".to_string()))
+        }
+
+        fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError> {
+            self.0.get_transaction(id)
+        }
+
+        fn insert_transaction(&mut self, _: Transaction) -> Result<(), StorageError> {
+            Err(StorageError("read only".to_string()))
+        }
+
+        fn clients(&self) -> Result<Vec<Client>, StorageError> {
+            self.0.clients()
+        }
+
+        fn transactions(&self) -> Result<Vec<Transaction>, StorageError> {
+            self.0.transactions()
+        }
+    }
+
+    /// A storage backend that records every unit it commits.
+    #[derive(Default)]
+    struct UnitStorage {
+        storage: MemoryStorage,
+        open: bool,
+        commits: Vec<(u32, bool, Option<u64>)>,
+        offset: Option<u64>
+    }
+
+    impl Storage for UnitStorage {
+        fn get_client(&self, id: u16) -> Result<Option<Client>, StorageError> {
+            self.storage.get_client(id)
+        }
+
+        fn update_client(&mut self, client: Client) -> Result<(), StorageError> {
+            assert!(self.open);
+            self.storage.update_client(client)
+        }
+
+        fn get_transaction(&self, id: u32) -> Result<Option<Transaction>, StorageError> {
+            self.storage.get_transaction(id)
+        }
+
+        fn insert_transaction(&mut self, transaction: Transaction) -> Result<(), StorageError> {
+            assert!(self.open);
+            self.storage.insert_transaction(transaction)
+        }
+
+        fn clients(&self) -> Result<Vec<Client>, StorageError> {
+            self.storage.clients()
+        }
+
+        fn transactions(&self) -> Result<Vec<Transaction>, StorageError> {
+            self.storage.transactions()
+        }
+
+        fn begin(&mut self) -> Result<(), StorageError> {
+            self.open = true;
+            Ok(())
+        }
+
+        fn commit(&mut self, transaction: &Transaction, result: &Result<Applied, TransactionError>) -> Result<(), StorageError> {
+            self.open = false;
+            self.commits.push((transaction.id, result.is_ok(), self.offset.take()));
+            Ok(())
+        }
+
+        fn track_offset(&mut self, _: &str, offset: u64) -> Result<(), StorageError> {
+            self.offset = Some(offset);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn memory_storage() {
+        let mut engine = Engine::with_storage(MemoryStorage::new());
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10").unwrap()))).unwrap();
+
+        let storage = engine.into_storage();
+        assert_eq!(storage.get_client(1).unwrap().unwrap().total(), BigDecimal::from(10));
+        assert_eq!(storage.get_transaction(1).unwrap().unwrap().client_id(), 1);
+        assert_eq!(storage.get_transaction(2).unwrap(), None);
+    }
+
+    #[test]
+    fn storage_errors_reject_transactions() {
+        let mut engine = Engine::with_storage(ReadOnlyStorage::default());
+
+        let result = engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10").unwrap())));
+
+        assert_eq!(result, Err(TransactionError::Storage(StorageError("read only".to_string()))));
+    }
+
+    #[test]
+    fn every_transaction_is_committed_with_its_outcome() {
+        let mut engine = Engine::with_storage(UnitStorage::default());
+
+        engine.storage_mut().track_offset("input.csv", 2).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(10)))).unwrap();
+        engine.storage_mut().track_offset("input.csv", 3).unwrap();
+        engine.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, Some(BigDecimal::from(20)))).unwrap_err();
+
+        assert_eq!(engine.storage().commits, vec![
+            (1, true, Some(2)),
+            (2, false, Some(3)),
+        ]);
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::BTreeMap, fmt};
+
+use crate::{Applied, Engine, MemoryStorage, Storage, StorageError, Transaction, TransactionError};
+
+/// The header a call to the server names its tenant in, such as `x-tenant: acme`.
+/// A call without one is applied to the default books.
+pub const TENANT_HEADER: &str = "x-tenant";
+
+/// A function making the engine of a tenant, given its name.
+type NewEngine<'a, S> = Box<dyn FnMut(Option<&str>) -> Result<Engine<S>, StorageError> + 'a>;
+
+/// The isolated books of several tenants, each kept by an engine of its own, so transaction ids and client ids are
+/// only unique within a tenant, and the accounts of every tenant are reported apart.
+///
+/// The engine of a tenant is made the first time one of its transactions is processed.
+pub struct Tenants<'a, S: Storage = MemoryStorage> {
+    /// The engine of every tenant with any transactions, keyed by its name, or `None` for the default books.
+    engines: BTreeMap<Option<String>, Engine<S>>,
+
+    /// Make the engine of a tenant, which must already keep the books of that tenant.
+    new_engine: NewEngine<'a, S>
+}
+
+impl<'a, S: Storage> Tenants<'a, S> {
+    /// Keep the books of every tenant in an engine made by the given function, which is passed the name of the tenant.
+    pub fn new<F>(new_engine: F) -> Self
+    where
+        F: FnMut(Option<&str>) -> Result<Engine<S>, StorageError> + 'a
+    {
+        Self { engines: BTreeMap::new(), new_engine: Box::new(new_engine) }
+    }
+
+    /// Keep the books of every tenant in the given engines, making the engine of any other as in [`Tenants::new`].
+    pub(crate) fn with_engines<F>(engines: BTreeMap<Option<String>, Engine<S>>, new_engine: F) -> Self
+    where
+        F: FnMut(Option<&str>) -> Result<Engine<S>, StorageError> + 'a
+    {
+        Self { engines, new_engine: Box::new(new_engine) }
+    }
+
+    /// Process a single transaction in the books of its tenant.
+    pub fn process(&mut self, transaction: &Transaction) -> Result<Applied, TransactionError> {
+        self.engine_mut(transaction.tenant())?.process(transaction)
+    }
+
+    /// The engine keeping the books of a tenant, or `None` for the default books, if it has any transactions.
+    pub fn engine(&self, tenant: Option<&str>) -> Option<&Engine<S>> {
+        self.engines.get(&tenant.map(str::to_string))
+    }
+
+    /// The engine keeping the books of a tenant, or `None` for the default books, making it if it does not yet exist.
+    pub fn engine_mut(&mut self, tenant: Option<&str>) -> Result<&mut Engine<S>, StorageError> {
+        let key = tenant.map(str::to_string);
+        if !self.engines.contains_key(&key) {
+            let engine = (self.new_engine)(tenant)?;
+            debug_assert_eq!(engine.tenant(), tenant);
+            self.engines.insert(key.clone(), engine);
+        }
+        Ok(self.engines.get_mut(&key).unwrap())
+    }
+
+    /// Every engine, alongside the tenant whose books it keeps, with the default books first and then by name.
+    pub fn iter(&self) -> impl Iterator<Item = (Option<&str>, &Engine<S>)> {
+        self.engines.iter().map(|(tenant, engine)| (tenant.as_deref(), engine))
+    }
+
+    /// Every engine, alongside the tenant whose books it keeps, as in [`Tenants::iter`].
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Option<&str>, &mut Engine<S>)> {
+        self.engines.iter_mut().map(|(tenant, engine)| (tenant.as_deref(), engine))
+    }
+
+    /// Whether any tenant other than the default books has any transactions.
+    pub fn is_multi_tenant(&self) -> bool {
+        self.engines.keys().any(Option::is_some)
+    }
+
+    /// The only engine, if the books of only a single tenant are kept.
+    pub fn single(&self) -> Option<&Engine<S>> {
+        match self.engines.len() {
+            1 => self.engines.values().next(),
+            _ => None
+        }
+    }
+}
+
+impl<S: Storage> From<Engine<S>> for Tenants<'_, S> {
+    /// Keep only the books of the engine's tenant, rejecting the transactions of every other tenant.
+    fn from(engine: Engine<S>) -> Self {
+        let tenant = engine.tenant().map(str::to_string);
+        let mut tenants = Self::new(|tenant| Err(StorageError(format!("no books are kept for tenant '{}'", tenant.unwrap_or_default()))));
+        tenants.engines.insert(tenant, engine);
+        tenants
+    }
+}
+
+impl<S: Storage + fmt::Debug> fmt::Debug for Tenants<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tenants")
+            .field("engines", &self.engines)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bigdecimal::BigDecimal;
+
+    use super::*;
+    use crate::{OutputFormat, Precision, TransactionType};
+
+    #[test]
+    fn tenants_keep_isolated_books() {
+        let mut tenants = Tenants::new(|tenant| Ok(Engine::new().with_tenant(tenant.map(str::to_string))));
+        tenants.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(10.into()))).unwrap();
+        tenants.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(3.into())).with_tenant("acme")).unwrap();
+        assert_eq!(tenants.process(&Transaction::new(TransactionType::Withdrawal, 1, 2, Some(5.into())).with_tenant("acme")), Err(TransactionError::InsufficientFunds));
+        assert_eq!(tenants.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(1.into())).with_tenant("acme")), Err(TransactionError::DuplicateTransaction(1)));
+
+        assert!(tenants.is_multi_tenant());
+        assert_eq!(tenants.engine(None).unwrap().client(1).unwrap().unwrap().available(), BigDecimal::from(10));
+        assert_eq!(tenants.engine(Some("acme")).unwrap().client(1).unwrap().unwrap().available(), BigDecimal::from(3));
+
+        let mut output = Vec::new();
+        OutputFormat::Csv.write_tenants(&mut output, &tenants, Precision::default()).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "tenant,id,available,held,total,locked\n,1,10.0000,0.0000,10.0000,false\nacme,1,3.0000,0.0000,3.0000,false\n");
+
+        let mut engine = Engine::new();
+        assert_eq!(engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(1.into())).with_tenant("acme")), Err(TransactionError::ForeignTenant(Some("acme".to_string()))));
+        let mut single = Tenants::from(engine);
+        assert!(single.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(1.into())).with_tenant("acme")).is_err());
+        assert!(single.single().is_some());
+    }
+}
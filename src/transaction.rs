@@ -0,0 +1,690 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, de};
+
+use crate::{Recurrence, TransactionError};
+
+/// An enumeration of each transaction type.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionType {
+    /// A deposit is a credit to the client's account.
+    Deposit,
+    
+    /// A withdraw is a debit to the client's account.
+    /// This should fail if the withdrawal amount is greater than the available balance.
+    Withdrawal,
+
+    /// A dispute represents a client's claim that a transaction was erroneous and should be reversed.
+    Dispute,
+
+    /// A resolve represents a resolution to a dispute, releasing the associated held funds.
+    /// Funds that were previously disputed are no longer disputed.
+    Resolve,
+
+    /// A chargeback is the final state of a dispute and represents the client reversing a transaction.
+    /// Funds that were held have now been withdrawn. Lock the account after this.
+    Chargeback,
+
+    /// A transfer atomically debits the client's account and credits the destination client's account.
+    /// This should fail if the amount is greater than the available balance, or if either account is locked.
+    Transfer,
+
+    /// An unlock clears the locked flag of the client's account, and may only be submitted by an operator.
+    Unlock,
+
+    /// A convert atomically debits the amount from one currency of the client's account, and credits it at a rate to another.
+    /// This should fail if the amount is greater than the available balance of the source currency.
+    Convert,
+
+    /// A representment reverses a chargeback once the merchant wins the dispute, restoring the original transaction.
+    /// The account may be unlocked again, if the engine is configured to.
+    Representment,
+
+    /// An interest posting credits the interest accrued on the client's available funds.
+    /// The engine posts these itself at the end of each interest period, and otherwise only an operator may submit one.
+    Interest,
+
+    /// An authorization places a hold on the amount, moving it from the client's available to its held funds,
+    /// until it is captured or voided. This should fail if the amount is greater than the available balance.
+    Authorize,
+
+    /// A capture settles the authorization with the same id, debiting its held amount from the account.
+    Capture,
+
+    /// A void cancels the authorization with the same id, releasing its held amount back to the available funds.
+    Void,
+
+    /// A freeze stops the client's account accepting anything the freeze policy does not permit, until it is unfrozen.
+    /// It is separate from the lock a chargeback places, and may only be submitted by an operator.
+    Freeze,
+
+    /// An unfreeze clears the frozen flag of the client's account, and may only be submitted by an operator.
+    Unfreeze,
+
+    /// A move takes the amount from the available funds of one of the client's sub-accounts to those of another, such
+    /// as from `main` to `escrow`. This should fail if the amount is greater than the available balance it is moved from.
+    Move,
+
+    /// A balance assertion checks the client's available funds against the amount, and its total funds against the total,
+    /// at that point in the stream, without changing the account. This should fail if either does not match.
+    #[serde(rename = "assert_balance")]
+    AssertBalance,
+}
+
+impl TransactionType {
+    /// Whether the transaction type is an operator-only administrative action.
+    pub fn is_admin(self) -> bool {
+        matches!(self, Self::Unlock | Self::Interest | Self::Freeze | Self::Unfreeze)
+    }
+
+    /// Whether the transaction type references an existing transaction, rather than introducing a new one.
+    pub fn references_existing(self) -> bool {
+        matches!(self, Self::Dispute | Self::Resolve | Self::Chargeback | Self::Representment | Self::Capture | Self::Void)
+    }
+
+    /// Whether a transaction of the type can be scheduled for later, or to recur.
+    pub fn is_schedulable(self) -> bool {
+        matches!(self, Self::Deposit | Self::Withdrawal | Self::Transfer | Self::Convert)
+    }
+}
+
+impl FromStr for TransactionType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deposit" => Ok(Self::Deposit),
+            "withdrawal" => Ok(Self::Withdrawal),
+            "dispute" => Ok(Self::Dispute),
+            "resolve" => Ok(Self::Resolve),
+            "chargeback" => Ok(Self::Chargeback),
+            "transfer" => Ok(Self::Transfer),
+            "unlock" => Ok(Self::Unlock),
+            "convert" => Ok(Self::Convert),
+            "representment" => Ok(Self::Representment),
+            "interest" => Ok(Self::Interest),
+            "authorize" => Ok(Self::Authorize),
+            "capture" => Ok(Self::Capture),
+            "void" => Ok(Self::Void),
+            "freeze" => Ok(Self::Freeze),
+            "unfreeze" => Ok(Self::Unfreeze),
+            "move" => Ok(Self::Move),
+            "assert_balance" => Ok(Self::AssertBalance),
+            _ => Err(format!("unknown transaction type '{}'", s))
+        }
+    }
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deposit => write!(f, "deposit"),
+            Self::Withdrawal => write!(f, "withdrawal"),
+            Self::Dispute => write!(f, "dispute"),
+            Self::Resolve => write!(f, "resolve"),
+            Self::Chargeback => write!(f, "chargeback"),
+            Self::Transfer => write!(f, "transfer"),
+            Self::Unlock => write!(f, "unlock"),
+            Self::Convert => write!(f, "convert"),
+            Self::Representment => write!(f, "representment"),
+            Self::Interest => write!(f, "interest"),
+            Self::Authorize => write!(f, "authorize"),
+            Self::Capture => write!(f, "capture"),
+            Self::Void => write!(f, "void"),
+            Self::Freeze => write!(f, "freeze"),
+            Self::Unfreeze => write!(f, "unfreeze"),
+            Self::Move => write!(f, "move"),
+            Self::AssertBalance => write!(f, "assert_balance"),
+        }
+    }
+}
+
+/// An enumeration of the states a stored deposit or withdrawal moves through as it is disputed,
+/// and that an authorization moves through as it is captured or voided.
+///
+/// A settled transaction can be disputed, and a dispute is either resolved, which lets the transaction be disputed again,
+/// or charged back. A chargeback can be reversed by a representment, after which the transaction is final.
+/// An authorization is held until it is either captured or voided, after which it is final.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// The transaction stands, and has never been disputed.
+    #[default]
+    Settled,
+
+    /// The transaction is in dispute, and its amount is held.
+    Disputed,
+
+    /// The last dispute of the transaction was resolved, and the transaction stands.
+    Resolved,
+
+    /// The transaction was reversed by a chargeback.
+    ChargedBack,
+
+    /// The chargeback of the transaction was reversed by a representment, so the transaction stands for good.
+    Reversed,
+
+    /// The authorization's amount is held, until it is captured or voided.
+    Authorized,
+
+    /// The authorization was captured, debiting its held amount.
+    Captured,
+
+    /// The authorization was voided, releasing its held amount.
+    Voided,
+}
+
+impl TransactionStatus {
+    /// The status a transaction moves to when a transaction of the given type references it, or why the move is illegal.
+    /// Once charged back, a transaction only accepts a representment, and once represented it accepts nothing.
+    /// An authorization only accepts a capture or void, and once captured or voided it accepts nothing.
+    pub fn transition(self, type_: TransactionType, id: u32) -> Result<Self, TransactionError> {
+        match (type_, self) {
+            (TransactionType::Capture, Self::Authorized) => Ok(Self::Captured),
+            (TransactionType::Void, Self::Authorized) => Ok(Self::Voided),
+            (_, Self::Captured) => Err(TransactionError::AlreadyCaptured(id)),
+            (_, Self::Voided) => Err(TransactionError::AlreadyVoided(id)),
+            (TransactionType::Capture | TransactionType::Void, _) => Err(TransactionError::NotAuthorization(id)),
+            (_, Self::Authorized) => Err(TransactionError::NotDisputable(id)),
+            (TransactionType::Representment, Self::ChargedBack) => Ok(Self::Reversed),
+            // NOTE: A chargeback has already moved the funds, so disputing, resolving or charging back again would move them twice.
+            (_, Self::ChargedBack) => Err(TransactionError::AlreadyChargedBack(id)),
+            (_, Self::Reversed) => Err(TransactionError::AlreadyReversed(id)),
+            (TransactionType::Dispute, Self::Settled | Self::Resolved) => Ok(Self::Disputed),
+            (TransactionType::Resolve, Self::Disputed) => Ok(Self::Resolved),
+            (TransactionType::Chargeback, Self::Disputed) => Ok(Self::ChargedBack),
+            (TransactionType::Dispute, Self::Disputed) => Err(TransactionError::AlreadyDisputed(id)),
+            (TransactionType::Representment, _) => Err(TransactionError::NotChargedBack(id)),
+            (TransactionType::Resolve | TransactionType::Chargeback, _) => Err(TransactionError::NotDisputed(id)),
+            _ => Err(TransactionError::NotDisputable(id))
+        }
+    }
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Settled => write!(f, "settled"),
+            Self::Disputed => write!(f, "disputed"),
+            Self::Resolved => write!(f, "resolved"),
+            Self::ChargedBack => write!(f, "charged_back"),
+            Self::Reversed => write!(f, "reversed"),
+            Self::Authorized => write!(f, "authorized"),
+            Self::Captured => write!(f, "captured"),
+            Self::Voided => write!(f, "voided"),
+        }
+    }
+}
+
+/// A decimal read from either a string or a number.
+struct Decimal(BigDecimal);
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a decimal number")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                value.parse().map(Decimal).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+                Ok(Decimal(value.into()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Self::Value, E> {
+                Ok(Decimal(value.into()))
+            }
+
+            // NOTE: The CSV reader offers every numeric looking field as a float first, so this goes through the shortest
+            // decimal representation of the float, rather than its exact binary value, to keep the digits that were written.
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Self::Value, E> {
+                self.visit_str(&value.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Deserialize an optional decimal, without picking up the rounding error of a float.
+fn deserialize_decimal<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<BigDecimal>, D::Error> {
+    Ok(Option::<Decimal>::deserialize(deserializer)?.map(|decimal| decimal.0))
+}
+
+/// A structure to represent a transaction.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    /// The transaction type.
+    #[serde(rename = "type")]
+    pub(crate) type_: TransactionType,
+
+    /// The id of the client.
+    #[serde(rename = "client")]
+    pub(crate) client_id: u16,
+
+    /// The id of the transaction.
+    #[serde(rename = "tx")]
+    pub(crate) id: u32,
+
+    /// The amount associated with the transaction.
+    #[serde(default, deserialize_with = "deserialize_decimal")]
+    pub(crate) amount: Option<BigDecimal>,
+
+    /// The id of the client receiving the funds of a transfer.
+    #[serde(default, rename = "to")]
+    pub(crate) destination: Option<u16>,
+
+    /// The currency of the amount, or the default currency when not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) currency: Option<String>,
+
+    /// The currency the amount of a conversion is credited in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) to_currency: Option<String>,
+
+    /// The amount of the target currency credited for each unit of the amount of a conversion.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_decimal")]
+    pub(crate) rate: Option<BigDecimal>,
+
+    /// The total funds a balance assertion expects the client to hold, if it checks them.
+    #[serde(default, skip_serializing_if = "Option::is_none", deserialize_with = "deserialize_decimal")]
+    pub(crate) total: Option<BigDecimal>,
+
+    /// The sub-account of the client the transaction applies to, or the main account when not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) account: Option<String>,
+
+    /// The sub-account the amount of a move is credited to, or the main account when not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) to_account: Option<String>,
+
+    /// The tenant whose books the transaction belongs to, or the default books when not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) tenant: Option<String>,
+
+    /// When the transaction happened, as an RFC 3339 timestamp, if the source records it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) timestamp: Option<DateTime<Utc>>,
+
+    /// When the transaction is first applied, as an RFC 3339 timestamp, if it is scheduled for later rather than applied at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) scheduled_for: Option<DateTime<Utc>>,
+
+    /// How often a scheduled transaction is applied again after it is first applied, if it recurs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) recurrence: Option<Recurrence>,
+
+    /// Every other field the source recorded, such as a `memo` or `reference`, which the engine carries but never reads.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) metadata: BTreeMap<String, String>,
+
+    /// Where the transaction is in its dispute lifecycle.
+    #[serde(skip)]
+    pub(crate) status: TransactionStatus
+}
+
+impl Transaction {
+    /// Create a new transaction.
+    /// Dispute, resolve and chargeback transactions reference an existing transaction and carry no amount.
+    pub fn new(type_: TransactionType, client_id: u16, id: u32, amount: Option<BigDecimal>) -> Self {
+        Self {
+            type_,
+            client_id,
+            id,
+            amount,
+            destination: None,
+            currency: None,
+            to_currency: None,
+            rate: None,
+            total: None,
+            account: None,
+            to_account: None,
+            tenant: None,
+            timestamp: None,
+            scheduled_for: None,
+            recurrence: None,
+            metadata: BTreeMap::new(),
+            status: TransactionStatus::Settled
+        }
+    }
+
+    /// Create a new transfer of the amount from one client to another.
+    pub fn transfer(client_id: u16, destination: u16, id: u32, amount: BigDecimal) -> Self {
+        Self {
+            destination: Some(destination),
+            ..Self::new(TransactionType::Transfer, client_id, id, Some(amount))
+        }
+    }
+
+    /// Create a new conversion of the amount from one currency of a client's account to another, at a rate.
+    /// A source currency of `None` converts from the default currency.
+    pub fn convert<C: Into<String>>(client_id: u16, id: u32, amount: BigDecimal, currency: Option<C>, to_currency: C, rate: BigDecimal) -> Self {
+        Self {
+            currency: currency.map(Into::into),
+            to_currency: Some(to_currency.into()),
+            rate: Some(rate),
+            ..Self::new(TransactionType::Convert, client_id, id, Some(amount))
+        }
+    }
+
+    /// Create a new assertion of the available and total funds of a client, where `None` leaves either unchecked.
+    pub fn assert_balance(client_id: u16, id: u32, available: Option<BigDecimal>, total: Option<BigDecimal>) -> Self {
+        Self {
+            total,
+            ..Self::new(TransactionType::AssertBalance, client_id, id, available)
+        }
+    }
+
+    /// Create a new move of the amount between two sub-accounts of a client, where `None` is the main account.
+    pub fn move_between<A: Into<String>>(client_id: u16, id: u32, amount: BigDecimal, account: Option<A>, to_account: Option<A>) -> Self {
+        Self {
+            account: account.map(Into::into),
+            to_account: to_account.map(Into::into),
+            ..Self::new(TransactionType::Move, client_id, id, Some(amount))
+        }
+    }
+
+    /// Apply the transaction to a sub-account of the client, rather than the main account.
+    pub fn with_account<A: Into<String>>(mut self, account: A) -> Self {
+        self.account = Some(account.into());
+        self
+    }
+
+    /// Record the transaction in the books of a tenant, rather than the default books.
+    pub fn with_tenant<T: Into<String>>(mut self, tenant: T) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+
+    /// Set the currency of the amount, rather than the default currency.
+    pub fn with_currency<C: Into<String>>(mut self, currency: C) -> Self {
+        self.currency = Some(currency.into());
+        self
+    }
+
+    /// Set when the transaction happened.
+    pub fn with_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    /// Schedule the transaction to be applied at a later time rather than at once, and again at every recurrence after it, if any.
+    pub fn with_schedule(mut self, scheduled_for: DateTime<Utc>, recurrence: Option<Recurrence>) -> Self {
+        self.scheduled_for = Some(scheduled_for);
+        self.recurrence = recurrence;
+        self
+    }
+
+    /// Record another field of the transaction as metadata.
+    pub fn with_metadata<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// The transaction type.
+    pub fn type_(&self) -> TransactionType {
+        self.type_
+    }
+
+    /// The id of the client.
+    pub fn client_id(&self) -> u16 {
+        self.client_id
+    }
+
+    /// The id of the transaction.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// The amount associated with the transaction, if any.
+    pub fn amount(&self) -> Option<&BigDecimal> {
+        self.amount.as_ref()
+    }
+
+    /// The id of the client receiving the funds of a transfer.
+    pub fn destination(&self) -> Option<u16> {
+        self.destination
+    }
+
+    /// The currency of the amount, or `None` for the default currency.
+    pub fn currency(&self) -> Option<&str> {
+        self.currency.as_deref()
+    }
+
+    /// The currency the amount of a conversion is credited in.
+    pub fn to_currency(&self) -> Option<&str> {
+        self.to_currency.as_deref()
+    }
+
+    /// The amount of the target currency credited for each unit of the amount of a conversion.
+    pub fn rate(&self) -> Option<&BigDecimal> {
+        self.rate.as_ref()
+    }
+
+    /// The total funds a balance assertion expects the client to hold, if it checks them.
+    pub fn total(&self) -> Option<&BigDecimal> {
+        self.total.as_ref()
+    }
+
+    /// The sub-account of the client the transaction applies to, or `None` for the main account.
+    pub fn account(&self) -> Option<&str> {
+        main_account(self.account.as_deref())
+    }
+
+    /// The sub-account the amount of a move is credited to, or `None` for the main account.
+    pub fn to_account(&self) -> Option<&str> {
+        main_account(self.to_account.as_deref())
+    }
+
+    /// The tenant whose books the transaction belongs to, or `None` for the default books.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref().filter(|tenant| !tenant.is_empty())
+    }
+
+    /// When the transaction happened, if the source records it.
+    pub fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.timestamp
+    }
+
+    /// When the transaction is first applied, if it is scheduled for later.
+    pub fn scheduled_for(&self) -> Option<DateTime<Utc>> {
+        self.scheduled_for
+    }
+
+    /// How often a scheduled transaction recurs, if it does.
+    pub fn recurrence(&self) -> Option<Recurrence> {
+        self.recurrence
+    }
+
+    /// Every other field the source recorded, by name.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    /// Where the transaction is in its dispute lifecycle.
+    pub fn status(&self) -> TransactionStatus {
+        self.status
+    }
+}
+
+/// The name of the main account of every client, which is the same as giving no sub-account at all.
+pub const MAIN_ACCOUNT: &str = "main";
+
+/// A sub-account, or `None` if it is the main account.
+fn main_account(account: Option<&str>) -> Option<&str> {
+    account.filter(|account| !account.is_empty() && *account != MAIN_ACCOUNT)
+}
+
+/// A transaction as written in JSON, where every field that is not a field of a transaction is kept as its metadata.
+#[derive(Deserialize)]
+pub(crate) struct JsonTransaction {
+    #[serde(flatten)]
+    transaction: Transaction,
+
+    #[serde(flatten)]
+    metadata: BTreeMap<String, serde_json::Value>
+}
+
+impl From<JsonTransaction> for Transaction {
+    fn from(json: JsonTransaction) -> Self {
+        let mut transaction = json.transaction;
+        for (key, value) in json.metadata {
+            let value = match value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::String(value) => value,
+                value => value.to_string()
+            };
+            transaction.metadata.insert(key, value);
+        }
+        transaction
+    }
+}
+
+/// An enumeration of the effects a successfully processed transaction had on an account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Applied {
+    /// The amount was credited to the available and total funds.
+    Deposited {
+        amount: BigDecimal,
+
+        /// The fee charged on top of the amount, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fee: Option<BigDecimal>
+    },
+
+    /// The amount was debited from the available and total funds.
+    Withdrew {
+        amount: BigDecimal,
+
+        /// The fee charged on top of the amount, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fee: Option<BigDecimal>
+    },
+
+    /// The amount was debited from the client's available and total funds, and credited to the destination client.
+    Transferred {
+        amount: BigDecimal,
+        destination: u16,
+
+        /// The fee charged on top of the amount, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fee: Option<BigDecimal>
+    },
+
+    /// The amount of the disputed transaction is held until the dispute is settled.
+    Disputed {
+        amount: BigDecimal
+    },
+
+    /// The held amount of the disputed transaction was released, and the original transaction stands.
+    Resolved {
+        amount: BigDecimal
+    },
+
+    /// The disputed transaction was reversed using the held amount, and the account was locked.
+    ChargedBack {
+        amount: BigDecimal
+    },
+
+    /// The locked account was unlocked by an operator.
+    Unlocked,
+
+    /// The account was frozen by an operator.
+    Frozen,
+
+    /// The frozen account was unfrozen by an operator.
+    Unfrozen,
+
+    /// The chargeback of the transaction was reversed, restoring the original transaction, and the account unlocked if `unlocked`.
+    Represented {
+        amount: BigDecimal,
+        unlocked: bool
+    },
+
+    /// The interest was credited to the available and total funds.
+    Interest {
+        amount: BigDecimal
+    },
+
+    /// The amount was moved from the available to the held funds, until the authorization is captured or voided.
+    Authorized {
+        amount: BigDecimal
+    },
+
+    /// The held amount of the authorization was debited from the held and total funds.
+    Captured {
+        amount: BigDecimal
+    },
+
+    /// The held amount of the authorization was released back to the available funds.
+    Voided {
+        amount: BigDecimal
+    },
+
+    /// The amount was moved from the available funds of one sub-account to those of another.
+    Moved {
+        amount: BigDecimal
+    },
+
+    /// The available and total funds matched those asserted, and nothing was changed.
+    Asserted,
+
+    /// The amount was debited from the source currency, and the converted amount credited to the target currency.
+    Converted {
+        amount: BigDecimal,
+        converted: BigDecimal,
+
+        /// The fee charged on top of the amount, if any.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fee: Option<BigDecimal>
+    },
+
+    /// Nothing was moved yet, as the transaction was scheduled, with its first occurrence falling due at the time.
+    Scheduled {
+        due: DateTime<Utc>
+    },
+}
+
+impl Applied {
+    /// The amount that was moved by the transaction, if any.
+    pub fn amount(&self) -> Option<&BigDecimal> {
+        match self {
+            Self::Deposited { amount, .. }
+            | Self::Withdrew { amount, .. }
+            | Self::Transferred { amount, .. }
+            | Self::Disputed { amount }
+            | Self::Resolved { amount }
+            | Self::ChargedBack { amount }
+            | Self::Represented { amount, .. }
+            | Self::Interest { amount }
+            | Self::Authorized { amount }
+            | Self::Captured { amount }
+            | Self::Voided { amount }
+            | Self::Moved { amount }
+            | Self::Converted { amount, .. } => Some(amount),
+            Self::Unlocked | Self::Frozen | Self::Unfrozen | Self::Asserted | Self::Scheduled { .. } => None
+        }
+    }
+
+    /// The fee charged on top of the amount, which was debited from the available funds and credited to the fee account, if any.
+    pub fn fee(&self) -> Option<&BigDecimal> {
+        match self {
+            Self::Deposited { fee, .. }
+            | Self::Withdrew { fee, .. }
+            | Self::Transferred { fee, .. }
+            | Self::Converted { fee, .. } => fee.as_ref(),
+            _ => None
+        }
+    }
+}
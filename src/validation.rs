@@ -0,0 +1,94 @@
+use bigdecimal::{BigDecimal, Zero};
+
+use crate::{Transaction, TransactionError, TransactionType};
+
+/// Validate a transaction on its own, before it is applied to any account.
+/// Deposits, withdrawals, transfers, interest postings, authorizations and conversions must carry a strictly positive amount,
+/// and conversions a strictly positive rate between two different currencies. Moves must carry a strictly positive amount
+/// between two different sub-accounts. Balance assertions must carry an available amount, a total, or both, either of
+/// which may be negative.
+/// Only deposits, withdrawals, transfers and conversions may be scheduled, and only a scheduled transaction may recur.
+pub fn validate(transaction: &Transaction) -> Result<(), TransactionError> {
+    if transaction.scheduled_for.is_some() && !transaction.type_.is_schedulable() {
+        return Err(TransactionError::NotSchedulable(transaction.type_));
+    }
+    if transaction.recurrence.is_some() && transaction.scheduled_for.is_none() {
+        return Err(TransactionError::MissingSchedule);
+    }
+
+    match transaction.type_ {
+        TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Transfer | TransactionType::Interest | TransactionType::Authorize => {
+            let amount = transaction.amount.as_ref().ok_or(TransactionError::MissingAmount)?;
+
+            if amount <= &BigDecimal::zero() {
+                return Err(TransactionError::NonPositiveAmount);
+            }
+
+            Ok(())
+        },
+        TransactionType::Convert => {
+            let amount = transaction.amount.as_ref().ok_or(TransactionError::MissingAmount)?;
+            let to_currency = transaction.to_currency.as_deref().ok_or(TransactionError::MissingConversion)?;
+            let rate = transaction.rate.as_ref().ok_or(TransactionError::MissingConversion)?;
+
+            if amount <= &BigDecimal::zero() {
+                return Err(TransactionError::NonPositiveAmount);
+            }
+            if rate <= &BigDecimal::zero() {
+                return Err(TransactionError::NonPositiveRate);
+            }
+            if transaction.currency.as_deref() == Some(to_currency) {
+                return Err(TransactionError::InvalidConversion);
+            }
+
+            Ok(())
+        },
+        TransactionType::Move => {
+            let amount = transaction.amount.as_ref().ok_or(TransactionError::MissingAmount)?;
+
+            if amount <= &BigDecimal::zero() {
+                return Err(TransactionError::NonPositiveAmount);
+            }
+            if transaction.account() == transaction.to_account() {
+                return Err(TransactionError::InvalidMove);
+            }
+
+            Ok(())
+        },
+        TransactionType::AssertBalance => match (&transaction.amount, &transaction.total) {
+            (None, None) => Err(TransactionError::MissingAmount),
+            _ => Ok(())
+        },
+        TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback | TransactionType::Representment | TransactionType::Unlock
+        | TransactionType::Capture | TransactionType::Void | TransactionType::Freeze | TransactionType::Unfreeze => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn amounts_must_be_positive() {
+        let transaction = |type_, amount: &str| Transaction::new(type_, 1, 1, Some(BigDecimal::from_str(amount).unwrap()));
+
+        assert_eq!(validate(&transaction(TransactionType::Deposit, "1.5")), Ok(()));
+        assert_eq!(validate(&transaction(TransactionType::Deposit, "-50.0")), Err(TransactionError::NonPositiveAmount));
+        assert_eq!(validate(&transaction(TransactionType::Withdrawal, "0")), Err(TransactionError::NonPositiveAmount));
+        assert_eq!(validate(&transaction(TransactionType::Withdrawal, "-0.0001")), Err(TransactionError::NonPositiveAmount));
+        assert_eq!(validate(&Transaction::new(TransactionType::Withdrawal, 1, 1, None)), Err(TransactionError::MissingAmount));
+        assert_eq!(validate(&Transaction::new(TransactionType::Dispute, 1, 1, None)), Ok(()));
+    }
+
+    #[test]
+    fn conversions_need_a_rate_between_currencies() {
+        let convert = |from: Option<&str>, to: &str, rate: &str| Transaction::convert(1, 1, BigDecimal::from(10), from, to, BigDecimal::from_str(rate).unwrap());
+
+        assert_eq!(validate(&convert(None, "EUR", "0.9")), Ok(()));
+        assert_eq!(validate(&convert(Some("USD"), "USD", "1")), Err(TransactionError::InvalidConversion));
+        assert_eq!(validate(&convert(Some("USD"), "EUR", "0")), Err(TransactionError::NonPositiveRate));
+        assert_eq!(validate(&Transaction::new(TransactionType::Convert, 1, 1, Some(BigDecimal::from(10)))), Err(TransactionError::MissingConversion));
+    }
+}
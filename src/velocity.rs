@@ -0,0 +1,169 @@
+use std::{collections::HashMap, io, str::FromStr};
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Caps on the funds that may leave a client's account, each of which is unlimited when `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WithdrawalLimits {
+    /// The largest amount a single withdrawal may take.
+    pub max_amount: Option<BigDecimal>,
+
+    /// The most withdrawals a client may make in a day.
+    pub max_per_day: Option<u32>,
+
+    /// The most funds a client may withdraw and transfer out in a day, across every currency.
+    pub max_daily_outflow: Option<BigDecimal>
+}
+
+impl WithdrawalLimits {
+    /// Whether no limit is set at all.
+    pub fn is_empty(&self) -> bool {
+        self.max_amount.is_none() && self.max_per_day.is_none() && self.max_daily_outflow.is_none()
+    }
+
+    /// These limits, with any that are not set taken from the fallback.
+    fn or(&self, fallback: &Self) -> Self {
+        Self {
+            max_amount: self.max_amount.clone().or_else(|| fallback.max_amount.clone()),
+            max_per_day: self.max_per_day.or(fallback.max_per_day),
+            max_daily_outflow: self.max_daily_outflow.clone().or_else(|| fallback.max_daily_outflow.clone())
+        }
+    }
+}
+
+/// The withdrawal limits of every client, which may be overridden for individual clients.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VelocityPolicy {
+    /// The limits of every client without an override.
+    default: WithdrawalLimits,
+
+    /// The limits of individual clients, with any that are not set taken from the default.
+    clients: HashMap<u16, WithdrawalLimits>
+}
+
+/// A row of a CSV file of per-client withdrawal limits, with the amounts left as written until they are checked.
+#[derive(Debug, Deserialize)]
+struct LimitsRow {
+    client: u16,
+
+    #[serde(default)]
+    max_withdrawal: Option<String>,
+
+    #[serde(default)]
+    max_withdrawals_per_day: Option<String>,
+
+    #[serde(default)]
+    max_daily_outflow: Option<String>
+}
+
+impl VelocityPolicy {
+    /// Create a policy applying the same limits to every client.
+    pub fn new(default: WithdrawalLimits) -> Self {
+        Self { default, clients: HashMap::new() }
+    }
+
+    /// Override the limits of a client, with any that are not set taken from the default.
+    pub fn with_client(mut self, client: u16, limits: WithdrawalLimits) -> Self {
+        self.clients.insert(client, limits);
+        self
+    }
+
+    /// Read per-client limits from a CSV file with `client`, `max_withdrawal`, `max_withdrawals_per_day` and
+    /// `max_daily_outflow` columns, where an empty limit is taken from the default.
+    pub fn read_clients<R: io::Read>(mut self, reader: R) -> io::Result<Self> {
+        let invalid = |line: u64, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let headers = reader.headers()?.clone();
+
+        let mut record = csv::StringRecord::new();
+        while reader.read_record(&mut record)? {
+            let line = record.position().map_or(0, |position| position.line());
+            let row = record.deserialize::<LimitsRow>(Some(&headers))
+                .map_err(|e| invalid(line, e.to_string()))?;
+
+            let amount = |column: &str, value: Option<String>| match value.as_deref() {
+                None | Some("") => Ok(None),
+                Some(value) => match BigDecimal::from_str(value) {
+                    Ok(amount) if amount >= BigDecimal::zero() => Ok(Some(amount)),
+                    Ok(_) => Err(invalid(line, format!("{} '{}' is negative", column, value))),
+                    Err(e) => Err(invalid(line, format!("invalid {} '{}': {}", column, value, e)))
+                }
+            };
+            let count = match row.max_withdrawals_per_day.as_deref() {
+                None | Some("") => None,
+                Some(value) => Some(value.parse::<u32>().map_err(|e| invalid(line, format!("invalid max_withdrawals_per_day '{}': {}", value, e)))?)
+            };
+
+            let limits = WithdrawalLimits {
+                max_amount: amount("max_withdrawal", row.max_withdrawal)?,
+                max_per_day: count,
+                max_daily_outflow: amount("max_daily_outflow", row.max_daily_outflow)?
+            };
+            if self.clients.insert(row.client, limits).is_some() {
+                return Err(invalid(line, format!("client {} has more than one row", row.client)));
+            }
+        }
+        Ok(self)
+    }
+
+    /// The limits of a client.
+    pub fn limits(&self, client: u16) -> WithdrawalLimits {
+        match self.clients.get(&client) {
+            Some(limits) => limits.or(&self.default),
+            None => self.default.clone()
+        }
+    }
+}
+
+/// The funds that left a client's account on a single day, counted against its daily limits.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DailyOutflow {
+    /// The id of the client.
+    pub client: u16,
+
+    /// The day, or `None` for transactions processed before any had a timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<NaiveDate>,
+
+    /// The number of withdrawals made.
+    pub withdrawals: u32,
+
+    /// The funds withdrawn and transferred out.
+    pub outflow: BigDecimal
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::{Engine, Transaction, TransactionError, TransactionType};
+
+    #[test]
+    fn withdrawals_are_limited_per_day() {
+        let velocity = VelocityPolicy::new(WithdrawalLimits { max_amount: Some(BigDecimal::from(50)), max_per_day: Some(2), max_daily_outflow: Some(BigDecimal::from(80)) })
+            .read_clients("client,max_withdrawal,max_withdrawals_per_day,max_daily_outflow\n2,,,1000\n".as_bytes()).unwrap();
+        let mut engine = Engine::new().with_velocity(Some(velocity));
+
+        let at = |timestamp: &str| DateTime::<Utc>::from_str(timestamp).unwrap();
+        let withdraw = |client, id, amount, timestamp| Transaction::new(TransactionType::Withdrawal, client, id, Some(BigDecimal::from(amount))).with_timestamp(at(timestamp));
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from(500)))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Deposit, 2, 2, Some(BigDecimal::from(500)))).unwrap();
+
+        assert_eq!(engine.process(&withdraw(1, 3, 60, "2024-01-01T09:00:00Z")), Err(TransactionError::WithdrawalAboveLimit(BigDecimal::from(50))));
+        engine.process(&withdraw(1, 4, 50, "2024-01-01T10:00:00Z")).unwrap();
+        assert_eq!(engine.process(&Transaction::transfer(1, 2, 5, BigDecimal::from(40)).with_timestamp(at("2024-01-01T11:00:00Z"))), Err(TransactionError::DailyOutflowAboveLimit(BigDecimal::from(80))));
+        engine.process(&withdraw(1, 6, 30, "2024-01-01T12:00:00Z")).unwrap();
+        assert_eq!(engine.process(&withdraw(1, 7, 1, "2024-01-01T13:00:00Z")), Err(TransactionError::TooManyWithdrawals(2)));
+        engine.process(&withdraw(1, 8, 50, "2024-01-02T09:00:00Z")).unwrap();
+
+        engine.process(&withdraw(2, 9, 50, "2024-01-02T09:00:00Z")).unwrap();
+        engine.process(&withdraw(2, 10, 50, "2024-01-02T10:00:00Z")).unwrap();
+        assert_eq!(engine.process(&withdraw(2, 11, 50, "2024-01-02T11:00:00Z")), Err(TransactionError::TooManyWithdrawals(2)));
+    }
+}
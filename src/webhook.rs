@@ -0,0 +1,198 @@
+use std::{sync::mpsc, thread, time::Duration};
+
+use bigdecimal::BigDecimal;
+use serde::Serialize;
+
+use crate::{Client, EngineObserver, Precision, Transaction};
+
+/// The longest delay between two attempts at delivering a notification.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A notification POSTed to every webhook as a JSON object tagged by `event`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Notification {
+    /// A disputed transaction was charged back, which locks the account.
+    /// The funds are those of the account after the chargeback, written to the engine's precision.
+    // NOTE: Only a chargeback locks an account, so a single event covers both.
+    Chargeback {
+        /// The tenant whose books hold the account, unless it is in the default books.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        client: u16,
+        tx: u32,
+        amount: String,
+        available: String,
+        held: String,
+        total: String,
+        locked: bool
+    },
+}
+
+/// A message to the delivery thread.
+#[derive(Debug)]
+enum Message {
+    /// Deliver a notification to every webhook.
+    Deliver(Notification),
+
+    /// Stop once every notification sent before this one has been delivered.
+    Stop,
+}
+
+/// An observer that POSTs a notification to every webhook when an account is charged back and locked.
+///
+/// Notifications are delivered in order by a background thread, so processing never waits on a webhook.
+/// A failed delivery is retried with exponential backoff, and given up on once every retry has failed.
+/// Clones share the same delivery thread, so one can be registered with each shard of an engine.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    /// Sends notifications to the delivery thread.
+    sender: mpsc::Sender<Message>,
+
+    /// The precision the funds in a notification are written to.
+    precision: Precision
+}
+
+/// The thread delivering the notifications of a [`Webhook`].
+#[derive(Debug)]
+pub struct WebhookWorker {
+    /// Sends the stop message to the delivery thread.
+    sender: mpsc::Sender<Message>,
+
+    /// The delivery thread.
+    thread: thread::JoinHandle<()>
+}
+
+impl Webhook {
+    /// Start delivering notifications to the URLs, retrying a failed delivery up to `retries` times.
+    /// The first retry waits for `backoff`, and every retry after it waits twice as long as the one before.
+    pub fn spawn(urls: Vec<String>, precision: Precision, retries: u32, backoff: Duration) -> (Self, WebhookWorker) {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            for message in receiver {
+                let Message::Deliver(notification) = message else {
+                    break;
+                };
+                for url in &urls {
+                    deliver(url, &notification, retries, backoff);
+                }
+            }
+        });
+
+        (Self { sender: sender.clone(), precision }, WebhookWorker { sender, thread })
+    }
+
+    /// Queue a notification for delivery.
+    fn notify(&self, notification: Notification) {
+        // NOTE: The worker only stops once asked to, so this can only fail after it has finished.
+        if self.sender.send(Message::Deliver(notification)).is_err() {
+            tracing::warn!("dropped a webhook notification sent after the webhooks were finished");
+        }
+    }
+}
+
+impl EngineObserver for Webhook {
+    fn on_account_locked(&mut self, client: &Client, transaction: &Transaction, amount: &BigDecimal) {
+        self.notify(Notification::Chargeback {
+            tenant: transaction.tenant().map(str::to_string),
+            client: client.id(),
+            tx: transaction.id(),
+            amount: self.precision.format(amount),
+            available: self.precision.format(&client.available()),
+            held: self.precision.format(&client.held()),
+            total: self.precision.format(&client.total()),
+            locked: client.locked()
+        });
+    }
+}
+
+impl WebhookWorker {
+    /// Wait for every notification queued so far to be delivered or given up on, then stop the delivery thread.
+    pub fn finish(self) {
+        let _ = self.sender.send(Message::Stop);
+        if self.thread.join().is_err() {
+            tracing::error!("the webhook delivery thread panicked");
+        }
+    }
+}
+
+/// POST a notification to a webhook, retrying with exponential backoff until it succeeds or the retries run out.
+/// Only server errors, rate limiting and transport errors are retried, as any other response will not change.
+fn deliver(url: &str, notification: &Notification, retries: u32, backoff: Duration) {
+    let body = serde_json::to_string(notification).expect("notifications always serialize");
+    let mut delay = backoff;
+    for attempt in 0..=retries {
+        if attempt > 0 {
+            thread::sleep(delay);
+            delay = (delay * 2).min(MAX_BACKOFF);
+        }
+
+        match ureq::post(url).set("Content-Type", "application/json").send_string(&body) {
+            Ok(_) => return,
+            Err(ureq::Error::Status(status, _)) if status != 429 && status < 500 => {
+                tracing::error!(url, status, "webhook rejected the notification");
+                return;
+            },
+            Err(e) => tracing::warn!(url, attempt, error = %e, "unable to deliver webhook notification")
+        }
+    }
+    tracing::error!(url, ?notification, "gave up delivering webhook notification after {} retries", retries);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{BufRead, BufReader, Read, Write}, net::TcpListener, str::FromStr};
+
+    use super::*;
+    use crate::{Engine, TransactionType};
+
+    /// Accept requests on a local port, answering each with the next status, and return the URL and the bodies received.
+    fn server(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(reader.into_inner(), "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn chargebacks_are_delivered_with_retries() {
+        let (url, server) = server(vec![503, 200]);
+        let (webhook, worker) = Webhook::spawn(vec![url], Precision::default(), 3, Duration::from_millis(1));
+        let mut engine = Engine::builder().observer(webhook).build();
+
+        engine.process(&Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10").unwrap()))).unwrap();
+        engine.process(&Transaction::new(TransactionType::Dispute, 1, 1, None)).unwrap();
+        engine.process(&Transaction::new(TransactionType::Chargeback, 1, 1, None)).unwrap();
+        worker.finish();
+
+        let expected = r#"{"event":"chargeback","client":1,"tx":1,"amount":"10.0000","available":"0.0000","held":"0.0000","total":"0.0000","locked":true}"#;
+        assert_eq!(server.join().unwrap(), vec![expected, expected]);
+    }
+}
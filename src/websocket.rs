@@ -0,0 +1,212 @@
+use std::{io, sync::Arc};
+
+use futures_util::SinkExt;
+use serde::Serialize;
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{Applied, Engine, Observer, Storage, StorageError, Transaction};
+
+/// The number of events kept for a subscriber that is falling behind, before it starts missing them.
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// A change to a client account, pushed to every subscriber as a JSON object tagged by `event`,
+/// with the `tenant` whose books hold the account unless it is in the default books.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AccountEvent {
+    /// The funds of a client changed in a currency of one of its sub-accounts, written to the engine's precision.
+    BalanceUpdated {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        client: u16,
+        tx: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        account: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        currency: Option<String>,
+        available: String,
+        held: String,
+        total: String
+    },
+
+    /// A transaction of the client was disputed, and its amount is held.
+    DisputeOpened {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        client: u16,
+        tx: u32,
+        amount: String
+    },
+
+    /// The account was locked by a chargeback.
+    AccountLocked {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        client: u16,
+        tx: u32
+    },
+
+    /// The account was unlocked by an operator.
+    AccountUnlocked {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        client: u16,
+        tx: u32
+    },
+}
+
+impl AccountEvent {
+    /// Describe every account change made by a transaction the engine has just applied.
+    pub fn from_applied<S: Storage>(engine: &Engine<S>, transaction: &Transaction, applied: &Applied) -> Result<Vec<Self>, StorageError> {
+        // NOTE: A balance assertion only reads the account, so there is no change to describe.
+        if let Applied::Asserted = applied {
+            return Ok(Vec::new());
+        }
+
+        let client = transaction.client_id();
+        let tx = transaction.id();
+        let tenant = transaction.tenant().map(str::to_string);
+
+        // NOTE: Disputes, captures and voids only carry the id of the transaction they refer to, which holds the currency.
+        let target = match transaction.type_().references_existing() {
+            true => engine.storage().get_transaction(tx)?,
+            false => None
+        };
+        let (account, currency) = target.as_ref().map_or((transaction.account(), transaction.currency()), |target| (target.account(), target.currency()));
+
+        let mut changed = vec![(client, account, currency)];
+        match applied {
+            Applied::Transferred { destination, .. } => changed.push((*destination, None, currency)),
+            Applied::Converted { .. } => changed.push((client, account, transaction.to_currency())),
+            Applied::Moved { .. } => changed.push((client, transaction.to_account(), currency)),
+            _ => {}
+        }
+
+        let precision = engine.precision();
+        let mut events = Vec::new();
+        for (id, account, currency) in changed {
+            let balance = engine.client(id)?
+                .and_then(|client| client.balance_in(account, currency).cloned());
+
+            if let Some(balance) = balance {
+                events.push(Self::BalanceUpdated {
+                    tenant: tenant.clone(),
+                    client: id,
+                    tx,
+                    account: balance.account().map(str::to_string),
+                    currency: balance.currency().map(str::to_string),
+                    available: precision.format(&balance.available()),
+                    held: precision.format(&balance.held()),
+                    total: precision.format(&balance.total())
+                });
+            }
+        }
+
+        match applied {
+            Applied::Disputed { amount } => events.push(Self::DisputeOpened { tenant, client, tx, amount: precision.format(amount) }),
+            Applied::ChargedBack { .. } => events.push(Self::AccountLocked { tenant, client, tx }),
+            Applied::Unlocked | Applied::Represented { unlocked: true, .. } => events.push(Self::AccountUnlocked { tenant, client, tx }),
+            _ => {}
+        }
+
+        Ok(events)
+    }
+}
+
+/// An observer for a sharded engine that publishes every account change to a channel, for WebSocket subscribers.
+pub fn publisher(events: broadcast::Sender<AccountEvent>) -> Observer {
+    Arc::new(move |engine: &Engine, transaction: &Transaction, applied: &Applied| {
+        match AccountEvent::from_applied(engine, transaction, applied) {
+            // Sending only fails when nobody is subscribed, in which case the events are simply dropped.
+            Ok(changes) => changes.into_iter().for_each(|event| drop(events.send(event))),
+            Err(e) => tracing::warn!(tx = transaction.id(), "unable to describe account changes: {}", e)
+        }
+    })
+}
+
+/// Accept WebSocket connections until the process is stopped, pushing every published event to each of them as a text message.
+/// A subscriber that falls too far behind skips the events it missed, rather than slowing down processing.
+pub async fn serve_events(listener: TcpListener, events: broadcast::Sender<AccountEvent>) -> io::Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+
+        // NOTE: Subscribe before the handshake, so no event published once the client is connected is missed.
+        let mut receiver = events.subscribe();
+        tokio::spawn(async move {
+            let mut socket = match tokio_tungstenite::accept_async(stream).await {
+                Ok(socket) => socket,
+                Err(e) => return tracing::warn!(%peer, "rejected WebSocket connection: {}", e)
+            };
+
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(%peer, "subscriber missed {} events", missed);
+                        continue;
+                    },
+                    Err(broadcast::error::RecvError::Closed) => break
+                };
+
+                let text = serde_json::to_string(&event).expect("events always serialize");
+                if socket.send(Message::text(text)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bigdecimal::BigDecimal;
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::TransactionType;
+
+    #[test]
+    fn describe_account_changes() {
+        let mut engine = Engine::new();
+        let deposit = Transaction::new(TransactionType::Deposit, 1, 1, Some(BigDecimal::from_str("10.5").unwrap()));
+        let applied = engine.process(&deposit).unwrap();
+        assert_eq!(AccountEvent::from_applied(&engine, &deposit, &applied).unwrap(), vec![AccountEvent::BalanceUpdated {
+            tenant: None,
+            client: 1,
+            tx: 1,
+            account: None,
+            currency: None,
+            available: "10.5000".to_string(),
+            held: "0.0000".to_string(),
+            total: "10.5000".to_string()
+        }]);
+
+        let dispute = Transaction::new(TransactionType::Dispute, 1, 1, None);
+        let applied = engine.process(&dispute).unwrap();
+        let events = AccountEvent::from_applied(&engine, &dispute, &applied).unwrap();
+        assert_eq!(events[1], AccountEvent::DisputeOpened { tenant: None, client: 1, tx: 1, amount: "10.5000".to_string() });
+
+        let chargeback = Transaction::new(TransactionType::Chargeback, 1, 1, None);
+        let applied = engine.process(&chargeback).unwrap();
+        let events = AccountEvent::from_applied(&engine, &chargeback, &applied).unwrap();
+        assert_eq!(events.last(), Some(&AccountEvent::AccountLocked { tenant: None, client: 1, tx: 1 }));
+        assert_eq!(serde_json::to_string(&events[1]).unwrap(), r#"{"event":"account_locked","client":1,"tx":1}"#);
+    }
+
+    #[tokio::test]
+    async fn push_events_to_subscribers() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        tokio::spawn(serve_events(listener, events.clone()));
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/", addr)).await.unwrap();
+        events.send(AccountEvent::AccountLocked { tenant: None, client: 1, tx: 2 }).unwrap();
+
+        let message = socket.next().await.unwrap().unwrap();
+        assert_eq!(message.into_text().unwrap().as_str(), r#"{"event":"account_locked","client":1,"tx":2}"#);
+    }
+}